@@ -0,0 +1,444 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `#[js_class]` and `#[js_methods]`.
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{
+    parse::Parser, punctuated::Punctuated, spanned::Spanned, Attribute, Error, FnArg, Ident,
+    ImplItem, ImplItemMethod, ItemImpl, ItemStruct, Lit, Meta, NestedMeta, ReturnType, Token,
+    Type,
+};
+
+/// Parses `name = "value"` pairs and bare flags of an attribute argument list.
+fn parse_args(args: TokenStream) -> Result<Vec<NestedMeta>, Error> {
+    let parser = Punctuated::<NestedMeta, Token![,]>::parse_terminated;
+    Ok(parser.parse2(args)?.into_iter().collect())
+}
+
+fn name_value(meta: &[NestedMeta], key: &str) -> Result<Option<String>, Error> {
+    for nested in meta {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = nested {
+            if nv.path.is_ident(key) {
+                return match &nv.lit {
+                    Lit::Str(s) => Ok(Some(s.value())),
+                    lit => Err(Error::new(lit.span(), "expected a string literal")),
+                };
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn has_flag(meta: &[NestedMeta], key: &str) -> bool {
+    meta.iter()
+        .any(|nested| matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident(key)))
+}
+
+pub fn js_class(args: TokenStream, input: TokenStream) -> Result<TokenStream, Error> {
+    let item: ItemStruct = syn::parse2(input)?;
+    let args = parse_args(args)?;
+    if !item.generics.params.is_empty() {
+        return Err(Error::new(
+            item.generics.span(),
+            "#[js_class] does not support generic types",
+        ));
+    }
+    let ident = &item.ident;
+    // `define_jsclass!` stringifies the class name, so it has to be an identifier.
+    let name = match name_value(&args, "name")? {
+        Some(name) => syn::parse_str::<Ident>(&name)
+            .map_err(|_| Error::new(Span::call_site(), "#[js_class] name must be an identifier"))?,
+        None => ident.clone(),
+    };
+
+    Ok(quote! {
+        #item
+
+        impl starlight::vm::class::JsClass for #ident {
+            fn class() -> &'static starlight::vm::class::Class {
+                use starlight::{define_jsclass, js_method_table, vm::method_table::MethodTable};
+
+                extern "C" fn drop(object: starlight::gc::cell::GcPointer<starlight::vm::object::JsObject>) {
+                    unsafe { std::mem::ManuallyDrop::drop(object.data::<#ident>()) }
+                }
+                #[allow(improper_ctypes_definitions)]
+                extern "C" fn trace(
+                    tracer: &mut starlight::gc::cell::Visitor,
+                    object: &starlight::vm::object::JsObject,
+                ) {
+                    starlight::gc::cell::Trace::trace(&**object.data::<#ident>(), tracer);
+                }
+                extern "C" fn additional_size() -> usize {
+                    std::mem::size_of::<#ident>()
+                }
+                define_jsclass!(#ident, #name, Some(drop), Some(trace), Some(additional_size))
+            }
+        }
+    })
+}
+
+#[derive(PartialEq)]
+enum MethodKind {
+    Constructor,
+    Method,
+    Static,
+    Getter,
+    Setter,
+}
+
+struct Binding {
+    kind: MethodKind,
+    js_name: String,
+    wrapper: Ident,
+    arity: TokenStream,
+}
+
+/// `snake_case` to `camelCase`.
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for (i, c) in name.chars().enumerate() {
+        if c == '_' && i != 0 {
+            upper = true;
+        } else if upper {
+            result.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn is_context(ty: &Type) -> bool {
+    if let Type::Path(path) = ty {
+        if let Some(last) = path.path.segments.last() {
+            return last.ident == "GcPointer"
+                && quote!(#last).to_string().replace(' ', "").ends_with("<Context>");
+        }
+    }
+    false
+}
+
+/// Removes our helper attributes from `method` and returns the `#[js(...)]` arguments and the
+/// kind selected by `#[constructor]`, `#[getter]` or `#[setter]`.
+fn take_attributes(method: &mut ImplItemMethod) -> Result<(Vec<NestedMeta>, Option<MethodKind>), Error> {
+    let mut args = vec![];
+    let mut kind = None;
+    let mut kept: Vec<Attribute> = vec![];
+    for attr in method.attrs.drain(..) {
+        let ident = attr.path.get_ident().map(|ident| ident.to_string());
+        match ident.as_deref() {
+            Some("js") => {
+                if let Meta::List(list) = attr.parse_meta()? {
+                    args.extend(list.nested);
+                }
+            }
+            Some("constructor") => kind = Some(MethodKind::Constructor),
+            Some("getter") | Some("setter") => {
+                if let Meta::List(list) = attr.parse_meta()? {
+                    args.extend(list.nested);
+                }
+                kind = Some(if ident.as_deref() == Some("getter") {
+                    MethodKind::Getter
+                } else {
+                    MethodKind::Setter
+                });
+            }
+            _ => kept.push(attr),
+        }
+    }
+    method.attrs = kept;
+    Ok((args, kind))
+}
+
+fn wrap_method(
+    method: &ImplItemMethod,
+    kind: &MethodKind,
+    wrapper: &Ident,
+) -> Result<(TokenStream, TokenStream), Error> {
+    let sig = &method.sig;
+    let name = &sig.ident;
+    let mut receiver = None;
+    let mut params = vec![];
+    let mut pass_context = false;
+    for (i, input) in sig.inputs.iter().enumerate() {
+        match input {
+            FnArg::Receiver(r) => receiver = Some(r.mutability.is_some()),
+            FnArg::Typed(pat) => {
+                let first_param = i == 0 || (i == 1 && receiver.is_some());
+                if first_param && is_context(&pat.ty) {
+                    pass_context = true;
+                } else {
+                    params.push((*pat.ty).clone());
+                }
+            }
+        }
+    }
+    match kind {
+        MethodKind::Constructor | MethodKind::Static if receiver.is_some() => {
+            return Err(Error::new(sig.span(), "this method must not take `self`"))
+        }
+        MethodKind::Method | MethodKind::Getter | MethodKind::Setter if receiver.is_none() => {
+            return Err(Error::new(sig.span(), "this method must take `&self` or `&mut self`"))
+        }
+        MethodKind::Getter if !params.is_empty() => {
+            return Err(Error::new(sig.span(), "getters must not take arguments"))
+        }
+        MethodKind::Setter if params.len() != 1 => {
+            return Err(Error::new(sig.span(), "setters must take exactly one argument"))
+        }
+        _ => {}
+    }
+
+    let arity = quote! {
+        0 #(+ !<#params as starlight::JsTryFrom<starlight::vm::value::JsValue>>::OPTIONAL as u32)*
+    };
+    let args = (0..params.len())
+        .map(|i| format_ident!("arg{}", i))
+        .collect::<Vec<_>>();
+    let indices = 0..params.len();
+    let conversions = quote! {
+        starlight::vm::function::check_argument_count(ctx, args, #arity)?;
+        #(
+            let #args = <#params as starlight::JsTryFrom<starlight::vm::value::JsValue>>::try_from(
+                ctx,
+                args.at(#indices),
+            )?;
+        )*
+    };
+    let context = if pass_context { quote!(ctx,) } else { quote!() };
+
+    let body = match kind {
+        MethodKind::Constructor => {
+            let returns_self = match &sig.output {
+                ReturnType::Type(_, ty) => {
+                    let ty = quote!(#ty).to_string();
+                    ty == "Self"
+                }
+                ReturnType::Default => false,
+            };
+            let call = quote!(Self::#name(#context #(#args),*));
+            let result = if returns_self {
+                quote!(Ok(#call))
+            } else {
+                quote! {
+                    #call.map_err(|error| {
+                        <starlight::vm::value::JsValue as starlight::vm::value::JsFrom<_>>::js_from(ctx, error)
+                    })
+                }
+            };
+            quote! {
+                #conversions
+                #result
+            }
+        }
+        MethodKind::Static => quote! {
+            use starlight::vm::function::IntoNativeResult;
+            #conversions
+            Self::#name(#context #(#args),*).into_native_result(ctx)
+        },
+        _ => quote! {
+            use starlight::vm::function::IntoNativeResult;
+            let mut this = <starlight::vm::object::TypedJsObject<Self> as starlight::JsTryFrom<
+                starlight::vm::value::JsValue,
+            >>::try_from(ctx, args.this)?;
+            #conversions
+            this.#name(#context #(#args),*).into_native_result(ctx)
+        },
+    };
+
+    let wrapper_fn = if *kind == MethodKind::Constructor {
+        quote_spanned! {sig.span()=>
+            #[doc(hidden)]
+            #[allow(unused_variables, unused_mut)]
+            fn #wrapper(
+                ctx: starlight::gc::cell::GcPointer<starlight::vm::context::Context>,
+                args: &starlight::vm::arguments::Arguments,
+            ) -> Result<Self, starlight::vm::value::JsValue> {
+                #body
+            }
+        }
+    } else {
+        quote_spanned! {sig.span()=>
+            #[doc(hidden)]
+            #[allow(unused_variables, unused_mut)]
+            fn #wrapper(
+                ctx: starlight::gc::cell::GcPointer<starlight::vm::context::Context>,
+                args: &starlight::vm::arguments::Arguments,
+            ) -> Result<starlight::vm::value::JsValue, starlight::vm::value::JsValue> {
+                #body
+            }
+        }
+    };
+    Ok((wrapper_fn, arity))
+}
+
+pub fn js_methods(_args: TokenStream, input: TokenStream) -> Result<TokenStream, Error> {
+    let mut item: ItemImpl = syn::parse2(input)?;
+    if item.trait_.is_some() {
+        return Err(Error::new(
+            item.span(),
+            "#[js_methods] must be used on an inherent impl block",
+        ));
+    }
+    let self_ty = item.self_ty.clone();
+
+    let mut wrappers = vec![];
+    let mut bindings = vec![];
+    for impl_item in item.items.iter_mut() {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        let (args, kind) = take_attributes(method)?;
+        if has_flag(&args, "skip") {
+            continue;
+        }
+        let has_receiver = matches!(method.sig.inputs.first(), Some(FnArg::Receiver(_)));
+        let kind = kind.unwrap_or(if has_receiver {
+            MethodKind::Method
+        } else {
+            MethodKind::Static
+        });
+        let rust_name = method.sig.ident.to_string();
+        let js_name = match name_value(&args, "name")? {
+            Some(name) => name,
+            None if kind == MethodKind::Setter => {
+                camel_case(rust_name.strip_prefix("set_").unwrap_or(&rust_name))
+            }
+            None => camel_case(&rust_name),
+        };
+        let wrapper = format_ident!("__starlight_js_{}", method.sig.ident);
+        let (wrapper_fn, arity) = wrap_method(method, &kind, &wrapper)?;
+        wrappers.push(wrapper_fn);
+        bindings.push(Binding {
+            kind,
+            js_name,
+            wrapper,
+            arity,
+        });
+    }
+
+    let constructors = bindings
+        .iter()
+        .filter(|binding| binding.kind == MethodKind::Constructor)
+        .collect::<Vec<_>>();
+    if constructors.len() > 1 {
+        return Err(Error::new(
+            Span::call_site(),
+            "#[js_methods] accepts at most one #[constructor]",
+        ));
+    }
+    let constructor = match constructors.first() {
+        Some(binding) => {
+            let wrapper = &binding.wrapper;
+            quote!(Self::#wrapper(ctx, args))
+        }
+        None => quote! {
+            Err(starlight::vm::value::JsValue::new(ctx.new_type_error(format!(
+                "Illegal constructor {}",
+                <Self as starlight::vm::class::JsClass>::class().name
+            ))))
+        },
+    };
+
+    let mut init = vec![];
+    for binding in bindings.iter() {
+        let js_name = &binding.js_name;
+        let wrapper = &binding.wrapper;
+        let arity = &binding.arity;
+        match binding.kind {
+            MethodKind::Constructor => {}
+            MethodKind::Method => init.push(quote! {
+                builder.method(#js_name, Self::#wrapper, #arity)?;
+            }),
+            MethodKind::Static => init.push(quote! {
+                builder.static_method(#js_name, Self::#wrapper, #arity)?;
+            }),
+            MethodKind::Getter => {
+                let setter = bindings.iter().find(|other| {
+                    other.kind == MethodKind::Setter && other.js_name == binding.js_name
+                });
+                let getter = quote! {
+                    starlight::vm::function::JsNativeFunction::new(
+                        builder.context,
+                        starlight::vm::symbol_table::Internable::intern(#js_name),
+                        Self::#wrapper,
+                        0,
+                    )
+                };
+                match setter {
+                    Some(setter) => {
+                        let setter_wrapper = &setter.wrapper;
+                        init.push(quote! {
+                            let getter = #getter;
+                            let setter = starlight::vm::function::JsNativeFunction::new(
+                                builder.context,
+                                starlight::vm::symbol_table::Internable::intern(#js_name),
+                                Self::#setter_wrapper,
+                                1,
+                            );
+                            builder.accessor(#js_name, getter, setter, starlight::vm::attributes::CONFIGURABLE)?;
+                        })
+                    }
+                    None => init.push(quote! {
+                        let getter = #getter;
+                        builder.getter(#js_name, getter, starlight::vm::attributes::CONFIGURABLE)?;
+                    }),
+                }
+            }
+            MethodKind::Setter => {
+                let has_getter = bindings.iter().any(|other| {
+                    other.kind == MethodKind::Getter && other.js_name == binding.js_name
+                });
+                if !has_getter {
+                    init.push(quote! {
+                        let setter = starlight::vm::function::JsNativeFunction::new(
+                            builder.context,
+                            starlight::vm::symbol_table::Internable::intern(#js_name),
+                            Self::#wrapper,
+                            1,
+                        );
+                        builder.setter(#js_name, setter, starlight::vm::attributes::CONFIGURABLE)?;
+                    });
+                }
+            }
+        }
+    }
+
+    let wrapper_names = bindings.iter().map(|binding| &binding.wrapper);
+    Ok(quote! {
+        #item
+
+        impl #self_ty {
+            #(#wrappers)*
+        }
+
+        impl starlight::vm::builder::ClassConstructor for #self_ty {
+            fn constructor(
+                ctx: starlight::gc::cell::GcPointer<starlight::vm::context::Context>,
+                args: &starlight::vm::arguments::Arguments,
+            ) -> Result<Self, starlight::vm::value::JsValue> {
+                #constructor
+            }
+
+            fn raw_constructor(
+                ctx: starlight::gc::cell::GcPointer<starlight::vm::context::Context>,
+                args: &starlight::vm::arguments::Arguments,
+            ) -> Result<starlight::vm::value::JsValue, starlight::vm::value::JsValue> {
+                starlight::vm::builder::construct_class_instance::<Self>(ctx, args)
+            }
+
+            fn init(
+                builder: &mut starlight::vm::builder::ClassBuilder,
+            ) -> Result<(), starlight::vm::value::JsValue> {
+                let mut ctx = builder.context;
+                #(ctx.register_external_reference(Self::#wrapper_names as usize);)*
+                #(#init)*
+                Ok(())
+            }
+        }
+    })
+}
@@ -1,9 +1,12 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+use proc_macro::TokenStream;
 use quote::quote;
 use synstructure::{decl_derive, BindStyle, Structure};
 
+mod class;
+
 decl_derive!([GcTrace, attributes(unsafe_ignore_trace)] => derive_trace);
 
 fn derive_trace(mut s: Structure<'_>) -> proc_macro2::TokenStream {
@@ -35,3 +38,31 @@ fn derive_trace(mut s: Structure<'_>) -> proc_macro2::TokenStream {
         #trace_impl
     }
 }
+
+/// Expose a Rust struct to JavaScript as a class. The struct must implement `Trace`, e.g. with
+/// `#[derive(GcTrace)]`. The JS name defaults to the struct name and can be changed with
+/// `#[js_class(name = "Name")]`, which must be an identifier. Methods are added with
+/// [macro@js_methods] and the class is installed with `Context::register_class`.
+#[proc_macro_attribute]
+pub fn js_class(args: TokenStream, input: TokenStream) -> TokenStream {
+    class::js_class(args.into(), input.into())
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Generate the constructor, methods and accessors of a [macro@js_class] type from an impl block.
+///
+/// * `#[constructor]` marks the function called by `new`, it returns `Self` or `Result<Self, E>`.
+/// * methods taking `&self`/`&mut self` go to the prototype, other functions become static methods.
+/// * `#[getter]` and `#[setter]` define accessors, a `set_` prefix is stripped from setter names.
+/// * `#[js(name = "name")]` overrides the JS name (camelCase of the Rust name by default) and
+///   `#[js(skip)]` hides a function.
+///
+/// Parameters are converted with `JsTryFrom<JsValue>`, results with `JsFrom`, and a first
+/// parameter of type `GcPointer<Context>` receives the current context.
+#[proc_macro_attribute]
+pub fn js_methods(args: TokenStream, input: TokenStream) -> TokenStream {
+    class::js_methods(args.into(), input.into())
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
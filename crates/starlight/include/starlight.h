@@ -0,0 +1,209 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+#ifndef STARLIGHT_H
+#define STARLIGHT_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum SlErrorKind {
+  SL_ERROR_KIND_ERROR = 0,
+  SL_ERROR_KIND_TYPE_ERROR = 1,
+  SL_ERROR_KIND_RANGE_ERROR = 2,
+  SL_ERROR_KIND_REFERENCE_ERROR = 3,
+  SL_ERROR_KIND_SYNTAX_ERROR = 4,
+} SlErrorKind;
+
+typedef enum SlStatus {
+  SL_STATUS_OK = 0,
+  // JS exception was thrown, `result` holds the exception.
+  SL_STATUS_EXCEPTION = 1,
+  // Null pointer, invalid UTF-8 or a value of the wrong type was passed.
+  SL_STATUS_INVALID_ARGUMENT = 2,
+  // The engine panicked, the VM should be disposed.
+  SL_STATUS_PANIC = 3,
+} SlStatus;
+
+typedef enum SlType {
+  SL_TYPE_UNDEFINED = 0,
+  SL_TYPE_NULL = 1,
+  SL_TYPE_BOOLEAN = 2,
+  SL_TYPE_NUMBER = 3,
+  SL_TYPE_STRING = 4,
+  SL_TYPE_SYMBOL = 5,
+  SL_TYPE_OBJECT = 6,
+  SL_TYPE_FUNCTION = 7,
+} SlType;
+
+// Context (realm) with its own global object.
+typedef struct SlContext SlContext;
+
+// VM options, see [Options].
+typedef struct SlOptions SlOptions;
+
+// Rooted handle to a JS value.
+typedef struct SlValue SlValue;
+
+// Virtual machine, owns the heap shared by its contexts.
+typedef struct SlVm SlVm;
+
+// Native function callable from JS. `this_value` and `argv` are valid for the duration of the
+// call only. Store the return value, or the exception when returning `SL_STATUS_EXCEPTION`, in
+// `result`. The API takes ownership of that handle; leaving it null returns `undefined`.
+typedef SlStatus (*SlNativeCallback)(SlContext *ctx,
+                                     const SlValue *this_value,
+                                     const SlValue *const *argv,
+                                     size_t argc,
+                                     SlValue **result,
+                                     void *data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Initialize the GC and the symbol table. Called implicitly by [sl_vm_new].
+void sl_platform_initialize(void);
+
+SlOptions *sl_options_new(void);
+
+void sl_options_free(SlOptions *options);
+
+SlStatus sl_options_set_heap_size(SlOptions *options, size_t size);
+
+// `0` removes the soft heap limit.
+SlStatus sl_options_set_heap_soft_limit(SlOptions *options, size_t limit);
+
+SlStatus sl_options_set_gc_threads(SlOptions *options, uint32_t threads);
+
+SlStatus sl_options_set_fuel(SlOptions *options, uint64_t fuel);
+
+// `0` removes the timeout.
+SlStatus sl_options_set_timeout_ms(SlOptions *options, uint64_t timeout);
+
+// Create a VM. Takes ownership of `options`, which may be null for the default options.
+SlVm *sl_vm_new(SlOptions *options);
+
+// Free the VM with its heap. Every context and handle of the VM must be released before.
+void sl_vm_dispose(SlVm *vm);
+
+SlContext *sl_context_new(SlVm *vm);
+
+// Release the context, its global object is collected once no handle refers to it.
+// Must not be called on the context passed to a [SlNativeCallback].
+void sl_context_dispose(SlContext *ctx);
+
+SlValue *sl_context_global(SlContext *ctx);
+
+// Stack trace of the last exception, allocated with `malloc`. Empty when there was no exception.
+char *sl_context_take_stacktrace(SlContext *ctx, size_t *len);
+
+// Evaluate a script of `len` bytes of UTF-8.
+SlStatus sl_eval(SlContext *ctx, const char *source, size_t len, SlValue **result);
+
+// Compile a script into a function that runs it when called. `path` may be null.
+SlStatus sl_compile(SlContext *ctx,
+                    const char *path,
+                    const char *name,
+                    const char *source,
+                    size_t len,
+                    SlValue **result);
+
+SlValue *sl_value_clone(const SlValue *value);
+
+void sl_value_release(SlValue *value);
+
+SlValue *sl_undefined(SlContext *ctx);
+
+SlValue *sl_null(SlContext *ctx);
+
+SlValue *sl_boolean(SlContext *ctx, bool value);
+
+SlValue *sl_number(SlContext *ctx, double value);
+
+// Returns null if `data` is not valid UTF-8.
+SlValue *sl_string(SlContext *ctx, const char *data, size_t len);
+
+SlValue *sl_object_new(SlContext *ctx);
+
+SlValue *sl_array_new(SlContext *ctx, uint32_t len);
+
+// Create an error object of `kind` with message `message` (NUL terminated UTF-8).
+SlValue *sl_error_new(SlContext *ctx, SlErrorKind kind, const char *message);
+
+// Create a function named `name` that calls `callback` with `data`.
+SlValue *sl_function_new(SlContext *ctx,
+                         const char *name,
+                         SlNativeCallback callback,
+                         void *data,
+                         uint32_t length);
+
+// Store the type of `value` in `ty`.
+SlStatus sl_value_type(const SlValue *value, SlType *ty);
+
+// `ToBoolean(value)`.
+SlStatus sl_value_to_boolean(const SlValue *value, bool *boolean);
+
+// `ToNumber(value)`.
+SlStatus sl_value_to_number(SlContext *ctx,
+                            const SlValue *value,
+                            double *number,
+                            SlValue **exception);
+
+// `ToString(value)` as UTF-8 allocated with `malloc`, free it with [sl_string_free].
+SlStatus sl_value_to_string(SlContext *ctx,
+                            const SlValue *value,
+                            char **string,
+                            size_t *len,
+                            SlValue **exception);
+
+void sl_string_free(char *string);
+
+// `object[key]` where `key` is NUL terminated UTF-8.
+SlStatus sl_object_get(SlContext *ctx, const SlValue *object, const char *key, SlValue **result);
+
+// `object[key] = value`, in strict mode.
+SlStatus sl_object_set(SlContext *ctx,
+                       const SlValue *object,
+                       const char *key,
+                       const SlValue *value,
+                       SlValue **exception);
+
+SlStatus sl_object_get_index(SlContext *ctx,
+                             const SlValue *object,
+                             uint32_t index,
+                             SlValue **result);
+
+SlStatus sl_object_set_index(SlContext *ctx,
+                             const SlValue *object,
+                             uint32_t index,
+                             const SlValue *value,
+                             SlValue **exception);
+
+// Call `function` with `this_value` (null for `undefined`) and `argc` arguments.
+SlStatus sl_call(SlContext *ctx,
+                 const SlValue *function,
+                 const SlValue *this_value,
+                 const SlValue *const *argv,
+                 size_t argc,
+                 SlValue **result);
+
+// `new function(...argv)`.
+SlStatus sl_construct(SlContext *ctx,
+                      const SlValue *function,
+                      const SlValue *const *argv,
+                      size_t argc,
+                      SlValue **result);
+
+// Deprecated alias of [capi::sl_platform_initialize].
+void platform_initialize(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* STARLIGHT_H */
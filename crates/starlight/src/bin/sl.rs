@@ -2,14 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
+use std::io::Read;
+
+use starlight::jsrt::jsstd::io::restore_terminal;
 use starlight::vm::context::Context;
 use starlight::{letroot, prelude::*};
 use structopt::*;
 
+mod repl;
+
 #[cfg(not(debug_assertions))]
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+/// Exit status after an uncaught exception or a compilation error.
+const EXIT_FAILURE: i32 = 1;
+
 fn main() {
     Platform::initialize();
     let options = Options::from_args();
@@ -21,69 +29,107 @@ fn main() {
         vm.add_ffi();
     }
 
-    let mut ctx = Context::new(&mut vm);
-
-    let string = std::fs::read_to_string(&vm.options().file);
-    match string {
-        Ok(source) => {
-            let name = vm.options().file.as_os_str().to_str().unwrap().to_string();
-            letroot!(
-                function = foo,
-                match ctx.compile_module(&name, "<script>", &source) {
-                    Ok(function) => function.get_jsobject(),
-                    Err(e) => {
-                        let string = e.to_string(ctx);
-                        match string {
-                            Ok(val) => {
-                                eprintln!("Compilation failed: {}", val);
-                                std::process::exit(1);
-                            }
-                            Err(_e) => {
-                                eprintln!("Failed to get error as string");
-                                std::process::exit(1);
-                            }
-                        }
-                    }
-                }
-            );
-
-            let global = ctx.global_object();
-            let mut module_object = JsObject::new_empty(ctx);
-            let exports = JsObject::new_empty(ctx);
-            module_object
-                .put(ctx, "@exports".intern(), JsValue::new(exports), false)
-                .unwrap_or_else(|_| unreachable!());
-            let mut args = [JsValue::new(module_object)];
-            let mut args = Arguments::new(JsValue::encode_object_value(global), &mut args);
-
-            let start = std::time::Instant::now();
-            let f = function;
-            match function
-                .as_function_mut()
-                .call(ctx, &mut args, JsValue::new(f))
-            {
-                Ok(_) => {
-                    let elapsed = start.elapsed();
-                    eprintln!("Executed in {}ms", elapsed.as_nanos() as f64 / 1000000f64);
-                }
-                Err(e) => {
-                    let str = match e.to_string(ctx) {
-                        Ok(s) => s,
-                        Err(_) => "<unknown error>".to_owned(),
-                    };
-                    eprintln!("Uncaught exception: {}", str);
-                    eprintln!("Stacktrace: \n{}", ctx.take_stacktrace());
-                }
-            }
+    let ctx = Context::new(&mut vm);
+
+    let options = vm.options();
+    let as_module = !options.script;
+    let dump_stats = options.dump_stats;
+    let input = match (&options.eval, &options.file) {
+        (Some(source), _) => Some((in_current_dir("[eval]"), Ok(source.clone()))),
+        (None, Some(file)) if file.as_os_str() == "-" => {
+            let mut source = String::new();
+            let result = std::io::stdin().read_to_string(&mut source);
+            Some((in_current_dir("[stdin]"), result.map(|_| source)))
         }
-        Err(error) => {
-            eprintln!("Error while reading JS source: {}", error);
-            std::process::exit(1);
+        (None, Some(file)) => Some((
+            file.to_string_lossy().into_owned(),
+            std::fs::read_to_string(file),
+        )),
+        (None, None) => None,
+    };
+
+    let status = match input {
+        Some((path, Ok(source))) => run(ctx, &path, &source, as_module, dump_stats),
+        Some((path, Err(error))) => {
+            eprintln!("Error while reading JS source '{}': {}", path, error);
+            EXIT_FAILURE
         }
-    }
+        None => {
+            repl::run(ctx);
+            0
+        }
+    };
     unsafe {
         vm.dispose();
     }
 
-    std::process::exit(0);
+    // a script that enabled raw mode of the terminal may have ended without disabling it
+    restore_terminal();
+    std::process::exit(status);
+}
+
+/// Run `source` and then the event loop. Returns the exit status of the process.
+fn run(mut ctx: GcPointer<Context>, path: &str, source: &str, module: bool, stats: bool) -> i32 {
+    let start = std::time::Instant::now();
+    let result = if module {
+        run_module(ctx, path, source)
+    } else {
+        ctx.eval_internal(Some(path), false, source, false)
+    };
+    match result.and_then(|_| ctx.run_event_loop()) {
+        Ok(_) => {
+            if stats {
+                let elapsed = start.elapsed();
+                eprintln!("Executed in {}ms", elapsed.as_nanos() as f64 / 1000000f64);
+            }
+            0
+        }
+        Err(e) => {
+            let str = match e.to_string(ctx) {
+                Ok(s) => s,
+                Err(_) => "<unknown error>".to_owned(),
+            };
+            eprintln!("Uncaught exception: {}", str);
+            eprintln!("Stacktrace: \n{}", ctx.take_stacktrace());
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn run_module(ctx: GcPointer<Context>, path: &str, source: &str) -> Result<JsValue, JsValue> {
+    letroot!(
+        function = foo,
+        match ctx.compile_module(path, "<script>", source) {
+            Ok(function) => function.get_jsobject(),
+            Err(e) => {
+                match e.to_string(ctx) {
+                    Ok(val) => eprintln!("Compilation failed: {}", val),
+                    Err(_e) => eprintln!("Failed to get error as string"),
+                }
+                std::process::exit(EXIT_FAILURE);
+            }
+        }
+    );
+
+    let global = ctx.global_object();
+    let mut module_object = JsObject::new_empty(ctx);
+    let exports = JsObject::new_empty(ctx);
+    module_object
+        .put(ctx, "@exports".intern(), JsValue::new(exports), false)
+        .unwrap_or_else(|_| unreachable!());
+    let mut args = [JsValue::new(module_object)];
+    let mut args = Arguments::new(JsValue::encode_object_value(global), &mut args);
+
+    let f = function;
+    function
+        .as_function_mut()
+        .call(ctx, &mut args, JsValue::new(f))
+}
+
+/// Path of a pseudo file in the working directory, relative imports of `-e` and stdin scripts are
+/// resolved against the working directory.
+fn in_current_dir(name: &str) -> String {
+    std::env::current_dir()
+        .map(|dir| dir.join(name).to_string_lossy().into_owned())
+        .unwrap_or_else(|_| name.to_owned())
 }
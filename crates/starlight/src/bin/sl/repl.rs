@@ -0,0 +1,115 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Interactive REPL started by `sl` when no file is given.
+use rustyline::{error::ReadlineError, Editor};
+use starlight::{jsrt::inspect::inspect, prelude::*, vm::context::Context};
+use swc_ecmascript::parser::error::SyntaxError;
+
+const HELP: &str = "\
+.break    Discard the multi-line input
+.exit     Exit the REPL
+.help     Print this help message
+.load     Evaluate a file in the REPL: .load ./file.js
+
+Lines are evaluated in the same context, globals persist between them. Input that is not
+complete yet (open braces, unterminated template literals...) continues on the next line.";
+
+pub fn run(ctx: GcPointer<Context>) {
+    let mut editor = Editor::<()>::new();
+    let history =
+        std::env::var_os("HOME").map(|home| std::path::Path::new(&home).join(".sl_history"));
+    if let Some(history) = &history {
+        let _ = editor.load_history(history);
+    }
+    println!("Welcome to Starlight, type .help for more information.");
+
+    let mut buffer = String::new();
+    loop {
+        let prompt = if buffer.is_empty() { "> " } else { "... " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                if buffer.is_empty() {
+                    println!("(To exit, press Ctrl+D or type .exit)");
+                }
+                buffer.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("Failed to read the input: {}", error);
+                break;
+            }
+        };
+
+        if buffer.is_empty() && line.trim_start().starts_with('.') {
+            editor.add_history_entry(line.as_str());
+            let line = line.trim();
+            let (command, argument) = match line.find(char::is_whitespace) {
+                Some(at) => (&line[..at], line[at..].trim()),
+                None => (line, ""),
+            };
+            match command {
+                ".exit" => break,
+                ".help" => println!("{}", HELP),
+                ".break" => (),
+                ".load" => match std::fs::read_to_string(argument) {
+                    Ok(source) => evaluate(ctx, &source),
+                    Err(error) => eprintln!("Failed to load '{}': {}", argument, error),
+                },
+                _ => eprintln!("Invalid REPL command, type .help for a list of commands"),
+            }
+            continue;
+        }
+        if !buffer.is_empty() && line.trim() == ".break" {
+            buffer.clear();
+            continue;
+        }
+
+        buffer.push_str(&line);
+        buffer.push('\n');
+        if is_incomplete(&buffer) {
+            continue;
+        }
+        editor.add_history_entry(buffer.trim_end());
+        let source = std::mem::take(&mut buffer);
+        if !source.trim().is_empty() {
+            evaluate(ctx, &source);
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+    let _ = ctx.run_event_loop();
+}
+
+/// Evaluate `source` and print the result or the uncaught exception.
+fn evaluate(mut ctx: GcPointer<Context>, source: &str) {
+    match ctx.eval(source).and_then(|value| {
+        ctx.run_until_idle()?;
+        Ok(value)
+    }) {
+        Ok(value) => match inspect(ctx, value) {
+            Ok(string) => println!("{}", string),
+            Err(_) => println!("<uninspectable value>"),
+        },
+        Err(error) => {
+            let string = inspect(ctx, error).unwrap_or_else(|_| "<unknown error>".to_owned());
+            eprintln!("Uncaught {}", string);
+        }
+    }
+    ctx.take_stacktrace();
+}
+
+/// Whether parsing `source` failed only because the input ended too early.
+fn is_incomplete(source: &str) -> bool {
+    match starlight::vm::parse(source, false) {
+        Ok(_) => false,
+        Err(error) => matches!(
+            error.kind(),
+            SyntaxError::Eof | SyntaxError::UnterminatedTpl | SyntaxError::UnterminatedBlockComment
+        ),
+    }
+}
@@ -6,39 +6,16 @@ function ___StringIterator(string) {
     this.stringIteratorIteratedString = string;
 }
 
-___StringIterator.prototype.next = function next() {
-    var done = true;
-    var value = undefined;
-    var position = this.stringIteratorFieldIndex;
-    if (position !== -1) {
-        var string = this.stringIteratorIteratedString;
-        var length = string.length >>> 0;
-        if (position >= length) {
-            this.stringIteratorFieldIndex = -1;
-        } else {
-            done = false;
-            var first = string.charCodeAt(position);
-            if (first < 0xD800 || first > 0xDBFF || position + 1 === length)
-                value = string[position];
-            else {
-                var second = string.charCodeAt(position + 1);
-                if (second < 0xDC00 || second > 0XDFFF)
-                    value = string[position];
-                else
-                    value = string[position] + string[position + 1]
-            }
-            this.stringIteratorFieldIndex = position + value.length;
-        }
-    }
-    return {
-        value, done
-    }
-}
+___StringIterator.prototype.next = String.___stringIteratorNext;
 
 Object.defineProperty(String.prototype, Symbol.iterator, {
-    get: function () {
-        return function () {
-            return new ___StringIterator(this);
-        }
-    }
-})
\ No newline at end of file
+    value: function () {
+        "use strict";
+        if (this === null | this === undefined)
+            throw new TypeError("String.prototype[Symbol.iterator] requires that |this| not be null or undefined");
+        return new ___StringIterator(this + "");
+    },
+    writable: true,
+    enumerable: false,
+    configurable: true
+})
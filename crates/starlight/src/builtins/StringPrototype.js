@@ -70,6 +70,33 @@ String.prototype.replace = function replace(search, replace) {
 
     return __replace.___call(this, search, replace);
 }
+let __replaceAll = String.___replaceAll;
+let symMatch = Symbol.match;
+
+String.prototype.replaceAll = function replaceAll(search, replace) {
+    "use strict";
+
+    if (this == undefined | this == null)
+        throw new TypeError("String.prototype.replaceAll requires that |this| not be null or undefined");
+
+    if (search !== undefined & search !== null) {
+        var matcher = search[symMatch];
+        var isRegExp = matcher !== undefined ? !!matcher : search instanceof RegExpCtor;
+        if (isRegExp) {
+            var flags = search.flags;
+            if (flags === undefined | flags === null)
+                throw new TypeError("String.prototype.replaceAll requires the flags of a RegExp not to be null or undefined");
+            if (!(flags + "").includes("g"))
+                throw new TypeError("String.prototype.replaceAll must be called with a global RegExp");
+        }
+        var replacer = search[symReplace];
+        if (replacer) {
+            return replacer.___call(search, this, replace);
+        }
+    }
+
+    return __replaceAll.___call(this, search, replace);
+}
 let split_sym = Symbol.split;
 let fastSplit = String.prototype.___splitFast;
 
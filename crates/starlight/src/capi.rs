@@ -0,0 +1,911 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Stable C API.
+//!
+//! The C header is `include/starlight.h`, regenerate it with
+//! `cbindgen --config cbindgen.toml --output include/starlight.h` after changing this module.
+//!
+//! Every value returned to C is a [SlValue] handle. A handle keeps its value alive across garbage
+//! collections until it is released with [sl_value_release]. Functions that can throw return an
+//! [SlStatus] and store either the result or the thrown exception in their `result` parameter.
+//! Functions returning a pointer return null when an argument is invalid. Panics do not unwind
+//! into C, they are reported as [SlStatus::Panic] or as a null pointer.
+//! A VM and all of its contexts and handles must be used from the thread that created the VM.
+use std::{
+    os::raw::{c_char, c_void},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{
+    bytecompiler::CompileError,
+    gc::cell::GcPointer,
+    options::Options,
+    vm::{
+        arguments::Arguments,
+        array::JsArray,
+        context::Context,
+        error::{JsError, JsRangeError, JsReferenceError, JsSyntaxError, JsTypeError},
+        function::JsClosureFunction,
+        object::JsObject,
+        string::JsString,
+        symbol_table::{Internable, Symbol},
+        value::JsValue,
+        PersistentRooted, VirtualMachineRef,
+    },
+    Platform,
+};
+
+/// VM options, see [Options].
+pub struct SlOptions {
+    options: Options,
+}
+
+/// Virtual machine, owns the heap shared by its contexts.
+pub struct SlVm {
+    vm: VirtualMachineRef,
+}
+
+/// Context (realm) with its own global object.
+pub struct SlContext {
+    ctx: GcPointer<Context>,
+}
+
+/// Rooted handle to a JS value.
+pub struct SlValue {
+    root: PersistentRooted,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlStatus {
+    Ok = 0,
+    /// JS exception was thrown, `result` holds the exception.
+    Exception = 1,
+    /// Null pointer, invalid UTF-8 or a value of the wrong type was passed.
+    InvalidArgument = 2,
+    /// The engine panicked, the VM should be disposed.
+    Panic = 3,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlType {
+    Undefined = 0,
+    Null = 1,
+    Boolean = 2,
+    Number = 3,
+    String = 4,
+    Symbol = 5,
+    Object = 6,
+    Function = 7,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SlErrorKind {
+    Error = 0,
+    TypeError = 1,
+    RangeError = 2,
+    ReferenceError = 3,
+    SyntaxError = 4,
+}
+
+/// Native function callable from JS. `this_value` and `argv` are valid for the duration of the
+/// call only. Store the return value, or the exception when returning `SL_STATUS_EXCEPTION`, in
+/// `result`. The API takes ownership of that handle; leaving it null returns `undefined`.
+pub type SlNativeCallback = unsafe extern "C" fn(
+    ctx: *mut SlContext,
+    this_value: *const SlValue,
+    argv: *const *const SlValue,
+    argc: usize,
+    result: *mut *mut SlValue,
+    data: *mut c_void,
+) -> SlStatus;
+
+/// Run `f`, returning `default` if it panics. Unwinding into C is undefined behavior.
+fn guard<T>(default: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(default)
+}
+
+/// Return `$error` if one of the pointers is null.
+macro_rules! check_null {
+    ($error: expr, $($ptr: expr),+) => {
+        if $($ptr.is_null())||+ {
+            return $error;
+        }
+    };
+}
+
+unsafe fn new_handle(ctx: GcPointer<Context>, value: JsValue) -> *mut SlValue {
+    let mut vm = ctx.vm();
+    Box::into_raw(Box::new(SlValue {
+        root: vm.add_persistent_root(value),
+    }))
+}
+
+unsafe fn complete(
+    ctx: GcPointer<Context>,
+    outcome: Result<JsValue, JsValue>,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    let (status, value) = match outcome {
+        Ok(value) => (SlStatus::Ok, value),
+        Err(exception) => (SlStatus::Exception, exception),
+    };
+    if !result.is_null() {
+        *result = new_handle(ctx, value);
+    }
+    status
+}
+
+unsafe fn str_arg<'a>(data: *const c_char, len: usize) -> Option<&'a str> {
+    if data.is_null() {
+        return None;
+    }
+    std::str::from_utf8(slice::from_raw_parts(data as *const u8, len)).ok()
+}
+
+unsafe fn cstr_arg<'a>(data: *const c_char) -> Option<&'a str> {
+    if data.is_null() {
+        return None;
+    }
+    std::ffi::CStr::from_ptr(data).to_str().ok()
+}
+
+unsafe fn collect_args(argv: *const *const SlValue, argc: usize) -> Option<Vec<JsValue>> {
+    if argc == 0 {
+        return Some(vec![]);
+    }
+    if argv.is_null() {
+        return None;
+    }
+    let mut args = Vec::with_capacity(argc);
+    for &arg in slice::from_raw_parts(argv, argc) {
+        if arg.is_null() {
+            return None;
+        }
+        args.push((*arg).root.get_value());
+    }
+    Some(args)
+}
+
+/// Copy `string` into a NUL terminated buffer allocated with `malloc`.
+unsafe fn malloc_string(string: &str, len: *mut usize) -> *mut c_char {
+    let buffer = libc::malloc(string.len() + 1) as *mut u8;
+    if buffer.is_null() {
+        return buffer as _;
+    }
+    ptr::copy_nonoverlapping(string.as_ptr(), buffer, string.len());
+    *buffer.add(string.len()) = 0;
+    if !len.is_null() {
+        *len = string.len();
+    }
+    buffer as _
+}
+
+/// Initialize the GC and the symbol table. Called implicitly by [sl_vm_new].
+#[no_mangle]
+pub extern "C" fn sl_platform_initialize() {
+    guard((), Platform::initialize)
+}
+
+#[no_mangle]
+pub extern "C" fn sl_options_new() -> *mut SlOptions {
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(SlOptions {
+            options: Options::default(),
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_free(options: *mut SlOptions) {
+    guard((), || {
+        if !options.is_null() {
+            drop(Box::from_raw(options));
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_set_heap_size(
+    options: *mut SlOptions,
+    size: usize,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, options);
+    guard(SlStatus::Panic, || {
+        (*options).options.heap_size = size;
+        SlStatus::Ok
+    })
+}
+
+/// `0` removes the soft heap limit.
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_set_heap_soft_limit(
+    options: *mut SlOptions,
+    limit: usize,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, options);
+    guard(SlStatus::Panic, || {
+        (*options).options.heap_soft_limit = if limit == 0 { None } else { Some(limit) };
+        SlStatus::Ok
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_set_gc_threads(
+    options: *mut SlOptions,
+    threads: u32,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, options);
+    guard(SlStatus::Panic, || {
+        (*options).options.gc_threads = threads;
+        SlStatus::Ok
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_set_fuel(options: *mut SlOptions, fuel: u64) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, options);
+    guard(SlStatus::Panic, || {
+        (*options).options.fuel = Some(fuel);
+        SlStatus::Ok
+    })
+}
+
+/// `0` removes the timeout.
+#[no_mangle]
+pub unsafe extern "C" fn sl_options_set_timeout_ms(
+    options: *mut SlOptions,
+    timeout: u64,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, options);
+    guard(SlStatus::Panic, || {
+        (*options).options.timeout = if timeout == 0 { None } else { Some(timeout) };
+        SlStatus::Ok
+    })
+}
+
+/// Create a VM. Takes ownership of `options`, which may be null for the default options.
+#[no_mangle]
+pub unsafe extern "C" fn sl_vm_new(options: *mut SlOptions) -> *mut SlVm {
+    guard(ptr::null_mut(), || {
+        let options = if options.is_null() {
+            Options::default()
+        } else {
+            Box::from_raw(options).options
+        };
+        Box::into_raw(Box::new(SlVm {
+            vm: Platform::new_runtime(options, None),
+        }))
+    })
+}
+
+/// Free the VM with its heap. Every context and handle of the VM must be released before.
+#[no_mangle]
+pub unsafe extern "C" fn sl_vm_dispose(vm: *mut SlVm) {
+    guard((), || {
+        if !vm.is_null() {
+            Box::from_raw(vm).vm.dispose();
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_context_new(vm: *mut SlVm) -> *mut SlContext {
+    check_null!(ptr::null_mut(), vm);
+    guard(ptr::null_mut(), || {
+        let ctx = Context::new(&mut (*vm).vm);
+        Box::into_raw(Box::new(SlContext { ctx }))
+    })
+}
+
+/// Release the context, its global object is collected once no handle refers to it.
+/// Must not be called on the context passed to a [SlNativeCallback].
+#[no_mangle]
+pub unsafe extern "C" fn sl_context_dispose(ctx: *mut SlContext) {
+    guard((), || {
+        if !ctx.is_null() {
+            let ctx = Box::from_raw(ctx).ctx;
+            ctx.vm().remove_context(ctx);
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_context_global(ctx: *mut SlContext) -> *mut SlValue {
+    check_null!(ptr::null_mut(), ctx);
+    guard(ptr::null_mut(), || {
+        let ctx = (*ctx).ctx;
+        new_handle(ctx, JsValue::new(ctx.global_object()))
+    })
+}
+
+/// Stack trace of the last exception, allocated with `malloc`. Empty when there was no exception.
+#[no_mangle]
+pub unsafe extern "C" fn sl_context_take_stacktrace(
+    ctx: *mut SlContext,
+    len: *mut usize,
+) -> *mut c_char {
+    check_null!(ptr::null_mut(), ctx);
+    guard(ptr::null_mut(), || {
+        let mut ctx = (*ctx).ctx;
+        malloc_string(&ctx.take_stacktrace(), len)
+    })
+}
+
+/// Evaluate a script of `len` bytes of UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn sl_eval(
+    ctx: *mut SlContext,
+    source: *const c_char,
+    len: usize,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx);
+    guard(SlStatus::Panic, || {
+        let mut ctx = (*ctx).ctx;
+        let source = match str_arg(source, len) {
+            Some(source) => source,
+            None => return SlStatus::InvalidArgument,
+        };
+        complete(ctx, ctx.eval(source), result)
+    })
+}
+
+/// Compile a script into a function that runs it when called. `path` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn sl_compile(
+    ctx: *mut SlContext,
+    path: *const c_char,
+    name: *const c_char,
+    source: *const c_char,
+    len: usize,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx);
+    guard(SlStatus::Panic, || {
+        let ctx = (*ctx).ctx;
+        let (name, source) = match (cstr_arg(name), str_arg(source, len)) {
+            (Some(name), Some(source)) => (name, source),
+            _ => return SlStatus::InvalidArgument,
+        };
+        let path = if path.is_null() {
+            "."
+        } else {
+            match cstr_arg(path) {
+                Some(path) => path,
+                None => return SlStatus::InvalidArgument,
+            }
+        };
+        let outcome = ctx
+            .compile(path, name, source, false)
+            .map_err(|error| match error {
+                CompileError::Val(value) => value,
+                CompileError::NotYetImpl(msg) => JsValue::new(ctx.new_syntax_error(msg)),
+            });
+        complete(ctx, outcome, result)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_clone(value: *const SlValue) -> *mut SlValue {
+    check_null!(ptr::null_mut(), value);
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(SlValue {
+            root: (*value).root.clone(),
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_release(value: *mut SlValue) {
+    guard((), || {
+        if !value.is_null() {
+            drop(Box::from_raw(value));
+        }
+    })
+}
+
+/// Handle to the value returned by `f`, null if `ctx` is null.
+unsafe fn new_value(
+    ctx: *mut SlContext,
+    f: impl FnOnce(GcPointer<Context>) -> Option<JsValue>,
+) -> *mut SlValue {
+    check_null!(ptr::null_mut(), ctx);
+    guard(ptr::null_mut(), || {
+        let ctx = (*ctx).ctx;
+        match f(ctx) {
+            Some(value) => new_handle(ctx, value),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_undefined(ctx: *mut SlContext) -> *mut SlValue {
+    new_value(ctx, |_| Some(JsValue::UNDEFINED))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_null(ctx: *mut SlContext) -> *mut SlValue {
+    new_value(ctx, |_| Some(JsValue::encode_null_value()))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_boolean(ctx: *mut SlContext, value: bool) -> *mut SlValue {
+    new_value(ctx, |_| Some(JsValue::new(value)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_number(ctx: *mut SlContext, value: f64) -> *mut SlValue {
+    new_value(ctx, |_| Some(JsValue::new(value)))
+}
+
+/// Returns null if `data` is not valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn sl_string(
+    ctx: *mut SlContext,
+    data: *const c_char,
+    len: usize,
+) -> *mut SlValue {
+    new_value(ctx, |ctx| {
+        str_arg(data, len).map(|string| JsValue::new(JsString::new(ctx, string)))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_object_new(ctx: *mut SlContext) -> *mut SlValue {
+    new_value(ctx, |ctx| Some(JsValue::new(JsObject::new_empty(ctx))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_array_new(ctx: *mut SlContext, len: u32) -> *mut SlValue {
+    new_value(ctx, |ctx| Some(JsValue::new(JsArray::new(ctx, len))))
+}
+
+/// Create an error object of `kind` with message `message` (NUL terminated UTF-8).
+#[no_mangle]
+pub unsafe extern "C" fn sl_error_new(
+    ctx: *mut SlContext,
+    kind: SlErrorKind,
+    message: *const c_char,
+) -> *mut SlValue {
+    new_value(ctx, |ctx| {
+        let message = JsString::new(ctx, cstr_arg(message)?);
+        let error = match kind {
+            SlErrorKind::Error => JsError::new(ctx, message, None),
+            SlErrorKind::TypeError => JsTypeError::new(ctx, message, None),
+            SlErrorKind::RangeError => JsRangeError::new(ctx, message, None),
+            SlErrorKind::ReferenceError => JsReferenceError::new(ctx, message, None),
+            SlErrorKind::SyntaxError => JsSyntaxError::new(ctx, message, None),
+        };
+        Some(JsValue::new(error))
+    })
+}
+
+/// Create a function named `name` that calls `callback` with `data`.
+#[no_mangle]
+pub unsafe extern "C" fn sl_function_new(
+    ctx: *mut SlContext,
+    name: *const c_char,
+    callback: Option<SlNativeCallback>,
+    data: *mut c_void,
+    length: u32,
+) -> *mut SlValue {
+    new_value(ctx, |ctx| {
+        let name = cstr_arg(name)?.intern();
+        let callback = callback?;
+        let function = JsClosureFunction::new(
+            ctx,
+            name,
+            move |ctx, args| {
+                let mut callee_ctx = SlContext { ctx };
+                let this = new_handle(ctx, args.this);
+                let argv = (0..args.size())
+                    .map(|i| new_handle(ctx, args.at(i)) as *const SlValue)
+                    .collect::<Vec<_>>();
+                let mut result = ptr::null_mut();
+                let status = callback(
+                    &mut callee_ctx,
+                    this,
+                    argv.as_ptr(),
+                    argv.len(),
+                    &mut result,
+                    data,
+                );
+                sl_value_release(this);
+                for arg in argv {
+                    sl_value_release(arg as *mut SlValue);
+                }
+                let value = if result.is_null() {
+                    JsValue::UNDEFINED
+                } else {
+                    let value = (*result).root.get_value();
+                    sl_value_release(result);
+                    value
+                };
+                match status {
+                    SlStatus::Ok => Ok(value),
+                    SlStatus::Exception => Err(value),
+                    SlStatus::InvalidArgument => Err(JsValue::new(
+                        ctx.new_type_error("invalid argument passed to native function"),
+                    )),
+                    SlStatus::Panic => {
+                        let message = JsString::new(ctx, "native function panicked");
+                        Err(JsValue::new(JsError::new(ctx, message, None)))
+                    }
+                }
+            },
+            length,
+        );
+        Some(JsValue::new(function))
+    })
+}
+
+/// Store the type of `value` in `ty`.
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_type(value: *const SlValue, ty: *mut SlType) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, value, ty);
+    guard(SlStatus::Panic, || {
+        let value = (*value).root.get_value();
+        *ty = if value.is_undefined() {
+            SlType::Undefined
+        } else if value.is_null() {
+            SlType::Null
+        } else if value.is_bool() {
+            SlType::Boolean
+        } else if value.is_number() {
+            SlType::Number
+        } else if value.is_jsstring() {
+            SlType::String
+        } else if value.is_symbol() {
+            SlType::Symbol
+        } else if value.is_callable() {
+            SlType::Function
+        } else {
+            SlType::Object
+        };
+        SlStatus::Ok
+    })
+}
+
+/// `ToBoolean(value)`.
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_to_boolean(
+    value: *const SlValue,
+    boolean: *mut bool,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, value, boolean);
+    guard(SlStatus::Panic, || {
+        *boolean = (*value).root.get_value().to_boolean();
+        SlStatus::Ok
+    })
+}
+
+/// `ToNumber(value)`.
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_to_number(
+    ctx: *mut SlContext,
+    value: *const SlValue,
+    number: *mut f64,
+    exception: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, value, number);
+    guard(SlStatus::Panic, || {
+        let ctx = (*ctx).ctx;
+        match (*value).root.get_value().to_number(ctx) {
+            Ok(value) => {
+                *number = value;
+                SlStatus::Ok
+            }
+            Err(error) => complete(ctx, Err(error), exception),
+        }
+    })
+}
+
+/// `ToString(value)` as UTF-8 allocated with `malloc`, free it with [sl_string_free].
+#[no_mangle]
+pub unsafe extern "C" fn sl_value_to_string(
+    ctx: *mut SlContext,
+    value: *const SlValue,
+    string: *mut *mut c_char,
+    len: *mut usize,
+    exception: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, value, string);
+    guard(SlStatus::Panic, || {
+        let ctx = (*ctx).ctx;
+        match (*value).root.get_value().to_string(ctx) {
+            Ok(value) => {
+                *string = malloc_string(&value, len);
+                SlStatus::Ok
+            }
+            Err(error) => complete(ctx, Err(error), exception),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_string_free(string: *mut c_char) {
+    guard((), || libc::free(string as _))
+}
+
+unsafe fn property_get(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    key: Symbol,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, object);
+    let ctx = (*ctx).ctx;
+    let outcome = (*object)
+        .root
+        .get_value()
+        .to_object(ctx)
+        .and_then(|mut object| object.get(ctx, key));
+    complete(ctx, outcome, result)
+}
+
+unsafe fn property_set(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    key: Symbol,
+    value: *const SlValue,
+    exception: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, object, value);
+    let ctx = (*ctx).ctx;
+    let value = (*value).root.get_value();
+    let outcome = (*object)
+        .root
+        .get_value()
+        .to_object(ctx)
+        .and_then(|mut object| object.put(ctx, key, value, true));
+    match outcome {
+        Ok(()) => SlStatus::Ok,
+        Err(error) => complete(ctx, Err(error), exception),
+    }
+}
+
+/// `object[key]` where `key` is NUL terminated UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn sl_object_get(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    key: *const c_char,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    guard(SlStatus::Panic, || match cstr_arg(key) {
+        Some(key) => property_get(ctx, object, key.intern(), result),
+        None => SlStatus::InvalidArgument,
+    })
+}
+
+/// `object[key] = value`, in strict mode.
+#[no_mangle]
+pub unsafe extern "C" fn sl_object_set(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    key: *const c_char,
+    value: *const SlValue,
+    exception: *mut *mut SlValue,
+) -> SlStatus {
+    guard(SlStatus::Panic, || match cstr_arg(key) {
+        Some(key) => property_set(ctx, object, key.intern(), value, exception),
+        None => SlStatus::InvalidArgument,
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_object_get_index(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    index: u32,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    guard(SlStatus::Panic, || {
+        property_get(ctx, object, Symbol::Index(index), result)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn sl_object_set_index(
+    ctx: *mut SlContext,
+    object: *const SlValue,
+    index: u32,
+    value: *const SlValue,
+    exception: *mut *mut SlValue,
+) -> SlStatus {
+    guard(SlStatus::Panic, || {
+        property_set(ctx, object, Symbol::Index(index), value, exception)
+    })
+}
+
+/// Call `function` with `this_value` (null for `undefined`) and `argc` arguments.
+#[no_mangle]
+pub unsafe extern "C" fn sl_call(
+    ctx: *mut SlContext,
+    function: *const SlValue,
+    this_value: *const SlValue,
+    argv: *const *const SlValue,
+    argc: usize,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, function);
+    guard(SlStatus::Panic, || {
+        let ctx = (*ctx).ctx;
+        let function = (*function).root.get_value();
+        let mut args = match collect_args(argv, argc) {
+            Some(args) if function.is_callable() => args,
+            _ => return SlStatus::InvalidArgument,
+        };
+        let this = if this_value.is_null() {
+            JsValue::UNDEFINED
+        } else {
+            (*this_value).root.get_value()
+        };
+        let mut args = Arguments::new(this, &mut args);
+        let outcome = function
+            .get_jsobject()
+            .as_function_mut()
+            .call(ctx, &mut args, function);
+        complete(ctx, outcome, result)
+    })
+}
+
+/// `new function(...argv)`.
+#[no_mangle]
+pub unsafe extern "C" fn sl_construct(
+    ctx: *mut SlContext,
+    function: *const SlValue,
+    argv: *const *const SlValue,
+    argc: usize,
+    result: *mut *mut SlValue,
+) -> SlStatus {
+    check_null!(SlStatus::InvalidArgument, ctx, function);
+    guard(SlStatus::Panic, || {
+        let ctx = (*ctx).ctx;
+        let function = (*function).root.get_value();
+        let mut args = match collect_args(argv, argc) {
+            Some(args) if function.is_callable() => args,
+            _ => return SlStatus::InvalidArgument,
+        };
+        let mut object = function.get_jsobject();
+        let outcome = object.func_construct_map(ctx).and_then(|map| {
+            let mut args = Arguments::new(JsValue::UNDEFINED, &mut args);
+            args.ctor_call = true;
+            object
+                .as_function_mut()
+                .construct(ctx, &mut args, Some(map), function)
+        });
+        complete(ctx, outcome, result)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    unsafe extern "C" fn add(
+        ctx: *mut SlContext,
+        _this: *const SlValue,
+        argv: *const *const SlValue,
+        argc: usize,
+        result: *mut *mut SlValue,
+        data: *mut c_void,
+    ) -> SlStatus {
+        if argc != 2 {
+            let message = CString::new("expected 2 arguments").unwrap();
+            *result = sl_error_new(ctx, SlErrorKind::TypeError, message.as_ptr());
+            return SlStatus::Exception;
+        }
+        let mut sum = *(data as *const f64);
+        for i in 0..argc {
+            let mut number = 0.0;
+            let status = sl_value_to_number(ctx, *argv.add(i), &mut number, result);
+            if status != SlStatus::Ok {
+                return status;
+            }
+            sum += number;
+        }
+        *result = sl_number(ctx, sum);
+        SlStatus::Ok
+    }
+
+    #[test]
+    fn test_c_api() {
+        unsafe {
+            let options = sl_options_new();
+            let vm = sl_vm_new(options);
+            let ctx = sl_context_new(vm);
+
+            let mut bias = 0.5f64;
+            let name = CString::new("add").unwrap();
+            let function =
+                sl_function_new(ctx, name.as_ptr(), Some(add), &mut bias as *mut f64 as _, 2);
+            let global = sl_context_global(ctx);
+            let mut exception = ptr::null_mut();
+            let status = sl_object_set(ctx, global, name.as_ptr(), function, &mut exception);
+            assert_eq!(status, SlStatus::Ok);
+
+            let source = "add(1, 2)";
+            let mut result = ptr::null_mut();
+            let status = sl_eval(ctx, source.as_ptr() as _, source.len(), &mut result);
+            assert_eq!(status, SlStatus::Ok);
+            let mut ty = SlType::Undefined;
+            assert_eq!(sl_value_type(result, &mut ty), SlStatus::Ok);
+            assert_eq!(ty, SlType::Number);
+            let mut number = 0.0;
+            sl_value_to_number(ctx, result, &mut number, &mut exception);
+            assert_eq!(number, 3.5);
+            sl_value_release(result);
+
+            let source = "try { add(1) } catch (e) { e instanceof TypeError }";
+            let status = sl_eval(ctx, source.as_ptr() as _, source.len(), &mut result);
+            assert_eq!(status, SlStatus::Ok);
+            let mut boolean = false;
+            assert_eq!(sl_value_to_boolean(result, &mut boolean), SlStatus::Ok);
+            assert!(boolean);
+            sl_value_release(result);
+
+            let source = "throw new RangeError('boom')";
+            let status = sl_eval(ctx, source.as_ptr() as _, source.len(), &mut result);
+            assert_eq!(status, SlStatus::Exception);
+            let mut string = ptr::null_mut();
+            let mut len = 0;
+            sl_value_to_string(ctx, result, &mut string, &mut len, &mut exception);
+            let message = std::str::from_utf8(slice::from_raw_parts(string as *const u8, len));
+            assert_eq!(message, Ok("RangeError: boom"));
+            sl_string_free(string);
+            sl_value_release(result);
+
+            let mut args = [sl_number(ctx, 2.0), sl_number(ctx, 3.0)];
+            let status = sl_call(
+                ctx,
+                function,
+                ptr::null(),
+                args.as_ptr() as _,
+                args.len(),
+                &mut result,
+            );
+            assert_eq!(status, SlStatus::Ok);
+            sl_value_to_number(ctx, result, &mut number, &mut exception);
+            assert_eq!(number, 5.5);
+            for arg in args.iter_mut() {
+                sl_value_release(*arg);
+            }
+
+            sl_value_release(result);
+
+            assert_eq!(
+                sl_options_set_fuel(ptr::null_mut(), 1),
+                SlStatus::InvalidArgument
+            );
+            assert_eq!(
+                sl_eval(
+                    ptr::null_mut(),
+                    source.as_ptr() as _,
+                    source.len(),
+                    &mut result
+                ),
+                SlStatus::InvalidArgument
+            );
+            assert_eq!(
+                sl_value_type(ptr::null(), &mut ty),
+                SlStatus::InvalidArgument
+            );
+            assert!(sl_context_global(ptr::null_mut()).is_null());
+            assert!(sl_function_new(ctx, name.as_ptr(), None, ptr::null_mut(), 0).is_null());
+            assert_eq!(guard(SlStatus::Panic, || panic!("boom")), SlStatus::Panic);
+
+            sl_value_release(function);
+            sl_value_release(global);
+            sl_context_dispose(ctx);
+            sl_vm_dispose(vm);
+        }
+    }
+}
@@ -8,16 +8,47 @@ pub use comet::internal::trace_trait::TraceTrait as Trace;
 pub use comet::visitor::Visitor;
 use cometgc::gcref::GcRef;
 use mopa::mopafy;
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::intrinsics::{size_of, transmute};
+use std::intrinsics::{size_of, transmute, unlikely};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::ptr::NonNull;
+use std::rc::Rc;
 
 use crate::options::Options;
+
+/// The near limit callback is invoked once live bytes after a collection reach this percentage
+/// of the soft limit, which leaves room to allocate while the embedder decides.
+const NEAR_LIMIT_PERCENT: usize = 90;
+
 pub struct Heap {
     heap: Box<CometHeap>,
+    /// Off-heap memory reported with [Heap::reserve_external].
+    external: Rc<Cell<usize>>,
+    soft_limit: Option<usize>,
+    near_limit_callback: Option<Box<dyn FnMut(usize, usize) -> usize>>,
+    limit_exceeded: bool,
+    /// Changes whenever a collection marks the heap, including collections that comet starts
+    /// on its own while allocating.
+    collections: Rc<Cell<usize>>,
+    /// Value of `collections` when the soft limit was last checked against the live bytes.
+    checked_collections: usize,
 }
+
+/// Off-heap memory owned by a cell, e.g. the data block of an `ArrayBuffer`. It counts against
+/// the soft limit of the [Heap] that reported it until it is dropped.
+pub struct ExternalMemory {
+    counter: Rc<Cell<usize>>,
+    size: usize,
+}
+
+impl Drop for ExternalMemory {
+    fn drop(&mut self) {
+        self.counter.set(self.counter.get() - self.size);
+    }
+}
+
 #[allow(dead_code)]
 pub struct SimpleMarkingConstraint {
     name: String,
@@ -51,16 +82,164 @@ impl Heap {
 
         let mut heap = CometHeap::new(configs);
         heap.add_core_constraints();
-        Self { heap }
+        let collections = Rc::new(Cell::new(0));
+        let counter = collections.clone();
+        heap.add_constraint(SimpleMarkingConstraint::new(
+            "Collection counter",
+            move |_| counter.set(counter.get().wrapping_add(1)),
+        ));
+        Self {
+            heap,
+            external: Rc::new(Cell::new(0)),
+            soft_limit: opts.heap_soft_limit,
+            near_limit_callback: None,
+            limit_exceeded: false,
+            collections,
+            checked_collections: 0,
+        }
+    }
+
+    /// Bytes that count against the soft limit: cells that survived the last sweep, cells
+    /// allocated since then and off-heap memory reported with [Heap::reserve_external].
+    pub fn live_bytes(&self) -> usize {
+        // comet resets its allocation counter to the size of the surviving cells when it sweeps
+        self.heap.stats().allocated + self.external.get()
+    }
+
+    /// Run a full collection and return the live bytes it left.
+    pub fn measure_live_bytes(&mut self) -> usize {
+        self.gc();
+        self.live_bytes()
+    }
+
+    pub fn soft_limit(&self) -> Option<usize> {
+        self.soft_limit
+    }
+
+    /// Set soft limit of the heap. Unlike `Options::heap_size` exceeding the soft limit does not
+    /// abort the process: the near limit callback is asked for a new limit and if it does not
+    /// provide one fallible allocations such as [Heap::try_allocate] fail. Other allocations
+    /// succeed and the interpreter throws `RangeError` at its next poll if collecting garbage
+    /// does not bring the heap back under the limit.
+    pub fn set_soft_limit(&mut self, limit: Option<usize>) {
+        self.soft_limit = limit;
+        self.limit_exceeded = false;
     }
+
+    /// Register callback invoked after a collection that left live bytes near the soft limit,
+    /// at 90% of it or above. It receives the live bytes and the current limit and returns the
+    /// new limit. Returning the current limit keeps it, allocations past it fail as usual.
+    pub fn set_near_heap_limit_callback(
+        &mut self,
+        callback: impl FnMut(usize, usize) -> usize + 'static,
+    ) {
+        self.near_limit_callback = Some(Box::new(callback));
+    }
+
+    /// Returns true once after an allocation that cannot fail exceeded the soft limit.
+    pub(crate) fn take_limit_exceeded(&mut self) -> bool {
+        std::mem::replace(&mut self.limit_exceeded, false)
+    }
+
+    /// Returns false when live bytes exceed the soft limit even after a collection.
+    pub(crate) fn check_soft_limit(&mut self) -> bool {
+        self.reserve(0)
+    }
+
+    /// Returns false when `size` more bytes do not fit under the soft limit. This has to be
+    /// called before the memory is allocated because it may collect garbage.
+    #[inline]
+    fn reserve(&mut self, size: usize) -> bool {
+        if self.soft_limit.is_none() {
+            return true;
+        }
+        if unlikely(self.collections.get() != self.checked_collections) {
+            self.collected();
+        }
+        match self.soft_limit {
+            Some(limit) if unlikely(self.live_bytes() + size > limit) => {
+                self.soft_limit_reached(size)
+            }
+            _ => true,
+        }
+    }
+
+    /// Live bytes include garbage until a collection sweeps it, collect before failing.
+    #[cold]
+    fn soft_limit_reached(&mut self, size: usize) -> bool {
+        self.gc();
+        match self.soft_limit {
+            Some(limit) => self.live_bytes() + size <= limit,
+            None => true,
+        }
+    }
+
+    /// Check live bytes against the soft limit once per collection and give the near limit
+    /// callback a chance to raise the limit before allocations start to fail.
+    fn collected(&mut self) {
+        self.checked_collections = self.collections.get();
+        let limit = match self.soft_limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        let live = self.live_bytes();
+        if live < limit / 100 * NEAR_LIMIT_PERCENT {
+            return;
+        }
+        if let Some(ref mut callback) = self.near_limit_callback {
+            self.soft_limit = Some(callback(live, limit));
+        }
+    }
+
+    /// Report `size` bytes of off-heap memory owned by a cell. Returns `None` when it does not
+    /// fit under the soft limit.
+    pub fn try_reserve_external(&mut self, size: usize) -> Option<ExternalMemory> {
+        if !self.reserve(size) {
+            return None;
+        }
+        Some(self.external_memory(size))
+    }
+
+    /// Like [Heap::try_reserve_external] but for memory that already exists. Exceeding the soft
+    /// limit is handled like in [Heap::allocate_raw].
+    pub fn reserve_external(&mut self, size: usize) -> ExternalMemory {
+        if !self.limit_exceeded && !self.reserve(size) {
+            self.limit_exceeded = true;
+        }
+        self.external_memory(size)
+    }
+
+    fn external_memory(&mut self, size: usize) -> ExternalMemory {
+        self.external.set(self.external.get() + size);
+        ExternalMemory {
+            counter: self.external.clone(),
+            size,
+        }
+    }
+
     pub fn gc(&mut self) {
         self.heap.collect_garbage();
+        self.collected();
     }
+    /// Allocate memory for a cell of `size` bytes. Returns `None` when the heap is full or when
+    /// the cell does not fit under the soft limit.
     pub fn allocate_(
         &mut self,
         size: usize,
         vtable: usize,
         idx: GCInfoIndex,
+    ) -> Option<NonNull<GcPointerBase>> {
+        if self.limit_exceeded || !self.reserve(size) {
+            return None;
+        }
+        self.allocate_unchecked(size, vtable, idx)
+    }
+
+    fn allocate_unchecked(
+        &mut self,
+        size: usize,
+        vtable: usize,
+        idx: GCInfoIndex,
     ) -> Option<NonNull<GcPointerBase>> {
         unsafe {
             let ptr = self
@@ -78,13 +257,19 @@ impl Heap {
         }
     }
 
+    /// Allocate memory for a cell of `size` bytes. Aborts when the heap is full. Exceeding the
+    /// soft limit does not fail the allocation, the interpreter throws `RangeError` at its next
+    /// poll instead.
     pub fn allocate_raw(
         &mut self,
         size: usize,
         vtable: usize,
         idx: GCInfoIndex,
     ) -> *mut GcPointerBase {
-        self.allocate_(size, vtable, idx)
+        if !self.limit_exceeded && !self.reserve(size) {
+            self.limit_exceeded = true;
+        }
+        self.allocate_unchecked(size, vtable, idx)
             .unwrap_or_else(|| memory_oom())
             .as_ptr()
     }
@@ -103,6 +288,23 @@ impl Heap {
             }
         }
     }
+
+    /// Like [Heap::allocate] but returns `None` when the heap is full or when the soft limit is
+    /// exceeded.
+    pub fn try_allocate<T: GcCell + GCInfoTrait<T> + Trace + Finalize<T>>(
+        &mut self,
+        value: T,
+    ) -> Option<GcPointer<T>> {
+        let size = value.compute_size();
+        let memory = self.allocate_(size, vtable_of(&value), T::index())?;
+        unsafe {
+            (*memory.as_ptr()).data::<T>().write(value);
+            Some(GcPointer {
+                base: memory,
+                marker: PhantomData,
+            })
+        }
+    }
     /*
     pub fn walk(&mut self,callback: &mut dyn FnMut(*mut GcPointerBase)) -> SafepointScope
     {
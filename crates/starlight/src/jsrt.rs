@@ -5,36 +5,51 @@ use crate::{
     constant::*,
     define_op_builtins,
     gc::cell::{GcPointer, WeakRef},
-    jsrt::{boolean::JsBoolean, date::JsDate, math::JsMath, regexp::JsRegExp, weak_ref::JsWeakRef},
+    jsrt::{
+        atomics::JsAtomics, boolean::JsBoolean, console::JsConsole, date::JsDate,
+        encoding::JsEncoding, math::JsMath, regexp::JsRegExp, weak_ref::JsWeakRef,
+        worker::JsWorker,
+    },
     vm::{
-        arguments::Arguments, array::JsArray, attributes::*, builder::Builtin, class::JsClass,
-        context::Context, function::*, object::*, property_descriptor::*, string::*, structure::*,
+        arguments::Arguments, array::JsArray, attributes::*, builder::Builtin,
+        capabilities::Capability, class::JsClass, context::Context, function::*, map::JsMap,
+        module_loader::ModuleSource, object::*, property_descriptor::*, string::*, structure::*,
         symbol_table::*, value::*, ModuleKind,
     },
 };
 use std::{collections::HashMap, rc::Rc};
 pub mod array;
 pub mod array_buffer;
+pub mod atomics;
 pub mod boolean;
+pub mod console;
 pub mod data_view;
 pub mod date;
+pub mod encoding;
 pub mod error;
 #[cfg(all(target_pointer_width = "64", feature = "ffi"))]
 pub mod ffi;
 pub mod function;
 pub mod generator;
 pub mod global;
+pub mod inspect;
+pub mod int32_array;
 pub mod js262;
 pub mod jsstd;
+pub mod map;
 pub mod math;
 pub mod number;
 pub mod object;
 pub mod promise;
 pub mod regexp;
 pub mod string;
+pub mod structured_clone;
 pub mod symbol;
+pub mod timers;
 pub mod weak_ref;
+pub mod worker;
 pub(crate) fn print(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
     for i in 0..args.size() {
         let value = args.at(i);
         let string = value.to_string(ctx)?;
@@ -187,6 +202,64 @@ pub static VM_NATIVE_REFERENCES: Lazy<Vec<usize>> = Lazy::new(|| {
         jsstd::file::std_file_read_bytes_to_end as _,
         jsstd::file::std_file_close as _,
         jsstd::std_args as _,
+        jsstd::std_exit as _,
+        jsstd::fs::init_fs as _,
+        jsstd::fs::fs_read_dir as _,
+        jsstd::fs::fs_mkdir as _,
+        jsstd::fs::fs_remove as _,
+        jsstd::fs::fs_rename as _,
+        jsstd::fs::fs_stat as _,
+        jsstd::fs::fs_exists as _,
+        jsstd::fs::fs_read_dir_async as _,
+        jsstd::fs::fs_mkdir_async as _,
+        jsstd::fs::fs_remove_async as _,
+        jsstd::fs::fs_rename_async as _,
+        jsstd::fs::fs_stat_async as _,
+        jsstd::fs::fs_exists_async as _,
+        jsstd::path::init_path as _,
+        jsstd::path::path_join as _,
+        jsstd::path::path_resolve as _,
+        jsstd::process::init_process as _,
+        jsstd::process::init_os as _,
+        jsstd::process::process_env as _,
+        jsstd::process::process_cwd as _,
+        jsstd::process::process_chdir as _,
+        jsstd::process::process_hrtime as _,
+        jsstd::process::os_homedir as _,
+        jsstd::process::os_tmpdir as _,
+        jsstd::process::os_available_parallelism as _,
+        jsstd::child_process::process_exec as _,
+        jsstd::child_process::process_spawn as _,
+        jsstd::child_process::child_write as _,
+        jsstd::child_process::child_close_stdin as _,
+        jsstd::child_process::child_kill as _,
+        jsstd::net::net_listen as _,
+        jsstd::net::net_connect as _,
+        jsstd::net::net_bind as _,
+        jsstd::net::listener_accept as _,
+        jsstd::net::listener_close as _,
+        jsstd::net::stream_read as _,
+        jsstd::net::stream_write as _,
+        jsstd::net::stream_end as _,
+        jsstd::net::stream_close as _,
+        jsstd::net::udp_send as _,
+        jsstd::net::udp_receive as _,
+        jsstd::net::udp_close as _,
+        jsstd::io::init_io as _,
+        jsstd::io::io_isatty as _,
+        jsstd::io::io_strip_ansi as _,
+        jsstd::io::io_stream_isatty as _,
+        jsstd::io::io_stream_size as _,
+        jsstd::io::io_read as _,
+        jsstd::io::io_read_line as _,
+        jsstd::io::io_read_all as _,
+        jsstd::io::io_listen as _,
+        jsstd::io::io_listener_close as _,
+        jsstd::io::io_set_raw_mode as _,
+        jsstd::io::io_is_raw as _,
+        jsstd::io::io_write as _,
+        jsstd::std_resolve_promise as _,
+        jsstd::std_reject_promise as _,
         // Misc
         JsArrayBuffer::class() as *const _ as usize,
         js262::_262_create_realm as _,
@@ -335,82 +408,71 @@ pub fn to_property_descriptor(
     }
 }
 
+/// `@loader` function called by `import` declarations. Natively registered modules are looked
+/// up by name, everything else goes through the [ModuleLoader](crate::vm::module_loader::ModuleLoader)
+/// of the context.
 pub(crate) fn module_load(
     mut ctx: GcPointer<Context>,
     args: &Arguments,
 ) -> Result<JsValue, JsValue> {
-    let name = args.at(0).to_string(ctx)?;
-    let rel_path = unsafe { (*ctx.stack.current).code_block.unwrap().path.clone() };
-    let _is_js_load = (name.starts_with("./")
-        || name.starts_with("../")
-        || name.starts_with('/')
-        || name.ends_with(".js"))
-        && name.ends_with(".js");
-    let spath = name;
-    let mut spath = if rel_path.is_empty() {
-        spath
+    let specifier = args.at(0).to_string(ctx)?;
+    let base = unsafe { (*ctx.stack.current).code_block.unwrap().path.clone() };
+
+    let key = if ctx.modules().contains_key(&specifier) {
+        specifier
     } else {
-        format!("{}/{}", rel_path, spath)
-    };
-    if cfg!(windows) {
-        spath = spath.replace("/", "\\");
-    }
-    let path = std::path::Path::new(&spath);
-    let path = match path.canonicalize() {
-        Err(e) => {
-            return Err(JsValue::new(ctx.new_reference_error(format!(
-                "Module '{}' not found: '{}'",
-                path.display(),
-                e
-            ))))
-        }
-        Ok(path) => path,
+        ctx.loader().resolve(ctx, &specifier, &base)?
     };
 
     let mut module_object = JsObject::new_empty(ctx);
-    let mut exports = JsObject::new_empty(ctx);
+    let exports = JsObject::new_empty(ctx);
     module_object.put(ctx, S_EXPORTS.intern(), JsValue::new(exports), false)?;
-    let mut args = [JsValue::new(module_object)];
-    let mut args = Arguments::new(JsValue::encode_undefined_value(), &mut args);
 
-    if let Some(module) = ctx.modules().get(&spath).copied() {
-        match module {
-            ModuleKind::Initialized(x) => {
-                return Ok(JsValue::new(x));
+    let source = match ctx.modules().get(&key).copied() {
+        Some(ModuleKind::Initialized(module)) => return Ok(JsValue::new(module)),
+        Some(ModuleKind::NativeUninit(init)) => ModuleSource::Native(init),
+        None => ctx.loader().load(ctx, &key)?,
+    };
+    match source {
+        ModuleSource::Module(mut module) => {
+            if !module.has_own_property(ctx, S_EXPORTS.intern()) {
+                return Err(JsValue::new(ctx.new_type_error(format!(
+                    "Module '{}' does not contain '@exports' property",
+                    key
+                ))));
             }
-            ModuleKind::NativeUninit(init) => {
-                let mut module = module_object;
-                init(ctx, module)?;
-                ctx.modules()
-                    .insert(spath.clone(), ModuleKind::Initialized(module));
-
-                return Ok(JsValue::new(module));
+            module_object = module;
+        }
+        ModuleSource::Native(init) => init(ctx, module_object)?,
+        ModuleSource::Source(source) => {
+            let path = std::path::Path::new(&key);
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| key.clone());
+            let base = path
+                .parent()
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let module_fun = ctx.compile_module_with_base(&key, &base, &name, &source)?;
+            let mut module_fun = module_fun.get_jsobject();
+            let mut args = [JsValue::new(module_object)];
+            let mut args = Arguments::new(JsValue::encode_undefined_value(), &mut args);
+            // register the module before running it so that import cycles terminate
+            ctx.modules()
+                .insert(key.clone(), ModuleKind::Initialized(module_object));
+            if let Err(e) =
+                module_fun
+                    .as_function_mut()
+                    .call(ctx, &mut args, JsValue::encode_undefined_value())
+            {
+                ctx.modules().remove(&key);
+                return Err(e);
             }
         }
     }
-    if !path.exists() {
-        return Err(JsValue::new(
-            ctx.new_type_error(format!("Module '{}' not found", spath)),
-        ));
-    }
-    let source = match std::fs::read_to_string(&path) {
-        Ok(source) => source,
-        Err(e) => {
-            return Err(JsValue::new(ctx.new_type_error(format!(
-                "Failed to read module '{}': {}",
-                spath,
-                e.to_string()
-            ))));
-        }
-    };
-    let name = path.file_name().unwrap().to_str().unwrap().to_string();
-    let module_fun = ctx.compile_module(&spath, &name, &source)?;
-    let mut module_fun = module_fun.get_jsobject();
-    module_fun
-        .as_function_mut()
-        .call(ctx, &mut args, JsValue::encode_undefined_value())?;
     ctx.modules()
-        .insert(spath.clone(), ModuleKind::Initialized(module_object));
+        .insert(key, ModuleKind::Initialized(module_object));
     Ok(JsValue::new(module_object))
 }
 
@@ -494,9 +556,15 @@ macro_rules! define_op_builtins {
         $op!(JsPromise);
         $op!(JsArrayBuffer);
         $op!(JsDataView);
+        $op!(JsInt32Array);
+        $op!(JsEncoding);
+        $op!(JsAtomics);
         $op!(JsWeakRef);
+        $op!(JsMap);
         $op!(JsDate);
         $op!(JsBoolean);
+        $op!(JsConsole);
+        $op!(JsWorker);
         $op!(SelfHost);
     };
 }
@@ -1,7 +1,13 @@
 use crate::{
     prelude::*,
     vm::{
-        array_buffer::JsArrayBuffer, builder::Builtin, context::Context, object::TypedJsObject,
+        array_buffer::{JsArrayBuffer, SharedDataBlock},
+        builder::Builtin,
+        capabilities::BuiltinGroup,
+        context::Context,
+        data_view::JsDataView,
+        int32_array::JsInt32Array,
+        object::TypedJsObject,
         structure_builder::StructureBuilder,
     },
 };
@@ -14,24 +20,265 @@ pub fn array_buffer_constructor(
             "ArrayBuffer() called in function context instead of constructor",
         )));
     }
-    
+
     letroot!(this = stack, JsArrayBuffer::new(ctx));
 
     let mut buf = TypedJsObject::<JsArrayBuffer>::new(this);
-    let length = args.at(0).to_int32(ctx)?;
+    let length = super::to_index(ctx, args.at(0))?;
+    let max_length = get_array_buffer_max_byte_length_option(ctx, args.at(1))?;
+    if let Some(max_length) = max_length {
+        if length > max_length {
+            return Err(JsValue::new(ctx.new_range_error(
+                "new ArrayBuffer(length, { maxByteLength }): length must be <= maxByteLength",
+            )));
+        }
+    }
     assert!(
         !buf.attached(),
         "A new array buffer should not have an existing buffer"
     );
-    buf.create_data_block(ctx, length as _, true)?;
+    buf.create_resizable_data_block(ctx, length, max_length, true)?;
     Ok(JsValue::new(this))
 }
 
+/// GetArrayBufferMaxByteLengthOption ( options )
+fn get_array_buffer_max_byte_length_option(
+    ctx: GcPointer<Context>,
+    options: JsValue,
+) -> Result<Option<usize>, JsValue> {
+    if !options.is_jsobject() {
+        return Ok(None);
+    }
+    let max_byte_length = options.get_jsobject().get(ctx, "maxByteLength".intern())?;
+    if max_byte_length.is_undefined() {
+        return Ok(None);
+    }
+    super::to_index(ctx, max_byte_length).map(Some)
+}
+
+fn this_array_buffer(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    method: &str,
+    shared: bool,
+) -> Result<TypedJsObject<JsArrayBuffer>, JsValue> {
+    let this = args.this.to_object(ctx)?;
+    if !this.is_class(JsArrayBuffer::class()) || this.data::<JsArrayBuffer>().is_shared() != shared
+    {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "{}.prototype.{} is not generic",
+            if shared {
+                "SharedArrayBuffer"
+            } else {
+                "ArrayBuffer"
+            },
+            method
+        ))));
+    }
+    Ok(TypedJsObject::<JsArrayBuffer>::new(this))
+}
+
+pub fn array_buffer_is_view(
+    _ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let arg = args.at(0);
+    Ok(JsValue::new(
+        arg.is_jsobject()
+            && (arg.get_jsobject().is_class(JsDataView::class())
+                || arg.get_jsobject().is_class(JsInt32Array::class())),
+    ))
+}
+
+pub fn array_buffer_max_byte_length(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let buf = this_array_buffer(ctx, args, "maxByteLength", false)?;
+    if !buf.attached() {
+        return Ok(JsValue::new(0));
+    }
+    Ok(JsValue::new(buf.max_byte_length() as u32))
+}
+
+pub fn array_buffer_resizable(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let buf = this_array_buffer(ctx, args, "resizable", false)?;
+    Ok(JsValue::new(buf.is_resizable()))
+}
+
+pub fn array_buffer_detached(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let buf = this_array_buffer(ctx, args, "detached", false)?;
+    Ok(JsValue::new(!buf.attached()))
+}
+
+pub fn array_buffer_resize(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut buf = this_array_buffer(ctx, args, "resize", false)?;
+    if !buf.is_resizable() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "ArrayBuffer.prototype.resize: buffer is not resizable",
+        )));
+    }
+    let new_length = super::to_index(ctx, args.at(0))?;
+    buf.resize(ctx, new_length)?;
+    Ok(JsValue::encode_undefined_value())
+}
+
+/// ArrayBufferCopyAndDetach ( arrayBuffer, newLength, preserveResizability )
+///
+/// The data block is moved into the new buffer, it is only reallocated when the new length does
+/// not fit into the capacity of the old one.
+fn array_buffer_copy_and_detach(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    method: &str,
+    preserve_resizability: bool,
+) -> Result<JsValue, JsValue> {
+    let mut buf = this_array_buffer(ctx, args, method, false)?;
+    let new_length = if args.at(0).is_undefined() {
+        buf.byte_length()
+    } else {
+        super::to_index(ctx, args.at(0))?
+    };
+    if !buf.attached() {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "ArrayBuffer.prototype.{} called on a detached ArrayBuffer",
+            method
+        ))));
+    }
+    let resizable = preserve_resizability && buf.is_resizable();
+    if resizable && new_length > buf.max_byte_length() {
+        return Err(JsValue::new(ctx.new_range_error(format!(
+            "ArrayBuffer.prototype.{}: new length exceeds maxByteLength",
+            method
+        ))));
+    }
+    if new_length > u32::MAX as usize {
+        return Err(JsValue::new(ctx.new_range_error(
+            "Cannot allocate a data block for the ArrayBuffer",
+        )));
+    }
+
+    letroot!(result = stack, JsArrayBuffer::new(ctx));
+    let mut new_buf = TypedJsObject::<JsArrayBuffer>::new(result);
+    let (mut data, size, mut capacity) = buf.take_data_block();
+    let required = if resizable { capacity } else { new_length };
+    if required > capacity || (!resizable && new_length != capacity) {
+        // fixed-length results must not keep unused capacity around
+        let new_data = unsafe { libc::realloc(data.cast(), required.max(1)).cast::<u8>() };
+        if new_data.is_null() {
+            let was_resizable = buf.is_resizable();
+            unsafe {
+                buf.adopt_data_block(ctx, data, size, capacity, was_resizable);
+            }
+            return Err(JsValue::new(ctx.new_range_error(
+                "Cannot allocate a data block for the ArrayBuffer",
+            )));
+        }
+        data = new_data;
+        capacity = required;
+    }
+    if new_length > size {
+        unsafe {
+            std::ptr::write_bytes(data.add(size), 0, new_length - size);
+        }
+    }
+    unsafe {
+        new_buf.adopt_data_block(ctx, data, new_length, capacity, resizable);
+    }
+    Ok(JsValue::new(result))
+}
+
+pub fn array_buffer_transfer(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    array_buffer_copy_and_detach(ctx, args, "transfer", true)
+}
+
+pub fn array_buffer_transfer_to_fixed_length(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    array_buffer_copy_and_detach(ctx, args, "transferToFixedLength", false)
+}
+
+pub fn shared_array_buffer_constructor(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    if !args.ctor_call {
+        return Err(JsValue::new(ctx.new_type_error(
+            "SharedArrayBuffer() called in function context instead of constructor",
+        )));
+    }
+    let length = super::to_index(ctx, args.at(0))?;
+    if length > u32::MAX as usize {
+        return Err(JsValue::new(ctx.new_range_error(
+            "Cannot allocate a data block for the SharedArrayBuffer",
+        )));
+    }
+    let block = match SharedDataBlock::new(length) {
+        Some(block) => block,
+        None => {
+            return Err(JsValue::new(ctx.new_range_error(
+                "Cannot allocate a data block for the SharedArrayBuffer",
+            )))
+        }
+    };
+    Ok(JsValue::new(JsArrayBuffer::new_shared(ctx, block)))
+}
+
+pub fn shared_array_buffer_growable(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    this_array_buffer(ctx, args, "growable", true)?;
+    Ok(JsValue::new(false))
+}
+
+pub fn shared_array_buffer_slice(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let buf = this_array_buffer(ctx, args, "slice", true)?;
+    let len = buf.size() as f64;
+    let relative = |value: JsValue, default: f64| -> Result<f64, JsValue> {
+        if value.is_undefined() {
+            return Ok(default);
+        }
+        let relative = value.to_interger(ctx)?;
+        Ok(if relative < 0.0 {
+            (len + relative).max(0.0)
+        } else {
+            relative.min(len)
+        })
+    };
+    let first = relative(args.at(0), 0.0)? as usize;
+    let fin = relative(args.at(1), len)? as usize;
+    let new_len = fin.saturating_sub(first);
+    let block = match SharedDataBlock::new(new_len) {
+        Some(block) => block,
+        None => {
+            return Err(JsValue::new(ctx.new_range_error(
+                "Cannot allocate a data block for the SharedArrayBuffer",
+            )))
+        }
+    };
+    let new_buf = TypedJsObject::<JsArrayBuffer>::new(JsArrayBuffer::new_shared(ctx, block));
+    JsArrayBuffer::copy_data_block_bytes(new_buf, 0, buf, first, new_len);
+    Ok(JsValue::new(new_buf))
+}
+
 pub fn array_buffer_byte_length(
     ctx: GcPointer<Context>,
     args: &Arguments,
 ) -> Result<JsValue, JsValue> {
-    
     letroot!(this = stack, args.this.to_object(ctx)?);
     if !this.is_class(JsArrayBuffer::class()) {
         return Err(JsValue::new(
@@ -44,14 +291,7 @@ pub fn array_buffer_byte_length(
 }
 
 pub fn array_buffer_slice(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
-    letroot!(this = stack, args.this.to_object(ctx)?);
-    if !this.is_class(JsArrayBuffer::class()) {
-        return Err(JsValue::new(
-            ctx.new_type_error("ArrayBuffer.prototype.slice is not generic"),
-        ));
-    }
-    let buf = TypedJsObject::<JsArrayBuffer>::new(this);
+    let buf = this_array_buffer(ctx, args, "slice", false)?;
     let start = args.at(0).to_int32(ctx)?;
     let end = args.at(1).to_int32(ctx)?;
     let len = buf.size();
@@ -96,11 +336,23 @@ pub fn array_buffer_slice(ctx: GcPointer<Context>, args: &Arguments) -> Result<J
 }
 
 impl Builtin for JsArrayBuffer {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::ArrayBuffer);
+
     fn native_references() -> Vec<usize> {
         vec![
             array_buffer_constructor as _,
             array_buffer_byte_length as _,
             array_buffer_slice as _,
+            array_buffer_is_view as _,
+            array_buffer_max_byte_length as _,
+            array_buffer_resizable as _,
+            array_buffer_detached as _,
+            array_buffer_resize as _,
+            array_buffer_transfer as _,
+            array_buffer_transfer_to_fixed_length as _,
+            shared_array_buffer_constructor as _,
+            shared_array_buffer_growable as _,
+            shared_array_buffer_slice as _,
         ]
     }
 
@@ -127,6 +379,9 @@ impl Builtin for JsArrayBuffer {
             data: std::ptr::null_mut(),
 
             attached: false,
+            max_byte_length: None,
+            shared: None,
+            external: None,
         });
 
         ctx.global_data.array_buffer_prototype = Some(prototype);
@@ -137,7 +392,28 @@ impl Builtin for JsArrayBuffer {
 
         def_native_property!(ctx, constructor, prototype, prototype)?;
         def_native_property!(ctx, prototype, constructor, constructor)?;
+        def_native_method!(ctx, constructor, isView, array_buffer_is_view, 1)?;
         def_native_method!(ctx, prototype, slice, array_buffer_slice, 2)?;
+        def_native_method!(ctx, prototype, resize, array_buffer_resize, 1)?;
+        def_native_method!(ctx, prototype, transfer, array_buffer_transfer, 0)?;
+        def_native_method!(
+            ctx,
+            prototype,
+            transferToFixedLength,
+            array_buffer_transfer_to_fixed_length,
+            0
+        )?;
+        let max_byte_length = JsNativeFunction::new(
+            ctx,
+            "maxByteLength".intern(),
+            array_buffer_max_byte_length,
+            0,
+        );
+        def_native_getter!(ctx, prototype, maxByteLength, max_byte_length, NONE)?;
+        let resizable = JsNativeFunction::new(ctx, "resizable".intern(), array_buffer_resizable, 0);
+        def_native_getter!(ctx, prototype, resizable, resizable, NONE)?;
+        let detached = JsNativeFunction::new(ctx, "detached".intern(), array_buffer_detached, 0);
+        def_native_getter!(ctx, prototype, detached, detached, NONE)?;
 
         ctx.global_object().put(
             ctx,
@@ -145,6 +421,92 @@ impl Builtin for JsArrayBuffer {
             JsValue::new(constructor),
             false,
         )?;
+
+        // SharedArrayBuffer objects use the same layout but their own prototype.
+        let mut builder = StructureBuilder::new(None);
+        builder.add("byteLength".intern(), create_data(AttrExternal::new(None)));
+        let mut shared_structure = builder.build(ctx, false, false);
+        let shared_proto_map = shared_structure
+            .change_prototype_transition(ctx, Some(ctx.global_data().object_prototype.unwrap()));
+        let mut shared_prototype = JsObject::new(
+            ctx,
+            &shared_proto_map,
+            JsObject::class(),
+            ObjectTag::Ordinary,
+        );
+        shared_structure.change_prototype_with_no_transition(shared_prototype);
+        ctx.global_data.shared_array_buffer_prototype = Some(shared_prototype);
+        ctx.global_data.shared_array_buffer_structure = Some(shared_structure);
+
+        let mut shared_constructor = JsNativeFunction::new(
+            ctx,
+            "SharedArrayBuffer".intern(),
+            shared_array_buffer_constructor,
+            1,
+        );
+        def_native_property!(ctx, shared_constructor, prototype, shared_prototype)?;
+        def_native_property!(ctx, shared_prototype, constructor, shared_constructor)?;
+        def_native_method!(ctx, shared_prototype, slice, shared_array_buffer_slice, 2)?;
+        let growable =
+            JsNativeFunction::new(ctx, "growable".intern(), shared_array_buffer_growable, 0);
+        def_native_getter!(ctx, shared_prototype, growable, growable, NONE)?;
+
+        ctx.global_object().put(
+            ctx,
+            "SharedArrayBuffer".intern(),
+            JsValue::new(shared_constructor),
+            false,
+        )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_resizable_and_shared_buffers() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var buf = new ArrayBuffer(4, { maxByteLength: 16 });
+             var view = new DataView(buf);
+             buf.resize(12);
+             view.setUint8(11, 7);
+             var moved = buf.transfer();",
+        );
+        assert_js(ctx, "buf.detached");
+        assert_js(ctx, "moved.resizable && moved.byteLength === 12");
+        assert_js(ctx, "new DataView(moved).getUint8(11) === 7");
+        assert_js(
+            ctx,
+            "(() => { try { view.byteLength; return false; } catch (e) { return e instanceof TypeError; } })()",
+        );
+
+        eval(
+            ctx,
+            "var sab = new SharedArrayBuffer(8);
+             Atomics.store(new Int32Array(sab), 1, 40);
+             Atomics.add(new Int32Array(sab), 1, 2);",
+        );
+        assert_js(ctx, "typeof Atomics === 'object'");
+        assert_js(
+            ctx,
+            "Atomics.load(new Int32Array(structuredClone(sab)), 1) === 42",
+        );
+        assert_js(
+            ctx,
+            "Atomics.wait(new Int32Array(sab), 0, 1) === 'not-equal'",
+        );
+        assert_js(
+            ctx,
+            "Atomics.wait(new Int32Array(sab), 0, 0, 0) === 'timed-out'",
+        );
+    }
+}
@@ -0,0 +1,400 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! The `Atomics` namespace object. Operations take an `Int32Array`, the only integer typed array
+//! Starlight implements so far. `Atomics.wait` and `Atomics.notify` synchronize agents that
+//! share a `SharedArrayBuffer`, e.g. a worker and its parent.
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+use crate::{
+    prelude::*,
+    vm::{
+        array_buffer::SharedDataBlock, builder::Builtin, capabilities::BuiltinGroup,
+        context::Context, int32_array::JsInt32Array,
+    },
+};
+
+pub struct JsAtomics;
+
+/// Element of the array validated by `validate_atomic_access`. `block` keeps a shared data
+/// block alive while a thread is blocked in `Atomics.wait`.
+struct AtomicLocation {
+    cell: *const AtomicI32,
+    block: Option<Arc<SharedDataBlock>>,
+}
+
+impl AtomicLocation {
+    fn cell(&self) -> &AtomicI32 {
+        unsafe { &*self.cell }
+    }
+}
+
+/// ValidateIntegerTypedArray: `target` must be an `Int32Array` that is not out of bounds.
+fn validate_integer_typed_array(
+    ctx: GcPointer<Context>,
+    target: JsValue,
+) -> Result<GcPointer<JsObject>, JsValue> {
+    if !(target.is_jsobject() && target.get_jsobject().is_class(JsInt32Array::class())) {
+        return Err(JsValue::new(
+            ctx.new_type_error("Atomics operation requires an Int32Array"),
+        ));
+    }
+    let array = target.get_jsobject();
+    if array.data::<JsInt32Array>().is_out_of_bounds() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "Atomics operation called on a detached or out of bounds Int32Array",
+        )));
+    }
+    Ok(array)
+}
+
+/// ValidateIntegerTypedArray + ValidateAtomicAccess. Returns the element index. The location
+/// itself is computed by [atomic_location] once every argument was converted, because
+/// conversions run JS code that may detach or shrink the buffer.
+fn validate_atomic_access(
+    ctx: GcPointer<Context>,
+    target: JsValue,
+    index: JsValue,
+) -> Result<usize, JsValue> {
+    validate_integer_typed_array(ctx, target)?;
+    let index = super::to_index(ctx, index)?;
+    let array = validate_integer_typed_array(ctx, target)?;
+    if index >= array.data::<JsInt32Array>().length() {
+        return Err(JsValue::new(
+            ctx.new_range_error("Atomics operation index out of range"),
+        ));
+    }
+    Ok(index)
+}
+
+/// Element `index` of `target`, revalidated without running any JS code.
+fn atomic_location(
+    ctx: GcPointer<Context>,
+    target: JsValue,
+    index: usize,
+) -> Result<AtomicLocation, JsValue> {
+    let array = validate_integer_typed_array(ctx, target)?;
+    let array = array.data::<JsInt32Array>();
+    match array.cell(index) {
+        Some(cell) => Ok(AtomicLocation {
+            cell,
+            block: array.get_buffer().shared_data_block(),
+        }),
+        None => Err(JsValue::new(
+            ctx.new_range_error("Atomics operation index out of range"),
+        )),
+    }
+}
+
+macro_rules! atomic_rmw {
+    ($name: ident, $op: expr) => {
+        pub fn $name(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+            let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+            let value = args.at(2).to_int32(ctx)?;
+            let location = atomic_location(ctx, args.at(0), index)?;
+            let cell = location.cell();
+            let op: fn(i32, i32) -> i32 = $op;
+            let old = cell
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |old| {
+                    Some(op(old, value))
+                })
+                .unwrap();
+            Ok(JsValue::new(old))
+        }
+    };
+}
+
+atomic_rmw!(atomics_add, |old, value| old.wrapping_add(value));
+atomic_rmw!(atomics_sub, |old, value| old.wrapping_sub(value));
+atomic_rmw!(atomics_and, |old, value| old & value);
+atomic_rmw!(atomics_or, |old, value| old | value);
+atomic_rmw!(atomics_xor, |old, value| old ^ value);
+atomic_rmw!(atomics_exchange, |_, value| value);
+
+pub fn atomics_load(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+    let location = atomic_location(ctx, args.at(0), index)?;
+    Ok(JsValue::new(location.cell().load(Ordering::SeqCst)))
+}
+
+pub fn atomics_store(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+    let value = args.at(2).to_interger(ctx)?;
+    let int_value = JsValue::new(value).to_int32(ctx)?;
+    let location = atomic_location(ctx, args.at(0), index)?;
+    location.cell().store(int_value, Ordering::SeqCst);
+    // Atomics.store returns the integer value, not the wrapped one
+    Ok(JsValue::new(value))
+}
+
+pub fn atomics_compare_exchange(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+    let expected = args.at(2).to_int32(ctx)?;
+    let replacement = args.at(3).to_int32(ctx)?;
+    let location = atomic_location(ctx, args.at(0), index)?;
+    let old = match location.cell().compare_exchange(
+        expected,
+        replacement,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    ) {
+        Ok(old) | Err(old) => old,
+    };
+    Ok(JsValue::new(old))
+}
+
+pub fn atomics_is_lock_free(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let size = args.at(0).to_interger(ctx)?;
+    Ok(JsValue::new(
+        size == 1.0 || size == 2.0 || size == 4.0 || size == 8.0,
+    ))
+}
+
+/// Agent blocked in `Atomics.wait`.
+struct Waiter {
+    notified: Mutex<bool>,
+    condvar: Condvar,
+}
+
+/// WaiterList records keyed by the address of the waited on element. Shared data blocks never
+/// move so the address identifies the same location in every agent.
+static WAITER_LISTS: Lazy<Mutex<HashMap<usize, Vec<Arc<Waiter>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn atomics_wait(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+    if atomic_location(ctx, args.at(0), index)?.block.is_none() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "Atomics.wait can only be used on an Int32Array over a SharedArrayBuffer",
+        )));
+    }
+    let value = args.at(2).to_int32(ctx)?;
+    let timeout = if args.at(3).is_undefined() {
+        f64::INFINITY
+    } else {
+        let timeout = args.at(3).to_number(ctx)?;
+        if timeout.is_nan() {
+            f64::INFINITY
+        } else {
+            timeout.max(0.0)
+        }
+    };
+    let location = atomic_location(ctx, args.at(0), index)?;
+    let key = location.cell as usize;
+
+    let waiter = {
+        let mut lists = WAITER_LISTS.lock().unwrap();
+        if location.cell().load(Ordering::SeqCst) != value {
+            return Ok(JsValue::new(JsString::new(ctx, "not-equal")));
+        }
+        let waiter = Arc::new(Waiter {
+            notified: Mutex::new(false),
+            condvar: Condvar::new(),
+        });
+        lists.entry(key).or_default().push(waiter.clone());
+        waiter
+    };
+
+    // timeouts too large for an `Instant` wait forever
+    let deadline = if timeout.is_finite() {
+        Instant::now().checked_add(Duration::from_millis(timeout as u64))
+    } else {
+        None
+    };
+    let mut notified = waiter.notified.lock().unwrap();
+    while !*notified {
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                notified = waiter
+                    .condvar
+                    .wait_timeout(notified, deadline - now)
+                    .unwrap()
+                    .0;
+            }
+            None => notified = waiter.condvar.wait(notified).unwrap(),
+        }
+    }
+    if *notified {
+        return Ok(JsValue::new(JsString::new(ctx, "ok")));
+    }
+    drop(notified);
+
+    // Timed out. `Atomics.notify` may have removed us in the meantime, check again under the
+    // list lock so that a notification is never lost.
+    let mut lists = WAITER_LISTS.lock().unwrap();
+    if *waiter.notified.lock().unwrap() {
+        return Ok(JsValue::new(JsString::new(ctx, "ok")));
+    }
+    if let Some(list) = lists.get_mut(&key) {
+        list.retain(|other| !Arc::ptr_eq(other, &waiter));
+        if list.is_empty() {
+            lists.remove(&key);
+        }
+    }
+    Ok(JsValue::new(JsString::new(ctx, "timed-out")))
+}
+
+pub fn atomics_notify(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let index = validate_atomic_access(ctx, args.at(0), args.at(1))?;
+    let count = if args.at(2).is_undefined() {
+        usize::MAX
+    } else {
+        args.at(2).to_interger(ctx)?.max(0.0) as usize
+    };
+    let location = atomic_location(ctx, args.at(0), index)?;
+    if location.block.is_none() {
+        // nobody can wait on a non-shared buffer
+        return Ok(JsValue::new(0));
+    }
+    let key = location.cell as usize;
+    let mut lists = WAITER_LISTS.lock().unwrap();
+    let mut woken = 0u32;
+    if let Some(list) = lists.get_mut(&key) {
+        let n = count.min(list.len());
+        for waiter in list.drain(..n) {
+            *waiter.notified.lock().unwrap() = true;
+            waiter.condvar.notify_one();
+            woken += 1;
+        }
+        if list.is_empty() {
+            lists.remove(&key);
+        }
+    }
+    Ok(JsValue::new(woken))
+}
+
+impl Builtin for JsAtomics {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::Atomics);
+
+    fn native_references() -> Vec<usize> {
+        vec![
+            atomics_add as _,
+            atomics_and as _,
+            atomics_compare_exchange as _,
+            atomics_exchange as _,
+            atomics_is_lock_free as _,
+            atomics_load as _,
+            atomics_notify as _,
+            atomics_or as _,
+            atomics_store as _,
+            atomics_sub as _,
+            atomics_wait as _,
+            atomics_xor as _,
+        ]
+    }
+
+    fn init(mut ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        let mut atomics = JsObject::new_empty(ctx);
+
+        def_native_method!(ctx, atomics, add, atomics_add, 3)?;
+        def_native_method!(ctx, atomics, and, atomics_and, 3)?;
+        def_native_method!(ctx, atomics, compareExchange, atomics_compare_exchange, 4)?;
+        def_native_method!(ctx, atomics, exchange, atomics_exchange, 3)?;
+        def_native_method!(ctx, atomics, isLockFree, atomics_is_lock_free, 1)?;
+        def_native_method!(ctx, atomics, load, atomics_load, 2)?;
+        def_native_method!(ctx, atomics, notify, atomics_notify, 3)?;
+        def_native_method!(ctx, atomics, or, atomics_or, 3)?;
+        def_native_method!(ctx, atomics, store, atomics_store, 3)?;
+        def_native_method!(ctx, atomics, sub, atomics_sub, 3)?;
+        def_native_method!(ctx, atomics, wait, atomics_wait, 4)?;
+        def_native_method!(ctx, atomics, xor, atomics_xor, 3)?;
+
+        let mut global_object = ctx.global_object();
+        def_native_property!(ctx, global_object, Atomics, atomics)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::gc::cell::GcPointer;
+    use crate::options::Options;
+    use crate::vm::array_buffer::{JsArrayBuffer, SharedDataBlock};
+    use crate::vm::context::Context;
+    use crate::vm::symbol_table::Internable;
+    use crate::vm::testing::{assert_js, describe, eval};
+    use crate::vm::value::JsValue;
+    use crate::Platform;
+
+    /// Define the global `array`, an `Int32Array` over a `SharedArrayBuffer` backed by `block`.
+    fn share(ctx: GcPointer<Context>, block: &Arc<SharedDataBlock>) {
+        let buffer = JsArrayBuffer::new_shared(ctx, block.clone());
+        if let Err(error) =
+            ctx.global_object()
+                .put(ctx, "shared".intern(), JsValue::new(buffer), false)
+        {
+            panic!("defining the buffer threw {}", describe(ctx, error));
+        }
+        eval(ctx, "var array = new Int32Array(shared);");
+    }
+
+    #[test]
+    fn test_atomics_across_threads() {
+        let block = SharedDataBlock::new(16).expect("failed to allocate the block");
+        let waiter = {
+            let block = block.clone();
+            std::thread::spawn(move || {
+                let mut vm = Platform::new_runtime(Options::default(), None);
+                let ctx = Context::new(&mut vm);
+                share(ctx, &block);
+                eval(
+                    ctx,
+                    "var added = Atomics.add(array, 1, 5);
+                     var exchanged = Atomics.compareExchange(array, 2, 0, 7);
+                     for (var i = 0; i < 1000; i++) { Atomics.add(array, 3, 1); }
+                     var result = Atomics.wait(array, 0, 0, 10000);",
+                );
+                assert_js(ctx, "added === 0 && exchanged === 0");
+                assert_js(ctx, "result === 'ok'");
+            })
+        };
+
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        share(ctx, &block);
+        eval(
+            ctx,
+            "for (var i = 0; i < 1000; i++) { Atomics.add(array, 3, 1); }
+             // wake the other thread once it is blocked in Atomics.wait
+             var start = Date.now();
+             while (Atomics.notify(array, 0, 1) === 0) {
+                 if (Date.now() - start > 10000) throw new Error('the other thread never waited');
+             }",
+        );
+        waiter.join().expect("the waiting thread failed");
+        assert_js(ctx, "Atomics.load(array, 1) === 5 && array[2] === 7");
+        assert_js(ctx, "Atomics.load(array, 3) === 2000");
+        assert_js(
+            ctx,
+            "Atomics.compareExchange(array, 2, 1, 9) === 7 && array[2] === 7",
+        );
+        assert_js(ctx, "Atomics.wait(array, 0, 1) === 'not-equal'");
+        assert_js(ctx, "Atomics.wait(array, 0, 0, 0) === 'timed-out'");
+        assert_js(
+            ctx,
+            "(() => { try { Atomics.wait(new Int32Array(4), 0, 0); } catch (e) { return e instanceof TypeError; } })()",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { Atomics.add(array, 4, 1); } catch (e) { return e instanceof RangeError; } })()",
+        );
+    }
+}
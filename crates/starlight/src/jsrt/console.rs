@@ -0,0 +1,503 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! The `console` namespace.
+//!
+//! Arguments are formatted like Node.js `util.format`: strings are printed as they are, other
+//! values go through the [inspector](crate::jsrt::inspect) and a leading string may contain
+//! `%s`, `%d`, `%i`, `%f`, `%o`, `%O`, `%j`, `%c` and `%%` substitutions. Output goes to the hook
+//! installed with [VirtualMachine::set_console_hook], or to stdout and stderr when the context
+//! was granted [Capability::Stdio].
+use std::{collections::HashMap, time::Instant};
+
+use crate::jsrt::inspect::Inspector;
+use crate::prelude::*;
+use crate::vm::{builder::Builtin, capabilities::Capability, context::Context};
+
+/// Severity of a console message. `Log`, `Info` and `Debug` go to stdout by default, `Warn` and
+/// `Error` to stderr.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleLevel {
+    Log,
+    Info,
+    Debug,
+    Warn,
+    Error,
+}
+
+/// Receives every message printed with `console`, see [VirtualMachine::set_console_hook].
+pub type ConsoleHook = Box<dyn Fn(ConsoleLevel, &str)>;
+
+/// Counters, timers and group nesting of the console of a context.
+#[derive(Default)]
+pub(crate) struct ConsoleState {
+    counts: HashMap<String, u32>,
+    timers: HashMap<String, Instant>,
+    group_indent: usize,
+}
+
+pub struct JsConsole;
+
+impl Builtin for JsConsole {
+    fn native_references() -> Vec<usize> {
+        vec![
+            console_log as _,
+            console_info as _,
+            console_debug as _,
+            console_warn as _,
+            console_error as _,
+            console_table as _,
+            console_time as _,
+            console_time_log as _,
+            console_time_end as _,
+            console_count as _,
+            console_count_reset as _,
+            console_assert as _,
+            console_group as _,
+            console_group_end as _,
+            console_trace as _,
+        ]
+    }
+
+    fn init(mut ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        let mut console = JsObject::new_empty(ctx);
+
+        def_native_method!(ctx, console, log, console_log, 0)?;
+        def_native_method!(ctx, console, info, console_info, 0)?;
+        def_native_method!(ctx, console, debug, console_debug, 0)?;
+        def_native_method!(ctx, console, warn, console_warn, 0)?;
+        def_native_method!(ctx, console, error, console_error, 0)?;
+        def_native_method!(ctx, console, table, console_table, 1)?;
+        def_native_method!(ctx, console, time, console_time, 0)?;
+        def_native_method!(ctx, console, timeLog, console_time_log, 0)?;
+        def_native_method!(ctx, console, timeEnd, console_time_end, 0)?;
+        def_native_method!(ctx, console, count, console_count, 0)?;
+        def_native_method!(ctx, console, countReset, console_count_reset, 0)?;
+        def_native_method!(ctx, console, assert, console_assert, 0)?;
+        def_native_method!(ctx, console, group, console_group, 0)?;
+        def_native_method!(ctx, console, groupCollapsed, console_group, 0)?;
+        def_native_method!(ctx, console, groupEnd, console_group_end, 0)?;
+        def_native_method!(ctx, console, trace, console_trace, 0)?;
+
+        let mut global_object = ctx.global_object();
+        def_native_property!(ctx, global_object, console, console)?;
+        Ok(())
+    }
+}
+
+/// Print `message` at `level`, indented by the current group nesting.
+fn write(ctx: GcPointer<Context>, level: ConsoleLevel, message: &str) {
+    let indent = " ".repeat(ctx.console.group_indent);
+    let message = message
+        .lines()
+        .map(|line| format!("{}{}", indent, line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Some(hook) = &ctx.vm.console_hook {
+        hook(level, &message);
+    } else if ctx.capabilities().allows(Capability::Stdio) {
+        match level {
+            ConsoleLevel::Log | ConsoleLevel::Info | ConsoleLevel::Debug => println!("{}", message),
+            ConsoleLevel::Warn | ConsoleLevel::Error => eprintln!("{}", message),
+        }
+    }
+}
+
+/// Format `args[start..]` like `util.format`.
+fn format_values(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    start: usize,
+) -> Result<String, JsValue> {
+    let mut inspector = Inspector::new();
+    let mut parts = vec![];
+    let mut next = start;
+    if start < args.size() && args.at(start).is_jsstring() {
+        let format = args.at(start).get_string().as_str().to_owned();
+        next += 1;
+        let mut result = String::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            let spec = match (c, chars.peek()) {
+                ('%', Some(&spec)) if "sdifoOjc%".contains(spec) => spec,
+                _ => {
+                    result.push(c);
+                    continue;
+                }
+            };
+            chars.next();
+            if spec == '%' {
+                result.push('%');
+                continue;
+            }
+            if next >= args.size() {
+                result.push('%');
+                result.push(spec);
+                continue;
+            }
+            let value = args.at(next);
+            next += 1;
+            match spec {
+                's' if value.is_jsobject() || value.is_symbol() => {
+                    result.push_str(&inspector.inspect(ctx, value)?)
+                }
+                's' => result.push_str(&value.to_string(ctx)?),
+                'd' | 'f' => result.push_str(&JsValue::new(value.to_number(ctx)?).to_string(ctx)?),
+                'i' => {
+                    let number = value.to_number(ctx)?.trunc();
+                    result.push_str(&JsValue::new(number).to_string(ctx)?)
+                }
+                'c' => (),
+                _ => result.push_str(&inspector.inspect(ctx, value)?),
+            }
+        }
+        parts.push(result);
+    }
+    for i in next..args.size() {
+        let value = args.at(i);
+        parts.push(if value.is_jsstring() {
+            value.get_string().as_str().to_owned()
+        } else {
+            inspector.inspect(ctx, value)?
+        });
+    }
+    Ok(parts.join(" "))
+}
+
+fn print_args(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    level: ConsoleLevel,
+) -> Result<JsValue, JsValue> {
+    let message = format_values(ctx, args, 0)?;
+    write(ctx, level, &message);
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Label argument of `time`, `count` and friends.
+fn label(ctx: GcPointer<Context>, args: &Arguments) -> Result<String, JsValue> {
+    let label = args.at(0);
+    if label.is_undefined() {
+        Ok("default".to_owned())
+    } else {
+        label.to_string(ctx)
+    }
+}
+
+pub fn console_log(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    print_args(ctx, args, ConsoleLevel::Log)
+}
+
+pub fn console_info(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    print_args(ctx, args, ConsoleLevel::Info)
+}
+
+pub fn console_debug(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    print_args(ctx, args, ConsoleLevel::Debug)
+}
+
+pub fn console_warn(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    print_args(ctx, args, ConsoleLevel::Warn)
+}
+
+pub fn console_error(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    print_args(ctx, args, ConsoleLevel::Error)
+}
+
+pub fn console_assert(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    if args.at(0).to_boolean() {
+        return Ok(JsValue::UNDEFINED);
+    }
+    let message = format_values(ctx, args, 1)?;
+    if message.is_empty() {
+        write(ctx, ConsoleLevel::Error, "Assertion failed");
+    } else {
+        write(
+            ctx,
+            ConsoleLevel::Error,
+            &format!("Assertion failed: {}", message),
+        );
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_count(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let label = label(ctx, args)?;
+    let count = ctx.console.counts.entry(label.clone()).or_insert(0);
+    *count += 1;
+    let message = format!("{}: {}", label, count);
+    write(ctx, ConsoleLevel::Info, &message);
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_count_reset(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let label = label(ctx, args)?;
+    if ctx.console.counts.remove(&label).is_none() {
+        write(
+            ctx,
+            ConsoleLevel::Warn,
+            &format!("Count for '{}' does not exist", label),
+        );
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_time(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let label = label(ctx, args)?;
+    if ctx.console.timers.contains_key(&label) {
+        write(
+            ctx,
+            ConsoleLevel::Warn,
+            &format!("Label '{}' already exists for console.time()", label),
+        );
+    } else {
+        ctx.console.timers.insert(label, Instant::now());
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+fn log_timer(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+    name: &str,
+    end: bool,
+) -> Result<JsValue, JsValue> {
+    let label = label(ctx, args)?;
+    let start = if end {
+        ctx.console.timers.remove(&label)
+    } else {
+        ctx.console.timers.get(&label).copied()
+    };
+    match start {
+        Some(start) => {
+            let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+            let mut message = format!("{}: {:.3}ms", label, elapsed);
+            let extra = format_values(ctx, args, 1)?;
+            if !end && !extra.is_empty() {
+                message.push(' ');
+                message.push_str(&extra);
+            }
+            write(ctx, ConsoleLevel::Info, &message);
+        }
+        None => write(
+            ctx,
+            ConsoleLevel::Warn,
+            &format!("No such label '{}' for console.{}()", label, name),
+        ),
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_time_log(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    log_timer(ctx, args, "timeLog", false)
+}
+
+pub fn console_time_end(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    log_timer(ctx, args, "timeEnd", true)
+}
+
+/// `console.group` and `console.groupCollapsed`.
+pub fn console_group(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    if args.size() != 0 {
+        print_args(ctx, args, ConsoleLevel::Log)?;
+    }
+    ctx.console.group_indent += 2;
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_group_end(
+    mut ctx: GcPointer<Context>,
+    _args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    ctx.console.group_indent = ctx.console.group_indent.saturating_sub(2);
+    Ok(JsValue::UNDEFINED)
+}
+
+pub fn console_trace(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let message = format_values(ctx, args, 0)?;
+    let stack = ctx.stacktrace();
+    let mut trace = if message.is_empty() {
+        "Trace".to_owned()
+    } else {
+        format!("Trace: {}", message)
+    };
+    for frame in stack.lines() {
+        trace.push_str("\n    ");
+        trace.push_str(frame.trim());
+    }
+    write(ctx, ConsoleLevel::Error, &trace);
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `console.table(data, columns)`: rows are the own enumerable properties of `data`, columns the
+/// properties of the rows. Primitive rows go to a `Values` column.
+pub fn console_table(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let data = args.at(0);
+    if !data.is_jsobject() {
+        return print_args(ctx, args, ConsoleLevel::Log);
+    }
+    let mut inspector = Inspector::new().depth(0);
+    let mut data = data.get_jsobject();
+
+    let filter = if args.at(1).is_jsobject() {
+        let mut columns = args.at(1).get_jsobject();
+        let length = columns.get(ctx, "length".intern())?.to_number(ctx)? as u32;
+        let mut names = vec![];
+        for i in 0..length {
+            names.push(columns.get(ctx, Symbol::Index(i))?.to_string(ctx)?);
+        }
+        Some(names)
+    } else {
+        None
+    };
+
+    let mut keys = vec![];
+    data.get_own_property_names(
+        ctx,
+        &mut |name, _| keys.push(name),
+        EnumerationMode::Default,
+    );
+    let mut columns: Vec<String> = filter.clone().unwrap_or_default();
+    let mut has_values = false;
+    let mut rows = vec![];
+    for key in keys {
+        if let Symbol::Private(_) = key {
+            continue;
+        }
+        let row = data.get(ctx, key)?;
+        let mut cells = HashMap::new();
+        let mut value = None;
+        if row.is_jsobject() && !row.is_callable() {
+            let mut row = row.get_jsobject();
+            let mut names = vec![];
+            row.get_own_property_names(
+                ctx,
+                &mut |name, _| names.push(name),
+                EnumerationMode::Default,
+            );
+            for name in names {
+                if let Symbol::Private(_) = name {
+                    continue;
+                }
+                let column = ctx.description(name);
+                if filter.is_none() && !columns.contains(&column) {
+                    columns.push(column.clone());
+                }
+                let cell = row.get(ctx, name)?;
+                cells.insert(column, inspector.inspect(ctx, cell)?);
+            }
+        } else {
+            has_values = true;
+            value = Some(inspector.inspect(ctx, row)?);
+        }
+        rows.push((ctx.description(key), cells, value));
+    }
+
+    let mut header = vec!["(index)".to_owned()];
+    header.extend(columns.iter().cloned());
+    if has_values {
+        header.push("Values".to_owned());
+    }
+    let body = rows
+        .into_iter()
+        .map(|(key, mut cells, value)| {
+            let mut line = vec![key];
+            for column in columns.iter() {
+                line.push(cells.remove(column).unwrap_or_default());
+            }
+            if has_values {
+                line.push(value.unwrap_or_default());
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    write(ctx, ConsoleLevel::Log, &render_table(&header, &body));
+    Ok(JsValue::UNDEFINED)
+}
+
+fn render_table(header: &[String], rows: &[Vec<String>]) -> String {
+    let widths = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(header[i].chars().count()))
+                .max()
+                .unwrap_or(0)
+                + 2
+        })
+        .collect::<Vec<_>>();
+    let border = |left: &str, middle: &str, right: &str| {
+        let lines = widths
+            .iter()
+            .map(|width| "─".repeat(*width))
+            .collect::<Vec<_>>();
+        format!("{}{}{}", left, lines.join(middle), right)
+    };
+    let line = |cells: &[String]| {
+        let cells = cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| {
+                let padding = width - cell.chars().count();
+                let left = padding / 2;
+                format!("{}{}{}", " ".repeat(left), cell, " ".repeat(padding - left))
+            })
+            .collect::<Vec<_>>();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let mut table = vec![border("┌", "┬", "┐"), line(header), border("├", "┼", "┤")];
+    table.extend(rows.iter().map(|row| line(row)));
+    table.push(border("└", "┴", "┘"));
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::jsrt::console::ConsoleLevel;
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::eval;
+    use crate::Platform;
+
+    #[test]
+    fn test_console_hook() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let output = Rc::new(RefCell::new(vec![]));
+        let sink = output.clone();
+        vm.set_console_hook(move |level, message| {
+            sink.borrow_mut().push((level, message.to_owned()))
+        });
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "console.log('%s has %d items', 'list', 3, { a: [1, 2] });
+             console.group('group');
+             console.warn('nested');
+             console.groupEnd();
+             console.count(); console.count();
+             console.assert(1 === 2, 'math');",
+        );
+
+        assert_eq!(
+            *output.borrow(),
+            vec![
+                (
+                    ConsoleLevel::Log,
+                    "list has 3 items { a: [ 1, 2 ] }".to_owned()
+                ),
+                (ConsoleLevel::Log, "group".to_owned()),
+                (ConsoleLevel::Warn, "  nested".to_owned()),
+                (ConsoleLevel::Info, "default: 1".to_owned()),
+                (ConsoleLevel::Info, "default: 2".to_owned()),
+                (ConsoleLevel::Error, "Assertion failed: math".to_owned()),
+            ]
+        );
+    }
+}
@@ -5,8 +5,8 @@ use wtf_rs::swap_byte_order::SwapByteOrder;
 use crate::{
     prelude::*,
     vm::{
-        array_buffer::JsArrayBuffer, builder::Builtin, context::Context, data_view::JsDataView,
-        object::TypedJsObject,
+        array_buffer::JsArrayBuffer, builder::Builtin, capabilities::BuiltinGroup,
+        context::Context, data_view::JsDataView, object::TypedJsObject,
     },
     JsTryFrom,
 };
@@ -32,6 +32,11 @@ pub fn data_view_prototype_byte_offset(
             "DataView.prototype.byteOffset called on a non DataView object",
         )));
     }
+    if this.data::<JsDataView>().is_out_of_bounds() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "DataView.prototype.byteOffset called on an out of bounds DataView",
+        )));
+    }
     Ok(JsValue::new(this.data::<JsDataView>().byte_offset() as u32))
 }
 pub fn data_view_prototype_byte_length(
@@ -44,6 +49,11 @@ pub fn data_view_prototype_byte_length(
             "DataView.prototype.byteLength called on a non DataView object",
         )));
     }
+    if this.data::<JsDataView>().is_out_of_bounds() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "DataView.prototype.byteLength called on an out of bounds DataView",
+        )));
+    }
     Ok(JsValue::new(this.data::<JsDataView>().byte_length() as u32))
 }
 
@@ -67,6 +77,11 @@ pub fn data_view_prototype_get<T: SwapByteOrder + Into<JsValue> + Copy>(
             "DataView.prototype.get<T> called on a detached ArrayBuffer",
         )));
     }
+    if this.data::<JsDataView>().is_out_of_bounds() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "DataView.prototype.get<T> called on an out of bounds DataView",
+        )));
+    }
 
     if byte_offset + size_of::<T>() > this.data::<JsDataView>().byte_length() {
         return Err(JsValue::new(ctx.new_range_error(format!(
@@ -81,6 +96,20 @@ pub fn data_view_prototype_get<T: SwapByteOrder + Into<JsValue> + Copy>(
     })
 }
 
+/// Wraps `num` modulo 2^`bits` after truncation as the ToInt*/ToUint*/ToBigInt64 conversions do.
+fn wrap_integer(num: f64, bits: u32) -> u64 {
+    // numbers this large are multiples of 2^64, and `as i128` would saturate on them
+    if !num.is_finite() || num.abs() >= 2f64.powi(127) {
+        return 0;
+    }
+    let wrapped = num.trunc() as i128 as u64;
+    if bits < 64 {
+        wrapped & ((1 << bits) - 1)
+    } else {
+        wrapped
+    }
+}
+
 pub fn data_view_prototype_set<T: SwapByteOrder + Into<JsValue> + Copy + 'static>(
     ctx: GcPointer<Context>,
     args: &Arguments,
@@ -89,6 +118,17 @@ pub fn data_view_prototype_set<T: SwapByteOrder + Into<JsValue> + Copy + 'static
 
     let res = super::to_index(ctx, args.at(0))?;
     let byte_offset = res as usize;
+    let is_bigint =
+        TypeId::of::<i64>() == TypeId::of::<T>() || TypeId::of::<u64>() == TypeId::of::<T>();
+    let num = args.at(1).to_number(ctx)?;
+    // Starlight has no BigInt primitive yet so 64-bit integer accessors work on integral
+    // numbers. Fractional values are rejected the same way NumberToBigInt rejects them.
+    if is_bigint && num.trunc() != num {
+        return Err(JsValue::new(ctx.new_range_error(format!(
+            "DataView.prototype.set<T>(): {} cannot be converted to a BigInt because it is not an integer",
+            num
+        ))));
+    }
     let little_endian = args.at(2).to_boolean();
 
     if !this.attached() {
@@ -96,6 +136,11 @@ pub fn data_view_prototype_set<T: SwapByteOrder + Into<JsValue> + Copy + 'static
             "DataView.prototype.set<T> called on a detached ArrayBuffer",
         )));
     }
+    if this.is_out_of_bounds() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "DataView.prototype.set<T> called on an out of bounds DataView",
+        )));
+    }
 
     if byte_offset + size_of::<T>() > this.byte_length() {
         return Err(JsValue::new(ctx.new_range_error(format!(
@@ -104,29 +149,35 @@ pub fn data_view_prototype_set<T: SwapByteOrder + Into<JsValue> + Copy + 'static
         ))));
     }
 
-    let num = args.at(1).to_number(ctx)?;
     unsafe {
         if TypeId::of::<u8>() == TypeId::of::<T>() {
-            let dest = num.clamp(u8::MIN as _, u8::MAX as _);
-            this.set::<u8>(byte_offset, dest as _, little_endian);
+            this.set::<u8>(byte_offset, wrap_integer(num, 8) as u8, little_endian);
         } else if TypeId::of::<f64>() == TypeId::of::<T>() {
             this.set::<f64>(byte_offset, num, little_endian);
         } else if TypeId::of::<f32>() == TypeId::of::<T>() {
             this.set::<f32>(byte_offset, num as _, little_endian);
         } else if TypeId::of::<i64>() == TypeId::of::<T>() {
-            this.set::<i64>(byte_offset, num as _, little_endian);
+            this.set::<i64>(byte_offset, wrap_integer(num, 64) as i64, little_endian);
         } else if TypeId::of::<u64>() == TypeId::of::<T>() {
-            this.set::<u64>(byte_offset, num as _, little_endian);
+            this.set::<u64>(byte_offset, wrap_integer(num, 64), little_endian);
         } else if TypeId::of::<u32>() == TypeId::of::<T>() {
-            this.set::<u32>(byte_offset, num as _, little_endian);
+            this.set::<u32>(byte_offset, wrap_integer(num, 32) as u32, little_endian);
         } else if TypeId::of::<u16>() == TypeId::of::<T>() {
-            this.set::<f64>(byte_offset, num as _, little_endian);
+            this.set::<u16>(byte_offset, wrap_integer(num, 16) as u16, little_endian);
         } else if TypeId::of::<i32>() == TypeId::of::<T>() {
-            this.set::<f64>(byte_offset, num as _, little_endian);
+            this.set::<i32>(
+                byte_offset,
+                wrap_integer(num, 32) as u32 as i32,
+                little_endian,
+            );
         } else if TypeId::of::<i16>() == TypeId::of::<T>() {
-            this.set::<f64>(byte_offset, num as _, little_endian);
+            this.set::<i16>(
+                byte_offset,
+                wrap_integer(num, 16) as u16 as i16,
+                little_endian,
+            );
         } else if TypeId::of::<i8>() == TypeId::of::<T>() {
-            this.set::<f64>(byte_offset, num as _, little_endian);
+            this.set::<i8>(byte_offset, wrap_integer(num, 8) as u8 as i8, little_endian);
         } else {
             unreachable!();
         }
@@ -161,19 +212,29 @@ pub fn data_view_constructor(
     let byte_length = args.at(2);
     let res = super::to_index(ctx, args.at(1))?;
     let offset = res as usize;
+    if !buffer.attached() {
+        return Err(JsValue::new(ctx.new_type_error(
+            "new DataView(buffer, [byteOffset], [byteLength]): buffer is detached",
+        )));
+    }
     let buffer_length = buffer.size();
     if offset > buffer_length {
         return Err(JsValue::new(ctx.new_range_error("new DataView(buffer, [byteOffset], byteLength]): byteOffset must be <= the buffer's byte length")));
     }
     let view_byte_length;
     if byte_length.is_undefined() {
-        view_byte_length = buffer_length - offset;
+        // views over resizable buffers without explicit length follow the buffer's length
+        view_byte_length = if buffer.is_resizable() {
+            None
+        } else {
+            Some(buffer_length - offset)
+        };
     } else {
         let res = super::to_index(ctx, byte_length)?;
-        view_byte_length = res as _;
-        if offset + view_byte_length > buffer_length {
+        if offset + res > buffer_length {
             return Err(JsValue::new(ctx.new_range_error("new DataView(buffer, [byteOffset], byteLength]): byteOffset + byteLength must be <= the buffer's byte length")));
         }
+        view_byte_length = Some(res);
     }
 
     let this = JsDataView::new(ctx, buffer, offset, view_byte_length);
@@ -181,6 +242,8 @@ pub fn data_view_constructor(
 }
 
 impl Builtin for JsDataView {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::ArrayBuffer);
+
     fn native_references() -> Vec<usize> {
         vec![
             JsDataView::class() as *const _ as usize,
@@ -196,6 +259,8 @@ impl Builtin for JsDataView {
             data_view_prototype_get::<i32> as _,
             data_view_prototype_get::<f32> as _,
             data_view_prototype_get::<f64> as _,
+            data_view_prototype_get::<i64> as _,
+            data_view_prototype_get::<u64> as _,
             data_view_prototype_set::<u8> as _,
             data_view_prototype_set::<u16> as _,
             data_view_prototype_set::<u32> as _,
@@ -204,6 +269,8 @@ impl Builtin for JsDataView {
             data_view_prototype_set::<i32> as _,
             data_view_prototype_set::<f32> as _,
             data_view_prototype_set::<f64> as _,
+            data_view_prototype_set::<i64> as _,
+            data_view_prototype_set::<u64> as _,
         ]
     }
 
@@ -245,6 +312,20 @@ impl Builtin for JsDataView {
             data_view_prototype_get::<f32>,
             2
         )?;
+        def_native_method!(
+            ctx,
+            prototype,
+            getBigInt64,
+            data_view_prototype_get::<i64>,
+            2
+        )?;
+        def_native_method!(
+            ctx,
+            prototype,
+            getBigUint64,
+            data_view_prototype_get::<u64>,
+            2
+        )?;
 
         def_native_method!(ctx, prototype, setInt8, data_view_prototype_set::<i8>, 2)?;
         def_native_method!(ctx, prototype, setUint8, data_view_prototype_set::<u8>, 2)?;
@@ -266,6 +347,20 @@ impl Builtin for JsDataView {
             data_view_prototype_set::<f32>,
             3
         )?;
+        def_native_method!(
+            ctx,
+            prototype,
+            setBigInt64,
+            data_view_prototype_set::<i64>,
+            3
+        )?;
+        def_native_method!(
+            ctx,
+            prototype,
+            setBigUint64,
+            data_view_prototype_set::<u64>,
+            3
+        )?;
 
         let byte_length =
             JsNativeFunction::new(ctx, "byteLength", data_view_prototype_byte_length, 0);
@@ -285,3 +380,52 @@ impl Builtin for JsDataView {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::wrap_integer;
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_data_view_accessors() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var view = new DataView(new ArrayBuffer(16));
+             view.setFloat64(0, 1.5, true);
+             view.setFloat32(8, -2.25);
+             view.setUint16(12, 0x1234, true);",
+        );
+        assert_js(ctx, "view.getFloat64(0, true) === 1.5");
+        assert_js(ctx, "view.getFloat32(8) === -2.25");
+        assert_js(ctx, "view.getUint8(12) === 0x34");
+        assert_js(ctx, "view.getUint16(12) === 0x3412");
+
+        eval(ctx, "view.setBigInt64(0, -2, true)");
+        assert_js(ctx, "view.getBigInt64(0, true) === -2");
+        assert_js(ctx, "view.getUint8(0) === 0xfe");
+
+        assert_js(
+            ctx,
+            "try { view.getFloat64(12); false } catch (e) { e instanceof RangeError }",
+        );
+    }
+
+    #[test]
+    fn test_wrap_integer() {
+        assert_eq!(wrap_integer(-2.0, 64) as i64, -2);
+        assert_eq!(wrap_integer(-1.5, 64), u64::MAX);
+        assert_eq!(wrap_integer(2f64.powi(64) + 4096.0, 64), 4096);
+        assert_eq!(wrap_integer(2f64.powi(200), 64), 0);
+        assert_eq!(wrap_integer(-2.0, 8), 0xfe);
+        assert_eq!(wrap_integer(65537.9, 16), 1);
+        assert_eq!(wrap_integer(-1.0, 32), 0xffff_ffff);
+        assert_eq!(wrap_integer(f64::NAN, 32), 0);
+        assert_eq!(wrap_integer(f64::NEG_INFINITY, 64), 0);
+    }
+}
@@ -8,7 +8,10 @@ use std::{
 use crate::{
     define_jsclass,
     prelude::*,
-    vm::{builder::Builtin, class::JsClass, context::Context, object::TypedJsObject},
+    vm::{
+        builder::Builtin, capabilities::BuiltinGroup, class::JsClass, context::Context,
+        object::TypedJsObject,
+    },
     JsTryFrom,
 };
 
@@ -1306,6 +1309,8 @@ pub fn date_now(_ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue,
 }
 
 impl Builtin for JsDate {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::Date);
+
     fn native_references() -> Vec<usize> {
         vec![
             date_constructor as _,
@@ -0,0 +1,625 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! WHATWG `TextEncoder` and `TextDecoder`, and the `atob` and `btoa` globals.
+//!
+//! There are no typed arrays yet, so `TextEncoder.prototype.encode` returns an `ArrayBuffer`, and
+//! `TextDecoder.prototype.decode` and `TextEncoder.prototype.encodeInto` accept `ArrayBuffer`,
+//! `SharedArrayBuffer` and `DataView`. The decoder supports UTF-8, UTF-16LE and windows-1252,
+//! which is what the `latin1`, `ascii` and `iso-8859-1` labels mean on the web.
+use crate::define_jsclass;
+use crate::prelude::*;
+use crate::vm::{
+    array_buffer::JsArrayBuffer,
+    attributes::CONFIGURABLE,
+    builder::{construct_class_instance, Builtin, ClassBuilder, ClassConstructor},
+    capabilities::BuiltinGroup,
+    context::Context,
+    data_view::JsDataView,
+    object::TypedJsObject,
+};
+use crate::JsTryFrom;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Code points of the bytes 0x80 to 0x9F in windows-1252, the other bytes map to themselves.
+const WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Windows1252,
+}
+
+impl Encoding {
+    /// Encoding named by a WHATWG label. Labels are case insensitive and surrounding whitespace
+    /// is ignored.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Self::Utf8),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Self::Utf16Le),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Self::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// Canonical name of the encoding.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Windows1252 => "windows-1252",
+        }
+    }
+}
+
+pub struct JsTextEncoder;
+
+impl JsClass for JsTextEncoder {
+    fn class() -> &'static Class {
+        define_jsclass!(JsTextEncoder, TextEncoder, None, None, None)
+    }
+}
+
+impl ClassConstructor for JsTextEncoder {
+    fn constructor(_ctx: GcPointer<Context>, _args: &Arguments) -> Result<Self, JsValue> {
+        Ok(Self)
+    }
+
+    fn raw_constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+        construct_class_instance::<Self>(ctx, args)
+    }
+
+    fn init(builder: &mut ClassBuilder) -> Result<(), JsValue> {
+        let ctx = builder.context;
+        builder.method("encode".intern(), text_encoder_encode, 0)?;
+        builder.method("encodeInto".intern(), text_encoder_encode_into, 2)?;
+        let encoding = JsNativeFunction::new(ctx, "encoding".intern(), text_encoder_encoding, 0);
+        builder.getter("encoding".intern(), encoding, CONFIGURABLE)?;
+        Ok(())
+    }
+}
+
+/// State of a `TextDecoder`, bytes of an incomplete sequence are kept between streaming calls.
+pub struct JsTextDecoder {
+    encoding: Encoding,
+    fatal: bool,
+    ignore_bom: bool,
+    bom_seen: bool,
+    pending: Vec<u8>,
+}
+
+extern "C" fn drop_decoder(obj: GcPointer<JsObject>) {
+    unsafe { std::mem::ManuallyDrop::drop(obj.data::<JsTextDecoder>()) }
+}
+
+extern "C" fn decoder_size() -> usize {
+    std::mem::size_of::<JsTextDecoder>()
+}
+
+impl JsClass for JsTextDecoder {
+    fn class() -> &'static Class {
+        define_jsclass!(
+            JsTextDecoder,
+            TextDecoder,
+            Some(drop_decoder),
+            None,
+            Some(decoder_size)
+        )
+    }
+}
+
+impl ClassConstructor for JsTextDecoder {
+    fn constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<Self, JsValue> {
+        let label = if args.at(0).is_undefined() {
+            "utf-8".to_owned()
+        } else {
+            args.at(0).to_string(ctx)?
+        };
+        let encoding = match Encoding::from_label(&label) {
+            Some(encoding) => encoding,
+            None => {
+                return Err(JsValue::new(ctx.new_range_error(format!(
+                    "TextDecoder: the encoding '{}' is not supported",
+                    label
+                ))))
+            }
+        };
+        let (fatal, ignore_bom) = if args.at(1).is_jsobject() {
+            let mut options = args.at(1).get_jsobject();
+            (
+                options.get(ctx, "fatal".intern())?.to_boolean(),
+                options.get(ctx, "ignoreBOM".intern())?.to_boolean(),
+            )
+        } else {
+            (false, false)
+        };
+        Ok(Self::new(encoding, fatal, ignore_bom))
+    }
+
+    fn raw_constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+        construct_class_instance::<Self>(ctx, args)
+    }
+
+    fn init(builder: &mut ClassBuilder) -> Result<(), JsValue> {
+        let ctx = builder.context;
+        builder.method("decode".intern(), text_decoder_decode, 0)?;
+        let encoding = JsNativeFunction::new(ctx, "encoding".intern(), text_decoder_encoding, 0);
+        builder.getter("encoding".intern(), encoding, CONFIGURABLE)?;
+        let fatal = JsNativeFunction::new(ctx, "fatal".intern(), text_decoder_fatal, 0);
+        builder.getter("fatal".intern(), fatal, CONFIGURABLE)?;
+        let ignore_bom =
+            JsNativeFunction::new(ctx, "ignoreBOM".intern(), text_decoder_ignore_bom, 0);
+        builder.getter("ignoreBOM".intern(), ignore_bom, CONFIGURABLE)?;
+        Ok(())
+    }
+}
+
+impl JsTextDecoder {
+    pub fn new(encoding: Encoding, fatal: bool, ignore_bom: bool) -> Self {
+        Self {
+            encoding,
+            fatal,
+            ignore_bom,
+            bom_seen: false,
+            pending: vec![],
+        }
+    }
+
+    /// Decode `input` after the bytes kept from the previous call. With `stream` an incomplete
+    /// sequence at the end is kept for the next call, otherwise the decoder is reset afterwards.
+    /// Returns `None` if the input is malformed and the decoder is fatal.
+    pub fn decode(&mut self, input: &[u8], stream: bool) -> Option<String> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(input);
+        let mut output = String::with_capacity(bytes.len());
+        let result = match self.encoding {
+            Encoding::Utf8 => decode_utf8(&bytes, stream, self.fatal, &mut output),
+            Encoding::Utf16Le => decode_utf16le(&bytes, stream, self.fatal, &mut output),
+            Encoding::Windows1252 => {
+                output.extend(bytes.iter().map(|&byte| windows_1252(byte)));
+                Some(bytes.len())
+            }
+        };
+        let consumed = match result {
+            Some(consumed) => consumed,
+            None => {
+                self.reset();
+                return None;
+            }
+        };
+        if !self.ignore_bom && !self.bom_seen && !output.is_empty() {
+            self.bom_seen = true;
+            if output.starts_with('\u{FEFF}') {
+                output.remove(0);
+            }
+        }
+        if stream {
+            self.pending = bytes[consumed..].to_vec();
+        } else {
+            self.reset();
+        }
+        Some(output)
+    }
+
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.bom_seen = false;
+    }
+}
+
+/// Returns the number of bytes consumed, the rest is an incomplete sequence kept for streaming.
+fn decode_utf8(bytes: &[u8], stream: bool, fatal: bool, output: &mut String) -> Option<usize> {
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(text) => {
+                output.push_str(text);
+                return Some(bytes.len());
+            }
+            Err(error) => {
+                let (valid, invalid) = rest.split_at(error.valid_up_to());
+                // `valid_up_to` is the length of the longest valid prefix
+                output.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                match error.error_len() {
+                    None if stream => return Some(bytes.len() - invalid.len()),
+                    _ if fatal => return None,
+                    None => {
+                        output.push(char::REPLACEMENT_CHARACTER);
+                        return Some(bytes.len());
+                    }
+                    Some(length) => {
+                        output.push(char::REPLACEMENT_CHARACTER);
+                        rest = &invalid[length..];
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of bytes consumed, the rest is an odd byte or a lead surrogate at the end
+/// that is kept for streaming.
+fn decode_utf16le(bytes: &[u8], stream: bool, fatal: bool, output: &mut String) -> Option<usize> {
+    let mut units = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+    let mut consumed = units.len() * 2;
+    if stream {
+        if let Some(0xD800..=0xDBFF) = units.last() {
+            units.pop();
+            consumed -= 2;
+        }
+    }
+    for c in char::decode_utf16(units) {
+        match c {
+            Ok(c) => output.push(c),
+            Err(_) if fatal => return None,
+            Err(_) => output.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
+    if bytes.len() % 2 != 0 && !stream {
+        if fatal {
+            return None;
+        }
+        output.push(char::REPLACEMENT_CHARACTER);
+        consumed += 1;
+    }
+    Some(consumed)
+}
+
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => {
+            char::from_u32(WINDOWS_1252_HIGH[byte as usize - 0x80] as u32).unwrap_or_default()
+        }
+        byte => byte as char,
+    }
+}
+
+/// Copy of the bytes viewed by an `ArrayBuffer`, `SharedArrayBuffer` or `DataView`, `None` for
+/// other values. Detached buffers and out of bounds views are empty.
+pub(crate) fn buffer_source_bytes(value: JsValue) -> Option<Vec<u8>> {
+    let (buffer, offset, length) = buffer_source(value)?;
+    if length == 0 {
+        return Some(vec![]);
+    }
+    Some(buffer.data()[offset..offset + length].to_vec())
+}
+
+/// Buffer, offset and length of the bytes viewed by `value`.
+fn buffer_source(value: JsValue) -> Option<(TypedJsObject<JsArrayBuffer>, usize, usize)> {
+    if !value.is_jsobject() {
+        return None;
+    }
+    let object = value.get_jsobject();
+    if object.is_class(JsArrayBuffer::class()) {
+        let buffer = TypedJsObject::<JsArrayBuffer>::new(object);
+        let length = if buffer.attached() { buffer.size() } else { 0 };
+        return Some((buffer, 0, length));
+    }
+    if object.is_class(JsDataView::class()) {
+        let view = TypedJsObject::<JsDataView>::new(object);
+        return Some((view.get_buffer(), view.byte_offset(), view.byte_length()));
+    }
+    None
+}
+
+/// `TextEncoder.prototype.encode(input = '')`: UTF-8 bytes of `input` in a new `ArrayBuffer`.
+pub fn text_encoder_encode(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    TypedJsObject::<JsTextEncoder>::try_from(ctx, args.this)?;
+    let input = if args.at(0).is_undefined() {
+        String::new()
+    } else {
+        args.at(0).to_string(ctx)?
+    };
+    letroot!(buffer = stack, JsArrayBuffer::new(ctx));
+    let mut typed = TypedJsObject::<JsArrayBuffer>::new(buffer);
+    typed.create_data_block(ctx, input.len(), false)?;
+    typed.data_mut().copy_from_slice(input.as_bytes());
+    Ok(JsValue::new(buffer))
+}
+
+/// `TextEncoder.prototype.encodeInto(source, destination)`: write as many complete characters of
+/// `source` as fit into `destination` and return `{ read, written }`, `read` counts UTF-16 code
+/// units like the lengths of strings do.
+pub fn text_encoder_encode_into(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    TypedJsObject::<JsTextEncoder>::try_from(ctx, args.this)?;
+    let source = args.at(0).to_string(ctx)?;
+    let (mut buffer, offset, length) = match buffer_source(args.at(1)) {
+        Some(destination) => destination,
+        None => return Err(JsValue::new(ctx.new_type_error(
+            "TextEncoder.prototype.encodeInto: destination must be an ArrayBuffer or a DataView",
+        ))),
+    };
+    let mut read = 0;
+    let mut written = 0;
+    if length != 0 {
+        let destination = &mut buffer.data_mut()[offset..offset + length];
+        for c in source.chars() {
+            let size = c.len_utf8();
+            if written + size > destination.len() {
+                break;
+            }
+            c.encode_utf8(&mut destination[written..]);
+            written += size;
+            read += c.len_utf16();
+        }
+    }
+    let mut result = JsObject::new_empty(ctx);
+    result.put(ctx, "read".intern(), JsValue::new(read as u32), false)?;
+    result.put(ctx, "written".intern(), JsValue::new(written as u32), false)?;
+    Ok(JsValue::new(result))
+}
+
+pub fn text_encoder_encoding(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    TypedJsObject::<JsTextEncoder>::try_from(ctx, args.this)?;
+    Ok(JsValue::new(JsString::new(ctx, Encoding::Utf8.name())))
+}
+
+/// `TextDecoder.prototype.decode(input?, { stream = false })`.
+pub fn text_decoder_decode(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut decoder = TypedJsObject::<JsTextDecoder>::try_from(ctx, args.this)?;
+    let input = if args.at(0).is_undefined() {
+        vec![]
+    } else {
+        match buffer_source_bytes(args.at(0)) {
+            Some(bytes) => bytes,
+            None => {
+                return Err(JsValue::new(ctx.new_type_error(
+                    "TextDecoder.prototype.decode: input must be an ArrayBuffer or a DataView",
+                )))
+            }
+        }
+    };
+    let stream = args.at(1).is_jsobject()
+        && args
+            .at(1)
+            .get_jsobject()
+            .get(ctx, "stream".intern())?
+            .to_boolean();
+    match decoder.decode(&input, stream) {
+        Some(text) => Ok(JsValue::new(JsString::new(ctx, text))),
+        None => Err(JsValue::new(ctx.new_type_error(format!(
+            "TextDecoder.prototype.decode: the input is not valid {}",
+            decoder.encoding.name()
+        )))),
+    }
+}
+
+pub fn text_decoder_encoding(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let decoder = TypedJsObject::<JsTextDecoder>::try_from(ctx, args.this)?;
+    Ok(JsValue::new(JsString::new(ctx, decoder.encoding.name())))
+}
+
+pub fn text_decoder_fatal(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let decoder = TypedJsObject::<JsTextDecoder>::try_from(ctx, args.this)?;
+    Ok(JsValue::new(decoder.fatal))
+}
+
+pub fn text_decoder_ignore_bom(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let decoder = TypedJsObject::<JsTextDecoder>::try_from(ctx, args.this)?;
+    Ok(JsValue::new(decoder.ignore_bom))
+}
+
+/// `btoa(data)`: base64 of a string whose characters are all in the range U+0000 to U+00FF.
+pub fn btoa(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let data = args.at(0).to_string(ctx)?;
+    let mut bytes = Vec::with_capacity(data.len());
+    for c in data.chars() {
+        if c as u32 > 0xFF {
+            return Err(invalid_character_error(
+                ctx,
+                "btoa: the string contains characters outside of the Latin1 range",
+            ));
+        }
+        bytes.push(c as u8);
+    }
+    Ok(JsValue::new(JsString::new(ctx, base64_encode(&bytes))))
+}
+
+/// `atob(data)`: decode base64, ASCII whitespace is ignored and padding is optional. Every byte
+/// becomes one character of the result.
+pub fn atob(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let data = args.at(0).to_string(ctx)?;
+    match base64_decode(&data) {
+        Some(bytes) => {
+            let string = bytes.iter().map(|&byte| byte as char).collect::<String>();
+            Ok(JsValue::new(JsString::new(ctx, string)))
+        }
+        None => Err(invalid_character_error(
+            ctx,
+            "atob: the string is not correctly encoded",
+        )),
+    }
+}
+
+/// Error named `InvalidCharacterError` like the `DOMException` thrown by browsers.
+fn invalid_character_error(ctx: GcPointer<Context>, message: &str) -> JsValue {
+    let message = JsString::new(ctx, message);
+    let mut error = JsError::new(ctx, message, None);
+    let name = JsString::new(ctx, "InvalidCharacterError");
+    let _ = error.put(ctx, "name".intern(), JsValue::new(name), false);
+    JsValue::new(error)
+}
+
+/// Standard base64 with padding.
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Forgiving base64 decode of the HTML standard. Returns `None` for malformed input.
+pub fn base64_decode(data: &str) -> Option<Vec<u8>> {
+    let mut data = data
+        .bytes()
+        .filter(|byte| !matches!(byte, b'\t' | b'\n' | b'\x0C' | b'\r' | b' '))
+        .collect::<Vec<_>>();
+    if data.len() % 4 == 0 {
+        for _ in 0..2 {
+            if data.last() == Some(&b'=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data {
+        let value = BASE64_ALPHABET.iter().position(|&c| c == byte)? as u32;
+        buffer = buffer << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// `TextEncoder` and `TextDecoder`, `atob` and `btoa` are installed by [crate::vm::global::JsGlobal].
+pub struct JsEncoding;
+
+impl Builtin for JsEncoding {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::ArrayBuffer);
+
+    fn native_references() -> Vec<usize> {
+        vec![
+            JsTextEncoder::class() as *const _ as _,
+            JsTextDecoder::class() as *const _ as _,
+            JsTextEncoder::raw_constructor as _,
+            JsTextDecoder::raw_constructor as _,
+            text_encoder_encode as _,
+            text_encoder_encode_into as _,
+            text_encoder_encoding as _,
+            text_decoder_decode as _,
+            text_decoder_encoding as _,
+            text_decoder_fatal as _,
+            text_decoder_ignore_bom as _,
+        ]
+    }
+
+    fn init(ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        ctx.register_class::<JsTextEncoder>()?;
+        ctx.register_class::<JsTextDecoder>()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jsrt::encoding::{base64_decode, base64_encode};
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_base64() {
+        let inputs: [&[u8]; 6] = [b"", b"f", b"fo", b"foo", b"foob", b"\xff\x00\xfe"];
+        for input in inputs {
+            assert_eq!(base64_decode(&base64_encode(input)).as_deref(), Some(input));
+        }
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_decode(" Zm 8\n").as_deref(), Some(&b"fo"[..]));
+        assert_eq!(base64_decode("Zm8"), base64_decode("Zm8="));
+        assert_eq!(base64_decode("Z"), None);
+        assert_eq!(base64_decode("Zm=8"), None);
+    }
+
+    #[test]
+    fn test_text_encoding() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var encoder = new TextEncoder();
+             var bytes = encoder.encode('h€llo');
+             var decoder = new TextDecoder();
+             var streamed = decoder.decode(new DataView(bytes, 0, 2), { stream: true }) +
+                 decoder.decode(new DataView(bytes, 2), { stream: true }) + decoder.decode();
+             var target = new ArrayBuffer(3);
+             var into = encoder.encodeInto('a€', target);
+             var utf16 = new ArrayBuffer(6);
+             var utf16View = new DataView(utf16);
+             utf16View.setUint16(0, 0xFEFF, true);
+             utf16View.setUint16(2, 0x68, true);
+             utf16View.setUint16(4, 0x20AC, true);
+             var latin1 = new ArrayBuffer(2);
+             new DataView(latin1).setUint8(0, 0x80);
+             new DataView(latin1).setUint8(1, 0xE9);",
+        );
+        assert_js(ctx, "bytes.byteLength === 7");
+        assert_js(ctx, "encoder.encoding === 'utf-8'");
+        assert_js(ctx, "streamed === 'h€llo'");
+        assert_js(ctx, "into.read === 1 && into.written === 1");
+        assert_js(
+            ctx,
+            "try {
+                 new TextDecoder('utf-8', { fatal: true }).decode(new DataView(bytes, 1, 2));
+                 false
+             } catch (e) { e instanceof TypeError }",
+        );
+        assert_js(ctx, "new TextDecoder('UTF-16').decode(utf16) === 'h€'");
+        assert_js(
+            ctx,
+            "new TextDecoder('utf-16le', { ignoreBOM: true }).decode(utf16) === '\\uFEFFh€'",
+        );
+        assert_js(
+            ctx,
+            "new TextDecoder(' latin1 ').encoding === 'windows-1252'",
+        );
+        assert_js(ctx, "new TextDecoder('latin1').decode(latin1) === '€é'");
+        assert_js(
+            ctx,
+            "new TextDecoder().decode(new DataView(bytes, 0, 2)) === 'h\\uFFFD'",
+        );
+        assert_js(ctx, "btoa('\\xff\\xfe') === '//4='");
+        assert_js(ctx, "atob(' //4 ') === '\\xff\\xfe'");
+    }
+}
@@ -9,6 +9,7 @@ use crate::{
         snapshot::{deserializer::Deserializer, serializer::SnapshotSerializer},
     },
     prelude::*,
+    vm::capabilities::Capability,
 };
 use libffi::low::{
     call as ffi_call, ffi_abi_FFI_DEFAULT_ABI as ABI, ffi_cif, ffi_type, prep_cif, types, CodePtr,
@@ -715,12 +716,7 @@ impl FFIFunction {
 
         let ffi_object = vm.global_object().get(vm, "CFunction".intern())?;
         let structure = Structure::new_indexed(vm, Some(ffi_object.get_jsobject()), false);
-        let mut object = JsObject::new(
-            vm,
-            &structure,
-            FFIFunction::class(),
-            ObjectTag::Ordinary,
-        );
+        let mut object = JsObject::new(vm, &structure, FFIFunction::class(), ObjectTag::Ordinary);
         unsafe {
             (object.data::<FFIFunction>() as *mut ManuallyDrop<Self> as *mut Self).write(f);
         }
@@ -803,6 +799,7 @@ impl FFIFunction {
 }
 
 pub fn ffi_library_open(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Ffi)?;
     let names = args.at(0);
     if !names.is_jsobject() {
         let msg = JsString::new(
@@ -811,7 +808,6 @@ pub fn ffi_library_open(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsV
         );
         return Err(JsValue::new(JsTypeError::new(vm, msg, None)));
     }
-    
 
     letroot!(rnames = stack, vec![]);
     letroot!(names = stack, names.get_jsobject());
@@ -832,7 +828,6 @@ pub fn ffi_library_open(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsV
 }
 
 pub fn ffi_function_attach(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
     let func = unsafe {
         let lib = {
             let val = args.at(0);
@@ -876,7 +871,6 @@ pub fn ffi_function_attach(ctx: GcPointer<Context>, args: &Arguments) -> Result<
 }
 
 pub fn ffi_function_call(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
     vm.heap().defer();
     let func = unsafe {
         let val = args.this;
@@ -26,7 +26,6 @@ use crate::{
 };
 
 pub fn function_to_string(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
     let obj = &args.this;
     if obj.is_callable() {
         letroot!(func = stack, obj.to_object(ctx)?);
@@ -71,7 +70,6 @@ pub fn function_prototype(ctx: GcPointer<Context>, args: &Arguments) -> Result<J
 }
 
 pub fn function_bind(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
     letroot!(obj = stack, args.this);
 
     if obj.is_callable() {
@@ -113,7 +111,6 @@ pub fn function_bind(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValu
 }
 
 pub fn function_apply(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    
     letroot!(this = stack, args.this);
     if this.is_callable() {
         letroot!(obj = stack, this.get_jsobject());
@@ -156,7 +153,7 @@ pub fn function_apply(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsVal
 
 pub fn function_call(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let this = args.this;
-    
+
     if this.is_callable() {
         letroot!(obj = stack, this.get_jsobject());
         letroot!(objc = stack, obj);
@@ -5,9 +5,12 @@ use crate::vm::class::JsClass;
 use crate::vm::symbol_table::Internable;
 use crate::{
     gc::cell::GcPointer,
-    jsrt::{self, global},
+    jsrt::{self, encoding, global, structured_clone, timers},
     prelude::JsString,
-    vm::{arguments::Arguments, builder::Builtin, context::Context, global::JsGlobal, value::*},
+    vm::{
+        arguments::Arguments, builder::Builtin, capabilities::Capability, context::Context,
+        global::JsGlobal, value::*,
+    },
 };
 use num::traits::*;
 use std::io::Write;
@@ -156,6 +159,7 @@ pub fn is_finite(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, J
 }
 
 pub fn gc(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::TestHooks)?;
     ctx.heap().gc();
     let _ = args;
     Ok(JsValue::encode_undefined_value())
@@ -198,6 +202,7 @@ pub fn ___is_constructor(_ctx: GcPointer<Context>, args: &Arguments) -> Result<J
 }
 
 pub fn read_line(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
     let prompt = if args.size() > 0 {
         Some(args.at(0).to_string(ctx)?)
     } else {
@@ -215,6 +220,197 @@ pub fn read_line(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, J
     Ok(JsValue::new(JsString::new(ctx, buf)))
 }
 
+const URI_RESERVED: &str = ";/?:@&=+$,";
+const URI_UNESCAPED_MARKS: &str = "-_.!~*'()";
+
+fn is_uri_unescaped(c: char) -> bool {
+    c.is_ascii_alphanumeric() || URI_UNESCAPED_MARKS.contains(c)
+}
+
+fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Encode ( string, unescapedSet )
+///
+/// https://tc39.es/ecma262/#sec-encode
+fn encode(string: &str, unescaped: impl Fn(char) -> bool) -> String {
+    let mut result = String::with_capacity(string.len());
+    let mut buf = [0u8; 4];
+    for c in string.chars() {
+        if unescaped(c) {
+            result.push(c);
+            continue;
+        }
+        for byte in c.encode_utf8(&mut buf).bytes() {
+            result.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    result
+}
+
+/// Decode ( string, reservedSet )
+///
+/// https://tc39.es/ecma262/#sec-decode
+fn decode(
+    ctx: GcPointer<Context>,
+    string: &str,
+    reserved: impl Fn(char) -> bool,
+) -> Result<String, JsValue> {
+    let malformed = || JsValue::new(ctx.new_uri_error("URI malformed"));
+    let bytes = string.as_bytes();
+    let read_escape = |k: usize| -> Option<u8> {
+        if k + 2 >= bytes.len() || bytes[k] != b'%' {
+            return None;
+        }
+        Some(hex_value(bytes[k + 1])? << 4 | hex_value(bytes[k + 2])?)
+    };
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut k = 0;
+    while k < bytes.len() {
+        if bytes[k] != b'%' {
+            result.push(bytes[k]);
+            k += 1;
+            continue;
+        }
+        let start = k;
+        let first = read_escape(k).ok_or_else(malformed)?;
+        k += 3;
+        if first & 0x80 == 0 {
+            if reserved(first as char) {
+                result.extend_from_slice(&bytes[start..k]);
+            } else {
+                result.push(first);
+            }
+            continue;
+        }
+        let n = first.leading_ones() as usize;
+        if n == 1 || n > 4 {
+            return Err(malformed());
+        }
+        let mut octets = vec![first];
+        for _ in 1..n {
+            let octet = read_escape(k).ok_or_else(malformed)?;
+            if octet & 0xC0 != 0x80 {
+                return Err(malformed());
+            }
+            octets.push(octet);
+            k += 3;
+        }
+        // `from_utf8` rejects overlong encodings and surrogate code points as the spec requires.
+        match std::str::from_utf8(&octets) {
+            Ok(decoded) => result.extend_from_slice(decoded.as_bytes()),
+            Err(_) => return Err(malformed()),
+        }
+    }
+    Ok(String::from_utf8(result).unwrap_or_else(|_| unreachable!()))
+}
+
+pub fn encode_uri(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let encoded = encode(&string, |c| {
+        is_uri_unescaped(c) || URI_RESERVED.contains(c) || c == '#'
+    });
+    Ok(JsValue::new(JsString::new(ctx, encoded)))
+}
+
+pub fn encode_uri_component(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let encoded = encode(&string, is_uri_unescaped);
+    Ok(JsValue::new(JsString::new(ctx, encoded)))
+}
+
+pub fn decode_uri(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let decoded = decode(ctx, &string, |c| URI_RESERVED.contains(c) || c == '#')?;
+    Ok(JsValue::new(JsString::new(ctx, decoded)))
+}
+
+pub fn decode_uri_component(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let decoded = decode(ctx, &string, |_| false)?;
+    Ok(JsValue::new(JsString::new(ctx, decoded)))
+}
+
+/// B.2.1.1 escape ( string )
+pub fn escape(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let mut result = String::with_capacity(string.len());
+    for unit in string.encode_utf16() {
+        match char::from_u32(unit as u32) {
+            Some(c) if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) => result.push(c),
+            _ if unit < 256 => result.push_str(&format!("%{:02X}", unit)),
+            _ => result.push_str(&format!("%u{:04X}", unit)),
+        }
+    }
+    Ok(JsValue::new(JsString::new(ctx, result)))
+}
+
+/// B.2.1.2 unescape ( string )
+pub fn unescape(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.at(0).to_string(ctx)?;
+    let units = string.encode_utf16().collect::<Vec<u16>>();
+    let hex = |units: &[u16]| -> Option<u16> {
+        units.iter().try_fold(0u16, |acc, unit| {
+            let digit = char::from_u32(*unit as u32)?.to_digit(16)?;
+            Some(acc << 4 | digit as u16)
+        })
+    };
+    let mut result = Vec::with_capacity(units.len());
+    let mut k = 0;
+    while k < units.len() {
+        let unit = units[k];
+        if unit == '%' as u16 {
+            if units.get(k + 1) == Some(&('u' as u16)) && k + 6 <= units.len() {
+                if let Some(decoded) = hex(&units[k + 2..k + 6]) {
+                    result.push(decoded);
+                    k += 6;
+                    continue;
+                }
+            }
+            if k + 3 <= units.len() {
+                if let Some(decoded) = hex(&units[k + 1..k + 3]) {
+                    result.push(decoded);
+                    k += 3;
+                    continue;
+                }
+            }
+        }
+        result.push(unit);
+        k += 1;
+    }
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        String::from_utf16_lossy(&result),
+    )))
+}
+
+pub fn queue_microtask(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let callback = args.at(0);
+    if !callback.is_callable() {
+        return Err(JsValue::new(
+            ctx.new_type_error("queueMicrotask: callback is not a function"),
+        ));
+    }
+    let root = ctx.vm().add_persistent_root(callback);
+    ctx.schedule_async(move |ctx| {
+        let mut callback = root.get_value().get_jsobject();
+        let this = JsValue::encode_undefined_value();
+        let mut args = Arguments::new(this, &mut []);
+        if let Err(error) = callback.as_function_mut().call(ctx, &mut args, this) {
+            let mut vm = ctx.vm();
+            let error = vm.persistent(error);
+            vm.event_loop.report_uncaught(error);
+        }
+    })?;
+    Ok(JsValue::encode_undefined_value())
+}
+
 impl Builtin for JsGlobal {
     fn native_references() -> Vec<usize> {
         vec![
@@ -230,6 +426,20 @@ impl Builtin for JsGlobal {
             ___is_callable as _,
             ___trunc as _,
             to_string as _,
+            encode_uri as _,
+            encode_uri_component as _,
+            decode_uri as _,
+            decode_uri_component as _,
+            escape as _,
+            unescape as _,
+            queue_microtask as _,
+            structured_clone::structured_clone as _,
+            timers::set_timeout as _,
+            timers::set_interval as _,
+            timers::set_immediate as _,
+            timers::clear_timer as _,
+            encoding::atob as _,
+            encoding::btoa as _,
         ]
     }
 
@@ -244,13 +454,17 @@ impl Builtin for JsGlobal {
         def_native_property!(ctx, global_object, Infinity, std::f64::INFINITY)?;
 
         // Method
-        def_native_method!(ctx, global_object, print, jsrt::print, 0)?;
+        if ctx.capabilities().allows(Capability::Stdio) {
+            def_native_method!(ctx, global_object, print, jsrt::print, 0)?;
+            def_native_method!(ctx, global_object, readLine, global::read_line, 1)?;
+        }
         def_native_method!(ctx, global_object, isFinite, global::is_finite, 1)?;
         def_native_method!(ctx, global_object, isNaN, global::is_nan, 1)?;
         def_native_method!(ctx, global_object, parseInt, global::parse_int, 1)?;
-        def_native_method!(ctx, global_object, readLine, global::read_line, 1)?;
         def_native_method!(ctx, global_object, parseFloat, global::parse_float, 1)?;
-        def_native_method!(ctx, global_object, gc, global::gc, 0)?;
+        if ctx.capabilities().allows(Capability::TestHooks) {
+            def_native_method!(ctx, global_object, gc, global::gc, 0)?;
+        }
         def_native_method!(ctx, global_object, ___trunc, global::___trunc, 1)?;
         def_native_method!(ctx, global_object, ___isCallable, global::___is_callable, 1)?;
         def_native_method!(
@@ -261,7 +475,75 @@ impl Builtin for JsGlobal {
             1
         )?;
         def_native_method!(ctx, global_object, toString, global::to_string, 1)?;
+        def_native_method!(ctx, global_object, encodeURI, global::encode_uri, 1)?;
+        def_native_method!(
+            ctx,
+            global_object,
+            encodeURIComponent,
+            global::encode_uri_component,
+            1
+        )?;
+        def_native_method!(ctx, global_object, decodeURI, global::decode_uri, 1)?;
+        def_native_method!(
+            ctx,
+            global_object,
+            decodeURIComponent,
+            global::decode_uri_component,
+            1
+        )?;
+        def_native_method!(ctx, global_object, escape, global::escape, 1)?;
+        def_native_method!(ctx, global_object, unescape, global::unescape, 1)?;
+        def_native_method!(
+            ctx,
+            global_object,
+            queueMicrotask,
+            global::queue_microtask,
+            1
+        )?;
+        def_native_method!(ctx, global_object, setTimeout, timers::set_timeout, 2)?;
+        def_native_method!(ctx, global_object, setInterval, timers::set_interval, 2)?;
+        def_native_method!(ctx, global_object, setImmediate, timers::set_immediate, 1)?;
+        def_native_method!(ctx, global_object, clearTimeout, timers::clear_timer, 1)?;
+        def_native_method!(ctx, global_object, clearInterval, timers::clear_timer, 1)?;
+        def_native_method!(ctx, global_object, clearImmediate, timers::clear_timer, 1)?;
+        def_native_method!(
+            ctx,
+            global_object,
+            structuredClone,
+            structured_clone::structured_clone,
+            1
+        )?;
+        def_native_method!(ctx, global_object, atob, encoding::atob, 1)?;
+        def_native_method!(ctx, global_object, btoa, encoding::btoa, 1)?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_uri_coding() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let encoded = eval(ctx, "encodeURIComponent('a b/c?d=\u{e9}')");
+        assert_eq!(
+            encoded.to_string(ctx).unwrap_or_default(),
+            "a%20b%2Fc%3Fd%3D%C3%A9"
+        );
+
+        let decoded = eval(ctx, "decodeURI('%41%2F%C3%A9')");
+        assert_eq!(decoded.to_string(ctx).unwrap_or_default(), "A%2F\u{e9}");
+
+        assert_js(
+            ctx,
+            "try { decodeURIComponent('%E0%A4%A'); false } catch (e) { e instanceof URIError }",
+        );
+    }
+}
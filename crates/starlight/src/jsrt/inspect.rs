@@ -0,0 +1,380 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Human readable formatting of values, used by `console` and to echo results in the REPL.
+//!
+//! Properties are read from their own descriptors, so getters are never invoked and accessors
+//! are printed as `[Getter]`, `[Setter]` or `[Getter/Setter]`. Errors, dates and regular
+//! expressions are the exception: they print as their `toString` returns. Cycles are printed as
+//! `[Circular]` and objects nested deeper than [Inspector::depth] are abbreviated to `[Object]`,
+//! `[Array]`, `[Map]` or `[Set]`.
+use crate::jsrt::map::map_storage;
+use crate::prelude::*;
+use crate::vm::{
+    array_buffer::JsArrayBuffer, context::Context, data_view::JsDataView, object::TypedJsObject,
+};
+
+/// Lines longer than this are broken into one entry per line.
+const LINE_WIDTH: usize = 72;
+/// Arrays, Maps and Sets print at most this many elements.
+const MAX_ARRAY_LENGTH: u32 = 100;
+/// Array buffers print at most this many bytes.
+const MAX_BYTES: usize = 50;
+
+pub struct Inspector {
+    depth: u32,
+    seen: Vec<*const JsObject>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self {
+            depth: 2,
+            seen: vec![],
+        }
+    }
+
+    /// Nesting level after which objects are abbreviated. The default is 2.
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn inspect(&mut self, ctx: GcPointer<Context>, value: JsValue) -> Result<String, JsValue> {
+        self.seen.clear();
+        self.format(ctx, value, 0)
+    }
+
+    fn format(
+        &mut self,
+        ctx: GcPointer<Context>,
+        value: JsValue,
+        level: u32,
+    ) -> Result<String, JsValue> {
+        if value.is_jsstring() {
+            return Ok(quote(value.get_string().as_str()));
+        }
+        if value.is_symbol() {
+            let symbol = value.get_object().downcast::<JsSymbol>().unwrap();
+            return Ok(format!("Symbol({})", ctx.description(symbol.symbol())));
+        }
+        if value.is_number() && value.get_number() == 0.0 && value.get_number().is_sign_negative() {
+            return Ok("-0".to_owned());
+        }
+        if !value.is_jsobject() {
+            return value.to_string(ctx);
+        }
+
+        let object = value.get_jsobject();
+        let key = &*object as *const JsObject;
+        if self.seen.contains(&key) {
+            return Ok("[Circular]".to_owned());
+        }
+        self.seen.push(key);
+        let result = self.format_object(ctx, object, level);
+        self.seen.pop();
+        result
+    }
+
+    fn format_object(
+        &mut self,
+        ctx: GcPointer<Context>,
+        mut object: GcPointer<JsObject>,
+        level: u32,
+    ) -> Result<String, JsValue> {
+        if object.is_callable() {
+            let name = match object.get_own_property(ctx, "name".intern()) {
+                Some(desc) if desc.is_data() => desc.value(),
+                _ => JsValue::UNDEFINED,
+            };
+            return Ok(
+                if name.is_jsstring() && !name.get_string().as_str().is_empty() {
+                    format!("[Function: {}]", name.get_string().as_str())
+                } else {
+                    "[Function (anonymous)]".to_owned()
+                },
+            );
+        }
+        match object.class.name {
+            "Error" | "Date" | "RegExp" => return JsValue::new(object).to_string(ctx),
+            _ => (),
+        }
+        if object.is_class(JsArrayBuffer::class()) {
+            return Ok(format_array_buffer(TypedJsObject::new(object)));
+        }
+        if object.is_class(JsDataView::class()) {
+            let view = TypedJsObject::<JsDataView>::new(object);
+            return Ok(format!(
+                "DataView {{ byteLength: {}, byteOffset: {}, buffer: {} }}",
+                view.byte_length(),
+                view.byte_offset(),
+                format_array_buffer(view.get_buffer())
+            ));
+        }
+
+        let is_array = object.is_class(JsArray::class());
+        let mut map = None;
+        let mut kind = "Map";
+        if !is_array {
+            map = map_storage(ctx, JsValue::new(object), "[[MapData]]".intern().private());
+            if map.is_none() {
+                map = map_storage(ctx, JsValue::new(object), "[[SetData]]".intern().private());
+                kind = "Set";
+            }
+        }
+        if level > self.depth {
+            return Ok(match (is_array, map.is_some()) {
+                (true, _) => "[Array]".to_owned(),
+                (_, true) => format!("[{}]", kind),
+                _ => "[Object]".to_owned(),
+            });
+        }
+
+        let mut entries = vec![];
+        if let Some(map) = map {
+            let pairs = map.iter().collect::<Vec<_>>();
+            for (key, value) in pairs.iter().take(MAX_ARRAY_LENGTH as usize) {
+                let key = self.format(ctx, *key, level + 1)?;
+                if kind == "Set" {
+                    entries.push(key);
+                    continue;
+                }
+                let value = self.format(ctx, *value, level + 1)?;
+                entries.push(format!("{} => {}", key, value));
+            }
+            if pairs.len() > MAX_ARRAY_LENGTH as usize {
+                entries.push(format!(
+                    "... {} more items",
+                    pairs.len() - MAX_ARRAY_LENGTH as usize
+                ));
+            }
+        }
+        if is_array {
+            let length = object.indexed.length();
+            let mut holes = 0;
+            for i in 0..length.min(MAX_ARRAY_LENGTH) {
+                let desc = match object.get_own_property(ctx, Symbol::Index(i)) {
+                    Some(desc) => desc,
+                    None => {
+                        holes += 1;
+                        continue;
+                    }
+                };
+                if holes != 0 {
+                    entries.push(empty_items(holes));
+                    holes = 0;
+                }
+                entries.push(self.format_property(ctx, &desc, level)?);
+            }
+            if holes != 0 {
+                entries.push(empty_items(holes));
+            }
+            if length > MAX_ARRAY_LENGTH {
+                entries.push(format!("... {} more items", length - MAX_ARRAY_LENGTH));
+            }
+        }
+
+        let mut names = vec![];
+        object.get_own_property_names(
+            ctx,
+            &mut |name, _| names.push(name),
+            EnumerationMode::Default,
+        );
+        for name in names {
+            let key = match name {
+                Symbol::Private(_) => continue,
+                Symbol::Index(_) if is_array => continue,
+                name => property_key(&ctx.description(name)),
+            };
+            let desc = match object.get_own_property(ctx, name) {
+                Some(desc) => desc,
+                None => continue,
+            };
+            let value = self.format_property(ctx, &desc, level)?;
+            entries.push(format!("{}: {}", key, value));
+        }
+
+        if let Some(map) = map {
+            let open = format!("{}({}) {{", kind, map.len());
+            return Ok(join_entries(&open, "}", &entries, level));
+        }
+        let (open, close) = if is_array { ("[", "]") } else { ("{", "}") };
+        Ok(join_entries(open, close, &entries, level))
+    }
+
+    /// Format the value of a property of an object at `level` without invoking its accessors.
+    fn format_property(
+        &mut self,
+        ctx: GcPointer<Context>,
+        desc: &PropertyDescriptor,
+        level: u32,
+    ) -> Result<String, JsValue> {
+        if !desc.is_accessor() {
+            return self.format(ctx, desc.value(), level + 1);
+        }
+        Ok(
+            match (desc.getter().is_callable(), desc.setter().is_callable()) {
+                (true, true) => "[Getter/Setter]",
+                (true, false) => "[Getter]",
+                _ => "[Setter]",
+            }
+            .to_owned(),
+        )
+    }
+}
+
+/// Format `value` with the default [Inspector].
+pub fn inspect(ctx: GcPointer<Context>, value: JsValue) -> Result<String, JsValue> {
+    Inspector::new().inspect(ctx, value)
+}
+
+fn join_entries(open: &str, close: &str, entries: &[String], level: u32) -> String {
+    if entries.is_empty() {
+        return format!("{}{}", open, close);
+    }
+    let width = entries.iter().map(|entry| entry.len() + 2).sum::<usize>();
+    if width + open.len() + close.len() <= LINE_WIDTH && !entries.iter().any(|e| e.contains('\n')) {
+        return format!("{} {} {}", open, entries.join(", "), close);
+    }
+    let indent = "  ".repeat(level as usize + 1);
+    let mut result = format!("{}\n", open);
+    for (i, entry) in entries.iter().enumerate() {
+        result.push_str(&indent);
+        result.push_str(entry);
+        if i + 1 != entries.len() {
+            result.push(',');
+        }
+        result.push('\n');
+    }
+    result.push_str(&"  ".repeat(level as usize));
+    result.push_str(close);
+    result
+}
+
+fn format_array_buffer(buffer: TypedJsObject<JsArrayBuffer>) -> String {
+    let name = if buffer.is_shared() {
+        "SharedArrayBuffer"
+    } else {
+        "ArrayBuffer"
+    };
+    if !buffer.attached() {
+        return format!("{} {{ (detached), byteLength: 0 }}", name);
+    }
+    let size = buffer.size();
+    let mut contents = String::new();
+    if size != 0 {
+        for byte in buffer.data().iter().take(MAX_BYTES) {
+            contents.push_str(&format!(" {:02x}", byte));
+        }
+    }
+    if size > MAX_BYTES {
+        contents.push_str(&format!(" ... {} more bytes", size - MAX_BYTES));
+    }
+    format!(
+        "{} {{ [Uint8Contents]: <{}>, byteLength: {} }}",
+        name,
+        contents.trim_start(),
+        size
+    )
+}
+
+fn empty_items(count: u32) -> String {
+    format!(
+        "<{} empty item{}>",
+        count,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+/// Keys that are not identifiers are printed quoted.
+fn property_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = match chars.next() {
+        Some(c) => {
+            (c.is_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        None => false,
+    };
+    if is_identifier {
+        key.to_owned()
+    } else {
+        quote(key)
+    }
+}
+
+fn quote(string: &str) -> String {
+    let mut result = String::with_capacity(string.len() + 2);
+    result.push('\'');
+    for c in string.chars() {
+        match c {
+            '\'' => result.push_str("\\'"),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\x{:02x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('\'');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jsrt::inspect::inspect;
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{describe, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_inspect() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let value = eval(
+            ctx,
+            "var o = { a: 1, 'b-c': 'x', list: [1, , 'two', [[[]]]], f: function named() {} };
+             o.self = o;
+             o",
+        );
+        let string = inspect(ctx, value)
+            .unwrap_or_else(|error| panic!("inspect threw {}", describe(ctx, error)));
+        assert_eq!(
+            string,
+            "{\n  a: 1,\n  'b-c': 'x',\n  list: [ 1, <1 empty item>, 'two', [ [Array] ] ],\n  f: [Function: named],\n  self: [Circular]\n}"
+        );
+    }
+
+    #[test]
+    fn test_inspect_throwing_getters() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let value = eval(
+            ctx,
+            "var thrower = { get() { throw new Error('getter invoked'); } };
+             var list = [1, 2];
+             Object.defineProperty(list, 1, thrower);
+             var f = function () {};
+             Object.defineProperty(f, 'name', thrower);
+             var o = { list, f };
+             Object.defineProperty(o, 'boom', { enumerable: true, get: thrower.get });
+             o",
+        );
+        let string = inspect(ctx, value)
+            .unwrap_or_else(|error| panic!("inspect threw {}", describe(ctx, error)));
+        assert_eq!(
+            string,
+            "{ list: [ 1, [Getter] ], f: [Function (anonymous)], boom: [Getter] }"
+        );
+    }
+}
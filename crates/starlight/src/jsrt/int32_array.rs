@@ -0,0 +1,268 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+use crate::{
+    prelude::*,
+    vm::{
+        array_buffer::JsArrayBuffer, builder::Builtin, capabilities::BuiltinGroup,
+        context::Context, int32_array::JsInt32Array, object::TypedJsObject,
+    },
+};
+
+/// `this` of an `Int32Array.prototype` getter.
+fn this_int32_array(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    name: &str,
+) -> Result<GcPointer<JsObject>, JsValue> {
+    let this = args.this.to_object(ctx)?;
+    if !this.is_class(JsInt32Array::class()) {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "Int32Array.prototype.{} called on a non Int32Array object",
+            name
+        ))));
+    }
+    Ok(this)
+}
+
+pub fn int32_array_prototype_buffer(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let this = this_int32_array(ctx, args, "buffer")?;
+    Ok(JsValue::new(this.data::<JsInt32Array>().get_buffer()))
+}
+
+pub fn int32_array_prototype_byte_length(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let this = this_int32_array(ctx, args, "byteLength")?;
+    Ok(JsValue::new(
+        this.data::<JsInt32Array>().byte_length() as u32
+    ))
+}
+
+pub fn int32_array_prototype_byte_offset(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let this = this_int32_array(ctx, args, "byteOffset")?;
+    let array = this.data::<JsInt32Array>();
+    if array.is_out_of_bounds() {
+        return Ok(JsValue::new(0));
+    }
+    Ok(JsValue::new(array.byte_offset() as u32))
+}
+
+pub fn int32_array_prototype_length(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let this = this_int32_array(ctx, args, "length")?;
+    Ok(JsValue::new(this.data::<JsInt32Array>().length() as u32))
+}
+
+/// `new Int32Array(length)`, `new Int32Array(arrayLike)` and
+/// `new Int32Array(buffer, [byteOffset], [length])`.
+pub fn int32_array_constructor(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    if !args.ctor_call {
+        return Err(JsValue::new(ctx.new_type_error(
+            "Int32Array() called in a function context instead of constructor",
+        )));
+    }
+    let first = args.at(0);
+    if first.is_jsobject() && first.get_jsobject().is_class(JsArrayBuffer::class()) {
+        let buffer = TypedJsObject::<JsArrayBuffer>::new(first.get_jsobject());
+        let offset = super::to_index(ctx, args.at(1))?;
+        if offset % JsInt32Array::BYTES_PER_ELEMENT != 0 {
+            return Err(JsValue::new(ctx.new_range_error(
+                "new Int32Array(buffer, [byteOffset], [length]): byteOffset must be a multiple of 4",
+            )));
+        }
+        let length = if args.at(2).is_undefined() {
+            None
+        } else {
+            Some(super::to_index(ctx, args.at(2))?)
+        };
+        if !buffer.attached() {
+            return Err(JsValue::new(ctx.new_type_error(
+                "new Int32Array(buffer, [byteOffset], [length]): buffer is detached",
+            )));
+        }
+        let buffer_length = buffer.byte_length();
+        let length = match length {
+            // arrays over resizable buffers without explicit length follow the buffer's length
+            None if buffer.is_resizable() && offset <= buffer_length => None,
+            None if buffer_length % JsInt32Array::BYTES_PER_ELEMENT != 0 => {
+                return Err(JsValue::new(ctx.new_range_error(
+                    "new Int32Array(buffer, [byteOffset], [length]): the buffer's byte length must be a multiple of 4",
+                )))
+            }
+            None if offset <= buffer_length => {
+                Some((buffer_length - offset) / JsInt32Array::BYTES_PER_ELEMENT)
+            }
+            Some(length)
+                if offset + length * JsInt32Array::BYTES_PER_ELEMENT <= buffer_length =>
+            {
+                Some(length)
+            }
+            _ => {
+                return Err(JsValue::new(ctx.new_range_error(
+                    "new Int32Array(buffer, [byteOffset], [length]): the array must be inside the buffer",
+                )))
+            }
+        };
+        return Ok(JsValue::new(JsInt32Array::new(ctx, buffer, offset, length)));
+    }
+
+    let (length, source) = if first.is_jsobject() {
+        let mut source = first.get_jsobject();
+        (super::get_length(ctx, &mut source)? as usize, Some(source))
+    } else {
+        (super::to_index(ctx, first)?, None)
+    };
+    if length > u32::MAX as usize / JsInt32Array::BYTES_PER_ELEMENT {
+        return Err(JsValue::new(ctx.new_range_error(
+            "new Int32Array(length): invalid typed array length",
+        )));
+    }
+    letroot!(buffer = stack, JsArrayBuffer::new(ctx));
+    let mut typed = TypedJsObject::<JsArrayBuffer>::new(buffer);
+    typed.create_data_block(ctx, length * JsInt32Array::BYTES_PER_ELEMENT, true)?;
+    letroot!(
+        array = stack,
+        JsInt32Array::new(ctx, typed, 0, Some(length))
+    );
+    if let Some(mut source) = source {
+        for index in 0..length as u32 {
+            let value = source.get(ctx, Symbol::Index(index))?;
+            array.put(ctx, Symbol::Index(index), value, true)?;
+        }
+    }
+    Ok(JsValue::new(*array))
+}
+
+impl Builtin for JsInt32Array {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::ArrayBuffer);
+
+    fn native_references() -> Vec<usize> {
+        vec![
+            JsInt32Array::class() as *const _ as usize,
+            int32_array_constructor as _,
+            int32_array_prototype_buffer as _,
+            int32_array_prototype_byte_length as _,
+            int32_array_prototype_byte_offset as _,
+            int32_array_prototype_length as _,
+        ]
+    }
+
+    fn init(mut ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        let obj_proto = ctx.global_data.object_prototype.unwrap();
+        ctx.global_data.int32_array_structure = Some(Structure::new_indexed(ctx, None, false));
+        let proto_map = ctx
+            .global_data
+            .int32_array_structure
+            .unwrap()
+            .change_prototype_transition(ctx, Some(obj_proto));
+        let mut prototype = JsObject::new(ctx, &proto_map, JsObject::class(), ObjectTag::Ordinary);
+        ctx.global_data
+            .int32_array_structure
+            .unwrap()
+            .change_prototype_with_no_transition(prototype);
+        let mut constructor =
+            JsNativeFunction::new(ctx, "Int32Array".intern(), int32_array_constructor, 3);
+
+        def_native_property!(ctx, constructor, prototype, prototype)?;
+        def_native_property!(ctx, prototype, constructor, constructor)?;
+        def_native_property!(
+            ctx,
+            constructor,
+            BYTES_PER_ELEMENT,
+            JsInt32Array::BYTES_PER_ELEMENT as u32,
+            NONE
+        )?;
+        def_native_property!(
+            ctx,
+            prototype,
+            BYTES_PER_ELEMENT,
+            JsInt32Array::BYTES_PER_ELEMENT as u32,
+            NONE
+        )?;
+
+        let buffer = JsNativeFunction::new(ctx, "buffer".intern(), int32_array_prototype_buffer, 0);
+        def_native_getter!(ctx, prototype, buffer, buffer, NONE)?;
+        let byte_length =
+            JsNativeFunction::new(ctx, "byteLength", int32_array_prototype_byte_length, 0);
+        def_native_getter!(ctx, prototype, byteLength, byte_length, NONE)?;
+        let byte_offset =
+            JsNativeFunction::new(ctx, "byteOffset", int32_array_prototype_byte_offset, 0);
+        def_native_getter!(ctx, prototype, byteOffset, byte_offset, NONE)?;
+        let length = JsNativeFunction::new(ctx, "length".intern(), int32_array_prototype_length, 0);
+        def_native_getter!(ctx, prototype, length, length, NONE)?;
+
+        ctx.global_data.int32_array_prototype = Some(prototype);
+
+        ctx.global_object()
+            .put(ctx, "Int32Array".intern(), JsValue::new(constructor), false)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_int32_array() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var array = new Int32Array(4);
+             array[0] = 2147483648;
+             array[1] = '7';
+             array[9] = 1;
+             var copy = new Int32Array([1, 2, 3]);
+             var buffer = new ArrayBuffer(16, { maxByteLength: 32 });
+             var view = new Int32Array(buffer, 4);
+             var fixed = new Int32Array(buffer, 4, 2);
+             new DataView(buffer).setInt32(4, -5, true);",
+        );
+        assert_js(ctx, "array.length === 4 && array.byteLength === 16");
+        assert_js(ctx, "array[0] === -2147483648 && array[1] === 7");
+        assert_js(ctx, "array[9] === undefined && !(9 in array)");
+        assert_js(ctx, "Object.keys(copy).join() === '0,1,2' && copy[2] === 3");
+        assert_js(
+            ctx,
+            "view[0] === -5 && view.byteOffset === 4 && view.length === 3",
+        );
+        assert_js(
+            ctx,
+            "(buffer.resize(32), view.length === 7 && fixed.length === 2)",
+        );
+        assert_js(
+            ctx,
+            "(buffer.resize(8), fixed.length === 0 && fixed[0] === undefined)",
+        );
+        assert_js(
+            ctx,
+            "ArrayBuffer.isView(array) && Int32Array.BYTES_PER_ELEMENT === 4",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { new Int32Array(new ArrayBuffer(8), 2); } catch (e) { return e instanceof RangeError; } })()",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { Int32Array(1); } catch (e) { return e instanceof TypeError; } })()",
+        );
+    }
+}
@@ -2,8 +2,8 @@ use crate::prelude::*;
 use crate::vm::context::*;
 
 pub fn _262_create_realm(ctx: GcPointer<Context>, _: &Arguments) -> Result<JsValue, JsValue> {
-    let new_ctx = ctx.vm().new_context();
-    init(new_ctx, "$".intern()).map(JsValue::new)
+    let realm = ctx.new_realm()?;
+    realm.global_object().get(realm, "$".intern())
 }
 pub fn _262_eval_script(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     if let Ok(source) = args.at(0).to_string(ctx) {
@@ -1,8 +1,26 @@
 //! Small standard library for JS featuring IO and other useful stuff.
+use crate::jsrt::encoding::buffer_source_bytes;
 use crate::prelude::*;
+use crate::vm::array_buffer::JsArrayBuffer;
+use crate::vm::capabilities::Capability;
 use crate::vm::context::Context;
-use crate::vm::object::JsObject;
+use crate::vm::event_loop::{IoEvent, IoSource};
+use crate::vm::handles::Persistent;
+use crate::vm::object::{JsObject, TypedJsObject};
+use crate::vm::promise::JsPromise;
+use std::{
+    any::Any,
+    io::Write,
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+pub mod child_process;
 pub mod file;
+pub mod fs;
+pub mod io;
+pub mod net;
+pub mod path;
+pub mod process;
 
 /// Initialize JS std.
 pub fn init_js_std(
@@ -11,17 +29,354 @@ pub fn init_js_std(
 ) -> Result<(), JsValue> {
     let defer = ctx.heap().defer();
     let mut std = JsObject::new_empty(ctx);
-    module.put(ctx, "@expoctxs".intern(), JsValue::new(std), false)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(std), false)?;
     module.put(ctx, "@default".intern(), JsValue::new(std), false)?;
-    file::std_init_file(ctx, std)?;
-    def_native_method!(ctx, std, args, std_args, 0)?;
+    let path = path::path_object(ctx)?;
+    std.put(ctx, "path".intern(), JsValue::new(path), false)?;
+    if ctx.capabilities().allows(Capability::FileSystem) {
+        file::std_init_file(ctx, std)?;
+        let fs = fs::fs_object(ctx)?;
+        std.put(ctx, "fs".intern(), JsValue::new(fs), false)?;
+    }
+    if ctx.capabilities().allows(Capability::Process) {
+        def_native_method!(ctx, std, args, std_args, 0)?;
+        def_native_method!(ctx, std, exit, std_exit, 1)?;
+        let process = process::process_object(ctx)?;
+        std.put(ctx, "process".intern(), JsValue::new(process), false)?;
+        let os = process::os_object(ctx)?;
+        std.put(ctx, "os".intern(), JsValue::new(os), false)?;
+    }
+    if ctx.capabilities().allows(Capability::Stdio) {
+        let io = io::io_object(ctx)?;
+        std.put(ctx, "io".intern(), JsValue::new(io), false)?;
+    }
+    if ctx.capabilities().allows(Capability::Network) {
+        let net = net::net_object(ctx)?;
+        std.put(ctx, "net".intern(), JsValue::new(net), false)?;
+    }
     drop(defer);
     Ok(())
 }
 
+/// `std.args()`: arguments passed to the script, see [Options::script_args].
 pub fn std_args(ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue, JsValue> {
-    let args = std::env::args()
+    ctx.require_capability(Capability::Process)?;
+    let args = ctx
+        .vm()
+        .options()
+        .script_args
+        .iter()
         .map(|x| JsValue::new(JsString::new(ctx, x)))
         .collect::<Vec<_>>();
     Ok(JsValue::new(JsArray::from_slice(ctx, &args)))
 }
+
+/// `std.exit(code = 0)`: dispose the VM, flush stdout and exit the process.
+pub fn std_exit(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let code = if args.size() > 0 {
+        args.at(0).to_int32(ctx)?
+    } else {
+        0
+    };
+    io::restore_terminal();
+    // finalizers run as on a normal exit, nothing returns to the VM after this
+    unsafe {
+        ctx.vm().dispose();
+    }
+    let _ = std::io::stdout().flush();
+    std::process::exit(code)
+}
+
+/// Error thrown by the file system and process natives when `op` on `path` fails.
+pub(crate) fn io_error(
+    ctx: GcPointer<Context>,
+    op: &str,
+    path: &str,
+    error: std::io::Error,
+) -> JsValue {
+    let message = JsString::new(ctx, format!("{} '{}': {}", op, path, error));
+    JsValue::new(JsError::new(ctx, message, None))
+}
+
+/// Run `work` on another thread and return a promise that the event loop settles with the value
+/// `finish` makes of its output, so the JS thread does not wait for blocking calls.
+pub(crate) fn run_in_background<T: Send + 'static>(
+    mut ctx: GcPointer<Context>,
+    work: impl FnOnce() -> T + Send + 'static,
+    finish: impl FnOnce(GcPointer<Context>, T) -> Result<JsValue, JsValue> + 'static,
+) -> Result<JsValue, JsValue> {
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let (sender, receiver) = ctx.vm.event_loop.channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(work());
+    });
+    let source = BackgroundSource {
+        ctx,
+        promise: ctx.vm().persistent(promise),
+        settle: Settle::new(ctx),
+        receiver,
+        finish: Some(Box::new(finish)),
+    };
+    ctx.vm.event_loop.add_source(Box::new(source));
+    Ok(promise)
+}
+
+/// Turns the output of background work into the value of its promise.
+type Finish<T> = Box<dyn FnOnce(GcPointer<Context>, T) -> Result<JsValue, JsValue>>;
+
+/// Work started by [run_in_background], polled until its output arrives.
+struct BackgroundSource<T> {
+    ctx: GcPointer<Context>,
+    promise: Persistent,
+    settle: Settle,
+    receiver: Receiver<T>,
+    finish: Option<Finish<T>>,
+}
+
+impl<T: 'static> IoSource for BackgroundSource<T> {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        let result = match self.receiver.try_recv() {
+            Ok(output) => match self.finish.take() {
+                Some(finish) => finish(self.ctx, output),
+                None => return false,
+            },
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => {
+                let message = JsString::new(self.ctx, "background operation panicked");
+                Err(JsValue::new(JsError::new(self.ctx, message, None)))
+            }
+        };
+        let promise = self.promise.clone();
+        match result {
+            Ok(value) => self.settle.resolve(self.ctx, promise, value, events),
+            Err(error) => self.settle.reject(self.ctx, promise, error, events),
+        }
+        false
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Natives the event loop calls to settle promises of IO sources.
+#[derive(Clone)]
+pub(crate) struct Settle {
+    resolve: Persistent,
+    reject: Persistent,
+}
+
+impl Settle {
+    pub(crate) fn new(ctx: GcPointer<Context>) -> Self {
+        let resolve = JsNativeFunction::new(ctx, "resolve".intern(), std_resolve_promise, 2);
+        let reject = JsNativeFunction::new(ctx, "reject".intern(), std_reject_promise, 2);
+        Self {
+            resolve: ctx.vm().persistent(JsValue::new(resolve)),
+            reject: ctx.vm().persistent(JsValue::new(reject)),
+        }
+    }
+
+    pub(crate) fn resolve(
+        &self,
+        ctx: GcPointer<Context>,
+        promise: Persistent,
+        value: JsValue,
+        events: &mut Vec<IoEvent>,
+    ) {
+        events.push(IoEvent {
+            ctx,
+            callback: self.resolve.clone(),
+            args: vec![promise, ctx.vm().persistent(value)],
+        });
+    }
+
+    pub(crate) fn reject(
+        &self,
+        ctx: GcPointer<Context>,
+        promise: Persistent,
+        error: JsValue,
+        events: &mut Vec<IoEvent>,
+    ) {
+        events.push(IoEvent {
+            ctx,
+            callback: self.reject.clone(),
+            args: vec![promise, ctx.vm().persistent(error)],
+        });
+    }
+}
+
+/// `resolve(promise, value)`: callback used by IO sources to resolve a promise from the event
+/// loop, see [crate::vm::event_loop::IoSource].
+pub fn std_resolve_promise(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let promise = args.at(0);
+    promise
+        .get_jsobject()
+        .as_promise_mut()
+        .resolve(ctx, promise, args.at(1))?;
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `reject(promise, error)`: counterpart of [std_resolve_promise].
+pub fn std_reject_promise(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let promise = args.at(0);
+    promise
+        .get_jsobject()
+        .as_promise_mut()
+        .reject(ctx, promise, args.at(1))?;
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Contents of `value` as bytes: strings are encoded as UTF-8, `ArrayBuffer`s and the bytes viewed
+/// by `DataView`s are copied.
+pub(crate) fn to_bytes(ctx: GcPointer<Context>, value: JsValue) -> Result<Vec<u8>, JsValue> {
+    if value.is_jsobject() && value.get_jsobject().is_class(JsArrayBuffer::class()) {
+        let buffer = TypedJsObject::<JsArrayBuffer>::new(value.get_jsobject());
+        if !buffer.attached() {
+            return Err(JsValue::new(ctx.new_type_error("ArrayBuffer is detached")));
+        }
+    }
+    match buffer_source_bytes(value) {
+        Some(bytes) => Ok(bytes),
+        None => Ok(value.to_string(ctx)?.into_bytes()),
+    }
+}
+
+/// New `ArrayBuffer` holding a copy of `bytes`.
+pub(crate) fn new_array_buffer(
+    ctx: GcPointer<Context>,
+    bytes: &[u8],
+) -> Result<GcPointer<JsObject>, JsValue> {
+    letroot!(buffer = stack, JsArrayBuffer::new(ctx));
+    let mut typed = TypedJsObject::<JsArrayBuffer>::new(buffer);
+    typed.create_data_block(ctx, bytes.len(), false)?;
+    typed.data_mut().copy_from_slice(bytes);
+    Ok(buffer)
+}
+
+/// How data read from pipes and sockets is passed to JS, chosen with the `encoding` option.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DataEncoding {
+    /// Strings decoded as UTF-8, `'utf8'`.
+    Utf8,
+    /// `ArrayBuffer`s, `'buffer'`.
+    Buffer,
+}
+
+impl DataEncoding {
+    /// Value of the `encoding` property of `options`, [DataEncoding::Utf8] if it is missing.
+    /// `name` is the function that received the options.
+    pub(crate) fn from_options(
+        ctx: GcPointer<Context>,
+        options: JsValue,
+        name: &str,
+    ) -> Result<Self, JsValue> {
+        if !options.is_jsobject() {
+            return Ok(Self::Utf8);
+        }
+        let encoding = options.get_jsobject().get(ctx, "encoding".intern())?;
+        if encoding.is_undefined() {
+            return Ok(Self::Utf8);
+        }
+        match encoding.to_string(ctx)?.as_str() {
+            "utf8" | "utf-8" => Ok(Self::Utf8),
+            "buffer" => Ok(Self::Buffer),
+            encoding => Err(JsValue::new(
+                ctx.new_type_error(format!("{}: unknown encoding '{}'", name, encoding)),
+            )),
+        }
+    }
+
+    /// `bytes` as a string or as an `ArrayBuffer`. Invalid UTF-8 is replaced with U+FFFD.
+    pub(crate) fn to_value(
+        self,
+        ctx: GcPointer<Context>,
+        bytes: &[u8],
+    ) -> Result<JsValue, JsValue> {
+        Ok(match self {
+            Self::Utf8 => JsValue::new(JsString::new(ctx, String::from_utf8_lossy(bytes))),
+            Self::Buffer => JsValue::new(new_array_buffer(ctx, bytes)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval_module, run_event_loop};
+    use crate::Platform;
+
+    #[test]
+    fn test_std_modules() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        let dir = std::env::temp_dir().join(format!("starlight-std-{}", std::process::id()));
+        let source = format!(
+            "import fs from 'std:fs';
+             import {{ join, dirname, extname, resolve }} from 'std:path';
+             import process from 'std:process';
+             let dir = {:?};
+             fs.mkdir(join(dir, 'a', 'b'), {{ recursive: true }});
+             fs.rename(join(dir, 'a', 'b'), join(dir, 'a', 'c.txt'));
+             let stat = fs.stat(join(dir, 'a', 'c.txt'));
+             globalThis.dir = dir;
+             globalThis.entries = fs.readDir(join(dir, 'a')).join();
+             globalThis.stat = stat;
+             globalThis.path = {{ join, dirname, extname, resolve }};
+             globalThis.process = process;
+             fs.remove(dir, {{ recursive: true }});
+             globalThis.removed = !fs.exists(dir);",
+            dir.to_string_lossy()
+        );
+        eval_module(ctx, &source);
+        assert_js(ctx, "entries === 'c.txt'");
+        assert_js(ctx, "stat.isDirectory && !stat.isFile");
+        assert_js(
+            ctx,
+            "path.join('a', './b/', '../c') === path.join('a', 'c')",
+        );
+        assert_js(ctx, "path.dirname(path.join(dir, 'a')) === dir");
+        assert_js(ctx, "path.extname('x/y.tar.gz') === '.gz'");
+        assert_js(
+            ctx,
+            "(() => { try { path.extname(1); } catch (e) { return e instanceof TypeError; } })()",
+        );
+        assert_js(ctx, "path.resolve(dir, 'a', '..') === dir");
+        assert_js(ctx, "path.resolve('/x', '/y') === path.resolve('/y')");
+        assert_js(ctx, "process.cwd() === path.resolve()");
+        assert_js(ctx, "process.hrtime(process.hrtime())[0] === 0");
+        assert_js(ctx, "removed");
+    }
+
+    #[test]
+    fn test_fs_promises() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        let dir = std::env::temp_dir().join(format!("starlight-fs-{}", std::process::id()));
+        let source = format!(
+            "import fs from 'std:fs';
+             import {{ join }} from 'std:path';
+             let dir = {:?};
+             fs.promises.mkdir(dir)
+                 .then(() => fs.promises.stat(dir))
+                 .then(stat => {{
+                     globalThis.stat = stat;
+                     return fs.promises.remove(dir);
+                 }})
+                 .then(() => fs.promises.exists(dir))
+                 .then(exists => {{
+                     globalThis.exists = exists;
+                 }});
+             fs.promises.readDir(join(dir, 'missing')).catch(error => {{
+                 globalThis.error = error;
+             }});",
+            dir.to_string_lossy()
+        );
+        eval_module(ctx, &source);
+        run_event_loop(ctx);
+        assert_js(ctx, "stat.isDirectory");
+        assert_js(ctx, "exists === false");
+        assert_js(ctx, "error.message.includes('readDir')");
+    }
+}
@@ -0,0 +1,512 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `process.exec` and `process.spawn`: running other programs.
+//!
+//! Both take `(command, args = [], options = {})` where `options` may contain `cwd`, `env`
+//! (variables added to the environment), `clearEnv` (start from an empty environment), `stdin`
+//! (a string or an `ArrayBuffer` written to the child) and `encoding` (`'utf8'`, the default, or
+//! `'buffer'` to receive the output as `ArrayBuffer`s).
+//!
+//! ```js
+//! let { status, stdout } = process.exec("git", ["rev-parse", "HEAD"]);
+//! let child = process.spawn("cargo", ["build"], { onStderr: chunk => print(chunk) });
+//! child.exited.then(({ status }) => print("cargo exited with " + status));
+//! ```
+use super::{io_error, new_array_buffer, std_resolve_promise, to_bytes, DataEncoding};
+use crate::jsrt::encoding::{Encoding, JsTextDecoder};
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::context::Context;
+use crate::vm::event_loop::{IoEvent, IoSender, IoSource};
+use crate::vm::handles::Persistent;
+use crate::vm::promise::JsPromise;
+use std::{
+    any::Any,
+    io::{Read, Write},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+/// Size of the chunks `spawn` reads from the output of a child.
+const CHUNK_SIZE: usize = 8192;
+
+/// Command and options parsed from the arguments of `exec` and `spawn`.
+struct Spawn {
+    program: String,
+    command: Command,
+    stdin: Option<Vec<u8>>,
+    encoding: DataEncoding,
+}
+
+fn parse(ctx: GcPointer<Context>, args: &Arguments, name: &str) -> Result<Spawn, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let program = args.at(0).to_string(ctx)?;
+    let mut command = Command::new(&program);
+    if args.at(1).is_jsobject() {
+        let mut list = args.at(1).get_jsobject();
+        let length = crate::jsrt::get_length(ctx, &mut list)?;
+        for i in 0..length {
+            command.arg(list.get(ctx, Symbol::Index(i))?.to_string(ctx)?);
+        }
+    }
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut spawn = Spawn {
+        program,
+        command,
+        stdin: None,
+        encoding: DataEncoding::from_options(ctx, args.at(2), name)?,
+    };
+    if !args.at(2).is_jsobject() {
+        return Ok(spawn);
+    }
+    let mut options = args.at(2).get_jsobject();
+    let cwd = options.get(ctx, "cwd".intern())?;
+    if !cwd.is_undefined() {
+        spawn.command.current_dir(cwd.to_string(ctx)?);
+    }
+    if options.get(ctx, "clearEnv".intern())?.to_boolean() {
+        spawn.command.env_clear();
+    }
+    let env = options.get(ctx, "env".intern())?;
+    if env.is_jsobject() {
+        let mut env = env.get_jsobject();
+        let mut names = vec![];
+        env.get_own_property_names(
+            ctx,
+            &mut |name, _| names.push(name),
+            EnumerationMode::Default,
+        );
+        for name in names {
+            if let Symbol::Private(_) = name {
+                continue;
+            }
+            let value = env.get(ctx, name)?.to_string(ctx)?;
+            spawn.command.env(&*ctx.description(name), value);
+        }
+    }
+    let stdin = options.get(ctx, "stdin".intern())?;
+    if !stdin.is_undefined() {
+        spawn.stdin = Some(to_bytes(ctx, stdin)?);
+    }
+    Ok(spawn)
+}
+
+/// `process.exec(command, args?, options?)`: run a program to completion and return
+/// `{ status, signal, stdout, stderr }`. `status` is `null` if the program was killed by a signal,
+/// `signal` is `null` otherwise. Without the `stdin` option the child reads from an empty input.
+pub fn process_exec(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut spawn = parse(ctx, args, "exec")?;
+    spawn.command.stdin(if spawn.stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    let mut child = spawn
+        .command
+        .spawn()
+        .map_err(|e| io_error(ctx, "exec", &spawn.program, e))?;
+    // the input is written from another thread, a child that fills the output pipes before it
+    // reads all of its input would deadlock otherwise
+    let writer = match (child.stdin.take(), spawn.stdin) {
+        (Some(mut pipe), Some(input)) => Some(std::thread::spawn(move || {
+            let _ = pipe.write_all(&input);
+        })),
+        _ => None,
+    };
+    let output = child
+        .wait_with_output()
+        .map_err(|e| io_error(ctx, "exec", &spawn.program, e))?;
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    let mut result = exit_object(ctx, output.status)?;
+    let stdout = spawn.encoding.to_value(ctx, &output.stdout)?;
+    result.put(ctx, "stdout".intern(), stdout, false)?;
+    let stderr = spawn.encoding.to_value(ctx, &output.stderr)?;
+    result.put(ctx, "stderr".intern(), stderr, false)?;
+    Ok(JsValue::new(result))
+}
+
+/// `process.spawn(command, args?, options?)`: start a program without waiting for it.
+///
+/// Output is delivered in chunks to the `onStdout` and `onStderr` callbacks of `options` by the
+/// event loop. The returned object has the `pid` of the child, an `exited` promise resolved with
+/// `{ status, signal }` after all output was delivered, and `write(data)`, `closeStdin()` and
+/// `kill()` methods. With the `stdin` option the input is written and stdin is closed right away.
+pub fn process_spawn(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut spawn = parse(ctx, args, "spawn")?;
+    let (on_stdout, on_stderr) = if args.at(2).is_jsobject() {
+        let mut options = args.at(2).get_jsobject();
+        (
+            callback(ctx, options.get(ctx, "onStdout".intern())?)?,
+            callback(ctx, options.get(ctx, "onStderr".intern())?)?,
+        )
+    } else {
+        (None, None)
+    };
+    spawn.command.stdin(Stdio::piped());
+    let mut child = spawn
+        .command
+        .spawn()
+        .map_err(|e| io_error(ctx, "spawn", &spawn.program, e))?;
+
+    let (sender, output) = ctx.vm.event_loop.channel();
+    if let Some(stdout) = child.stdout.take() {
+        read_in_background(stdout, Stream::Stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        read_in_background(stderr, Stream::Stderr, sender);
+    }
+    let stdin = child.stdin.take().map(write_in_background);
+    let stdin = match (stdin, spawn.stdin) {
+        (Some(stdin), Some(input)) => {
+            let _ = stdin.send(input);
+            None
+        }
+        (stdin, _) => stdin,
+    };
+
+    let exited = JsPromise::new_unresolving(ctx)?;
+    let resolve = JsNativeFunction::new(ctx, "resolve".intern(), std_resolve_promise, 2);
+    let pid = child.id();
+    let source = ChildSource {
+        ctx,
+        child,
+        output,
+        open_streams: 2,
+        stdin,
+        stdout: StreamState::new(on_stdout),
+        stderr: StreamState::new(on_stderr),
+        encoding: spawn.encoding,
+        exited: ctx.vm().persistent(exited),
+        resolve: ctx.vm().persistent(JsValue::new(resolve)),
+    };
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    match child_object(ctx, id, pid, exited) {
+        Ok(object) => Ok(JsValue::new(object)),
+        Err(error) => {
+            // JS cannot reach a child without its object, so it is not left running
+            if let Some(source) = ctx.vm.event_loop.source_mut::<ChildSource>(id) {
+                let _ = source.child.kill();
+            }
+            ctx.vm.event_loop.remove_source(id);
+            Err(error)
+        }
+    }
+}
+
+/// Object returned by `spawn` for the child polled by source `id`.
+fn child_object(
+    ctx: GcPointer<Context>,
+    id: u32,
+    pid: u32,
+    exited: JsValue,
+) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut object = JsObject::new_empty(ctx);
+    object.put(ctx, child_key(), JsValue::new(id), false)?;
+    object.put(ctx, "pid".intern(), JsValue::new(pid), false)?;
+    object.put(ctx, "exited".intern(), exited, false)?;
+    def_native_method!(ctx, object, write, child_write, 1)?;
+    def_native_method!(ctx, object, closeStdin, child_close_stdin, 0)?;
+    def_native_method!(ctx, object, kill, child_kill, 0)?;
+    Ok(object)
+}
+
+/// `child.write(data)`: queue a string or an `ArrayBuffer` for the stdin of the child. Returns
+/// `false` if stdin was already closed.
+pub fn child_write(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = child_id(ctx, args)?;
+    let data = to_bytes(ctx, args.at(0))?;
+    let written = match ctx.vm.event_loop.source_mut::<ChildSource>(id) {
+        Some(ChildSource {
+            stdin: Some(stdin), ..
+        }) => stdin.send(data).is_ok(),
+        _ => false,
+    };
+    Ok(JsValue::new(written))
+}
+
+/// `child.closeStdin()`: close stdin after the queued writes, the child reads end of file then.
+pub fn child_close_stdin(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let id = child_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<ChildSource>(id) {
+        source.stdin = None;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `child.kill()`: kill the child. Returns `false` if it has already exited.
+pub fn child_kill(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = child_id(ctx, args)?;
+    let killed = match ctx.vm.event_loop.source_mut::<ChildSource>(id) {
+        Some(source) => source.child.kill().is_ok(),
+        None => false,
+    };
+    Ok(JsValue::new(killed))
+}
+
+fn child_key() -> Symbol {
+    "@@child".intern().private()
+}
+
+fn child_id(ctx: GcPointer<Context>, args: &Arguments) -> Result<u32, JsValue> {
+    if args.this.is_jsobject() {
+        let mut this = args.this.get_jsobject();
+        let id = this.get(ctx, child_key())?;
+        if id.is_number() {
+            return Ok(id.get_number() as u32);
+        }
+    }
+    Err(JsValue::new(
+        ctx.new_type_error("this is not a child process"),
+    ))
+}
+
+fn callback(ctx: GcPointer<Context>, value: JsValue) -> Result<Option<Persistent>, JsValue> {
+    if value.is_undefined() {
+        return Ok(None);
+    }
+    if !value.is_callable() {
+        return Err(JsValue::new(
+            ctx.new_type_error("spawn: output callbacks must be functions"),
+        ));
+    }
+    Ok(Some(ctx.vm().persistent(value)))
+}
+
+/// `{ status, signal }` of an exited child.
+fn exit_object(
+    ctx: GcPointer<Context>,
+    status: ExitStatus,
+) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut object = JsObject::new_empty(ctx);
+    let code = match status.code() {
+        Some(code) => JsValue::new(code),
+        None => JsValue::encode_null_value(),
+    };
+    object.put(ctx, "status".intern(), code, false)?;
+    #[cfg(unix)]
+    let signal = {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    };
+    #[cfg(not(unix))]
+    let signal: Option<i32> = None;
+    let signal = match signal {
+        Some(signal) => JsValue::new(signal),
+        None => JsValue::encode_null_value(),
+    };
+    object.put(ctx, "signal".intern(), signal, false)?;
+    Ok(object)
+}
+
+#[derive(Copy, Clone)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Message from the threads that read the output of a spawned child.
+enum Output {
+    Data(Stream, Vec<u8>),
+    Closed(Stream),
+}
+
+fn read_in_background(
+    mut pipe: impl Read + Send + 'static,
+    stream: Stream,
+    sender: IoSender<Output>,
+) {
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(count) => {
+                    if sender
+                        .send(Output::Data(stream, buffer[..count].to_vec()))
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+        let _ = sender.send(Output::Closed(stream));
+    });
+}
+
+/// Forward the data sent to the returned channel to `pipe`, the pipe is closed when the sender
+/// is dropped.
+fn write_in_background(mut pipe: impl Write + Send + 'static) -> Sender<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>();
+    std::thread::spawn(move || {
+        for data in receiver {
+            if pipe.write_all(&data).is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+struct StreamState {
+    callback: Option<Persistent>,
+    /// Keeps the trailing bytes of an UTF-8 sequence that was split between two chunks.
+    decoder: JsTextDecoder,
+}
+
+impl StreamState {
+    fn new(callback: Option<Persistent>) -> Self {
+        Self {
+            callback,
+            decoder: JsTextDecoder::new(Encoding::Utf8, false, true),
+        }
+    }
+
+    /// Text of `chunk` up to the last complete UTF-8 sequence. Invalid sequences are replaced
+    /// with U+FFFD.
+    fn decode(&mut self, chunk: &[u8]) -> String {
+        self.decoder.decode(chunk, true).unwrap_or_default()
+    }
+}
+
+/// Spawned child polled by the event loop.
+struct ChildSource {
+    ctx: GcPointer<Context>,
+    child: Child,
+    output: Receiver<Output>,
+    open_streams: u32,
+    stdin: Option<Sender<Vec<u8>>>,
+    stdout: StreamState,
+    stderr: StreamState,
+    encoding: DataEncoding,
+    exited: Persistent,
+    resolve: Persistent,
+}
+
+impl ChildSource {
+    fn state(&mut self, stream: Stream) -> &mut StreamState {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    fn deliver(&mut self, stream: Stream, bytes: &[u8], events: &mut Vec<IoEvent>) {
+        if self.state(stream).callback.is_none() {
+            return;
+        }
+        let chunk = match self.encoding {
+            DataEncoding::Utf8 => self.state(stream).decode(bytes),
+            DataEncoding::Buffer => match new_array_buffer(self.ctx, bytes) {
+                Ok(buffer) => {
+                    self.emit(stream, JsValue::new(buffer), events);
+                    return;
+                }
+                Err(_) => return,
+            },
+        };
+        self.emit_text(stream, chunk, events);
+    }
+
+    /// Deliver what is left of an incomplete UTF-8 sequence at the end of the output.
+    fn flush(&mut self, stream: Stream, events: &mut Vec<IoEvent>) {
+        let text = self.state(stream).decoder.decode(&[], false);
+        self.emit_text(stream, text.unwrap_or_default(), events);
+    }
+
+    fn emit_text(&mut self, stream: Stream, text: String, events: &mut Vec<IoEvent>) {
+        if !text.is_empty() {
+            let chunk = JsValue::new(JsString::new(self.ctx, text));
+            self.emit(stream, chunk, events);
+        }
+    }
+
+    fn emit(&mut self, stream: Stream, chunk: JsValue, events: &mut Vec<IoEvent>) {
+        let ctx = self.ctx;
+        if let Some(callback) = &self.state(stream).callback {
+            events.push(IoEvent {
+                ctx,
+                callback: callback.clone(),
+                args: vec![ctx.vm().persistent(chunk)],
+            });
+        }
+    }
+}
+
+impl IoSource for ChildSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        while let Ok(output) = self.output.try_recv() {
+            match output {
+                Output::Data(stream, bytes) => self.deliver(stream, &bytes, events),
+                Output::Closed(stream) => {
+                    self.flush(stream, events);
+                    self.open_streams -= 1;
+                }
+            }
+        }
+        if self.open_streams > 0 {
+            return true;
+        }
+        let status = match self.child.try_wait() {
+            Ok(None) => return true,
+            Ok(Some(status)) => exit_object(self.ctx, status).map(JsValue::new),
+            Err(_) => Ok(JsValue::encode_null_value()),
+        };
+        let status = status.unwrap_or_else(|_| JsValue::encode_null_value());
+        events.push(IoEvent {
+            ctx: self.ctx,
+            callback: self.resolve.clone(),
+            args: vec![self.exited.clone(), self.ctx.vm().persistent(status)],
+        });
+        false
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval_module, run_event_loop};
+    use crate::Platform;
+
+    #[test]
+    fn test_exec_and_spawn() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval_module(
+            ctx,
+            "import process from 'std:process';
+             globalThis.execResult = process.exec('sh', ['-c', 'cat; echo $GREETING >&2; exit 3'], {
+                 stdin: 'input',
+                 env: { GREETING: 'hi' },
+             });
+             globalThis.output = '';
+             let child = process.spawn('cat', [], { onStdout: chunk => globalThis.output += chunk });
+             child.write('stream');
+             child.write('ed');
+             child.closeStdin();
+             child.exited.then(result => {
+                 globalThis.spawnResult = result;
+             });",
+        );
+        run_event_loop(ctx);
+        assert_js(ctx, "execResult.status === 3");
+        assert_js(ctx, "execResult.stdout === 'input'");
+        assert_js(ctx, "execResult.stderr === 'hi\\n'");
+        assert_js(ctx, "spawnResult.status === 0");
+        assert_js(ctx, "output === 'streamed'");
+    }
+}
@@ -1,5 +1,6 @@
 use crate::define_jsclass;
 use crate::prelude::*;
+use crate::vm::capabilities::Capability;
 use crate::vm::context::Context;
 use crate::{gc::cell::GcPointer, vm::object::JsObject};
 use std::{
@@ -31,6 +32,7 @@ pub(super) fn std_init_file(
 }
 
 pub fn std_file_open(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
     let path = args.at(0).to_string(ctx)?;
     let flags = if args.at(1).is_jsstring() {
         args.at(1).to_string(ctx)?
@@ -80,7 +82,6 @@ pub fn std_file_write(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsVal
     }
     let mut buffer: Vec<u8>;
     if args.at(0).is_jsobject() {
-        
         letroot!(buffer_object = stack, args.at(0).get_jsobject());
         let length = crate::jsrt::get_length(ctx, &mut buffer_object)?;
         buffer = Vec::with_capacity(length as _);
@@ -121,7 +122,6 @@ pub fn std_file_write_all(ctx: GcPointer<Context>, args: &Arguments) -> Result<J
     }
     let mut buffer: Vec<u8>;
     if args.at(0).is_jsobject() {
-        
         letroot!(buffer_object = stack, args.at(0).get_jsobject());
         let length = crate::jsrt::get_length(ctx, &mut buffer_object)?;
         buffer = Vec::with_capacity(length as _);
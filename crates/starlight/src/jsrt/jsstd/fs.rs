@@ -0,0 +1,274 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `std:fs`: directories and file metadata.
+//!
+//! Every function has a promise-returning twin under `fs.promises`, e.g. `fs.promises.stat`,
+//! which runs on another thread while JS keeps running.
+use super::{io_error, run_in_background};
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::context::Context;
+use std::{fs, io, path::Path, time::SystemTime};
+
+/// Initialize `std:fs`.
+pub fn init_fs(ctx: GcPointer<Context>, mut module: GcPointer<JsObject>) -> Result<(), JsValue> {
+    let fs = fs_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(fs), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(fs), false)?;
+    Ok(())
+}
+
+pub(super) fn fs_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut fs = JsObject::new_empty(ctx);
+    def_native_method!(ctx, fs, readDir, fs_read_dir, 1)?;
+    def_native_method!(ctx, fs, mkdir, fs_mkdir, 2)?;
+    def_native_method!(ctx, fs, remove, fs_remove, 2)?;
+    def_native_method!(ctx, fs, rename, fs_rename, 2)?;
+    def_native_method!(ctx, fs, stat, fs_stat, 1)?;
+    def_native_method!(ctx, fs, exists, fs_exists, 1)?;
+
+    let mut promises = JsObject::new_empty(ctx);
+    def_native_method!(ctx, promises, readDir, fs_read_dir_async, 1)?;
+    def_native_method!(ctx, promises, mkdir, fs_mkdir_async, 2)?;
+    def_native_method!(ctx, promises, remove, fs_remove_async, 2)?;
+    def_native_method!(ctx, promises, rename, fs_rename_async, 2)?;
+    def_native_method!(ctx, promises, stat, fs_stat_async, 1)?;
+    def_native_method!(ctx, promises, exists, fs_exists_async, 1)?;
+    fs.put(ctx, "promises".intern(), JsValue::new(promises), false)?;
+    Ok(fs)
+}
+
+/// File system operation made from the arguments of a native. It does not touch the heap, so
+/// `fs.promises` can run it on another thread.
+type Operation = Box<dyn FnOnce() -> Result<Output, Failure> + Send>;
+
+/// Output of an [Operation], turned into a JS value by [finish].
+enum Output {
+    Undefined,
+    Boolean(bool),
+    Names(Vec<String>),
+    Stat(Stat),
+}
+
+/// Failed [Operation]: name of the native, path and error.
+struct Failure(&'static str, String, io::Error);
+
+/// Define the native `$sync`, which runs the operation made by `$operation` right away, and its
+/// twin `$async` of `fs.promises`, which runs it on another thread.
+macro_rules! fs_natives {
+    ($($operation: ident: $sync: ident, $async: ident;)*) => {$(
+        pub fn $sync(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+            let operation = $operation(ctx, args)?;
+            finish(ctx, operation())
+        }
+
+        pub fn $async(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+            let operation = $operation(ctx, args)?;
+            run_in_background(ctx, operation, finish)
+        }
+    )*};
+}
+
+fs_natives! {
+    read_dir: fs_read_dir, fs_read_dir_async;
+    mkdir: fs_mkdir, fs_mkdir_async;
+    remove: fs_remove, fs_remove_async;
+    rename: fs_rename, fs_rename_async;
+    stat: fs_stat, fs_stat_async;
+    exists: fs_exists, fs_exists_async;
+}
+
+/// `fs.readDir(path)`: names of the entries of a directory, without `.` and `..`.
+fn read_dir(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let path = args.at(0).to_string(ctx)?;
+    Ok(Box::new(move || {
+        let names = fs::read_dir(&path).and_then(|entries| {
+            entries
+                .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+                .collect::<io::Result<Vec<_>>>()
+        });
+        match names {
+            Ok(names) => Ok(Output::Names(names)),
+            Err(error) => Err(Failure("readDir", path, error)),
+        }
+    }))
+}
+
+/// `fs.mkdir(path, { recursive = false })`.
+fn mkdir(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let path = args.at(0).to_string(ctx)?;
+    let recursive = recursive(ctx, args.at(1))?;
+    Ok(Box::new(move || {
+        let result = if recursive {
+            fs::create_dir_all(&path)
+        } else {
+            fs::create_dir(&path)
+        };
+        match result {
+            Ok(()) => Ok(Output::Undefined),
+            Err(error) => Err(Failure("mkdir", path, error)),
+        }
+    }))
+}
+
+/// `fs.remove(path, { recursive = false })`: remove a file or a directory. Non-empty directories
+/// are only removed with `recursive`.
+fn remove(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let path = args.at(0).to_string(ctx)?;
+    let recursive = recursive(ctx, args.at(1))?;
+    Ok(Box::new(move || {
+        let result = fs::symlink_metadata(&path).and_then(|metadata| {
+            if !metadata.is_dir() {
+                fs::remove_file(&path)
+            } else if recursive {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_dir(&path)
+            }
+        });
+        match result {
+            Ok(()) => Ok(Output::Undefined),
+            Err(error) => Err(Failure("remove", path, error)),
+        }
+    }))
+}
+
+/// `fs.rename(from, to)`.
+fn rename(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let from = args.at(0).to_string(ctx)?;
+    let to = args.at(1).to_string(ctx)?;
+    Ok(Box::new(move || match fs::rename(&from, &to) {
+        Ok(()) => Ok(Output::Undefined),
+        Err(error) => Err(Failure("rename", from, error)),
+    }))
+}
+
+/// `fs.stat(path)`: `{ size, isFile, isDirectory, isSymlink, readonly, mode, mtimeMs, atimeMs,
+/// birthtimeMs }`. Symbolic links are followed, `isSymlink` tells whether `path` itself is one.
+/// Times that the platform does not record are `null`, `mode` is `null` outside of Unix.
+fn stat(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let path = args.at(0).to_string(ctx)?;
+    Ok(Box::new(move || match Stat::new(&path) {
+        Ok(stat) => Ok(Output::Stat(stat)),
+        Err(error) => Err(Failure("stat", path, error)),
+    }))
+}
+
+/// `fs.exists(path)`: whether `path` exists, broken symbolic links do not.
+fn exists(ctx: GcPointer<Context>, args: &Arguments) -> Result<Operation, JsValue> {
+    ctx.require_capability(Capability::FileSystem)?;
+    let path = args.at(0).to_string(ctx)?;
+    Ok(Box::new(move || {
+        Ok(Output::Boolean(Path::new(&path).exists()))
+    }))
+}
+
+/// Value of a native made from the result of its operation.
+fn finish(ctx: GcPointer<Context>, result: Result<Output, Failure>) -> Result<JsValue, JsValue> {
+    match result {
+        Ok(Output::Undefined) => Ok(JsValue::UNDEFINED),
+        Ok(Output::Boolean(value)) => Ok(JsValue::new(value)),
+        Ok(Output::Names(names)) => {
+            letroot!(array = stack, JsArray::new(ctx, names.len() as u32));
+            for (index, name) in names.iter().enumerate() {
+                let name = JsValue::new(JsString::new(ctx, name));
+                array.put(ctx, Symbol::Index(index as u32), name, false)?;
+            }
+            Ok(JsValue::new(array))
+        }
+        Ok(Output::Stat(stat)) => stat.to_object(ctx),
+        Err(Failure(op, path, error)) => Err(io_error(ctx, op, &path, error)),
+    }
+}
+
+/// Metadata returned by `fs.stat`.
+struct Stat {
+    size: u64,
+    is_file: bool,
+    is_directory: bool,
+    is_symlink: bool,
+    readonly: bool,
+    mode: Option<u32>,
+    mtime: Option<f64>,
+    atime: Option<f64>,
+    birthtime: Option<f64>,
+}
+
+impl Stat {
+    fn new(path: &str) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let is_symlink = fs::symlink_metadata(path)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false);
+        #[cfg(unix)]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            Some(metadata.permissions().mode())
+        };
+        #[cfg(not(unix))]
+        let mode = None;
+        Ok(Self {
+            size: metadata.len(),
+            is_file: metadata.is_file(),
+            is_directory: metadata.is_dir(),
+            is_symlink,
+            readonly: metadata.permissions().readonly(),
+            mode,
+            mtime: time(metadata.modified()),
+            atime: time(metadata.accessed()),
+            birthtime: time(metadata.created()),
+        })
+    }
+
+    fn to_object(&self, ctx: GcPointer<Context>) -> Result<JsValue, JsValue> {
+        let nullable = |value: Option<f64>| match value {
+            Some(value) => JsValue::new(value),
+            None => JsValue::encode_null_value(),
+        };
+        let mut stat = JsObject::new_empty(ctx);
+        stat.put(ctx, "size".intern(), JsValue::new(self.size as f64), false)?;
+        stat.put(ctx, "isFile".intern(), JsValue::new(self.is_file), false)?;
+        stat.put(
+            ctx,
+            "isDirectory".intern(),
+            JsValue::new(self.is_directory),
+            false,
+        )?;
+        stat.put(
+            ctx,
+            "isSymlink".intern(),
+            JsValue::new(self.is_symlink),
+            false,
+        )?;
+        stat.put(ctx, "readonly".intern(), JsValue::new(self.readonly), false)?;
+        let mode = nullable(self.mode.map(f64::from));
+        stat.put(ctx, "mode".intern(), mode, false)?;
+        stat.put(ctx, "mtimeMs".intern(), nullable(self.mtime), false)?;
+        stat.put(ctx, "atimeMs".intern(), nullable(self.atime), false)?;
+        stat.put(ctx, "birthtimeMs".intern(), nullable(self.birthtime), false)?;
+        Ok(JsValue::new(stat))
+    }
+}
+
+fn recursive(ctx: GcPointer<Context>, options: JsValue) -> Result<bool, JsValue> {
+    if !options.is_jsobject() {
+        return Ok(false);
+    }
+    Ok(options
+        .get_jsobject()
+        .get(ctx, "recursive".intern())?
+        .to_boolean())
+}
+
+/// Milliseconds since the Unix epoch, `None` if the time is not known.
+fn time(time: io::Result<SystemTime>) -> Option<f64> {
+    time.ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,676 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `std:io`: the standard streams and the terminal behind them.
+//!
+//! `stdin` is read synchronously with `read`, `readLine` and `readAll`, or through the event
+//! loop with `listen`, which reads on another thread and passes chunks or lines to a callback.
+//! Synchronous reads should not be mixed with an open listener, whichever reads first gets the
+//! input. Strings written to `stdout` and `stderr` keep their ANSI escape sequences only when the
+//! stream is a terminal and `NO_COLOR` is not set, so colored output stays readable when it is
+//! piped to a file.
+//!
+//! ```js
+//! import io from "std:io";
+//! io.stdin.setRawMode(true);
+//! let keys = io.stdin.listen(key => {
+//!     if (key === "q") { keys.close(); io.stdin.setRawMode(false); }
+//!     else io.stdout.write("\x1b[32m" + JSON.stringify(key) + "\x1b[0m\n");
+//! });
+//! ```
+use super::{io_error, to_bytes, DataEncoding};
+use crate::jsrt::encoding::{buffer_source_bytes, Encoding, JsTextDecoder};
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::context::Context;
+use crate::vm::event_loop::{IoEvent, IoSource};
+use crate::vm::handles::Persistent;
+use std::{
+    any::Any,
+    io::{self, BufRead, Read, Write},
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+/// Size of the chunks read from stdin.
+const CHUNK_SIZE: usize = 65536;
+
+/// Initialize `std:io`.
+pub fn init_io(ctx: GcPointer<Context>, mut module: GcPointer<JsObject>) -> Result<(), JsValue> {
+    let io = io_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(io), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(io), false)?;
+    Ok(())
+}
+
+pub(super) fn io_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut io = JsObject::new_empty(ctx);
+    def_native_method!(ctx, io, isatty, io_isatty, 1)?;
+    def_native_method!(ctx, io, stripAnsi, io_strip_ansi, 1)?;
+
+    let mut stdin = stream_object(ctx, 0)?;
+    def_native_method!(ctx, stdin, read, io_read, 2)?;
+    def_native_method!(ctx, stdin, readLine, io_read_line, 1)?;
+    def_native_method!(ctx, stdin, readAll, io_read_all, 1)?;
+    def_native_method!(ctx, stdin, listen, io_listen, 2)?;
+    def_native_method!(ctx, stdin, setRawMode, io_set_raw_mode, 1)?;
+    def_native_method!(ctx, stdin, isRaw, io_is_raw, 0)?;
+    io.put(ctx, "stdin".intern(), JsValue::new(stdin), false)?;
+
+    let mut stdout = stream_object(ctx, 1)?;
+    def_native_method!(ctx, stdout, write, io_write, 2)?;
+    io.put(ctx, "stdout".intern(), JsValue::new(stdout), false)?;
+    let mut stderr = stream_object(ctx, 2)?;
+    def_native_method!(ctx, stderr, write, io_write, 2)?;
+    io.put(ctx, "stderr".intern(), JsValue::new(stderr), false)?;
+    Ok(io)
+}
+
+/// `io.isatty(fd)`: whether the file descriptor `fd` refers to a terminal.
+pub fn io_isatty(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let fd = args.at(0).to_int32(ctx)?;
+    Ok(JsValue::new(terminal::isatty(fd)))
+}
+
+/// `io.stripAnsi(text)`: `text` without ANSI escape sequences.
+pub fn io_strip_ansi(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let text = args.at(0).to_string(ctx)?;
+    Ok(JsValue::new(JsString::new(ctx, strip_ansi(&text))))
+}
+
+/// `stream.isatty()`: whether the stream is a terminal.
+pub fn io_stream_isatty(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let fd = stream_fd(ctx, args)?;
+    Ok(JsValue::new(terminal::isatty(fd)))
+}
+
+/// `stream.size()`: `{ columns, rows }` of the terminal, `null` if the stream is not a terminal.
+pub fn io_stream_size(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let fd = stream_fd(ctx, args)?;
+    let (columns, rows) = match terminal::size(fd) {
+        Some(size) => size,
+        None => return Ok(JsValue::encode_null_value()),
+    };
+    let mut size = JsObject::new_empty(ctx);
+    size.put(ctx, "columns".intern(), JsValue::new(columns), false)?;
+    size.put(ctx, "rows".intern(), JsValue::new(rows), false)?;
+    Ok(JsValue::new(size))
+}
+
+/// `stdin.read(size = 65536, { encoding })`: block until input is available and return up to
+/// `size` bytes of it, `null` at the end of the input. One call returns at most 65536 bytes
+/// whatever `size` is. A UTF-8 sequence split between two reads
+/// is replaced with U+FFFD, use `encoding: 'buffer'` to get the bytes.
+pub fn io_read(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let size = if args.at(0).is_undefined() {
+        CHUNK_SIZE
+    } else {
+        let size = args.at(0).to_number(ctx)?;
+        if !(0.0..=u32::MAX as f64).contains(&size) {
+            return Err(JsValue::new(
+                ctx.new_range_error(format!("read: invalid size {}", size)),
+            ));
+        }
+        size as usize
+    };
+    let encoding = DataEncoding::from_options(ctx, args.at(1), "read")?;
+    // a single read returns what is available, a larger buffer would only be allocated
+    let mut buffer = vec![0; size.min(CHUNK_SIZE)];
+    let count = io::stdin()
+        .read(&mut buffer)
+        .map_err(|e| io_error(ctx, "read", "stdin", e))?;
+    if count == 0 && size != 0 {
+        return Ok(JsValue::encode_null_value());
+    }
+    encoding.to_value(ctx, &buffer[..count])
+}
+
+/// `stdin.readLine(prompt?)`: write `prompt` to stdout and read a line without its line
+/// terminator, `null` at the end of the input. Unlike the global `readLine` the terminator is
+/// removed.
+pub fn io_read_line(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    if !args.at(0).is_undefined() {
+        let prompt = args.at(0).to_string(ctx)?;
+        let mut stdout = io::stdout();
+        stdout
+            .write_all(prompt.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| io_error(ctx, "write", "stdout", e))?;
+    }
+    let mut line = vec![];
+    let count = io::stdin()
+        .lock()
+        .read_until(b'\n', &mut line)
+        .map_err(|e| io_error(ctx, "readLine", "stdin", e))?;
+    if count == 0 {
+        return Ok(JsValue::encode_null_value());
+    }
+    let line = trim_line_terminator(&line);
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        String::from_utf8_lossy(line),
+    )))
+}
+
+/// `stdin.readAll({ encoding })`: read until the end of the input.
+pub fn io_read_all(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let encoding = DataEncoding::from_options(ctx, args.at(0), "readAll")?;
+    let mut data = vec![];
+    io::stdin()
+        .read_to_end(&mut data)
+        .map_err(|e| io_error(ctx, "readAll", "stdin", e))?;
+    encoding.to_value(ctx, &data)
+}
+
+/// `stdin.listen(callback, { lines = false, encoding })`: pass the input to `callback` as it
+/// arrives, in chunks or line by line without line terminators, and `null` at the end of the
+/// input. Returns a listener whose `close()` stops delivering input, the listener keeps the
+/// event loop running until then or until the input ends.
+pub fn io_listen(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let callback = args.at(0);
+    if !callback.is_callable() {
+        return Err(JsValue::new(
+            ctx.new_type_error("listen: callback must be a function"),
+        ));
+    }
+    let options = args.at(1);
+    let lines = options.is_jsobject()
+        && options
+            .get_jsobject()
+            .get(ctx, "lines".intern())?
+            .to_boolean();
+    let encoding = DataEncoding::from_options(ctx, options, "listen")?;
+
+    let (sender, receiver) = ctx.vm.event_loop.channel();
+    // a blocked read cannot be cancelled, after `close()` the thread ends with its next read
+    std::thread::spawn(move || {
+        let mut buffer = vec![0; CHUNK_SIZE];
+        loop {
+            match io::stdin().read(&mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(count) => {
+                    if sender.send(buffer[..count].to_vec()).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut listener = JsObject::new_empty(ctx);
+    def_native_method!(ctx, listener, close, io_listener_close, 0)?;
+    let source = StdinSource {
+        ctx,
+        callback: ctx.vm().persistent(callback),
+        receiver,
+        lines,
+        encoding,
+        pending: vec![],
+        decoder: JsTextDecoder::new(Encoding::Utf8, false, true),
+    };
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    listener.put(ctx, listener_key(), JsValue::new(id), false)?;
+    Ok(JsValue::new(listener))
+}
+
+/// `listener.close()`: stop passing input to the callback of the listener.
+pub fn io_listener_close(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    if args.this.is_jsobject() {
+        let id = args.this.get_jsobject().get(ctx, listener_key())?;
+        if id.is_number() {
+            ctx.vm.event_loop.remove_source(id.get_number() as u32);
+            return Ok(JsValue::UNDEFINED);
+        }
+    }
+    Err(JsValue::new(ctx.new_type_error("this is not a listener")))
+}
+
+/// `stdin.setRawMode(enabled)`: switch the terminal to raw mode, where input is passed on key by
+/// key without echo and without turning Ctrl+C into a signal, or back to the mode it was in
+/// before. Throws if stdin is not a terminal.
+pub fn io_set_raw_mode(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    terminal::set_raw_mode(args.at(0).to_boolean())
+        .map_err(|e| io_error(ctx, "setRawMode", "stdin", e))?;
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `stdin.isRaw()`: whether raw mode is enabled.
+pub fn io_is_raw(ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    Ok(JsValue::new(terminal::is_raw()))
+}
+
+/// `stream.write(data, { ansi = 'auto' })`: write a string or the bytes of an `ArrayBuffer` or
+/// `DataView` and flush the stream. ANSI escape sequences of strings are kept if `ansi` is
+/// `true`, removed if it is `false`, and with `'auto'` kept only when the stream is a terminal
+/// and `NO_COLOR` is not set.
+pub fn io_write(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Stdio)?;
+    let fd = stream_fd(ctx, args)?;
+    let data = args.at(0);
+    let bytes = if buffer_source_bytes(data).is_some() {
+        to_bytes(ctx, data)?
+    } else {
+        let text = data.to_string(ctx)?;
+        if keep_ansi(ctx, args.at(1), fd)? {
+            text.into_bytes()
+        } else {
+            strip_ansi(&text).into_bytes()
+        }
+    };
+    let (name, result) = if fd == 2 {
+        let mut stderr = io::stderr();
+        (
+            "stderr",
+            stderr.write_all(&bytes).and_then(|_| stderr.flush()),
+        )
+    } else {
+        let mut stdout = io::stdout();
+        (
+            "stdout",
+            stdout.write_all(&bytes).and_then(|_| stdout.flush()),
+        )
+    };
+    result.map_err(|e| io_error(ctx, "write", name, e))?;
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Leave raw mode if a script enabled it, called before the process exits.
+pub fn restore_terminal() {
+    let _ = terminal::set_raw_mode(false);
+}
+
+/// `text` without CSI sequences like colors and cursor movement, OSC sequences like window
+/// titles and hyperlinks, and two character escapes.
+pub fn strip_ansi(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            // parameters and intermediates up to a final byte in `@..=~`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // terminated by BEL or ST
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    result
+}
+
+fn keep_ansi(ctx: GcPointer<Context>, options: JsValue, fd: i32) -> Result<bool, JsValue> {
+    let ansi = if options.is_jsobject() {
+        options.get_jsobject().get(ctx, "ansi".intern())?
+    } else {
+        JsValue::UNDEFINED
+    };
+    if ansi.is_bool() {
+        return Ok(ansi.get_bool());
+    }
+    if !ansi.is_undefined() && ansi.to_string(ctx)? != "auto" {
+        return Err(JsValue::new(
+            ctx.new_type_error("write: ansi must be a boolean or 'auto'"),
+        ));
+    }
+    Ok(terminal::isatty(fd) && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()))
+}
+
+fn trim_line_terminator(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn fd_key() -> Symbol {
+    "@@fd".intern().private()
+}
+
+fn listener_key() -> Symbol {
+    "@@listener".intern().private()
+}
+
+/// Object of a standard stream without its reading or writing methods.
+fn stream_object(ctx: GcPointer<Context>, fd: i32) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut stream = JsObject::new_empty(ctx);
+    stream.put(ctx, "fd".intern(), JsValue::new(fd), false)?;
+    stream.put(ctx, fd_key(), JsValue::new(fd), false)?;
+    def_native_method!(ctx, stream, isatty, io_stream_isatty, 0)?;
+    def_native_method!(ctx, stream, size, io_stream_size, 0)?;
+    Ok(stream)
+}
+
+fn stream_fd(ctx: GcPointer<Context>, args: &Arguments) -> Result<i32, JsValue> {
+    if args.this.is_jsobject() {
+        let fd = args.this.get_jsobject().get(ctx, fd_key())?;
+        if fd.is_number() {
+            return Ok(fd.get_number() as i32);
+        }
+    }
+    Err(JsValue::new(
+        ctx.new_type_error("this is not a standard stream"),
+    ))
+}
+
+/// Listener of stdin polled by the event loop. The input ends when the reading thread drops its
+/// sender.
+struct StdinSource {
+    ctx: GcPointer<Context>,
+    callback: Persistent,
+    receiver: Receiver<Vec<u8>>,
+    lines: bool,
+    encoding: DataEncoding,
+    /// Input after the last line terminator in line mode.
+    pending: Vec<u8>,
+    /// Keeps the trailing bytes of an UTF-8 sequence that was split between two chunks.
+    decoder: JsTextDecoder,
+}
+
+impl StdinSource {
+    fn deliver(&mut self, chunk: &[u8], events: &mut Vec<IoEvent>) {
+        if self.lines {
+            self.pending.extend_from_slice(chunk);
+            while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+                let line = self.pending.drain(..=end).collect::<Vec<_>>();
+                self.emit_bytes(trim_line_terminator(&line), events);
+            }
+        } else if self.encoding == DataEncoding::Utf8 {
+            let text = self.decoder.decode(chunk, true).unwrap_or_default();
+            self.emit_text(text, events);
+        } else {
+            self.emit_bytes(chunk, events);
+        }
+    }
+
+    /// Deliver what is left of the input and the `null` that ends it.
+    fn finish(&mut self, events: &mut Vec<IoEvent>) {
+        if self.lines {
+            if !self.pending.is_empty() {
+                let line = std::mem::take(&mut self.pending);
+                self.emit_bytes(trim_line_terminator(&line), events);
+            }
+        } else if self.encoding == DataEncoding::Utf8 {
+            let text = self.decoder.decode(&[], false).unwrap_or_default();
+            self.emit_text(text, events);
+        }
+        self.emit(JsValue::encode_null_value(), events);
+    }
+
+    fn emit_bytes(&mut self, bytes: &[u8], events: &mut Vec<IoEvent>) {
+        if let Ok(value) = self.encoding.to_value(self.ctx, bytes) {
+            self.emit(value, events);
+        }
+    }
+
+    fn emit_text(&mut self, text: String, events: &mut Vec<IoEvent>) {
+        if !text.is_empty() {
+            let text = JsString::new(self.ctx, text);
+            self.emit(JsValue::new(text), events);
+        }
+    }
+
+    fn emit(&mut self, value: JsValue, events: &mut Vec<IoEvent>) {
+        events.push(IoEvent {
+            ctx: self.ctx,
+            callback: self.callback.clone(),
+            args: vec![self.ctx.vm().persistent(value)],
+        });
+    }
+}
+
+impl IoSource for StdinSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(chunk) => self.deliver(&chunk, events),
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => {
+                    self.finish(events);
+                    return false;
+                }
+            }
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::{io, mem::MaybeUninit, sync::Mutex};
+
+    /// Mode of the terminal before raw mode was enabled.
+    static SAVED_MODE: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+    pub fn isatty(fd: i32) -> bool {
+        unsafe { libc::isatty(fd) == 1 }
+    }
+
+    pub fn size(fd: i32) -> Option<(u16, u16)> {
+        let mut size = MaybeUninit::<libc::winsize>::zeroed();
+        unsafe {
+            if libc::ioctl(fd, libc::TIOCGWINSZ, size.as_mut_ptr()) != 0 {
+                return None;
+            }
+            let size = size.assume_init();
+            if size.ws_col == 0 {
+                return None;
+            }
+            Some((size.ws_col, size.ws_row))
+        }
+    }
+
+    pub fn set_raw_mode(enabled: bool) -> io::Result<()> {
+        let mut saved = SAVED_MODE.lock().unwrap_or_else(|e| e.into_inner());
+        if !enabled {
+            if let Some(mode) = saved.take() {
+                if unsafe { libc::tcsetattr(0, libc::TCSANOW, &mode) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            return Ok(());
+        }
+        if saved.is_some() {
+            return Ok(());
+        }
+        let mut mode = MaybeUninit::<libc::termios>::uninit();
+        let mode = unsafe {
+            if libc::tcgetattr(0, mode.as_mut_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            mode.assume_init()
+        };
+        let mut raw = mode;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+        }
+        // keep output processing so that "\n" still returns the cursor to the first column
+        raw.c_oflag |= libc::OPOST;
+        if unsafe { libc::tcsetattr(0, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        *saved = Some(mode);
+        Ok(())
+    }
+
+    pub fn is_raw() -> bool {
+        SAVED_MODE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_some()
+    }
+}
+
+#[cfg(not(unix))]
+mod terminal {
+    use std::io::{self, IsTerminal};
+
+    pub fn isatty(fd: i32) -> bool {
+        match fd {
+            0 => io::stdin().is_terminal(),
+            1 => io::stdout().is_terminal(),
+            2 => io::stderr().is_terminal(),
+            _ => false,
+        }
+    }
+
+    pub fn size(_fd: i32) -> Option<(u16, u16)> {
+        None
+    }
+
+    pub fn set_raw_mode(enabled: bool) -> io::Result<()> {
+        if enabled {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "raw mode is not supported on this platform",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn is_raw() -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_ansi, trim_line_terminator, StdinSource};
+    use crate::gc::cell::GcPointer;
+    use crate::jsrt::encoding::{Encoding, JsTextDecoder};
+    use crate::jsrt::jsstd::DataEncoding;
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::event_loop::IoEvent;
+    use crate::vm::testing::{assert_js, eval, eval_module};
+    use crate::Platform;
+
+    fn stdin_source(ctx: GcPointer<Context>, lines: bool) -> StdinSource {
+        let callback = eval(ctx, "(function () {})");
+        StdinSource {
+            ctx,
+            callback: ctx.vm().persistent(callback),
+            receiver: std::sync::mpsc::channel().1,
+            lines,
+            encoding: DataEncoding::Utf8,
+            pending: vec![],
+            decoder: JsTextDecoder::new(Encoding::Utf8, false, true),
+        }
+    }
+
+    /// Values passed to the callback by `events`, `null` for the end of the input.
+    fn delivered(ctx: GcPointer<Context>, events: &[IoEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event.args[0].get().to_string(ctx) {
+                Ok(value) => value,
+                Err(_) => panic!("the delivered value cannot be converted to a string"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trim_line_terminator() {
+        assert_eq!(trim_line_terminator(b"line\n"), b"line");
+        assert_eq!(trim_line_terminator(b"line\r\n"), b"line");
+        assert_eq!(trim_line_terminator(b"line"), b"line");
+        assert_eq!(trim_line_terminator(b"\n"), b"");
+        // only one terminator is removed
+        assert_eq!(trim_line_terminator(b"line\n\n"), b"line\n");
+    }
+
+    #[test]
+    fn test_stdin_lines() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        // lines split between chunks are joined, the last line needs no terminator
+        let mut source = stdin_source(ctx, true);
+        let mut events = vec![];
+        source.deliver(b"one\r\ntw", &mut events);
+        source.deliver(b"o\n\nthr", &mut events);
+        source.deliver(b"ee", &mut events);
+        assert_eq!(delivered(ctx, &events), ["one", "two", ""]);
+        source.finish(&mut events);
+        assert_eq!(delivered(ctx, &events), ["one", "two", "", "three", "null"]);
+    }
+
+    #[test]
+    fn test_stdin_utf8_chunks() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        // a sequence split between chunks is delivered whole, an incomplete one at the end of
+        // the input is replaced
+        let mut source = stdin_source(ctx, false);
+        let mut events = vec![];
+        source.deliver(&[b'a', 0xe2, 0x82], &mut events);
+        source.deliver(&[0xac, b'b', 0xe2], &mut events);
+        source.finish(&mut events);
+        assert_eq!(
+            delivered(ctx, &events),
+            ["a", "\u{20ac}b", "\u{fffd}", "null"]
+        );
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m plain"), "red plain");
+        assert_eq!(strip_ansi("\x1b]0;title\x07\x1b[2K\x1b[1Gline"), "line");
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://example.com\x1b\\link\x1b]8;;\x1b\\"),
+            "link"
+        );
+        assert_eq!(strip_ansi("no escapes"), "no escapes");
+    }
+
+    #[test]
+    fn test_std_io() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval_module(
+            ctx,
+            "import io from 'std:io';
+             globalThis.io = io;
+             globalThis.size = io.stdout.size();
+             io.stdout.write(new ArrayBuffer(0));",
+        );
+        assert_js(ctx, "io.stdin.fd === 0 && io.stderr.fd === 2");
+        assert_js(ctx, "typeof io.stdout.isatty() === 'boolean'");
+        assert_js(ctx, "io.isatty(1) === io.stdout.isatty()");
+        assert_js(ctx, "size === null || size.columns > 0");
+        assert_js(ctx, "io.stdin.isRaw() === false");
+        assert_js(ctx, "io.stripAnsi('\\x1b[32mok\\x1b[0m') === 'ok'");
+    }
+}
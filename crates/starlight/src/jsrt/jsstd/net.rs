@@ -0,0 +1,1092 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `std:net`: TCP listeners and streams, and UDP sockets.
+//!
+//! Sockets are non-blocking and registered as sources of the event loop, which wakes up when
+//! they are ready: `sl` runs the loop until no work is left, embedders pump it with
+//! [Context::run_until_idle]. An open listener or UDP socket keeps the loop running until it is
+//! closed, a stream until it is closed or its peer ended it and the queued writes are done. Host
+//! names are resolved on other threads. Every function takes an `encoding` option, `'utf8'` (the
+//! default) or `'buffer'` to receive data as `ArrayBuffer`s.
+//!
+//! ```js
+//! import net from "std:net";
+//! net.listen({ port: 8080, onConnection: stream => {
+//!     stream.read().then(request => stream.write("echo " + request)).then(() => stream.end());
+//! } }).then(server => net.connect({ port: server.port })).then(client => {
+//!     client.write("hello");
+//!     client.read().then(reply => print(reply));
+//! });
+//! ```
+use super::{io_error, run_in_background, to_bytes, DataEncoding, Settle};
+use crate::jsrt::encoding::{Encoding, JsTextDecoder};
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::context::Context;
+use crate::vm::event_loop::{IoEvent, IoSource};
+use crate::vm::handles::Persistent;
+use crate::vm::promise::JsPromise;
+use mio::{
+    net::{TcpListener, TcpStream, UdpSocket},
+    Interest,
+};
+use std::{
+    any::Any,
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{self as std_net, IpAddr, Shutdown, SocketAddr, ToSocketAddrs},
+    sync::mpsc::{Receiver, TryRecvError},
+};
+
+/// Size of the reads from sockets, which is also the largest UDP message received whole.
+const CHUNK_SIZE: usize = 65536;
+/// Received bytes a stream buffers until they are read, receiving pauses beyond that.
+const MAX_BUFFERED: usize = 1 << 20;
+/// Messages a UDP socket without `onMessage` buffers until they are received, later messages
+/// are dropped.
+const MAX_MESSAGES: usize = 1024;
+
+/// Initialize `std:net`.
+pub fn init_net(ctx: GcPointer<Context>, mut module: GcPointer<JsObject>) -> Result<(), JsValue> {
+    let net = net_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(net), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(net), false)?;
+    Ok(())
+}
+
+pub(super) fn net_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut net = JsObject::new_empty(ctx);
+    def_native_method!(ctx, net, listen, net_listen, 1)?;
+    def_native_method!(ctx, net, connect, net_connect, 1)?;
+    def_native_method!(ctx, net, bind, net_bind, 1)?;
+    Ok(net)
+}
+
+/// `net.listen({ host = '127.0.0.1', port = 0, encoding, onConnection })`: promise of a listener
+/// for TCP connections, bound on another thread. Port 0 picks a free port, the listener has the
+/// actual `address` and `port`. Connections are passed to `onConnection`, or to the promises
+/// returned by `listener.accept()` without it.
+pub fn net_listen(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Network)?;
+    let options = args.at(0);
+    let (host, port) = host_and_port(ctx, options, "listen", Some(0))?;
+    let encoding = DataEncoding::from_options(ctx, options, "listen")?;
+    let on_connection = callback(ctx, options, "onConnection", "listen")?;
+    let target = format!("{}:{}", host, port);
+    run_in_background(
+        ctx,
+        move || std_net::TcpListener::bind((host.as_str(), port)),
+        move |ctx, listener| {
+            let listener = listener.map_err(|e| io_error(ctx, "listen", &target, e))?;
+            listener_object(ctx, listener, &target, encoding, on_connection)
+        },
+    )
+}
+
+/// Register a listener bound by `listen` and make its object.
+fn listener_object(
+    mut ctx: GcPointer<Context>,
+    listener: std_net::TcpListener,
+    target: &str,
+    encoding: DataEncoding,
+    on_connection: Option<Persistent>,
+) -> Result<JsValue, JsValue> {
+    let error = move |e| io_error(ctx, "listen", target, e);
+    listener.set_nonblocking(true).map_err(error)?;
+    let address = listener.local_addr().map_err(error)?;
+    let mut listener = TcpListener::from_std(listener);
+    ctx.vm
+        .event_loop
+        .register(&mut listener, Interest::READABLE)
+        .map_err(error)?;
+
+    let mut object = JsObject::new_empty(ctx);
+    put_address(ctx, object, "address", "port", address)?;
+    def_native_method!(ctx, object, accept, listener_accept, 0)?;
+    def_native_method!(ctx, object, close, listener_close, 0)?;
+    let source = ListenerSource {
+        ctx,
+        listener: Some(listener),
+        settle: Settle::new(ctx),
+        encoding,
+        on_connection,
+        accepted: VecDeque::new(),
+        accepts: VecDeque::new(),
+    };
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    object.put(ctx, socket_key(), JsValue::new(id), false)?;
+    Ok(JsValue::new(object))
+}
+
+/// `net.connect({ host = '127.0.0.1', port, encoding })`: promise of a TCP stream connected to
+/// `host` and `port`. Host names are resolved and connected to on another thread.
+///
+/// Streams have `remoteAddress`, `remotePort`, `localAddress` and `localPort`, and the methods
+/// `read()`, `write(data)`, `end()` and `close()`.
+pub fn net_connect(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Network)?;
+    let options = args.at(0);
+    let (host, port) = host_and_port(ctx, options, "connect", None)?;
+    let encoding = DataEncoding::from_options(ctx, options, "connect")?;
+    let target = format!("{}:{}", host, port);
+    let (sender, receiver) = ctx.vm.event_loop.channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(std_net::TcpStream::connect((host.as_str(), port)));
+    });
+
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let mut object = stream_object(ctx)?;
+    let connection = Connection::Connecting {
+        receiver,
+        object: ctx.vm().persistent(JsValue::new(object)),
+    };
+    let mut source = StreamSource::new(ctx, connection, Settle::new(ctx), encoding, target);
+    source.connect = Some(ctx.vm().persistent(promise));
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    object.put(ctx, socket_key(), JsValue::new(id), false)?;
+    Ok(promise)
+}
+
+/// `net.bind({ host = '127.0.0.1', port = 0, encoding, onMessage })`: promise of a UDP socket
+/// bound to `host` and `port` on another thread. Received messages `{ data, address, port }` are
+/// passed to `onMessage`, or to the promises returned by `socket.receive()` without it.
+pub fn net_bind(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Network)?;
+    let options = args.at(0);
+    let (host, port) = host_and_port(ctx, options, "bind", Some(0))?;
+    let encoding = DataEncoding::from_options(ctx, options, "bind")?;
+    let on_message = callback(ctx, options, "onMessage", "bind")?;
+    let target = format!("{}:{}", host, port);
+    run_in_background(
+        ctx,
+        move || std_net::UdpSocket::bind((host.as_str(), port)),
+        move |ctx, socket| {
+            let socket = socket.map_err(|e| io_error(ctx, "bind", &target, e))?;
+            udp_object(ctx, socket, &target, encoding, on_message)
+        },
+    )
+}
+
+/// Register a socket bound by `bind` and make its object.
+fn udp_object(
+    mut ctx: GcPointer<Context>,
+    socket: std_net::UdpSocket,
+    target: &str,
+    encoding: DataEncoding,
+    on_message: Option<Persistent>,
+) -> Result<JsValue, JsValue> {
+    let error = move |e| io_error(ctx, "bind", target, e);
+    socket.set_nonblocking(true).map_err(error)?;
+    let address = socket.local_addr().map_err(error)?;
+    let mut socket = UdpSocket::from_std(socket);
+    let interest = Interest::READABLE | Interest::WRITABLE;
+    ctx.vm
+        .event_loop
+        .register(&mut socket, interest)
+        .map_err(error)?;
+
+    let mut object = JsObject::new_empty(ctx);
+    put_address(ctx, object, "address", "port", address)?;
+    def_native_method!(ctx, object, send, udp_send, 3)?;
+    def_native_method!(ctx, object, receive, udp_receive, 0)?;
+    def_native_method!(ctx, object, close, udp_close, 0)?;
+    let source = UdpSource {
+        ctx,
+        socket: Some(socket),
+        settle: Settle::new(ctx),
+        encoding,
+        on_message,
+        messages: VecDeque::new(),
+        receives: VecDeque::new(),
+        sends: VecDeque::new(),
+        closing: false,
+    };
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    object.put(ctx, socket_key(), JsValue::new(id), false)?;
+    Ok(JsValue::new(object))
+}
+
+/// `listener.accept()`: promise of the next connection, `null` once the listener is closed.
+pub fn listener_accept(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let persistent = ctx.vm().persistent(promise);
+    let pending = match ctx.vm.event_loop.source_mut::<ListenerSource>(id) {
+        Some(source) => {
+            source.accepts.push_back(persistent);
+            true
+        }
+        None => false,
+    };
+    if !pending {
+        resolve_now(ctx, promise, JsValue::encode_null_value())?;
+    }
+    Ok(promise)
+}
+
+/// `listener.close()`: stop listening, connections that were accepted stay open.
+pub fn listener_close(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<ListenerSource>(id) {
+        source.listener = None;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `stream.read()`: promise of the next chunk of received data, `null` at the end of the stream.
+/// The promise is rejected if receiving failed.
+pub fn stream_read(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let persistent = ctx.vm().persistent(promise);
+    let pending = match ctx.vm.event_loop.source_mut::<StreamSource>(id) {
+        Some(source) => {
+            source.reads.push_back(persistent);
+            true
+        }
+        None => false,
+    };
+    if !pending {
+        resolve_now(ctx, promise, JsValue::encode_null_value())?;
+    }
+    Ok(promise)
+}
+
+/// `stream.write(data)`: queue a string or an `ArrayBuffer`. The promise is resolved once all of
+/// `data` was handed to the operating system.
+pub fn stream_write(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    let data = to_bytes(ctx, args.at(0))?;
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let persistent = ctx.vm().persistent(promise);
+    let queued = match ctx.vm.event_loop.source_mut::<StreamSource>(id) {
+        Some(source) if source.writable() => {
+            source.writes.push_back((data, 0, persistent));
+            true
+        }
+        _ => false,
+    };
+    if !queued {
+        let error = closed_error(ctx, "write: the stream is closed");
+        let mut object = promise.get_jsobject();
+        object.as_promise_mut().reject(ctx, promise, error)?;
+    }
+    Ok(promise)
+}
+
+/// `stream.end()`: close the sending half after the queued writes, the peer receives the end of
+/// the stream and can still send.
+pub fn stream_end(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<StreamSource>(id) {
+        source.ending = true;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `stream.close()`: close the stream right away. Pending reads are resolved with `null`, pending
+/// writes are rejected.
+pub fn stream_close(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<StreamSource>(id) {
+        source.connection = Connection::Closed;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `socket.send(data, port, host = '127.0.0.1')`: send a string or an `ArrayBuffer` as one
+/// message. Returns a promise of the number of bytes sent. Messages are sent in the order of the
+/// calls, host names are resolved on another thread.
+pub fn udp_send(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    let data = to_bytes(ctx, args.at(0))?;
+    let port = parse_port(ctx, args.at(1), "send")?;
+    let host = if args.at(2).is_undefined() {
+        "127.0.0.1".to_owned()
+    } else {
+        args.at(2).to_string(ctx)?
+    };
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let open = matches!(
+        ctx.vm.event_loop.source_mut::<UdpSource>(id),
+        Some(UdpSource { closing: false, .. })
+    );
+    if !open {
+        let error = closed_error(ctx, "send: the socket is closed");
+        let mut object = promise.get_jsobject();
+        object.as_promise_mut().reject(ctx, promise, error)?;
+        return Ok(promise);
+    }
+    let message = Outgoing {
+        target: format!("{}:{}", host, port),
+        destination: Destination::new(ctx, host, port),
+        data,
+        promise: ctx.vm().persistent(promise),
+    };
+    if let Some(source) = ctx.vm.event_loop.source_mut::<UdpSource>(id) {
+        source.sends.push_back(message);
+    }
+    Ok(promise)
+}
+
+/// `socket.receive()`: promise of the next message, `null` once the socket is closed.
+pub fn udp_receive(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    let promise = JsPromise::new_unresolving(ctx)?;
+    let persistent = ctx.vm().persistent(promise);
+    let pending = match ctx.vm.event_loop.source_mut::<UdpSource>(id) {
+        Some(source) => {
+            source.receives.push_back(persistent);
+            true
+        }
+        None => false,
+    };
+    if !pending {
+        resolve_now(ctx, promise, JsValue::encode_null_value())?;
+    }
+    Ok(promise)
+}
+
+/// `socket.close()`: close the socket once the queued messages are sent. Pending receives are
+/// resolved with `null`.
+pub fn udp_close(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = socket_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<UdpSource>(id) {
+        source.closing = true;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+fn socket_key() -> Symbol {
+    "@@socket".intern().private()
+}
+
+fn socket_id(ctx: GcPointer<Context>, args: &Arguments) -> Result<u32, JsValue> {
+    if args.this.is_jsobject() {
+        let mut this = args.this.get_jsobject();
+        let id = this.get(ctx, socket_key())?;
+        if id.is_number() {
+            return Ok(id.get_number() as u32);
+        }
+    }
+    Err(JsValue::new(ctx.new_type_error("this is not a socket")))
+}
+
+/// Stream object without its id, which is only known once its source is registered.
+fn stream_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut object = JsObject::new_empty(ctx);
+    def_native_method!(ctx, object, read, stream_read, 0)?;
+    def_native_method!(ctx, object, write, stream_write, 1)?;
+    def_native_method!(ctx, object, end, stream_end, 0)?;
+    def_native_method!(ctx, object, close, stream_close, 0)?;
+    Ok(object)
+}
+
+fn put_addresses(
+    ctx: GcPointer<Context>,
+    object: GcPointer<JsObject>,
+    stream: &TcpStream,
+) -> Result<(), JsValue> {
+    if let Ok(address) = stream.peer_addr() {
+        put_address(ctx, object, "remoteAddress", "remotePort", address)?;
+    }
+    if let Ok(address) = stream.local_addr() {
+        put_address(ctx, object, "localAddress", "localPort", address)?;
+    }
+    Ok(())
+}
+
+fn put_address(
+    ctx: GcPointer<Context>,
+    mut object: GcPointer<JsObject>,
+    address_key: &str,
+    port_key: &str,
+    address: SocketAddr,
+) -> Result<(), JsValue> {
+    let ip = JsString::new(ctx, address.ip().to_string());
+    object.put(ctx, address_key.intern(), JsValue::new(ip), false)?;
+    object.put(ctx, port_key.intern(), JsValue::new(address.port()), false)?;
+    Ok(())
+}
+
+/// `host` and `port` of `options`. `default_port` is used if there is no port, connecting
+/// requires one.
+fn host_and_port(
+    ctx: GcPointer<Context>,
+    options: JsValue,
+    name: &str,
+    default_port: Option<u16>,
+) -> Result<(String, u16), JsValue> {
+    let (host, port) = if options.is_jsobject() {
+        let mut options = options.get_jsobject();
+        (
+            options.get(ctx, "host".intern())?,
+            options.get(ctx, "port".intern())?,
+        )
+    } else {
+        (JsValue::UNDEFINED, JsValue::UNDEFINED)
+    };
+    let host = if host.is_undefined() {
+        "127.0.0.1".to_owned()
+    } else {
+        host.to_string(ctx)?
+    };
+    let port = match default_port {
+        Some(default_port) if port.is_undefined() => default_port,
+        _ => parse_port(ctx, port, name)?,
+    };
+    Ok((host, port))
+}
+
+fn parse_port(ctx: GcPointer<Context>, value: JsValue, name: &str) -> Result<u16, JsValue> {
+    if value.is_undefined() {
+        return Err(JsValue::new(
+            ctx.new_type_error(format!("{}: the port is missing", name)),
+        ));
+    }
+    let port = value.to_number(ctx)?;
+    if port.fract() != 0.0 || !(0.0..=65535.0).contains(&port) {
+        return Err(JsValue::new(
+            ctx.new_range_error(format!("{}: invalid port {}", name, port)),
+        ));
+    }
+    Ok(port as u16)
+}
+
+fn callback(
+    ctx: GcPointer<Context>,
+    options: JsValue,
+    key: &str,
+    name: &str,
+) -> Result<Option<Persistent>, JsValue> {
+    if !options.is_jsobject() {
+        return Ok(None);
+    }
+    let value = options.get_jsobject().get(ctx, key.intern())?;
+    if value.is_undefined() {
+        return Ok(None);
+    }
+    if !value.is_callable() {
+        return Err(JsValue::new(
+            ctx.new_type_error(format!("{}: {} must be a function", name, key)),
+        ));
+    }
+    Ok(Some(ctx.vm().persistent(value)))
+}
+
+fn closed_error(ctx: GcPointer<Context>, message: &str) -> JsValue {
+    let message = JsString::new(ctx, message);
+    JsValue::new(JsError::new(ctx, message, None))
+}
+
+/// Register a connected stream, it is polled for reading and writing.
+fn register_stream(mut ctx: GcPointer<Context>, mut stream: TcpStream) -> io::Result<TcpStream> {
+    let interest = Interest::READABLE | Interest::WRITABLE;
+    ctx.vm.event_loop.register(&mut stream, interest)?;
+    Ok(stream)
+}
+
+fn resolve_now(ctx: GcPointer<Context>, promise: JsValue, value: JsValue) -> Result<(), JsValue> {
+    promise
+        .get_jsobject()
+        .as_promise_mut()
+        .resolve(ctx, promise, value)?;
+    Ok(())
+}
+
+/// TCP listener polled by the event loop.
+struct ListenerSource {
+    ctx: GcPointer<Context>,
+    /// `None` once the listener is closed.
+    listener: Option<TcpListener>,
+    settle: Settle,
+    encoding: DataEncoding,
+    on_connection: Option<Persistent>,
+    /// Connections waiting for `accept`.
+    accepted: VecDeque<Persistent>,
+    /// Promises of pending `accept` calls.
+    accepts: VecDeque<Persistent>,
+}
+
+impl ListenerSource {
+    /// Register an accepted stream and hand it to JS.
+    fn connection(&mut self, stream: TcpStream, events: &mut Vec<IoEvent>) {
+        let mut ctx = self.ctx;
+        let stream = match register_stream(ctx, stream) {
+            Ok(stream) => stream,
+            Err(_) => return,
+        };
+        let mut object = match stream_object(ctx) {
+            Ok(object) => object,
+            Err(_) => return,
+        };
+        let _ = put_addresses(ctx, object, &stream);
+        let peer = stream
+            .peer_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        let connection = Connection::Open(stream);
+        let source = StreamSource::new(ctx, connection, self.settle.clone(), self.encoding, peer);
+        let id = ctx.vm.event_loop.add_source(Box::new(source));
+        let _ = object.put(ctx, socket_key(), JsValue::new(id), false);
+        let object = ctx.vm().persistent(JsValue::new(object));
+        match &self.on_connection {
+            Some(callback) => events.push(IoEvent {
+                ctx,
+                callback: callback.clone(),
+                args: vec![object],
+            }),
+            None => self.accepted.push_back(object),
+        }
+    }
+}
+
+impl IoSource for ListenerSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        let listener = match &self.listener {
+            Some(listener) => listener,
+            None => {
+                for promise in self.accepts.drain(..) {
+                    let null = JsValue::encode_null_value();
+                    self.settle.resolve(self.ctx, promise, null, events);
+                }
+                return false;
+            }
+        };
+        let mut streams = vec![];
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => streams.push(stream),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                // out of file descriptors and the like, accepting is retried on the next poll
+                Err(_) => break,
+            }
+        }
+        for stream in streams {
+            self.connection(stream, events);
+        }
+        while !self.accepts.is_empty() && !self.accepted.is_empty() {
+            if let (Some(promise), Some(stream)) =
+                (self.accepts.pop_front(), self.accepted.pop_front())
+            {
+                self.settle.resolve(self.ctx, promise, stream.get(), events);
+            }
+        }
+        true
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+enum Connection {
+    /// Waiting for the thread that connects, `object` is the stream passed to the promise of
+    /// `connect`.
+    Connecting {
+        receiver: Receiver<io::Result<std_net::TcpStream>>,
+        object: Persistent,
+    },
+    Open(TcpStream),
+    Closed,
+}
+
+/// TCP stream polled by the event loop.
+struct StreamSource {
+    ctx: GcPointer<Context>,
+    connection: Connection,
+    settle: Settle,
+    /// Address of the peer for error messages.
+    peer: String,
+    /// Promise of `connect` while connecting.
+    connect: Option<Persistent>,
+    encoding: DataEncoding,
+    decoder: JsTextDecoder,
+    /// Chunks that were received but not read yet, and their total size.
+    received: VecDeque<Vec<u8>>,
+    buffered: usize,
+    /// Promises of pending `read` calls.
+    reads: VecDeque<Persistent>,
+    /// Queued writes with the number of bytes already written and the promise of `write`.
+    writes: VecDeque<(Vec<u8>, usize, Persistent)>,
+    /// The peer ended the stream or receiving failed with `error`.
+    eof: bool,
+    error: Option<io::Error>,
+    /// `end` was called, the sending half is shut down after the queued writes.
+    ending: bool,
+    shut: bool,
+}
+
+impl StreamSource {
+    fn new(
+        ctx: GcPointer<Context>,
+        connection: Connection,
+        settle: Settle,
+        encoding: DataEncoding,
+        peer: String,
+    ) -> Self {
+        Self {
+            ctx,
+            connection,
+            settle,
+            peer,
+            connect: None,
+            encoding,
+            decoder: JsTextDecoder::new(Encoding::Utf8, false, true),
+            received: VecDeque::new(),
+            buffered: 0,
+            reads: VecDeque::new(),
+            writes: VecDeque::new(),
+            eof: false,
+            error: None,
+            ending: false,
+            shut: false,
+        }
+    }
+
+    fn writable(&self) -> bool {
+        !self.ending && !matches!(self.connection, Connection::Closed)
+    }
+
+    fn poll_connect(&mut self, events: &mut Vec<IoEvent>) {
+        let result = match &self.connection {
+            Connection::Connecting { receiver, .. } => match receiver.try_recv() {
+                Ok(result) => result.and_then(|stream| {
+                    stream.set_nonblocking(true)?;
+                    register_stream(self.ctx, TcpStream::from_std(stream))
+                }),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    Err(io::Error::other("the connecting thread stopped"))
+                }
+            },
+            _ => return,
+        };
+        let object = match std::mem::replace(&mut self.connection, Connection::Closed) {
+            Connection::Connecting { object, .. } => object,
+            _ => return,
+        };
+        let promise = match self.connect.take() {
+            Some(promise) => promise,
+            None => return,
+        };
+        match result {
+            Ok(stream) => {
+                let _ = put_addresses(self.ctx, object.get().get_jsobject(), &stream);
+                self.settle.resolve(self.ctx, promise, object.get(), events);
+                self.connection = Connection::Open(stream);
+            }
+            Err(error) => {
+                let error = io_error(self.ctx, "connect", &self.peer, error);
+                self.settle.reject(self.ctx, promise, error, events);
+            }
+        }
+    }
+
+    /// Read what is available without blocking.
+    fn receive(&mut self) {
+        let stream = match &mut self.connection {
+            Connection::Open(stream) => stream,
+            _ => return,
+        };
+        let mut buffer = [0; CHUNK_SIZE];
+        while !self.eof && self.buffered < MAX_BUFFERED {
+            match stream.read(&mut buffer) {
+                Ok(0) => self.eof = true,
+                Ok(count) => {
+                    self.buffered += count;
+                    self.received.push_back(buffer[..count].to_vec());
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => {
+                    self.eof = true;
+                    self.error = Some(error);
+                }
+            }
+        }
+    }
+
+    /// Settle the pending reads with the received chunks.
+    fn deliver(&mut self, events: &mut Vec<IoEvent>) {
+        while !self.reads.is_empty() {
+            let chunk = match self.received.pop_front() {
+                Some(bytes) => {
+                    self.buffered -= bytes.len();
+                    match self.chunk(&bytes) {
+                        Some(chunk) => chunk,
+                        None => continue,
+                    }
+                }
+                None if !self.eof => return,
+                None => {
+                    // an incomplete UTF-8 sequence at the end, the error, then `null`
+                    let rest = self.decoder.decode(&[], false).unwrap_or_default();
+                    if !rest.is_empty() {
+                        JsValue::new(JsString::new(self.ctx, rest))
+                    } else if let Some(error) = self.error.take() {
+                        let error = io_error(self.ctx, "read", &self.peer, error);
+                        if let Some(promise) = self.reads.pop_front() {
+                            self.settle.reject(self.ctx, promise, error, events);
+                        }
+                        continue;
+                    } else {
+                        JsValue::encode_null_value()
+                    }
+                }
+            };
+            if let Some(promise) = self.reads.pop_front() {
+                self.settle.resolve(self.ctx, promise, chunk, events);
+            }
+        }
+    }
+
+    /// Received bytes as they are passed to JS, `None` if they only start a UTF-8 sequence.
+    fn chunk(&mut self, bytes: &[u8]) -> Option<JsValue> {
+        match self.encoding {
+            DataEncoding::Utf8 => {
+                let text = self.decoder.decode(bytes, true).unwrap_or_default();
+                if text.is_empty() {
+                    return None;
+                }
+                Some(JsValue::new(JsString::new(self.ctx, text)))
+            }
+            DataEncoding::Buffer => self.encoding.to_value(self.ctx, bytes).ok(),
+        }
+    }
+
+    /// Write the queued data until the socket would block.
+    fn send(&mut self, events: &mut Vec<IoEvent>) {
+        let stream = match &mut self.connection {
+            Connection::Open(stream) => stream,
+            _ => return,
+        };
+        while let Some((data, written, _)) = self.writes.front_mut() {
+            if *written < data.len() {
+                let result = match stream.write(&data[*written..]) {
+                    Ok(0) => Err(io::Error::from(io::ErrorKind::WriteZero)),
+                    result => result,
+                };
+                match result {
+                    Ok(count) => *written += count,
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                    Err(error) => {
+                        let error = io_error(self.ctx, "write", &self.peer, error);
+                        for (_, _, promise) in self.writes.drain(..) {
+                            self.settle.reject(self.ctx, promise, error, events);
+                        }
+                        self.ending = true;
+                    }
+                }
+                continue;
+            }
+            if let Some((_, _, promise)) = self.writes.pop_front() {
+                self.settle
+                    .resolve(self.ctx, promise, JsValue::UNDEFINED, events);
+            }
+        }
+        if self.ending && self.writes.is_empty() && !self.shut {
+            let _ = stream.shutdown(Shutdown::Write);
+            self.shut = true;
+        }
+    }
+
+    /// Settle everything that is pending once the stream is closed.
+    fn finish(&mut self, events: &mut Vec<IoEvent>) {
+        let ctx = self.ctx;
+        if let Some(promise) = self.connect.take() {
+            let error = closed_error(ctx, "connect: the stream was closed");
+            self.settle.reject(ctx, promise, error, events);
+        }
+        for promise in self.reads.drain(..) {
+            let null = JsValue::encode_null_value();
+            self.settle.resolve(ctx, promise, null, events);
+        }
+        if !self.writes.is_empty() {
+            let error = closed_error(ctx, "write: the stream was closed");
+            for (_, _, promise) in self.writes.drain(..) {
+                self.settle.reject(ctx, promise, error, events);
+            }
+        }
+    }
+}
+
+impl IoSource for StreamSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        self.poll_connect(events);
+        match self.connection {
+            Connection::Connecting { .. } => return true,
+            Connection::Closed => {
+                self.finish(events);
+                return false;
+            }
+            Connection::Open(_) => (),
+        }
+        self.receive();
+        self.deliver(events);
+        self.send(events);
+        // done once both halves are closed and everything received was read
+        !(self.eof && self.shut && self.received.is_empty())
+    }
+
+    fn is_active(&self) -> bool {
+        // once the peer ended the stream nothing more arrives, JS can still write to it while
+        // other work keeps the loop running
+        !self.eof || !self.reads.is_empty() || !self.writes.is_empty()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// UDP socket polled by the event loop.
+struct UdpSource {
+    ctx: GcPointer<Context>,
+    /// `None` once the socket is closed.
+    socket: Option<UdpSocket>,
+    settle: Settle,
+    encoding: DataEncoding,
+    on_message: Option<Persistent>,
+    /// Messages waiting for `receive`.
+    messages: VecDeque<Persistent>,
+    /// Promises of pending `receive` calls.
+    receives: VecDeque<Persistent>,
+    /// Messages of `send` that were not sent yet.
+    sends: VecDeque<Outgoing>,
+    /// `close` was called, the socket is closed once `sends` is empty.
+    closing: bool,
+}
+
+/// Message queued by `send`.
+struct Outgoing {
+    /// Host and port for error messages.
+    target: String,
+    destination: Destination,
+    data: Vec<u8>,
+    promise: Persistent,
+}
+
+enum Destination {
+    Resolved(SocketAddr),
+    /// Waiting for the thread that resolves the host name.
+    Resolving(Receiver<io::Result<SocketAddr>>),
+}
+
+impl Destination {
+    fn new(mut ctx: GcPointer<Context>, host: String, port: u16) -> Self {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Destination::Resolved(SocketAddr::new(ip, port));
+        }
+        let (sender, receiver) = ctx.vm.event_loop.channel();
+        std::thread::spawn(move || {
+            let address = (host.as_str(), port)
+                .to_socket_addrs()
+                .and_then(|mut addresses| {
+                    addresses
+                        .next()
+                        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found"))
+                });
+            let _ = sender.send(address);
+        });
+        Destination::Resolving(receiver)
+    }
+}
+
+impl UdpSource {
+    /// `{ data, address, port }` of a received message.
+    fn message(&self, bytes: &[u8], from: SocketAddr) -> Result<GcPointer<JsObject>, JsValue> {
+        let ctx = self.ctx;
+        let mut message = JsObject::new_empty(ctx);
+        let data = self.encoding.to_value(ctx, bytes)?;
+        message.put(ctx, "data".intern(), data, false)?;
+        put_address(ctx, message, "address", "port", from)?;
+        Ok(message)
+    }
+
+    /// Send the queued messages in order, until the socket would block or a host name is not
+    /// resolved yet.
+    fn send(&mut self, events: &mut Vec<IoEvent>) {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => return,
+        };
+        while let Some(message) = self.sends.front_mut() {
+            let address = match &message.destination {
+                Destination::Resolved(address) => Ok(*address),
+                Destination::Resolving(receiver) => match receiver.try_recv() {
+                    Ok(address) => address,
+                    Err(TryRecvError::Empty) => return,
+                    Err(TryRecvError::Disconnected) => {
+                        Err(io::Error::other("the resolving thread stopped"))
+                    }
+                },
+            };
+            let result = match address {
+                Ok(address) => {
+                    message.destination = Destination::Resolved(address);
+                    match socket.send_to(&message.data, address) {
+                        Err(error) if error.kind() == io::ErrorKind::WouldBlock => return,
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                        result => result,
+                    }
+                }
+                Err(error) => Err(error),
+            };
+            let message = match self.sends.pop_front() {
+                Some(message) => message,
+                None => return,
+            };
+            match result {
+                Ok(sent) => {
+                    let sent = JsValue::new(sent as u32);
+                    self.settle.resolve(self.ctx, message.promise, sent, events);
+                }
+                Err(error) => {
+                    let error = io_error(self.ctx, "send", &message.target, error);
+                    self.settle.reject(self.ctx, message.promise, error, events);
+                }
+            }
+        }
+    }
+}
+
+impl IoSource for UdpSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        self.send(events);
+        if self.closing && self.sends.is_empty() {
+            self.socket = None;
+        }
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => {
+                for promise in self.receives.drain(..) {
+                    let null = JsValue::encode_null_value();
+                    self.settle.resolve(self.ctx, promise, null, events);
+                }
+                return false;
+            }
+        };
+        let mut buffer = [0; CHUNK_SIZE];
+        let mut received = vec![];
+        loop {
+            match socket.recv_from(&mut buffer) {
+                Ok((count, from)) => received.push((buffer[..count].to_vec(), from)),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                // would block, or an error reported for an earlier message which is skipped
+                Err(_) => break,
+            }
+        }
+        for (bytes, from) in received {
+            let message = match self.message(&bytes, from) {
+                Ok(message) => self.ctx.vm().persistent(JsValue::new(message)),
+                Err(_) => continue,
+            };
+            match &self.on_message {
+                Some(callback) => events.push(IoEvent {
+                    ctx: self.ctx,
+                    callback: callback.clone(),
+                    args: vec![message],
+                }),
+                None if self.messages.len() < MAX_MESSAGES => self.messages.push_back(message),
+                None => (),
+            }
+        }
+        while !self.receives.is_empty() && !self.messages.is_empty() {
+            if let (Some(promise), Some(message)) =
+                (self.receives.pop_front(), self.messages.pop_front())
+            {
+                self.settle
+                    .resolve(self.ctx, promise, message.get(), events);
+            }
+        }
+        true
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval_module, run_event_loop};
+    use crate::Platform;
+
+    #[test]
+    fn test_tcp_and_udp() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval_module(
+            ctx,
+            "import net from 'std:net';
+             net.listen({ port: 0, onConnection: stream => {
+                 stream.read().then(chunk => stream.write('echo ' + chunk)).then(() => stream.end());
+             } }).then(server => {
+                 globalThis.serverPort = server.port;
+                 return net.connect({ port: server.port }).then(client => {
+                     client.write('hello');
+                     return client.read().then(reply => client.read().then(end => {
+                         globalThis.tcp = { reply: reply, end: end, remotePort: client.remotePort };
+                         client.close();
+                         server.close();
+                     }));
+                 });
+             });
+
+             let b;
+             let echo = message => {
+                 b.send('pong ' + message.data, message.port);
+                 b.close();
+             };
+             Promise.all([net.bind({ port: 0 }), net.bind({ port: 0, onMessage: echo })]).then(sockets => {
+                 let a = sockets[0];
+                 b = sockets[1];
+                 globalThis.bPort = b.port;
+                 return a.send('ping', b.port).then(sent => {
+                     globalThis.sent = sent;
+                     return a.receive();
+                 }).then(message => {
+                     globalThis.udp = message;
+                     a.close();
+                 });
+             });",
+        );
+        run_event_loop(ctx);
+        assert_js(ctx, "tcp.reply === 'echo hello'");
+        assert_js(ctx, "tcp.end === null");
+        assert_js(ctx, "tcp.remotePort === serverPort");
+        assert_js(ctx, "udp.data === 'pong ping'");
+        assert_js(ctx, "udp.port === bPort");
+        assert_js(ctx, "sent === 4");
+    }
+
+    #[test]
+    fn test_stream_ended_by_peer() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        // the accepted stream is never closed, the loop ends once its peer closed it
+        eval_module(
+            ctx,
+            "import net from 'std:net';
+             let server;
+             net.listen({ port: 0, onConnection: stream => {
+                 server.close();
+                 globalThis.accepted = stream;
+                 stream.read().then(end => globalThis.end = end);
+             } }).then(listener => {
+                 server = listener;
+                 return net.connect({ port: listener.port });
+             }).then(client => client.close());",
+        );
+        run_event_loop(ctx);
+        assert_js(ctx, "end === null");
+        assert_js(ctx, "typeof accepted.write === 'function'");
+    }
+}
@@ -0,0 +1,160 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `std:path`: lexical path manipulation with the separator of the host. Apart from `resolve`,
+//! which starts at the working directory, nothing here touches the file system. Resolving a
+//! relative path reveals the working directory and needs [Capability::Process] or
+//! [Capability::FileSystem].
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::{
+    context::Context,
+    function::{IntoNativeFunction, JsClosureFunction},
+};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+/// Initialize `std:path`.
+pub fn init_path(ctx: GcPointer<Context>, mut module: GcPointer<JsObject>) -> Result<(), JsValue> {
+    let path = path_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(path), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(path), false)?;
+    Ok(())
+}
+
+pub(super) fn path_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut path = JsObject::new_empty(ctx);
+    def_native_method!(ctx, path, join, path_join, 0)?;
+    def_native_method!(ctx, path, resolve, path_resolve, 0)?;
+    // the functions taking a single path are typed, passing anything but a string throws
+    def_typed_method(ctx, path, "normalize", path_normalize)?;
+    def_typed_method(ctx, path, "dirname", path_dirname)?;
+    def_typed_method(ctx, path, "basename", path_basename)?;
+    def_typed_method(ctx, path, "extname", path_extname)?;
+    def_typed_method(ctx, path, "isAbsolute", path_is_absolute)?;
+    let sep = JsString::new(ctx, MAIN_SEPARATOR.to_string());
+    def_native_property!(ctx, path, sep, sep)?;
+    Ok(path)
+}
+
+fn def_typed_method<F, Params>(
+    ctx: GcPointer<Context>,
+    mut object: GcPointer<JsObject>,
+    name: &str,
+    f: F,
+) -> Result<(), JsValue>
+where
+    F: IntoNativeFunction<Params>,
+{
+    let name = name.intern();
+    let method = JsClosureFunction::new_typed(ctx, name, f);
+    object.put(ctx, name, JsValue::new(method), true)
+}
+
+/// `path.join(...segments)`: concatenate the segments and normalize the result.
+pub fn path_join(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut joined = String::new();
+    for i in 0..args.size() {
+        let segment = args.at(i).to_string(ctx)?;
+        if segment.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push(MAIN_SEPARATOR);
+        }
+        joined.push_str(&segment);
+    }
+    Ok(string(ctx, &normalize(Path::new(&joined))))
+}
+
+/// `path.resolve(...segments)`: absolute path obtained by applying the segments from left to right
+/// to the working directory. An absolute segment discards everything before it.
+pub fn path_resolve(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut resolved = PathBuf::new();
+    for i in 0..args.size() {
+        let segment = args.at(i).to_string(ctx)?;
+        resolved.push(segment);
+    }
+    if !resolved.has_root() {
+        let capabilities = ctx.capabilities();
+        if !capabilities.allows(Capability::Process) && !capabilities.allows(Capability::FileSystem)
+        {
+            ctx.require_capability(Capability::Process)?;
+        }
+        let cwd = std::env::current_dir().map_err(|e| super::io_error(ctx, "resolve", ".", e))?;
+        resolved = cwd.join(resolved);
+    }
+    Ok(string(ctx, &normalize(&resolved)))
+}
+
+/// `path.normalize(path)`: resolve `.` and `..` segments and duplicate separators.
+pub fn path_normalize(path: String) -> String {
+    normalize(Path::new(&path))
+}
+
+/// `path.dirname(path)`: `path` without its last segment, `.` when there is none.
+pub fn path_dirname(path: String) -> String {
+    let path = Path::new(&path);
+    match path.parent() {
+        Some(parent) if parent.as_os_str().is_empty() => ".".to_owned(),
+        Some(parent) => parent.to_string_lossy().into_owned(),
+        None if path.has_root() => path.to_string_lossy().into_owned(),
+        None => ".".to_owned(),
+    }
+}
+
+/// `path.basename(path, suffix?)`: last segment of `path`, without `suffix` if it ends with it.
+pub fn path_basename(path: String, suffix: Option<String>) -> String {
+    let mut basename = Path::new(&path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if let Some(suffix) = suffix {
+        if basename.len() > suffix.len() && basename.ends_with(&suffix) {
+            basename.truncate(basename.len() - suffix.len());
+        }
+    }
+    basename
+}
+
+/// `path.extname(path)`: extension of the last segment including the dot, `''` if it has none.
+pub fn path_extname(path: String) -> String {
+    match Path::new(&path).extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    }
+}
+
+pub fn path_is_absolute(path: String) -> bool {
+    Path::new(&path).is_absolute()
+}
+
+fn normalize(path: &Path) -> String {
+    let mut result = PathBuf::new();
+    // number of leading `..` segments that cannot be resolved in a relative path
+    let mut normal = 0;
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => result.push(component),
+            Component::CurDir => (),
+            Component::ParentDir if normal > 0 => {
+                result.pop();
+                normal -= 1;
+            }
+            Component::ParentDir if !result.has_root() => result.push(".."),
+            Component::ParentDir => (),
+            Component::Normal(segment) => {
+                result.push(segment);
+                normal += 1;
+            }
+        }
+    }
+    if result.as_os_str().is_empty() {
+        ".".to_owned()
+    } else {
+        result.to_string_lossy().into_owned()
+    }
+}
+
+fn string(ctx: GcPointer<Context>, string: &str) -> JsValue {
+    JsValue::new(JsString::new(ctx, string))
+}
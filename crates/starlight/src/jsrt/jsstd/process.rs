@@ -0,0 +1,200 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `std:process` and `std:os`: the process running the VM and the host it runs on. Child
+//! processes are in [super::child_process].
+use super::child_process::{process_exec, process_spawn};
+use super::{io_error, std_args, std_exit};
+use crate::prelude::*;
+use crate::vm::capabilities::Capability;
+use crate::vm::context::Context;
+use once_cell::sync::Lazy;
+use std::time::Instant;
+
+/// Origin of `process.hrtime`.
+static HRTIME_ORIGIN: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Initialize `std:process`.
+pub fn init_process(
+    ctx: GcPointer<Context>,
+    mut module: GcPointer<JsObject>,
+) -> Result<(), JsValue> {
+    let process = process_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(process), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(process), false)?;
+    Ok(())
+}
+
+/// Initialize `std:os`.
+pub fn init_os(ctx: GcPointer<Context>, mut module: GcPointer<JsObject>) -> Result<(), JsValue> {
+    let os = os_object(ctx)?;
+    module.put(ctx, "@exports".intern(), JsValue::new(os), false)?;
+    module.put(ctx, "@default".intern(), JsValue::new(os), false)?;
+    Ok(())
+}
+
+pub(super) fn process_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    // `argv` and `pid` are read right away
+    ctx.require_capability(Capability::Process)?;
+    let mut process = JsObject::new_empty(ctx);
+    def_native_method!(ctx, process, env, process_env, 1)?;
+    def_native_method!(ctx, process, cwd, process_cwd, 0)?;
+    def_native_method!(ctx, process, chdir, process_chdir, 1)?;
+    def_native_method!(ctx, process, exit, std_exit, 1)?;
+    def_native_method!(ctx, process, args, std_args, 0)?;
+    def_native_method!(ctx, process, hrtime, process_hrtime, 1)?;
+    def_native_method!(ctx, process, exec, process_exec, 3)?;
+    def_native_method!(ctx, process, spawn, process_spawn, 3)?;
+    let argv = process_argv(ctx)?;
+    def_native_property!(ctx, process, argv, argv)?;
+    def_native_property!(ctx, process, pid, std::process::id())?;
+    let platform = JsString::new(ctx, platform());
+    def_native_property!(ctx, process, platform, platform)?;
+    Lazy::force(&HRTIME_ORIGIN);
+    Ok(process)
+}
+
+pub(super) fn os_object(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let mut os = JsObject::new_empty(ctx);
+    def_native_method!(ctx, os, homedir, os_homedir, 0)?;
+    def_native_method!(ctx, os, tmpdir, os_tmpdir, 0)?;
+    def_native_method!(ctx, os, availableParallelism, os_available_parallelism, 0)?;
+    let platform = JsString::new(ctx, platform());
+    def_native_property!(ctx, os, platform, platform)?;
+    let arch = JsString::new(ctx, arch());
+    def_native_property!(ctx, os, arch, arch)?;
+    let eol = JsString::new(ctx, if cfg!(windows) { "\r\n" } else { "\n" });
+    def_native_property!(ctx, os, EOL, eol)?;
+    Ok(os)
+}
+
+/// `process.argv`: path of the executable, path of the script unless it runs in a REPL, then the
+/// arguments passed to the script, see [Options::script_args](crate::options::Options).
+fn process_argv(ctx: GcPointer<Context>) -> Result<GcPointer<JsObject>, JsValue> {
+    let vm = ctx.vm();
+    let options = vm.options();
+    let executable = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let script = match (&options.eval, &options.file) {
+        (Some(_), _) => Some("[eval]".to_owned()),
+        (None, Some(file)) if file.as_os_str() == "-" => Some("[stdin]".to_owned()),
+        (None, Some(file)) => Some(file.to_string_lossy().into_owned()),
+        (None, None) => None,
+    };
+    let args = std::iter::once(executable)
+        .chain(script)
+        .chain(options.script_args.iter().cloned())
+        .collect::<Vec<_>>();
+    letroot!(argv = stack, JsArray::new(ctx, args.len() as u32));
+    for (index, arg) in args.iter().enumerate() {
+        let arg = JsString::new(ctx, arg);
+        argv.put(ctx, Symbol::Index(index as u32), JsValue::new(arg), false)?;
+    }
+    Ok(argv)
+}
+
+/// `process.env(name?)`: value of the environment variable `name` or `undefined` if it is not set.
+/// Without `name` returns a snapshot of the whole environment as an object.
+pub fn process_env(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    if args.size() > 0 && !args.at(0).is_undefined() {
+        let name = args.at(0).to_string(ctx)?;
+        return Ok(match std::env::var_os(&name) {
+            Some(value) => JsValue::new(JsString::new(ctx, value.to_string_lossy())),
+            None => JsValue::UNDEFINED,
+        });
+    }
+    let mut env = JsObject::new_empty(ctx);
+    for (name, value) in std::env::vars_os() {
+        let value = JsString::new(ctx, value.to_string_lossy());
+        env.put(
+            ctx,
+            name.to_string_lossy().intern(),
+            JsValue::new(value),
+            false,
+        )?;
+    }
+    Ok(JsValue::new(env))
+}
+
+pub fn process_cwd(ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let cwd = std::env::current_dir().map_err(|e| io_error(ctx, "cwd", ".", e))?;
+    Ok(JsValue::new(JsString::new(ctx, cwd.to_string_lossy())))
+}
+
+/// `process.chdir(path)`: change the working directory of the whole process.
+pub fn process_chdir(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let path = args.at(0).to_string(ctx)?;
+    std::env::set_current_dir(&path).map_err(|e| io_error(ctx, "chdir", &path, e))?;
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `process.hrtime(previous?)`: monotonic `[seconds, nanoseconds]` relative to an arbitrary time
+/// in the past, or relative to `previous` if a result of an earlier call is passed.
+pub fn process_hrtime(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let elapsed = HRTIME_ORIGIN.elapsed();
+    let mut seconds = elapsed.as_secs() as f64;
+    let mut nanos = elapsed.subsec_nanos() as f64;
+    if args.at(0).is_jsobject() {
+        let mut previous = args.at(0).get_jsobject();
+        seconds -= previous.get(ctx, Symbol::Index(0))?.to_number(ctx)?;
+        nanos -= previous.get(ctx, Symbol::Index(1))?.to_number(ctx)?;
+        if nanos < 0.0 {
+            seconds -= 1.0;
+            nanos += 1e9;
+        }
+    }
+    let time = [JsValue::new(seconds), JsValue::new(nanos)];
+    Ok(JsValue::new(JsArray::from_slice(ctx, &time)))
+}
+
+pub fn os_homedir(ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let home = if cfg!(windows) {
+        std::env::var_os("USERPROFILE")
+    } else {
+        std::env::var_os("HOME")
+    };
+    Ok(match home {
+        Some(home) => JsValue::new(JsString::new(ctx, home.to_string_lossy())),
+        None => JsValue::UNDEFINED,
+    })
+}
+
+pub fn os_tmpdir(ctx: GcPointer<Context>, _args: &Arguments) -> Result<JsValue, JsValue> {
+    ctx.require_capability(Capability::Process)?;
+    let tmp = std::env::temp_dir();
+    Ok(JsValue::new(JsString::new(ctx, tmp.to_string_lossy())))
+}
+
+/// `os.availableParallelism()`: number of threads the host can run in parallel.
+pub fn os_available_parallelism(
+    _ctx: GcPointer<Context>,
+    _args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Ok(JsValue::new(threads as u32))
+}
+
+/// Operating system with the names used by Node.js.
+fn platform() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    }
+}
+
+/// CPU architecture with the names used by Node.js.
+fn arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
@@ -0,0 +1,482 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `Map` and `Set`. Both are ordinary objects that keep their entries in a [MapInternal] under a
+//! private property, a `Set` stores its values as keys.
+use std::intrinsics::unlikely;
+
+use crate::{
+    prelude::*,
+    vm::{builder::Builtin, context::Context, map::JsMap, map::MapInternal},
+};
+
+const KIND_KEYS: i32 = 0;
+const KIND_VALUES: i32 = 1;
+const KIND_ENTRIES: i32 = 2;
+
+fn map_data() -> Symbol {
+    "[[MapData]]".intern().private()
+}
+
+fn set_data() -> Symbol {
+    "[[SetData]]".intern().private()
+}
+
+/// Entries of `value` if it is an object that has `data`, i.e. a `Map` for `[[MapData]]` or a
+/// `Set` for `[[SetData]]`.
+pub fn map_storage(
+    ctx: GcPointer<Context>,
+    value: JsValue,
+    data: Symbol,
+) -> Option<GcPointer<MapInternal>> {
+    if !value.is_jsobject() {
+        return None;
+    }
+    let mut object = value.get_jsobject();
+    let desc = object.get_own_property(ctx, data)?;
+    let storage = desc.value();
+    if !storage.is_object() {
+        return None;
+    }
+    storage.get_object().downcast::<MapInternal>()
+}
+
+fn this_storage(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    data: Symbol,
+    method: &str,
+) -> Result<GcPointer<MapInternal>, JsValue> {
+    match map_storage(ctx, args.this, data) {
+        Some(storage) => Ok(storage),
+        None => Err(JsValue::new(
+            ctx.new_type_error(format!("{} is not generic", method)),
+        )),
+    }
+}
+
+/// Create an empty `Map` or `Set` with `structure`.
+pub fn new_collection(
+    mut ctx: GcPointer<Context>,
+    structure: GcPointer<Structure>,
+    data: Symbol,
+) -> Result<(GcPointer<JsObject>, GcPointer<MapInternal>), JsValue> {
+    let mut object = JsObject::new(ctx, &structure, JsObject::class(), ObjectTag::Ordinary);
+    let storage = ctx.heap().allocate(MapInternal::new());
+    object.define_own_property(
+        ctx,
+        data,
+        &*DataDescriptor::new(JsValue::new(storage), NONE),
+        false,
+    )?;
+    Ok((object, storage))
+}
+
+/// Items of `iterable` collected into an array with the iteration protocol.
+fn iterate(ctx: GcPointer<Context>, iterable: JsValue) -> Result<GcPointer<JsObject>, JsValue> {
+    let mut spread = ctx.global_data.spread_builtin.unwrap();
+    let mut slice = [iterable];
+    let mut args = Arguments::new(JsValue::encode_undefined_value(), &mut slice);
+    Ok(spread
+        .as_function_mut()
+        .call(ctx, &mut args, JsValue::encode_undefined_value())?
+        .get_jsobject())
+}
+
+fn construct(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    name: &str,
+    structure: GcPointer<Structure>,
+    data: Symbol,
+) -> Result<JsValue, JsValue> {
+    if unlikely(!args.ctor_call) {
+        return Err(JsValue::new(
+            ctx.new_type_error(format!("Constructor {} requires 'new'", name)),
+        ));
+    }
+    let (mut object, _) = new_collection(ctx, structure, data)?;
+    let iterable = args.at(0);
+    if iterable.is_undefined() || iterable.is_null() {
+        return Ok(JsValue::new(object));
+    }
+    let is_map = name == "Map";
+    let adder = object.get(ctx, if is_map { "set" } else { "add" }.intern())?;
+    if unlikely(!adder.is_callable()) {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "{}.prototype.{} is not a function",
+            name,
+            if is_map { "set" } else { "add" }
+        ))));
+    }
+    let mut adder = adder.get_jsobject();
+    let mut items = iterate(ctx, iterable)?;
+    for i in 0..crate::jsrt::get_length(ctx, &mut items)? {
+        let item = items.get(ctx, Symbol::Index(i))?;
+        let mut slice = if is_map {
+            if unlikely(!item.is_jsobject()) {
+                return Err(JsValue::new(
+                    ctx.new_type_error("Iterator value is not an entry object"),
+                ));
+            }
+            let mut entry = item.get_jsobject();
+            [
+                entry.get(ctx, Symbol::Index(0))?,
+                entry.get(ctx, Symbol::Index(1))?,
+            ]
+        } else {
+            [item, JsValue::encode_undefined_value()]
+        };
+        let mut args = Arguments::new(
+            JsValue::encode_undefined_value(),
+            &mut slice[..if is_map { 2 } else { 1 }],
+        );
+        adder
+            .as_function_mut()
+            .call(ctx, &mut args, JsValue::new(object))?;
+    }
+    Ok(JsValue::new(object))
+}
+
+fn for_each(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    storage: GcPointer<MapInternal>,
+    name: &str,
+) -> Result<JsValue, JsValue> {
+    let callback = args.at(0);
+    if unlikely(!callback.is_callable()) {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "{}.prototype.forEach callback must be a function",
+            name
+        ))));
+    }
+    let mut callback = callback.get_jsobject();
+    let this_arg = args.at(1);
+    let is_set = name == "Set";
+    let mut slot = 0;
+    // entries added by the callback are visited as well, so the bound is read on every step
+    while slot < storage.slots() {
+        if let Some((key, value)) = storage.entry_at(slot) {
+            let value = if is_set { key } else { value };
+            let mut slice = [value, key, args.this];
+            let mut args = Arguments::new(this_arg, &mut slice);
+            callback.as_function_mut().call(ctx, &mut args, this_arg)?;
+        }
+        slot += 1;
+    }
+    Ok(JsValue::encode_undefined_value())
+}
+
+fn create_iterator(
+    ctx: GcPointer<Context>,
+    storage: GcPointer<MapInternal>,
+    kind: i32,
+) -> Result<JsValue, JsValue> {
+    let structure = ctx.global_data.map_iterator_structure.unwrap();
+    let mut iterator = JsObject::new(ctx, &structure, JsObject::class(), ObjectTag::Ordinary);
+    for (name, value) in [
+        ("[[IteratedData]]", JsValue::new(storage)),
+        ("[[IteratorNextSlot]]", JsValue::new(0)),
+        ("[[IteratorKind]]", JsValue::new(kind)),
+    ]
+    .iter()
+    {
+        iterator.define_own_property(
+            ctx,
+            name.intern().private(),
+            &*DataDescriptor::new(*value, W),
+            false,
+        )?;
+    }
+    Ok(JsValue::new(iterator))
+}
+
+pub fn map_constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let structure = ctx.global_data.map_structure.unwrap();
+    construct(ctx, args, "Map", structure, map_data())
+}
+
+pub fn map_get(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.get")?;
+    Ok(storage
+        .get(args.at(0))
+        .unwrap_or_else(JsValue::encode_undefined_value))
+}
+
+pub fn map_set(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, map_data(), "Map.prototype.set")?;
+    storage.insert(args.at(0), args.at(1));
+    Ok(args.this)
+}
+
+pub fn map_has(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.has")?;
+    Ok(JsValue::new(storage.contains_key(args.at(0))))
+}
+
+pub fn map_delete(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, map_data(), "Map.prototype.delete")?;
+    Ok(JsValue::new(storage.remove(args.at(0)).is_some()))
+}
+
+pub fn map_clear(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, map_data(), "Map.prototype.clear")?;
+    storage.clear();
+    Ok(JsValue::encode_undefined_value())
+}
+
+pub fn map_size(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.size")?;
+    Ok(JsValue::new(storage.len() as u32))
+}
+
+pub fn map_for_each(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.forEach")?;
+    for_each(ctx, args, storage, "Map")
+}
+
+pub fn map_keys(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.keys")?;
+    create_iterator(ctx, storage, KIND_KEYS)
+}
+
+pub fn map_values(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.values")?;
+    create_iterator(ctx, storage, KIND_VALUES)
+}
+
+pub fn map_entries(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, map_data(), "Map.prototype.entries")?;
+    create_iterator(ctx, storage, KIND_ENTRIES)
+}
+
+pub fn set_constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let structure = ctx.global_data.set_structure.unwrap();
+    construct(ctx, args, "Set", structure, set_data())
+}
+
+pub fn set_add(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, set_data(), "Set.prototype.add")?;
+    if !storage.contains_key(args.at(0)) {
+        storage.insert(args.at(0), args.at(0));
+    }
+    Ok(args.this)
+}
+
+pub fn set_has(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, set_data(), "Set.prototype.has")?;
+    Ok(JsValue::new(storage.contains_key(args.at(0))))
+}
+
+pub fn set_delete(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, set_data(), "Set.prototype.delete")?;
+    Ok(JsValue::new(storage.remove(args.at(0)).is_some()))
+}
+
+pub fn set_clear(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut storage = this_storage(ctx, args, set_data(), "Set.prototype.clear")?;
+    storage.clear();
+    Ok(JsValue::encode_undefined_value())
+}
+
+pub fn set_size(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, set_data(), "Set.prototype.size")?;
+    Ok(JsValue::new(storage.len() as u32))
+}
+
+pub fn set_for_each(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, set_data(), "Set.prototype.forEach")?;
+    for_each(ctx, args, storage, "Set")
+}
+
+pub fn set_values(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, set_data(), "Set.prototype.values")?;
+    create_iterator(ctx, storage, KIND_KEYS)
+}
+
+pub fn set_entries(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let storage = this_storage(ctx, args, set_data(), "Set.prototype.entries")?;
+    create_iterator(ctx, storage, KIND_ENTRIES)
+}
+
+/// `%MapIteratorPrototype%.next`, shared by the iterators of `Map` and `Set`.
+pub fn map_iterator_next(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let data_key = "[[IteratedData]]".intern().private();
+    let slot_key = "[[IteratorNextSlot]]".intern().private();
+    if unlikely(
+        !args.this.is_jsobject() || !args.this.get_jsobject().has_own_property(ctx, slot_key),
+    ) {
+        return Err(JsValue::new(
+            ctx.new_type_error("%MapIteratorPrototype%.next is not generic"),
+        ));
+    }
+    let mut iterator = args.this.get_jsobject();
+    let mut result = JsObject::new_empty(ctx);
+    let mut value = JsValue::encode_undefined_value();
+    let mut done = true;
+    // `[[IteratedData]]` is cleared once the iterator is exhausted, it then stays exhausted even
+    // if entries are added later
+    if let Some(storage) = map_storage(ctx, args.this, data_key) {
+        let kind = iterator
+            .get(ctx, "[[IteratorKind]]".intern().private())?
+            .get_int32();
+        let mut slot = iterator.get(ctx, slot_key)?.get_int32() as usize;
+        while slot < storage.slots() {
+            let entry = storage.entry_at(slot);
+            slot += 1;
+            if let Some((key, entry_value)) = entry {
+                value = match kind {
+                    KIND_KEYS => key,
+                    KIND_VALUES => entry_value,
+                    _ => {
+                        let mut pair = JsArray::new(ctx, 2);
+                        pair.put(ctx, Symbol::Index(0), key, false)?;
+                        pair.put(ctx, Symbol::Index(1), entry_value, false)?;
+                        JsValue::new(pair)
+                    }
+                };
+                done = false;
+                break;
+            }
+        }
+        iterator.put(ctx, slot_key, JsValue::new(slot as i32), false)?;
+        if done {
+            iterator.put(ctx, data_key, JsValue::encode_undefined_value(), false)?;
+        }
+    }
+    result.put(ctx, "value".intern(), value, false)?;
+    result.put(ctx, "done".intern(), JsValue::new(done), false)?;
+    Ok(JsValue::new(result))
+}
+
+pub fn map_iterator_iterator(_: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    Ok(args.this)
+}
+
+impl Builtin for JsMap {
+    fn native_references() -> Vec<usize> {
+        vec![
+            map_constructor as _,
+            map_get as _,
+            map_set as _,
+            map_has as _,
+            map_delete as _,
+            map_clear as _,
+            map_size as _,
+            map_for_each as _,
+            map_keys as _,
+            map_values as _,
+            map_entries as _,
+            set_constructor as _,
+            set_add as _,
+            set_has as _,
+            set_delete as _,
+            set_clear as _,
+            set_size as _,
+            set_for_each as _,
+            set_values as _,
+            set_entries as _,
+            map_iterator_next as _,
+            map_iterator_iterator as _,
+        ]
+    }
+
+    fn init(mut ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        let obj_proto = ctx.global_data().object_prototype.unwrap();
+        let iterator_symbol = "Symbol.iterator".intern().private();
+
+        let structure = Structure::new_indexed(ctx, Some(obj_proto), false);
+        let mut iterator_proto =
+            JsObject::new(ctx, &structure, JsObject::class(), ObjectTag::Ordinary);
+        def_native_method!(ctx, iterator_proto, next, map_iterator_next, 0)?;
+        let iterator = JsNativeFunction::new(ctx, iterator_symbol, map_iterator_iterator, 0);
+        iterator_proto.put(ctx, iterator_symbol, JsValue::new(iterator), false)?;
+        ctx.global_data.map_iterator_structure =
+            Some(Structure::new_indexed(ctx, Some(iterator_proto), false));
+
+        let structure = Structure::new_indexed(ctx, Some(obj_proto), false);
+        let mut prototype = JsObject::new(ctx, &structure, JsObject::class(), ObjectTag::Ordinary);
+        let mut constructor = JsNativeFunction::new(ctx, "Map".intern(), map_constructor, 0);
+        def_native_property!(ctx, constructor, prototype, prototype, NONE)?;
+        def_native_property!(ctx, prototype, constructor, constructor, W | C)?;
+        def_native_method!(ctx, prototype, get, map_get, 1)?;
+        def_native_method!(ctx, prototype, set, map_set, 2)?;
+        def_native_method!(ctx, prototype, has, map_has, 1)?;
+        def_native_method!(ctx, prototype, delete, map_delete, 1)?;
+        def_native_method!(ctx, prototype, clear, map_clear, 0)?;
+        def_native_method!(ctx, prototype, forEach, map_for_each, 1)?;
+        def_native_method!(ctx, prototype, keys, map_keys, 0)?;
+        def_native_method!(ctx, prototype, values, map_values, 0)?;
+        let entries = JsNativeFunction::new(ctx, "entries".intern(), map_entries, 0);
+        def_native_property!(ctx, prototype, entries, entries, W | C)?;
+        prototype.put(ctx, iterator_symbol, JsValue::new(entries), false)?;
+        let size = JsNativeFunction::new(ctx, "size".intern(), map_size, 0);
+        def_native_getter!(ctx, prototype, size, size, C)?;
+        ctx.global_data.map_prototype = Some(prototype);
+        ctx.global_data.map_structure = Some(Structure::new_indexed(ctx, Some(prototype), false));
+        ctx.global_object()
+            .put(ctx, "Map".intern(), JsValue::new(constructor), false)?;
+
+        let structure = Structure::new_indexed(ctx, Some(obj_proto), false);
+        let mut prototype = JsObject::new(ctx, &structure, JsObject::class(), ObjectTag::Ordinary);
+        let mut constructor = JsNativeFunction::new(ctx, "Set".intern(), set_constructor, 0);
+        def_native_property!(ctx, constructor, prototype, prototype, NONE)?;
+        def_native_property!(ctx, prototype, constructor, constructor, W | C)?;
+        def_native_method!(ctx, prototype, add, set_add, 1)?;
+        def_native_method!(ctx, prototype, has, set_has, 1)?;
+        def_native_method!(ctx, prototype, delete, set_delete, 1)?;
+        def_native_method!(ctx, prototype, clear, set_clear, 0)?;
+        def_native_method!(ctx, prototype, forEach, set_for_each, 1)?;
+        def_native_method!(ctx, prototype, entries, set_entries, 0)?;
+        let values = JsNativeFunction::new(ctx, "values".intern(), set_values, 0);
+        def_native_property!(ctx, prototype, values, values, W | C)?;
+        def_native_property!(ctx, prototype, keys, values, W | C)?;
+        prototype.put(ctx, iterator_symbol, JsValue::new(values), false)?;
+        let size = JsNativeFunction::new(ctx, "size".intern(), set_size, 0);
+        def_native_getter!(ctx, prototype, size, size, C)?;
+        ctx.global_data.set_prototype = Some(prototype);
+        ctx.global_data.set_structure = Some(Structure::new_indexed(ctx, Some(prototype), false));
+        ctx.global_object()
+            .put(ctx, "Set".intern(), JsValue::new(constructor), false)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        options::Options,
+        vm::{
+            context::Context,
+            testing::{assert_js, eval},
+        },
+        Platform,
+    };
+
+    #[test]
+    fn test_map_and_set() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        eval(
+            ctx,
+            "var map = new Map([['a', 1], ['b', 2]]);
+             map.set(NaN, 3).set(-0, 4);
+             map.delete('a');
+             map.set('a', 5);
+             var keys = [];
+             for (let key of map.keys()) keys.push(String(key));
+             var set = new Set([1, 2, 2, 3]);
+             var seen = [];
+             set.forEach(value => { seen.push(value); if (value === 1) set.add(4); });",
+        );
+        assert_js(ctx, "map.size === 4");
+        assert_js(ctx, "map.get(NaN) === 3");
+        assert_js(ctx, "map.get(0) === 4 && map.has(+0)");
+        assert_js(ctx, "keys.join() === 'b,NaN,0,a'");
+        assert_js(ctx, "set.size === 4");
+        assert_js(ctx, "seen.join() === '1,2,3,4'");
+        assert_js(ctx, "[...new Set('abca')].join('') === 'abc'");
+        assert_js(ctx, "!set.has('1')");
+    }
+}
@@ -1,7 +1,11 @@
 use core::f64;
 use std::intrinsics::unlikely;
 
-use crate::{prelude::*, vm::{builder::{Builtin}, context::Context}};
+use crate::vm::capabilities::BuiltinGroup;
+use crate::{
+    prelude::*,
+    vm::{builder::Builtin, context::Context},
+};
 pub fn math_abs(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     if args.size() != 0 {
         if args.at(0).is_int32() {
@@ -125,42 +129,42 @@ pub fn math_pow(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, Js
     Ok(JsValue::new(left.powf(right)))
 }
 
-pub fn math_acosh(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue>{
+pub fn math_acosh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(left.acosh()))
 }
 
-pub fn math_asinh(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue>{
+pub fn math_asinh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(left.asinh()))
 }
 
-pub fn math_atanh(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue>{
+pub fn math_atanh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(left.atanh()))
 }
 
-pub fn math_cbrt(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue>{
+pub fn math_cbrt(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(left.cbrt()))
 }
 
-pub fn math_clz32(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue>{
+pub fn math_clz32(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_uint32(ctx)?;
     Ok(JsValue::new(left.leading_zeros()))
 }
 
-pub fn math_expm1(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue> {
+pub fn math_expm1(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_number(ctx)?;
-    Ok(JsValue::new(left.exp()-1.0))
+    Ok(JsValue::new(left.exp() - 1.0))
 }
 
-pub fn math_fround(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue>{
+pub fn math_fround(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_f32(ctx)?;
     Ok(JsValue::new(left))
 }
 
-pub fn math_hypot(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue,JsValue> {
+pub fn math_hypot(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let mut sum = 0f64;
     for index in 0..args.size() {
         let number = args.at(index).to_number(ctx)?;
@@ -168,56 +172,56 @@ pub fn math_hypot(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue,J
     }
     Ok(JsValue::new(sum.sqrt()))
 }
-pub fn math_imul(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue,JsValue> {
+pub fn math_imul(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let left = args.at(0).to_uint32(ctx)?;
     let right = args.at(1).to_uint32(ctx)?;
     Ok(JsValue::new(left.wrapping_mul(right)))
 }
 
-pub fn math_log10(ctx: GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue> {
+pub fn math_log10(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.log10()))
 }
 
-pub fn math_log1p(ctx: GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue> {
+pub fn math_log1p(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
-    Ok(JsValue::new((number+1.0).ln()))
+    Ok(JsValue::new((number + 1.0).ln()))
 }
 
-pub fn math_log2(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue> {
+pub fn math_log2(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.log2()))
 }
 
-pub fn math_sign(ctx:GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+pub fn math_sign(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
-    if unlikely(number ==0.0 || number==-0.0){
+    if unlikely(number == 0.0 || number == -0.0) {
         return Ok(JsValue::new(0));
     }
     Ok(JsValue::new(number.signum()))
 }
 
-pub fn math_round(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue> {
+pub fn math_round(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.round()))
 }
 
-pub fn math_sinh(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue, JsValue> {
+pub fn math_sinh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.sinh()))
 }
 
-pub fn math_cosh(ctx:GcPointer<Context>, args:&Arguments) -> Result<JsValue,JsValue> {
+pub fn math_cosh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.cosh()))
 }
 
-pub fn math_tan(ctx:GcPointer<Context>, args: &Arguments)-> Result<JsValue,JsValue> {
+pub fn math_tan(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.tan()))
 }
 
-pub fn math_tanh(ctx:GcPointer<Context>, args: &Arguments) -> Result<JsValue,JsValue> {
+pub fn math_tanh(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let number = args.at(0).to_number(ctx)?;
     Ok(JsValue::new(number.tanh()))
 }
@@ -225,6 +229,8 @@ pub fn math_tanh(ctx:GcPointer<Context>, args: &Arguments) -> Result<JsValue,JsV
 pub struct JsMath;
 
 impl Builtin for JsMath {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::Math);
+
     fn native_references() -> Vec<usize> {
         vec![
             math_abs as _,
@@ -259,7 +265,7 @@ impl Builtin for JsMath {
             math_sqrt as _,
             math_tan as _,
             math_tanh as _,
-            math_trunc as _
+            math_trunc as _,
         ]
     }
 
@@ -274,30 +280,30 @@ impl Builtin for JsMath {
         def_native_method!(ctx, math, atan, math_atan, 1)?;
         def_native_method!(ctx, math, atan2, math_atan2, 1)?;
         def_native_method!(ctx, math, atanh, math_atanh, 1)?;
-        def_native_method!(ctx, math, cbrt, math_cbrt,1)?;
-        def_native_method!(ctx, math, ceil, math_ceil,1)?;
+        def_native_method!(ctx, math, cbrt, math_cbrt, 1)?;
+        def_native_method!(ctx, math, ceil, math_ceil, 1)?;
         def_native_method!(ctx, math, clz32, math_clz32, 1)?;
         def_native_method!(ctx, math, cos, math_cos, 1)?;
-        def_native_method!(ctx, math, cosh, math_cosh,1)?;
+        def_native_method!(ctx, math, cosh, math_cosh, 1)?;
         def_native_method!(ctx, math, exp, math_exp, 1)?;
-        def_native_method!(ctx, math, expm1, math_expm1,1)?;
+        def_native_method!(ctx, math, expm1, math_expm1, 1)?;
         def_native_method!(ctx, math, floor, math_floor, 1)?;
-        def_native_method!(ctx, math, fround, math_fround,1)?;
-        def_native_method!(ctx, math, hypot, math_hypot,2)?;
-        def_native_method!(ctx, math, imul, math_imul,2)?;
+        def_native_method!(ctx, math, fround, math_fround, 1)?;
+        def_native_method!(ctx, math, hypot, math_hypot, 2)?;
+        def_native_method!(ctx, math, imul, math_imul, 2)?;
         def_native_method!(ctx, math, log, math_log, 2)?;
-        def_native_method!(ctx, math, log10, math_log10,1)?;
-        def_native_method!(ctx, math, log1p, math_log1p,1)?;
+        def_native_method!(ctx, math, log10, math_log10, 1)?;
+        def_native_method!(ctx, math, log1p, math_log1p, 1)?;
         def_native_method!(ctx, math, log2, math_log2, 1)?;
         def_native_method!(ctx, math, pow, math_pow, 2)?;
         def_native_method!(ctx, math, random, math_random, 0)?;
         def_native_method!(ctx, math, round, math_round, 0)?;
-        def_native_method!(ctx, math, sign,math_sign,1)?;
+        def_native_method!(ctx, math, sign, math_sign, 1)?;
         def_native_method!(ctx, math, sin, math_sin, 1)?;
-        def_native_method!(ctx, math, sinh, math_sinh,1)?;
+        def_native_method!(ctx, math, sinh, math_sinh, 1)?;
         def_native_method!(ctx, math, sqrt, math_sqrt, 1)?;
-        def_native_method!(ctx, math, tan, math_tan,1)?;
-        def_native_method!(ctx, math, tanh, math_tanh,1)?;
+        def_native_method!(ctx, math, tan, math_tan, 1)?;
+        def_native_method!(ctx, math, tanh, math_tanh, 1)?;
         def_native_method!(ctx, math, trunc, math_trunc, 1)?;
 
         def_native_property!(ctx, math, E, f64::consts::E)?;
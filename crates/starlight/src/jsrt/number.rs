@@ -95,171 +95,222 @@ pub fn number_to_int(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValu
     let num = args.at(0);
     num.to_int32(ctx).map(JsValue::new)
 }
+/// Number::toString(x) for radix 10. `ryu-js` produces the shortest round-trip digits laid out
+/// exactly as the spec requires, e.g. `1e+21`, `1.5e-7` and `0` for `-0`.
+pub fn number_to_js_string(num: f64) -> String {
+    let mut buffer = ryu_js::Buffer::new();
+    buffer.format(num).to_string()
+}
+
+/// Exact decimal digits of a finite non-negative `x` as `(digits, exponent)` with
+/// `x = d.ddd... * 10^exponent`. Doubles have at most 767 significant decimal digits so the
+/// expansion is never truncated.
+fn exact_exponential_digits(x: f64) -> (Vec<u8>, i32) {
+    let formatted = format!("{:.800e}", x);
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+    let digits = mantissa.bytes().filter(|c| *c != b'.').collect();
+    (digits, exponent[1..].parse().unwrap())
+}
+
+/// Keeps the first `count` digits of `digits` rounding half up, i.e. picking the larger `n`
+/// on ties as the spec asks. Returns true when the carry produced an extra leading digit.
+fn round_digits_half_up(digits: &mut Vec<u8>, count: usize) -> bool {
+    let round_up = digits.get(count).map_or(false, |digit| *digit >= b'5');
+    digits.truncate(count);
+    while digits.len() < count {
+        digits.push(b'0');
+    }
+    if !round_up {
+        return false;
+    }
+    for digit in digits.iter_mut().rev() {
+        if *digit == b'9' {
+            *digit = b'0';
+        } else {
+            *digit += 1;
+            return false;
+        }
+    }
+    digits.insert(0, b'1');
+    true
+}
+
+/// Digits and exponent of `x` rounded to `precision` significant digits, `x` must be finite
+/// and non-negative.
+fn to_precision_digits(x: f64, precision: usize) -> (String, i32) {
+    if x == 0.0 {
+        return ("0".repeat(precision), 0);
+    }
+    let (mut digits, mut exponent) = exact_exponential_digits(x);
+    if round_digits_half_up(&mut digits, precision) {
+        digits.pop();
+        exponent += 1;
+    }
+    (String::from_utf8(digits).unwrap(), exponent)
+}
+
+fn format_exponential(mut digits: String, exponent: i32) -> String {
+    if digits.len() > 1 {
+        digits.insert(1, '.');
+    }
+    digits.push('e');
+    digits.push(if exponent < 0 { '-' } else { '+' });
+    digits.push_str(&exponent.abs().to_string());
+    digits
+}
+
 pub fn number_to_precisiion(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let precision_var = args.at(0);
     let mut this_num = this_number_val(ctx, args.this)?;
-    if precision_var.is_undefined() || !this_num.is_finite() {
-        return number_to_string(ctx, &Arguments::new(args.this, &mut []));
-    }
-
-    let precision = match precision_var.to_int32(ctx)? {
-        x if (1..=100).contains(&x) => x as usize,
-        _ => {
-            let msg = JsString::new(
-                ctx,
-                "precision must be an integer in range between 1 and 100",
-            );
-            return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
-        }
-    };
-
-    let precision_i32 = precision as i32;
+    if precision_var.is_undefined() {
+        return Ok(JsValue::new(JsString::new(
+            ctx,
+            number_to_js_string(this_num),
+        )));
+    }
+    let precision = precision_var.to_interger(ctx)?;
+    if !this_num.is_finite() {
+        return Ok(JsValue::new(JsString::new(
+            ctx,
+            number_to_js_string(this_num),
+        )));
+    }
 
-    // 7
-    let mut prefix = String::new(); // spec: 's'
-    let mut suffix: String; // spec: 'm'
-    let exponent: i32; // spec: 'e'
+    if !(1.0..=100.0).contains(&precision) {
+        let msg = JsString::new(
+            ctx,
+            "precision must be an integer in range between 1 and 100",
+        );
+        return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
+    }
+    let precision = precision as usize;
 
-    // 8
+    let mut prefix = String::new();
     if this_num < 0.0 {
         prefix.push('-');
         this_num = -this_num;
     }
 
-    // 9
-    if this_num == 0.0 {
-        suffix = "0".repeat(precision);
-        exponent = 0;
-    // 10
+    let (mut digits, exponent) = to_precision_digits(this_num, precision);
+    if exponent < -6 || exponent >= precision as i32 {
+        return Ok(JsValue::new(JsString::new(
+            ctx,
+            prefix + &format_exponential(digits, exponent),
+        )));
+    }
+    if exponent + 1 == precision as i32 {
+        return Ok(JsValue::new(JsString::new(ctx, prefix + &digits)));
+    }
+    if exponent >= 0 {
+        digits.insert(exponent as usize + 1, '.');
     } else {
-        // Due to f64 limitations, this pactx differs a bit from the spec,
-        // but has the same effect. It manipulates the string constructed
-        // by ryu-js: digits with an optional dot between two of them.
-
-        let mut buffer = ryu_js::Buffer::new();
-        suffix = buffer.format(this_num).to_string();
-
-        // a: getting an exponent
-        exponent = flt_str_to_exp(&suffix);
-        // b: getting relevant digits only
-        if exponent < 0 {
-            suffix = suffix.split_off((1 - exponent) as usize);
-        } else if let Some(n) = suffix.find('.') {
-            suffix.remove(n);
-        }
-        // impl: having exactly `precision` digits in `suffix`
-        suffix = round_to_precision(&mut suffix, precision);
-
-        // c: switching to scientific notation
-        let great_exp = exponent >= precision_i32;
-        if exponent < -6 || great_exp {
-            // ii
-            if precision > 1 {
-                suffix.insert(1, '.');
-            }
-            // vi
-            suffix.push('e');
-            // iii
-            if great_exp {
-                suffix.push('+');
-            }
-            // iv, v
-            suffix.push_str(&exponent.to_string());
+        prefix.push_str("0.");
+        prefix.push_str(&"0".repeat(-(exponent + 1) as usize));
+    }
+    Ok(JsValue::new(JsString::new(ctx, prefix + &digits)))
+}
 
-            return Ok(JsValue::new(JsString::new(ctx, prefix + &suffix)));
-        }
-    } // 11
-    let e_inc = exponent + 1;
-    if e_inc == precision_i32 {
-        return Ok(JsValue::from(JsString::new(ctx, prefix + &suffix)));
+pub fn number_to_exponential(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let mut this_num = this_number_val(ctx, args.this)?;
+    let fraction_digits = args.at(0).to_interger(ctx)?;
+    if !this_num.is_finite() {
+        return Ok(JsValue::new(JsString::new(
+            ctx,
+            number_to_js_string(this_num),
+        )));
+    }
+    if !(0.0..=100.0).contains(&fraction_digits) {
+        let msg = JsString::new(ctx, "toExponential() argument must be between 0 and 100");
+        return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
     }
 
-    // 12
-    if exponent >= 0 {
-        suffix.insert(e_inc as usize, '.');
-    // 13
-    } else {
-        prefix.push('0');
-        prefix.push('.');
-        prefix.push_str(&"0".repeat(-e_inc as usize));
+    let mut prefix = String::new();
+    if this_num < 0.0 {
+        prefix.push('-');
+        this_num = -this_num;
     }
 
-    // 14
-    Ok(JsValue::new(JsString::new(ctx, prefix + &suffix)))
+    let (digits, exponent) = if args.at(0).is_undefined() {
+        // as many digits as necessary to uniquely represent the value
+        let shortest = format!("{:e}", this_num);
+        let (mantissa, exponent) = shortest.split_at(shortest.find('e').unwrap());
+        (
+            mantissa.replace('.', ""),
+            exponent[1..].parse::<i32>().unwrap(),
+        )
+    } else {
+        to_precision_digits(this_num, fraction_digits as usize + 1)
+    };
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        prefix + &format_exponential(digits, exponent),
+    )))
 }
 
 pub fn number_to_fixed(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    let fixed_var = args.at(0);
     let mut this_num = this_number_val(ctx, args.this)?;
+    let fixed = args.at(0).to_interger(ctx)?;
 
-    let mut fixed = 0;
-    if !fixed_var.is_undefined() {
-        fixed = fixed_var.to_int32(ctx)?;
-    }
-
-    if !(0..=20).contains(&fixed) {
-        let msg = JsString::new(ctx, "toFixed() digits argument must be between 0 and 20");
+    if !(0.0..=100.0).contains(&fixed) {
+        let msg = JsString::new(ctx, "toFixed() digits argument must be between 0 and 100");
         return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
     }
+    if !this_num.is_finite() || this_num.abs() >= 1e21 {
+        return Ok(JsValue::new(JsString::new(
+            ctx,
+            number_to_js_string(this_num),
+        )));
+    }
 
-    let fixed = fixed as usize;
+    Ok(JsValue::new(JsString::new(ctx, {
+        let mut prefix = String::new();
+        if this_num < 0.0 {
+            this_num = -this_num;
+            prefix.push('-');
+        }
+        prefix + &to_fixed_string(this_num, fixed as usize)
+    })))
+}
 
-    let mut buffer = ryu_js::Buffer::new();
-    let mut string = buffer.format(this_num).to_string();
-    // after .
-    string = round_to_fixed(&mut string, fixed);
-    Ok(JsValue::new(JsString::new(ctx, string)))
+/// `x` with exactly `fixed` fractional digits, `x` must be finite, non-negative and below 1e21.
+fn to_fixed_string(x: f64, fixed: usize) -> String {
+    let exact = format!("{:.1100}", x);
+    let point = exact.find('.').unwrap();
+    let mut digits = exact.bytes().filter(|c| *c != b'.').collect::<Vec<u8>>();
+    let mut integer_digits = point;
+    if round_digits_half_up(&mut digits, point + fixed) {
+        integer_digits += 1;
+    }
+    let mut result = String::from_utf8(digits).unwrap();
+    if fixed != 0 {
+        result.insert(integer_digits, '.');
+    }
+    result
 }
 
 pub fn number_to_string(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    let obj = args.this;
-    let num;
-    if !obj.is_number() {
-        if obj.is_jsobject() && obj.get_jsobject().is_class(JsNumber::class()) {
-            num = JsNumber::to_ref(&obj.get_jsobject()).get();
-        } else {
-            return Err(JsValue::new(ctx.new_type_error(
-                "Number.prototype.toString is not generic function",
-            )));
-        }
-    } else {
-        num = obj.get_number();
-    }
+    let num = this_number_val(ctx, args.this)?;
 
-    if args.size() != 0 {
-        let first = args.at(0);
-        let radix;
-        if first.is_undefined() {
-            radix = 10;
-        } else {
-            radix = first.to_int32(ctx)?;
-        }
-        if radix == 10 {
-            return Ok(JsValue::new(JsString::new(ctx, num.to_string())));
-        }
-        if (2..=36).contains(&radix) {
-            if radix != 10 {
-                if num.is_nan() {
-                    return Ok(JsValue::new(JsString::new(ctx, "NaN")));
-                } else if num.is_infinite() {
-                    if num.is_sign_positive() {
-                        return Ok(JsValue::new(JsString::new(ctx, "Infinity")));
-                    } else {
-                        return Ok(JsValue::new(JsString::new(ctx, "-Infinity")));
-                    }
-                }
-            }
-            Ok(JsValue::new(JsString::new(
-                ctx,
-                to_native_string_radix(num, radix as _),
-            )))
-        } else {
-            let msg = JsString::new(ctx, "Illegal radix");
-            return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
-        }
+    let radix = if args.at(0).is_undefined() {
+        10.0
     } else {
-        return Ok(JsValue::new(JsString::new(ctx, num.to_string())));
+        args.at(0).to_interger(ctx)?
+    };
+    if !(2.0..=36.0).contains(&radix) {
+        let msg = JsString::new(ctx, "toString() radix must be between 2 and 36");
+        return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
+    }
+    let radix = radix as u8;
+    if radix == 10 || !num.is_finite() {
+        return Ok(JsValue::new(JsString::new(ctx, number_to_js_string(num))));
     }
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        to_native_string_radix(num, radix),
+    )))
 }
 
 // https://chromium.googlesource.com/v8/v8/+/refs/heads/master/src/numbers/conversions.cc#1230
@@ -268,7 +319,10 @@ pub(crate) fn to_native_string_radix(mut value: f64, radix: u8) -> String {
     assert!(radix >= 2);
     assert!(radix <= 36);
     assert!(value.is_finite());
-    // assectx_ne!(0.0, value);
+    if value == 0.0 {
+        // also covers -0
+        return "0".to_string();
+    }
 
     // Character array used for conversion.
     // Temporary buffer for the result. We start with the decimal point in the
@@ -322,7 +376,7 @@ pub(crate) fn to_native_string_radix(mut value: f64, radix: u8) -> String {
                     } else {
                         let c: u8 = frac_buf[fraction_cursor];
                         // Reconstruct digit.
-                        let digit_0 = (c as char).to_digit(10).unwrap();
+                        let digit_0 = (c as char).to_digit(radix as u32).unwrap();
                         if digit_0 + 1 >= radix as u32 {
                             continue;
                         }
@@ -383,136 +437,6 @@ fn next_after(x: f64, y: f64) -> f64 {
         f64::from_bits(x.to_bits() - 1)
     }
 }
-/// flt_str_to_exp - used in to_precision
-///
-/// This function traverses a string representing a number,
-/// returning the floored log10 of this number.
-///
-fn flt_str_to_exp(flt: &str) -> i32 {
-    let mut non_zero_encountered = false;
-    let mut dot_encountered = false;
-    for (i, c) in flt.chars().enumerate() {
-        if c == '.' {
-            if non_zero_encountered {
-                return (i as i32) - 1;
-            }
-            dot_encountered = true;
-        } else if c != '0' {
-            if dot_encountered {
-                return 1 - (i as i32);
-            }
-            non_zero_encountered = true;
-        }
-    }
-    (flt.len() as i32) - 1
-}
-
-/// round_to_precision - used in to_precision
-///
-/// This procedure has two roles:
-/// - If there are enough or more than enough digits in the
-///   string to show the required precision, the number
-///   represented by these digits is rounded using string
-///   manipulation.
-/// - Else, zeroes are appended to the string.
-///
-/// When this procedure returns, `digits` is exactly `precision` long.
-///
-pub fn round_to_precision(digits: &mut String, precision: usize) -> String {
-    if digits.len() > precision {
-        let to_round = digits.split_off(precision);
-        let mut digit_bytes = digits.clone().into_bytes();
-        let mut stop_index = (digit_bytes.len() - 1) as i32;
-        let mut digit = *digit_bytes.last().unwrap();
-
-        for c in to_round.chars() {
-            match c {
-                c if c < '4' => break,
-                c if c > '4' => {
-                    while digit == b'9' {
-                        digit_bytes[stop_index as usize] = b'0';
-                        stop_index -= 1;
-                        if stop_index == -1 {
-                            break;
-                        }
-                        digit = digit_bytes[stop_index as usize];
-                    }
-                    break;
-                }
-                _ => {}
-            }
-        }
-        if stop_index == -1 {
-            digit_bytes.insert(0, b'1');
-            digit_bytes.pop();
-        } else {
-            digit_bytes[stop_index as usize] += 1;
-        }
-        return String::from_utf8(digit_bytes).unwrap();
-    } else {
-        digits.push_str(&"0".repeat(precision - digits.len()));
-        return digits.to_string();
-    }
-}
-
-pub fn round_to_fixed(string: &mut String, fixed: usize) -> String {
-    if let Some(n) = string.find('.') {
-        if (string.len() - 1 - n) > fixed {
-            let to_round = string.split_off(n + fixed + 1);
-            let mut digit_bytes = string.clone().into_bytes();
-            let mut stop_index = (digit_bytes.len() - 1) as i32;
-            let mut digit = *digit_bytes.last().unwrap();
-
-            for c in to_round.chars() {
-                match c {
-                    c if c < '4' => break,
-                    c if c > '4' => {
-                        loop {
-                            if digit == b'9' {
-                                digit_bytes[stop_index as usize] = b'0';
-                                stop_index -= 1;
-                                if stop_index == -1 {
-                                    break;
-                                }
-                                digit = digit_bytes[stop_index as usize];
-                            } else if digit == b'.' {
-                                stop_index -= 1;
-                                digit = digit_bytes[stop_index as usize];
-                            } else {
-                                break;
-                            }
-                        }
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-            if stop_index == -1 {
-                digit_bytes.insert(0, b'1');
-            } else {
-                digit_bytes[stop_index as usize] += 1;
-            }
-            if fixed == 0 {
-                digit_bytes.pop();
-            }
-            return String::from_utf8(digit_bytes).unwrap();
-        } else {
-            let mut digits = string.split_off(n + 1);
-            digits = round_to_precision(&mut digits, fixed);
-            string.push_str(&digits);
-            return string.to_string();
-        }
-    } else {
-        let mut digits = String::from("");
-        digits = round_to_precision(&mut digits, fixed);
-        if !digits.is_empty() {
-            string.push('.');
-            string.push_str(&digits);
-        }
-        return string.to_string();
-    }
-}
-
 pub fn number_is_safe_integer(
     _ctx: GcPointer<Context>,
     args: &Arguments,
@@ -527,11 +451,44 @@ pub fn number_is_safe_integer(
     }
 }
 
+/// `Number.prototype.toLocaleString` without locale data: `en-US` style with grouped integer
+/// digits and at most three fraction digits.
 pub fn number_to_local_string(
     ctx: GcPointer<Context>,
     args: &Arguments,
 ) -> Result<JsValue, JsValue> {
-    number_to_string(ctx, args)
+    let num = this_number_val(ctx, args.this)?;
+    if num.is_nan() {
+        return Ok(JsValue::new(JsString::new(ctx, "NaN")));
+    }
+    let mut result = String::new();
+    if num.is_sign_negative() {
+        result.push('-');
+    }
+    if num.is_infinite() {
+        result.push('∞');
+        return Ok(JsValue::new(JsString::new(ctx, result)));
+    }
+    let fixed = if num.abs() >= 1e21 {
+        format!("{:.0}", num.abs())
+    } else {
+        to_fixed_string(num.abs(), 3)
+    };
+    let (integer, fraction) = match fixed.find('.') {
+        Some(point) => fixed.split_at(point),
+        None => (fixed.as_str(), ""),
+    };
+    for (i, digit) in integer.chars().enumerate() {
+        if i != 0 && (integer.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction != "." {
+        result.push_str(fraction);
+    }
+    Ok(JsValue::new(JsString::new(ctx, result)))
 }
 
 impl JsNumber {
@@ -555,6 +512,7 @@ impl Builtin for JsNumber {
             number_value_of as _,
             number_is_safe_integer as _,
             number_to_local_string as _,
+            number_to_exponential as _,
         ]
     }
 
@@ -599,6 +557,7 @@ impl Builtin for JsNumber {
         def_native_method!(ctx, constructor, parseInt, global::parse_int, 1)?;
 
         def_native_property!(ctx, prototype, constructor, constructor)?;
+        def_native_method!(ctx, prototype, toExponential, number_to_exponential, 1)?;
         def_native_method!(ctx, prototype, toFixed, number_to_fixed, 1)?;
         def_native_method!(ctx, prototype, toLocaleString, number_to_local_string, 0)?;
         def_native_method!(ctx, prototype, toString, number_to_string, 1)?;
@@ -615,3 +574,30 @@ impl Builtin for JsNumber {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::eval;
+    use crate::Platform;
+
+    #[test]
+    fn test_number_formatting() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let result = eval(
+            ctx,
+            "[
+                (123.456).toExponential(2), (0).toExponential(), (2.5).toPrecision(1),
+                (1.005).toFixed(2), (1e21).toFixed(2), String(1e21), (0.000001234).toPrecision(2),
+                (255.5).toString(16), (-0).toString(2), (1234567.891).toLocaleString()
+            ].join(' ')",
+        );
+        assert_eq!(
+            result.to_string(ctx).unwrap_or_default(),
+            "1.23e+2 0e+0 3 1.00 1e+21 1e+21 0.0000012 ff.8 0 1,234,567.891"
+        );
+    }
+}
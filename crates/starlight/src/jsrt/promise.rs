@@ -3,6 +3,7 @@ use crate::gc::cell::GcPointer;
 use crate::prelude::JsArray;
 use crate::vm::arguments::Arguments;
 use crate::vm::builder::Builtin;
+use crate::vm::capabilities::BuiltinGroup;
 use crate::vm::class::JsClass;
 use crate::vm::context::Context;
 use crate::vm::function::JsNativeFunction;
@@ -254,6 +255,8 @@ pub fn promise_static_any(ctx: GcPointer<Context>, args: &Arguments) -> Result<J
 }
 
 impl Builtin for JsPromise {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::Promise);
+
     fn native_references() -> Vec<usize> {
         vec![
             promise_constructor as _,
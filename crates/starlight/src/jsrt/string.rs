@@ -11,6 +11,7 @@ use crate::{
         context::Context,
         error::{JsRangeError, JsTypeError},
         function::JsNativeFunction,
+        object::JsObject,
         property_descriptor::DataDescriptor,
         string::{JsString, JsStringObject},
         structure::Structure,
@@ -23,6 +24,7 @@ use std::{
     cmp::{max, min},
     intrinsics::unlikely,
 };
+use unicode_normalization::UnicodeNormalization;
 
 use super::regexp::JsRegExp;
 
@@ -573,6 +575,15 @@ impl Builtin for JsStringObject {
             string_to_uppercase as _,
             string_includes as _,
             string_slice as _,
+            string_at as _,
+            string_normalize as _,
+            string_locale_compare as _,
+            string_is_well_formed as _,
+            string_to_well_formed as _,
+            string_replace_all as _,
+            string_iterator_next as _,
+            string_raw as _,
+            string_from_code_point as _,
         ]
     }
     fn init(mut ctx: GcPointer<Context>) -> Result<(), JsValue> {
@@ -609,13 +620,27 @@ impl Builtin for JsStringObject {
         def_native_method!(ctx, proto, slice, string_slice, 1)?;
         def_native_method!(ctx, constructor, ___replace, string_replace, 2)?;
         def_native_method!(ctx, proto, trim, string_trim, 0)?;
-        def_native_method!(ctx, proto, trimStactx, string_trim_start, 0)?;
+        def_native_method!(ctx, proto, trimStart, string_trim_start, 0)?;
         def_native_method!(ctx, proto, trimEnd, string_trim_end, 0)?;
         def_native_method!(ctx, proto, trimLeft, string_trim_start, 0)?;
         def_native_method!(ctx, proto, trimRight, string_trim_end, 0)?;
-        def_native_method!(ctx, proto, padStactx, string_pad_start, 2)?;
+        def_native_method!(ctx, proto, padStart, string_pad_start, 2)?;
         def_native_method!(ctx, proto, padEnd, string_pad_end, 2)?;
-        def_native_method!(ctx, proto, repeat, string_repeat, 1)?;
+        def_native_method!(ctx, proto, at, string_at, 1)?;
+        def_native_method!(ctx, proto, normalize, string_normalize, 0)?;
+        def_native_method!(ctx, proto, localeCompare, string_locale_compare, 1)?;
+        def_native_method!(ctx, proto, isWellFormed, string_is_well_formed, 0)?;
+        def_native_method!(ctx, proto, toWellFormed, string_to_well_formed, 0)?;
+        def_native_method!(ctx, constructor, ___replaceAll, string_replace_all, 2)?;
+        def_native_method!(
+            ctx,
+            constructor,
+            ___stringIteratorNext,
+            string_iterator_next,
+            0
+        )?;
+        def_native_method!(ctx, constructor, raw, string_raw, 1)?;
+        def_native_method!(ctx, constructor, fromCodePoint, string_from_code_point, 1)?;
 
         ctx.global_data.string_prototype = Some(proto);
         ctx.global_object().put(ctx, "String", constructor, false)?;
@@ -646,7 +671,6 @@ pub(crate) fn code_point_at(string: &str, position: i32) -> Option<(u32, u8, boo
 
 /// Helper function to check if a `char` is trimmable.
 #[inline]
-#[allow(dead_code)]
 pub(crate) fn is_trimmable_whitespace(c: char) -> bool {
     // The rust implementation of `trim` does not regard the same characters whitespace as ecma standard does
     //
@@ -692,21 +716,30 @@ fn get_regex_string(_ctx: GcPointer<Context>, val: JsValue) -> Result<(String, S
 
 pub fn string_trim(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let prim = args.this.to_string(ctx)?;
-    Ok(JsValue::new(JsString::new(ctx, prim.trim())))
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        prim.trim_matches(is_trimmable_whitespace),
+    )))
 }
 
 pub fn string_trim_start(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let prim = args.this.to_string(ctx)?;
-    Ok(JsValue::new(JsString::new(ctx, prim.trim_start())))
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        prim.trim_start_matches(is_trimmable_whitespace),
+    )))
 }
 
 pub fn string_trim_end(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
     let prim = args.this.to_string(ctx)?;
-    Ok(JsValue::new(JsString::new(ctx, prim.trim_end())))
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        prim.trim_end_matches(is_trimmable_whitespace),
+    )))
 }
 
 pub enum Alignment {
-    Stactx,
+    Start,
     End,
 }
 
@@ -743,7 +776,7 @@ pub fn string_pad(
             }
         }
         match alignment {
-            Alignment::Stactx => {
+            Alignment::Start => {
                 to_pad_str.push_str(&string);
                 Ok(JsValue::new(JsString::new(ctx, to_pad_str)))
             }
@@ -760,5 +793,282 @@ pub fn string_pad_end(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsVal
 }
 
 pub fn string_pad_start(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-    string_pad(ctx, args, Alignment::Stactx)
+    string_pad(ctx, args, Alignment::Start)
+}
+
+pub fn string_at(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    args.this.check_object_coercible(ctx)?;
+    let primitive_val = args.this.to_string(ctx)?;
+    let units = primitive_val.encode_utf16().collect::<Vec<u16>>();
+    let relative_index = args.at(0).to_interger(ctx)?;
+    let k = if relative_index >= 0.0 {
+        relative_index
+    } else {
+        units.len() as f64 + relative_index
+    };
+    if k < 0.0 || k >= units.len() as f64 {
+        return Ok(JsValue::encode_undefined_value());
+    }
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        String::from_utf16_lossy(&units[k as usize..k as usize + 1]),
+    )))
+}
+
+pub fn string_normalize(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    args.this.check_object_coercible(ctx)?;
+    let primitive_val = args.this.to_string(ctx)?;
+    let form = if args.at(0).is_undefined() {
+        "NFC".to_string()
+    } else {
+        args.at(0).to_string(ctx)?
+    };
+    let normalized: String = match form.as_str() {
+        "NFC" => primitive_val.nfc().collect(),
+        "NFD" => primitive_val.nfd().collect(),
+        "NFKC" => primitive_val.nfkc().collect(),
+        "NFKD" => primitive_val.nfkd().collect(),
+        _ => {
+            return Err(JsValue::new(ctx.new_range_error(format!(
+                "String.prototype.normalize: form must be one of NFC, NFD, NFKC or NFKD, got '{}'",
+                form
+            ))))
+        }
+    };
+    Ok(JsValue::new(JsString::new(ctx, normalized)))
+}
+
+/// Starlight has no locale data so `localeCompare` ignores `locales` and `options` and compares
+/// canonically equivalent strings as equal, ordering the rest by code point.
+pub fn string_locale_compare(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    args.this.check_object_coercible(ctx)?;
+    let this = args.this.to_string(ctx)?;
+    let that = args.at(0).to_string(ctx)?;
+    let ordering = this.nfc().cmp(that.nfc());
+    Ok(JsValue::new(ordering as i32))
+}
+
+/// Strings are stored as UTF-8 so lone surrogates are replaced when a string is created and
+/// every string is well formed.
+pub fn string_is_well_formed(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    args.this.check_object_coercible(ctx)?;
+    args.this.to_string(ctx)?;
+    Ok(JsValue::new(true))
+}
+
+pub fn string_to_well_formed(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    args.this.check_object_coercible(ctx)?;
+    let primitive_val = args.this.to_string(ctx)?;
+    Ok(JsValue::new(JsString::new(ctx, primitive_val)))
+}
+
+/// GetSubstitution for matches without capture groups. Positions are in UTF-16 code units.
+fn get_substitution(
+    matched: &[u16],
+    string: &[u16],
+    position: usize,
+    replacement: &[u16],
+) -> Vec<u16> {
+    let mut result = Vec::with_capacity(replacement.len());
+    let tail_pos = min(position + matched.len(), string.len());
+    let mut i = 0;
+    while i < replacement.len() {
+        let unit = replacement[i];
+        if unit == b'$' as u16 && i + 1 < replacement.len() {
+            let next = replacement[i + 1];
+            if next == b'$' as u16 {
+                result.push(b'$' as u16);
+            } else if next == b'&' as u16 {
+                result.extend_from_slice(matched);
+            } else if next == b'`' as u16 {
+                result.extend_from_slice(&string[..position]);
+            } else if next == b'\'' as u16 {
+                result.extend_from_slice(&string[tail_pos..]);
+            } else {
+                result.push(unit);
+                i += 1;
+                continue;
+            }
+            i += 2;
+            continue;
+        }
+        result.push(unit);
+        i += 1;
+    }
+    result
+}
+
+/// String path of `String.prototype.replaceAll`, RegExp searches are handled in
+/// `builtins/StringPrototype.js`.
+pub fn string_replace_all(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let string = args.this.to_string(ctx)?;
+    let search_string = args.at(0).to_string(ctx)?;
+    let replace_value = args.at(1);
+    let functional_replace = replace_value.is_callable();
+    let replace_string = if functional_replace {
+        String::new()
+    } else {
+        replace_value.to_string(ctx)?
+    };
+
+    let string = string.encode_utf16().collect::<Vec<u16>>();
+    let search = search_string.encode_utf16().collect::<Vec<u16>>();
+    let replacement = replace_string.encode_utf16().collect::<Vec<u16>>();
+    let advance_by = max(1, search.len());
+
+    let mut match_positions = vec![];
+    let mut position = 0;
+    while position + search.len() <= string.len() {
+        if string[position..position + search.len()] == search[..] {
+            match_positions.push(position);
+            position += advance_by;
+        } else {
+            position += 1;
+        }
+    }
+
+    let mut end_of_last_match = 0;
+    let mut result = Vec::with_capacity(string.len());
+    for p in match_positions {
+        result.extend_from_slice(&string[end_of_last_match..p]);
+        if functional_replace {
+            let matched = JsValue::new(JsString::new(ctx, &search_string));
+            let whole = JsValue::new(JsString::new(ctx, String::from_utf16_lossy(&string)));
+            let mut argv = [matched, JsValue::new(p as u32), whole];
+            let mut fargs = Arguments::new(JsValue::encode_undefined_value(), &mut argv);
+            let replaced = replace_value
+                .get_jsobject()
+                .as_function_mut()
+                .call(ctx, &mut fargs, JsValue::encode_undefined_value())?
+                .to_string(ctx)?;
+            result.extend(replaced.encode_utf16());
+        } else {
+            result.extend(get_substitution(&search, &string, p, &replacement));
+        }
+        end_of_last_match = p + search.len();
+    }
+    if end_of_last_match < string.len() {
+        result.extend_from_slice(&string[end_of_last_match..]);
+    }
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        String::from_utf16_lossy(&result),
+    )))
+}
+
+/// `%StringIteratorPrototype%.next`. The iterated position is kept as a byte offset into the
+/// UTF-8 representation so every step yields a whole code point.
+pub fn string_iterator_next(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let mut this = args.this.to_object(ctx)?;
+    let index_key = "stringIteratorFieldIndex".intern();
+    let string_key = "stringIteratorIteratedString".intern();
+    let position = this.get(ctx, index_key)?.to_number(ctx)?;
+    let mut result = JsObject::new_empty(ctx);
+    let mut value = JsValue::encode_undefined_value();
+    let mut done = true;
+    if position >= 0.0 {
+        let string = this.get(ctx, string_key)?.to_string(ctx)?;
+        match string
+            .get(position as usize..)
+            .and_then(|rest| rest.chars().next())
+        {
+            Some(ch) => {
+                done = false;
+                value = JsValue::new(JsString::new(ctx, ch.to_string()));
+                this.put(
+                    ctx,
+                    index_key,
+                    JsValue::new((position as usize + ch.len_utf8()) as u32),
+                    false,
+                )?;
+            }
+            None => {
+                this.put(ctx, index_key, JsValue::new(-1), false)?;
+            }
+        }
+    }
+    result.put(ctx, "value".intern(), value, false)?;
+    result.put(ctx, "done".intern(), JsValue::new(done), false)?;
+    Ok(JsValue::new(result))
+}
+
+pub fn string_raw(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let cooked = args.at(0).to_object(ctx)?;
+    let raw = cooked.get(ctx, "raw".intern())?.to_object(ctx)?;
+    let literal_count = raw.get(ctx, "length".intern())?.to_length(ctx)?;
+    let mut result = String::new();
+    for index in 0..literal_count {
+        result.push_str(&raw.get(ctx, Symbol::Index(index))?.to_string(ctx)?);
+        if index + 1 == literal_count {
+            break;
+        }
+        let substitution_index = index as usize + 1;
+        if substitution_index < args.size() {
+            result.push_str(&args.at(substitution_index).to_string(ctx)?);
+        }
+    }
+    Ok(JsValue::new(JsString::new(ctx, result)))
+}
+
+pub fn string_from_code_point(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let mut units = Vec::with_capacity(args.size());
+    for i in 0..args.size() {
+        let next = args.at(i).to_number(ctx)?;
+        if next.trunc() != next || !(0.0..=1114111.0).contains(&next) {
+            return Err(JsValue::new(ctx.new_range_error(format!(
+                "String.fromCodePoint: invalid code point {}",
+                next
+            ))));
+        }
+        let code_point = next as u32;
+        if code_point > 0xFFFF {
+            let code_point = code_point - 0x10000;
+            units.push((0xD800 + (code_point >> 10)) as u16);
+            units.push((0xDC00 + (code_point & 0x3FF)) as u16);
+        } else {
+            units.push(code_point as u16);
+        }
+    }
+    Ok(JsValue::new(JsString::new(
+        ctx,
+        String::from_utf16_lossy(&units),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::assert_js;
+    use crate::Platform;
+
+    #[test]
+    fn test_string_prototype_additions() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        assert_js(ctx, "'a-b-c'.replaceAll('-', '$&$&') === 'a--b--c'");
+        assert_js(ctx, "'abc'.at(-1) === 'c'");
+        assert_js(ctx, "'  x '.trimStart() === 'x '");
+        assert_js(ctx, "'x'.padStart(3, 'ab') === 'abx'");
+        assert_js(ctx, "'\\u0041\\u030A'.normalize('NFC') === '\\u00C5'");
+        assert_js(ctx, "'a'.localeCompare('b') === -1");
+        assert_js(ctx, "String.raw({ raw: ['a', 'c'] }, 'b') === 'abc'");
+        assert_js(
+            ctx,
+            "String.fromCodePoint(0x1F600).length === [...String.fromCodePoint(0x1F600, 65)][0].length",
+        );
+    }
 }
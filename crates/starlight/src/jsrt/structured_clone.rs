@@ -0,0 +1,501 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Implementation of the HTML structured clone algorithm. Values are [Serialized] and then
+//! recreated, in the same VM by `structuredClone` or in another VM by workers.
+use std::{collections::HashMap, mem::ManuallyDrop, sync::Arc};
+
+use crate::{
+    jsrt::{
+        date::JsDate,
+        map::{map_storage, new_collection},
+        regexp::{regexp_constructor, JsRegExp},
+    },
+    prelude::*,
+    vm::{
+        array_buffer::{JsArrayBuffer, SharedDataBlock},
+        context::Context,
+        object::TypedJsObject,
+    },
+};
+
+/// Value serialized with the structured clone algorithm so that it can be recreated in any
+/// [VirtualMachine](crate::vm::VirtualMachine), possibly on another thread. Cycles and shared
+/// references are preserved. `ArrayBuffer`s of the transfer list are moved instead of copied and
+/// `SharedArrayBuffer`s keep sharing their data block.
+pub struct Serialized {
+    root: SerializedValue,
+    objects: Vec<SerializedObject>,
+    transferred: Vec<TransferredBlock>,
+}
+
+enum SerializedValue {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    /// Index into `Serialized::objects`.
+    Object(usize),
+}
+
+enum SerializedObject {
+    Array(u32, Vec<(u32, SerializedValue)>),
+    /// Property keys are interned process-wide, so they are valid in every VM.
+    Object(Vec<(Symbol, SerializedValue)>),
+    Map(Vec<(SerializedValue, SerializedValue)>),
+    Set(Vec<SerializedValue>),
+    Date(JsDate),
+    RegExp(String, String),
+    ArrayBuffer(Vec<u8>),
+    /// Index into `Serialized::transferred`.
+    Transferred(usize),
+    SharedArrayBuffer(Arc<SharedDataBlock>),
+    StringObject(String),
+}
+
+/// Data block detached from a transferred `ArrayBuffer`. It is owned by the message until a
+/// buffer of the receiving VM adopts it.
+struct TransferredBlock {
+    data: *mut u8,
+    size: usize,
+    capacity: usize,
+    resizable: bool,
+}
+
+// the buffer the block was taken from is detached, nothing else refers to the block
+unsafe impl Send for TransferredBlock {}
+
+impl Drop for TransferredBlock {
+    fn drop(&mut self) {
+        if !self.data.is_null() {
+            unsafe {
+                libc::free(self.data.cast());
+            }
+        }
+    }
+}
+
+impl Serialized {
+    /// Serialize `value`. The `ArrayBuffer`s in `transfer` are detached once the whole value was
+    /// serialized, nothing is detached if serialization fails.
+    pub fn new(
+        ctx: GcPointer<Context>,
+        value: JsValue,
+        transfer: &[JsValue],
+    ) -> Result<Self, JsValue> {
+        let mut transfers = HashMap::new();
+        for (index, buffer) in transfer.iter().enumerate() {
+            if !buffer.is_jsobject() || !buffer.get_jsobject().is_class(JsArrayBuffer::class()) {
+                return Err(data_clone_error(
+                    ctx,
+                    "only ArrayBuffers can be transferred",
+                ));
+            }
+            let buffer = buffer.get_jsobject();
+            let typed = TypedJsObject::<JsArrayBuffer>::new(buffer);
+            if typed.is_shared() {
+                return Err(data_clone_error(
+                    ctx,
+                    "SharedArrayBuffer cannot be transferred",
+                ));
+            }
+            if !typed.attached() {
+                return Err(data_clone_error(
+                    ctx,
+                    "detached ArrayBuffer cannot be transferred",
+                ));
+            }
+            if transfers
+                .insert(&*buffer as *const JsObject as usize, index)
+                .is_some()
+            {
+                return Err(data_clone_error(
+                    ctx,
+                    "ArrayBuffer is in the transfer list twice",
+                ));
+            }
+        }
+
+        let mut serializer = Serializer {
+            memory: HashMap::new(),
+            objects: vec![],
+            transfers,
+        };
+        let root = serializer.serialize_value(ctx, value)?;
+        let transferred = transfer
+            .iter()
+            .map(|buffer| {
+                let mut buffer = TypedJsObject::<JsArrayBuffer>::new(buffer.get_jsobject());
+                let resizable = buffer.is_resizable();
+                let (data, size, capacity) = buffer.take_data_block();
+                TransferredBlock {
+                    data,
+                    size,
+                    capacity,
+                    resizable,
+                }
+            })
+            .collect();
+        Ok(Self {
+            root,
+            objects: serializer.objects,
+            transferred,
+        })
+    }
+
+    /// Recreate the value in `ctx`. Transferred data blocks are adopted by the new buffers.
+    pub fn deserialize(mut self, ctx: GcPointer<Context>) -> Result<JsValue, JsValue> {
+        // objects are created before their contents so that cycles can be restored, the array
+        // keeps them alive until they are reachable from the result
+        letroot!(
+            objects = stack,
+            JsArray::new(ctx, self.objects.len() as u32)
+        );
+        for (index, object) in self.objects.iter().enumerate() {
+            let created = match object {
+                SerializedObject::Array(length, _) => JsArray::new(ctx, *length),
+                SerializedObject::Object(_) => JsObject::new_empty(ctx),
+                SerializedObject::Map(_) => {
+                    let structure = ctx.global_data().map_structure.unwrap();
+                    new_collection(ctx, structure, map_data())?.0
+                }
+                SerializedObject::Set(_) => {
+                    let structure = ctx.global_data().set_structure.unwrap();
+                    new_collection(ctx, structure, set_data())?.0
+                }
+                SerializedObject::Date(value) => {
+                    let structure = ctx.global_data().date_structure.unwrap();
+                    let date = JsObject::new(ctx, &structure, JsDate::class(), ObjectTag::Ordinary);
+                    *date.data::<JsDate>() = ManuallyDrop::new(*value);
+                    date
+                }
+                SerializedObject::RegExp(source, flags) => {
+                    let source = JsString::new(ctx, source);
+                    let flags = JsString::new(ctx, flags);
+                    let mut argv = [JsValue::new(source), JsValue::new(flags)];
+                    let args = Arguments::new(JsValue::encode_undefined_value(), &mut argv);
+                    regexp_constructor(ctx, &args)?.get_jsobject()
+                }
+                SerializedObject::ArrayBuffer(bytes) => {
+                    let buffer = JsArrayBuffer::new(ctx);
+                    let mut target = TypedJsObject::<JsArrayBuffer>::new(buffer);
+                    target.create_data_block(ctx, bytes.len(), false)?;
+                    target.data_mut().copy_from_slice(bytes);
+                    buffer
+                }
+                SerializedObject::Transferred(block) => {
+                    let block = &mut self.transferred[*block];
+                    let buffer = JsArrayBuffer::new(ctx);
+                    let mut target = TypedJsObject::<JsArrayBuffer>::new(buffer);
+                    let data = std::mem::replace(&mut block.data, std::ptr::null_mut());
+                    unsafe {
+                        target.adopt_data_block(
+                            ctx,
+                            data,
+                            block.size,
+                            block.capacity,
+                            block.resizable,
+                        );
+                    }
+                    buffer
+                }
+                SerializedObject::SharedArrayBuffer(block) => {
+                    JsArrayBuffer::new_shared(ctx, block.clone())
+                }
+                SerializedObject::StringObject(value) => {
+                    let value = JsString::new(ctx, value);
+                    JsStringObject::new(ctx, value)
+                }
+            };
+            objects.put(
+                ctx,
+                Symbol::Index(index as u32),
+                JsValue::new(created),
+                true,
+            )?;
+        }
+
+        for (index, object) in self.objects.iter().enumerate() {
+            let mut target = objects
+                .get(ctx, Symbol::Index(index as u32))?
+                .get_jsobject();
+            match object {
+                SerializedObject::Array(_, elements) => {
+                    for (i, value) in elements {
+                        let value = deserialize_value(ctx, objects, value)?;
+                        target.put(ctx, Symbol::Index(*i), value, true)?;
+                    }
+                }
+                SerializedObject::Object(properties) => {
+                    for (name, value) in properties {
+                        let value = deserialize_value(ctx, objects, value)?;
+                        target.put(ctx, *name, value, true)?;
+                    }
+                }
+                // the storage is reachable from `objects`, so the GC sees the inserted entries
+                SerializedObject::Map(entries) => {
+                    if let Some(mut storage) = map_storage(ctx, JsValue::new(target), map_data()) {
+                        for (key, value) in entries {
+                            let key = deserialize_value(ctx, objects, key)?;
+                            let value = deserialize_value(ctx, objects, value)?;
+                            storage.insert(key, value);
+                        }
+                    }
+                }
+                SerializedObject::Set(values) => {
+                    if let Some(mut storage) = map_storage(ctx, JsValue::new(target), set_data()) {
+                        for value in values {
+                            let value = deserialize_value(ctx, objects, value)?;
+                            storage.insert(value, value);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        deserialize_value(ctx, objects, &self.root)
+    }
+}
+
+fn deserialize_value(
+    ctx: GcPointer<Context>,
+    mut objects: GcPointer<JsObject>,
+    value: &SerializedValue,
+) -> Result<JsValue, JsValue> {
+    Ok(match value {
+        SerializedValue::Undefined => JsValue::UNDEFINED,
+        SerializedValue::Null => JsValue::encode_null_value(),
+        SerializedValue::Boolean(value) => JsValue::new(*value),
+        SerializedValue::Number(value) => JsValue::new(*value),
+        SerializedValue::String(value) => JsValue::new(JsString::new(ctx, value)),
+        SerializedValue::Object(index) => objects.get(ctx, Symbol::Index(*index as u32))?,
+    })
+}
+
+struct Serializer {
+    /// Indices of the objects that were already visited.
+    memory: HashMap<usize, usize>,
+    objects: Vec<SerializedObject>,
+    /// Indices of the buffers in the transfer list.
+    transfers: HashMap<usize, usize>,
+}
+
+impl Serializer {
+    fn serialize_value(
+        &mut self,
+        ctx: GcPointer<Context>,
+        value: JsValue,
+    ) -> Result<SerializedValue, JsValue> {
+        Ok(if value.is_undefined() {
+            SerializedValue::Undefined
+        } else if value.is_null() {
+            SerializedValue::Null
+        } else if value.is_bool() {
+            SerializedValue::Boolean(value.get_bool())
+        } else if value.is_number() {
+            SerializedValue::Number(value.get_number())
+        } else if value.is_jsstring() {
+            SerializedValue::String(value.get_jsstring().as_str().to_owned())
+        } else if value.is_jsobject() {
+            SerializedValue::Object(self.serialize_object(ctx, value.get_jsobject())?)
+        } else if value.is_symbol() {
+            return Err(data_clone_error(ctx, "Symbol values cannot be cloned"));
+        } else {
+            return Err(data_clone_error(ctx, "value cannot be cloned"));
+        })
+    }
+
+    fn serialize_object(
+        &mut self,
+        ctx: GcPointer<Context>,
+        mut object: GcPointer<JsObject>,
+    ) -> Result<usize, JsValue> {
+        let key = &*object as *const JsObject as usize;
+        if let Some(index) = self.memory.get(&key) {
+            return Ok(*index);
+        }
+
+        if object.is_callable() {
+            return Err(data_clone_error(ctx, "functions cannot be cloned"));
+        }
+
+        let index = self.objects.len();
+        self.memory.insert(key, index);
+        // placeholder until the contents are serialized, they may refer back to this object
+        self.objects.push(SerializedObject::Object(vec![]));
+
+        let serialized = if object.is_class(JsArray::class()) {
+            let length = object.indexed.length();
+            let mut elements = vec![];
+            for i in 0..length {
+                if !object.has_own_property(ctx, Symbol::Index(i)) {
+                    continue;
+                }
+                let value = object.get(ctx, Symbol::Index(i))?;
+                elements.push((i, self.serialize_value(ctx, value)?));
+            }
+            SerializedObject::Array(length, elements)
+        } else if object.is_class(JsDate::class()) {
+            SerializedObject::Date(**object.data::<JsDate>())
+        } else if object.is_class(JsRegExp::class()) {
+            let regexp = object.data::<JsRegExp>();
+            SerializedObject::RegExp(
+                regexp.original_source.to_string(),
+                regexp.original_flags.to_string(),
+            )
+        } else if object.is_class(JsArrayBuffer::class()) {
+            let buffer = TypedJsObject::<JsArrayBuffer>::new(object);
+            if let Some(index) = self.transfers.get(&key) {
+                SerializedObject::Transferred(*index)
+            } else if !buffer.attached() {
+                return Err(data_clone_error(
+                    ctx,
+                    "detached ArrayBuffer cannot be cloned",
+                ));
+            } else if let Some(block) = buffer.shared_data_block() {
+                SerializedObject::SharedArrayBuffer(block)
+            } else {
+                SerializedObject::ArrayBuffer(buffer.data().to_vec())
+            }
+        } else if object.is_class(JsStringObject::class()) {
+            SerializedObject::StringObject(object.as_string_object().value.as_str().to_owned())
+        } else if !object.is_class(JsObject::class()) {
+            return Err(data_clone_error(
+                ctx,
+                format!("{} objects cannot be cloned", object.class.name),
+            ));
+        } else if let Some(storage) = map_storage(ctx, JsValue::new(object), map_data()) {
+            // entries are read slot by slot, serializing them can run getters that change the map
+            let mut entries = vec![];
+            let mut slot = 0;
+            while slot < storage.slots() {
+                if let Some((key, value)) = storage.entry_at(slot) {
+                    let key = self.serialize_value(ctx, key)?;
+                    let value = self.serialize_value(ctx, value)?;
+                    entries.push((key, value));
+                }
+                slot += 1;
+            }
+            SerializedObject::Map(entries)
+        } else if let Some(storage) = map_storage(ctx, JsValue::new(object), set_data()) {
+            let mut values = vec![];
+            let mut slot = 0;
+            while slot < storage.slots() {
+                if let Some((value, _)) = storage.entry_at(slot) {
+                    values.push(self.serialize_value(ctx, value)?);
+                }
+                slot += 1;
+            }
+            SerializedObject::Set(values)
+        } else {
+            let mut names = vec![];
+            object.get_own_property_names(
+                ctx,
+                &mut |name, _| names.push(name),
+                EnumerationMode::Default,
+            );
+            let mut properties = vec![];
+            for name in names {
+                if let Symbol::Private(_) = name {
+                    continue;
+                }
+                let value = object.get(ctx, name)?;
+                properties.push((name, self.serialize_value(ctx, value)?));
+            }
+            SerializedObject::Object(properties)
+        };
+        self.objects[index] = serialized;
+        Ok(index)
+    }
+}
+
+fn map_data() -> Symbol {
+    "[[MapData]]".intern().private()
+}
+
+fn set_data() -> Symbol {
+    "[[SetData]]".intern().private()
+}
+
+fn data_clone_error(ctx: GcPointer<Context>, msg: impl AsRef<str>) -> JsValue {
+    JsValue::new(ctx.new_type_error(format!("DataCloneError: {}", msg.as_ref())))
+}
+
+/// `structuredClone(value)`
+pub fn structured_clone(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    Serialized::new(ctx, args.at(0), &[])?.deserialize(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        options::Options,
+        vm::{
+            context::Context,
+            testing::{assert_js, eval},
+        },
+        Platform,
+    };
+
+    #[test]
+    fn test_structured_clone() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        eval(
+            ctx,
+            "var date = new Date(0);
+             var source = { date: date, re: /a+b/gi, list: [1, 'two', { three: 3 }] };
+             source.self = source;
+             source.map = new Map([['key', { n: 1 }], ['source', source]]);
+             source.set = new Set([date, 'x']);
+             var copy = structuredClone(source);",
+        );
+        assert_js(ctx, "copy !== source && copy.self === copy");
+        assert_js(ctx, "copy.map.get('source') === copy");
+        assert_js(
+            ctx,
+            "copy.date instanceof Date && copy.date !== date && copy.date.getTime() === 0",
+        );
+        assert_js(
+            ctx,
+            "copy.re instanceof RegExp && copy.re !== source.re && copy.re.source === 'a+b'",
+        );
+        assert_js(ctx, "copy.re.flags === 'gi'");
+        assert_js(ctx, "copy.list.length === 3 && copy.list[2].three === 3");
+        assert_js(ctx, "copy.map instanceof Map && copy.map.size === 2");
+        assert_js(ctx, "copy.map.get('key').n === 1");
+        assert_js(ctx, "copy.map.get('key') !== source.map.get('key')");
+        assert_js(ctx, "copy.set instanceof Set && copy.set.size === 2");
+        assert_js(ctx, "copy.set.has('x')");
+        assert_js(ctx, "copy.set.has(copy.date) && !copy.set.has(date)");
+    }
+
+    #[test]
+    fn test_structured_clone_collections() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        // enough entries that the GC runs while the copies are filled
+        eval(
+            ctx,
+            "var map = new Map();
+             var set = new Set();
+             for (var i = 0; i < 20000; i++) {
+                 map.set('key' + i, { i: i });
+                 set.add({ i: i });
+             }
+             var copies = structuredClone([map, set]);",
+        );
+        vm.heap().gc();
+        assert_js(ctx, "copies[0] instanceof Map && copies[0].size === 20000");
+        assert_js(ctx, "copies[0].get('key19999').i === 19999");
+        assert_js(ctx, "copies[1] instanceof Set && copies[1].size === 20000");
+        eval(
+            ctx,
+            "var next = 0;
+             var ordered = true;
+             copies[1].forEach(value => ordered = ordered && value.i === next++);",
+        );
+        assert_js(ctx, "ordered && next === 20000");
+    }
+}
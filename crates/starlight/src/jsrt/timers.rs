@@ -0,0 +1,86 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `setTimeout`, `setInterval`, `setImmediate` and their `clear*` counterparts. The callbacks run
+//! on the built-in event loop, see [crate::vm::event_loop].
+use std::time::Duration;
+
+use crate::{
+    gc::cell::GcPointer,
+    vm::{
+        arguments::Arguments, context::Context, event_loop::MIN_TIMER_DELAY, handles::Persistent,
+        value::JsValue,
+    },
+};
+
+/// Longest delay of a timer in milliseconds, the largest signed 32-bit integer as in browsers.
+const MAX_DELAY_MS: f64 = 2147483647.0;
+
+pub fn set_timeout(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    add_timer(ctx, args, "setTimeout", false)
+}
+
+pub fn set_interval(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    add_timer(ctx, args, "setInterval", true)
+}
+
+pub fn set_immediate(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let callback = callback(ctx, args, "setImmediate")?;
+    let extra = root_arguments(ctx, args, 1);
+    let id = ctx.vm.event_loop.add_immediate(ctx, callback, extra);
+    Ok(JsValue::new(id as f64))
+}
+
+/// `clearTimeout`, `clearInterval` and `clearImmediate`.
+pub fn clear_timer(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = args.at(0);
+    if id.is_number() {
+        let id = id.get_number();
+        if (1.0..=u32::MAX as f64).contains(&id) {
+            ctx.vm.event_loop.cancel(id as u32);
+        }
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+fn add_timer(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+    name: &str,
+    repeat: bool,
+) -> Result<JsValue, JsValue> {
+    let callback = callback(ctx, args, name)?;
+    let delay = if args.size() > 1 {
+        args.at(1).to_number(ctx)?
+    } else {
+        0.0
+    };
+    // NaN, negative and too long delays run after the shortest delay like in Node.js
+    let delay = if delay > 0.0 && delay <= MAX_DELAY_MS {
+        Duration::from_secs_f64(delay / 1000.0).max(MIN_TIMER_DELAY)
+    } else {
+        MIN_TIMER_DELAY
+    };
+    let extra = root_arguments(ctx, args, 2);
+    let id = ctx
+        .vm
+        .event_loop
+        .add_timer(ctx, callback, extra, delay, repeat);
+    Ok(JsValue::new(id as f64))
+}
+
+fn callback(ctx: GcPointer<Context>, args: &Arguments, name: &str) -> Result<Persistent, JsValue> {
+    let callback = args.at(0);
+    if !callback.is_callable() {
+        return Err(JsValue::new(
+            ctx.new_type_error(format!("{}: callback is not a function", name)),
+        ));
+    }
+    Ok(ctx.vm().persistent(callback))
+}
+
+fn root_arguments(ctx: GcPointer<Context>, args: &Arguments, start: usize) -> Vec<Persistent> {
+    (start..args.size())
+        .map(|i| ctx.vm().persistent(args.at(i)))
+        .collect()
+}
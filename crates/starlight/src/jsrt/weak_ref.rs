@@ -6,6 +6,7 @@ use crate::js_method_table;
 use crate::jsrt::weak_ref;
 use crate::prelude::*;
 use crate::vm::builder::Builtin;
+use crate::vm::capabilities::BuiltinGroup;
 use crate::vm::class::JsClass;
 use crate::vm::context::Context;
 use crate::vm::object::TypedJsObject;
@@ -61,6 +62,8 @@ pub fn weak_ref_prototype_deref(
 }
 
 impl Builtin for JsWeakRef {
+    const GROUP: Option<BuiltinGroup> = Some(BuiltinGroup::WeakRef);
+
     fn native_references() -> Vec<usize> {
         vec![
             JsWeakRef::class() as *const _ as _,
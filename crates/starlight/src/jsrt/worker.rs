@@ -0,0 +1,534 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `Worker`: modules running in their own [VirtualMachine] on another OS thread.
+//!
+//! Workers share nothing with the context that started them but the data blocks of
+//! `SharedArrayBuffer`s. Messages are [Serialized] with the structured clone algorithm, and
+//! `ArrayBuffer`s in the transfer list are moved to the receiver without copying. Both sides
+//! receive messages through the event loop: a worker keeps the loop of its parent running until
+//! it is terminated or exits, and the worker keeps running until it calls `close()` or the parent
+//! terminates it.
+//!
+//! ```js
+//! let worker = new Worker("sum.js");
+//! worker.onmessage = event => { print(event.data); worker.terminate(); };
+//! worker.postMessage([1, 2, 3]);
+//!
+//! // sum.js
+//! onmessage = event => postMessage(event.data.reduce((a, b) => a + b, 0));
+//! ```
+use super::jsstd::io_error;
+use super::structured_clone::Serialized;
+use crate::define_jsclass;
+use crate::prelude::*;
+use crate::vm::{
+    builder::{construct_class_instance, Builtin, ClassBuilder, ClassConstructor},
+    capabilities::{Capabilities, Capability, ContextBuilder},
+    context::Context,
+    event_loop::{IoEvent, IoSender, IoSource},
+    handles::Persistent,
+    interrupt::{InterruptHandle, TerminationReason},
+};
+use crate::Platform;
+use std::{
+    any::Any,
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+};
+
+/// Stack size of worker threads, the main thread usually gets as much.
+const WORKER_STACK_SIZE: usize = 8 << 20;
+
+/// Parent side of a worker, the id of its [WorkerSource].
+pub struct JsWorker {
+    id: u32,
+}
+
+impl JsClass for JsWorker {
+    fn class() -> &'static Class {
+        define_jsclass!(JsWorker, Worker)
+    }
+}
+
+impl ClassConstructor for JsWorker {
+    fn constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<Self, JsValue> {
+        start_worker(ctx, args).map(|id| Self { id })
+    }
+
+    fn raw_constructor(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+        let worker = construct_class_instance::<Self>(ctx, args)?;
+        // the source of the worker is registered before its object exists
+        let id = worker.get_jsobject().data::<JsWorker>().id;
+        let target = ctx.vm().persistent(worker);
+        if let Some(source) = ctx.vm.event_loop.source_mut::<WorkerSource>(id) {
+            source.port.target = target;
+        }
+        Ok(worker)
+    }
+
+    fn init(builder: &mut ClassBuilder) -> Result<(), JsValue> {
+        let null = JsValue::encode_null_value();
+        builder.method("postMessage".intern(), worker_post_message, 2)?;
+        builder.method("terminate".intern(), worker_terminate, 0)?;
+        builder.property("onmessage".intern(), null, W | C | E)?;
+        builder.property("onerror".intern(), null, W | C | E)?;
+        Ok(())
+    }
+}
+
+impl Builtin for JsWorker {
+    fn native_references() -> Vec<usize> {
+        vec![
+            JsWorker::class() as *const _ as _,
+            JsWorker::raw_constructor as _,
+            worker_post_message as _,
+            worker_terminate as _,
+            worker_dispatch as _,
+            worker_scope_post_message as _,
+            worker_scope_close as _,
+        ]
+    }
+
+    fn init(ctx: GcPointer<Context>) -> Result<(), JsValue> {
+        if !ctx.capabilities().allows(Capability::Workers) {
+            return Ok(());
+        }
+        ctx.register_class::<JsWorker>()
+    }
+}
+
+/// What a worker reports to the thread that started it. The worker exited once its channel is
+/// disconnected.
+enum WorkerEvent {
+    Message(Serialized),
+    /// Uncaught exception that ended the worker.
+    Error(String),
+}
+
+/// `new Worker(path, { eval = false })`: run the module at `path` on a new thread, or the module
+/// source passed instead of a path when `eval` is set. The worker gets a new VM with the options
+/// and capabilities of this context.
+///
+/// Messages of the worker are passed to `worker.onmessage` as `{ data }`. An exception the worker
+/// does not catch ends it and is passed to `worker.onerror` as an `Error` with its description,
+/// without `onerror` it is uncaught in this context as well.
+///
+/// Returns the id of the source of the worker, whose events have no target yet.
+fn start_worker(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<u32, JsValue> {
+    ctx.require_capability(Capability::Workers)?;
+    let specifier = args.at(0).to_string(ctx)?;
+    let eval = args.at(1).is_jsobject()
+        && args
+            .at(1)
+            .get_jsobject()
+            .get(ctx, "eval".intern())?
+            .to_boolean();
+    let (path, source) = if eval {
+        let path = std::env::current_dir()
+            .map(|dir| dir.join("[worker]").to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "[worker]".to_owned());
+        (path, specifier)
+    } else {
+        ctx.require_capability(Capability::FileSystem)?;
+        let path = std::fs::canonicalize(&specifier)
+            .map_err(|e| io_error(ctx, "Worker", &specifier, e))?;
+        let source =
+            std::fs::read_to_string(&path).map_err(|e| io_error(ctx, "Worker", &specifier, e))?;
+        (path.to_string_lossy().into_owned(), source)
+    };
+
+    let mut options = ctx.vm().options().clone();
+    options.file = None;
+    options.eval = None;
+    let capabilities = ctx.capabilities();
+    let (to_parent, from_worker) = ctx.vm.event_loop.channel();
+    let (started, handle) = mpsc::sync_channel(1);
+    std::thread::Builder::new()
+        .name("worker".to_owned())
+        .stack_size(WORKER_STACK_SIZE)
+        .spawn(move || run_worker(options, capabilities, path, source, to_parent, started))
+        .map_err(|e| io_error(ctx, "Worker", &specifier, e))?;
+    let (handle, to_worker) = match handle.recv() {
+        Ok(started) => started,
+        Err(_) => {
+            let message = JsString::new(ctx, format!("Worker '{}': failed to start", specifier));
+            return Err(JsValue::new(JsError::new(ctx, message, None)));
+        }
+    };
+
+    let source = WorkerSource {
+        port: Port::new(ctx, JsValue::UNDEFINED),
+        receiver: from_worker,
+        sender: to_worker,
+        handle,
+    };
+    Ok(ctx.vm.event_loop.add_source(Box::new(source)))
+}
+
+/// `worker.postMessage(value, transfer)`: send a structured clone of `value` to the worker.
+/// `transfer` is an array of `ArrayBuffer`s, or `{ transfer }`, that are moved to the worker and
+/// detached here. Messages to a worker that has exited are dropped.
+pub fn worker_post_message(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let id = worker_id(ctx, args)?;
+    let message = serialize_message(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<WorkerSource>(id) {
+        let _ = source.sender.send(message);
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `worker.terminate()`: stop the worker at once, even while it runs JS code. Messages it has
+/// not delivered yet are dropped.
+pub fn worker_terminate(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let id = worker_id(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<WorkerSource>(id) {
+        source.handle.interrupt();
+    }
+    ctx.vm.event_loop.remove_source(id);
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `postMessage(value, transfer)` in a worker: send a message to `worker.onmessage` of the
+/// parent, see `worker.postMessage`.
+pub fn worker_scope_post_message(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let id = port_id(ctx)?;
+    let message = serialize_message(ctx, args)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<ScopeSource>(id) {
+        let _ = source.sender.send(WorkerEvent::Message(message));
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// `close()` in a worker: stop receiving messages. The worker exits once its timers and IO are
+/// done.
+pub fn worker_scope_close(
+    mut ctx: GcPointer<Context>,
+    _args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    let id = port_id(ctx)?;
+    if let Some(source) = ctx.vm.event_loop.source_mut::<ScopeSource>(id) {
+        source.closed = true;
+    }
+    Ok(JsValue::UNDEFINED)
+}
+
+/// Called by the event loop with `(target, type, value)`: pass a message to `target.onmessage`,
+/// or an error to `target.onerror` or `target.onmessageerror`. Errors without a handler are
+/// thrown.
+pub fn worker_dispatch(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+    let target = args.at(0);
+    let kind = args.at(1).to_string(ctx)?;
+    let value = args.at(2);
+    let handler = target
+        .get_jsobject()
+        .get(ctx, format!("on{}", kind).intern())?;
+    if !handler.is_callable() {
+        return if kind == "message" {
+            Ok(JsValue::UNDEFINED)
+        } else {
+            Err(value)
+        };
+    }
+    let event = if kind == "message" {
+        let mut event = JsObject::new_empty(ctx);
+        event.put(ctx, "data".intern(), value, false)?;
+        JsValue::new(event)
+    } else {
+        value
+    };
+    let mut argv = [event];
+    let mut arguments = Arguments::new(target, &mut argv);
+    handler
+        .get_jsobject()
+        .as_function_mut()
+        .call(ctx, &mut arguments, handler)
+}
+
+/// Body of a worker thread.
+fn run_worker(
+    options: Options,
+    capabilities: Capabilities,
+    path: String,
+    source: String,
+    sender: IoSender<WorkerEvent>,
+    started: SyncSender<(InterruptHandle, IoSender<Serialized>)>,
+) {
+    let mut vm = Platform::new_runtime(options, None);
+    // messages of the parent wake the loop of the worker
+    let (to_worker, receiver) = vm.event_loop.channel();
+    let _ = started.send((vm.interrupt_handle(), to_worker));
+    let error = match ContextBuilder::new(&mut vm)
+        .capabilities(capabilities)
+        .build()
+    {
+        Ok(ctx) => {
+            let result = init_worker_scope(ctx, receiver, sender.clone())
+                .and_then(|_| ctx.evalm(Some(&path), false, &source))
+                .and_then(|_| ctx.run_event_loop());
+            match result {
+                Ok(_) => None,
+                // terminated by the parent, which does not listen anymore
+                Err(_)
+                    if ctx.vm().take_termination_reason()
+                        == Some(TerminationReason::Interrupted) =>
+                {
+                    None
+                }
+                Err(error) => Some(
+                    error
+                        .to_string(ctx)
+                        .unwrap_or_else(|_| "uncaught exception".to_owned()),
+                ),
+            }
+        }
+        Err(_) => Some("failed to create the context of the worker".to_owned()),
+    };
+    if let Some(error) = error {
+        let _ = sender.send(WorkerEvent::Error(error));
+    }
+    // the parent learns that the worker exited when the senders are dropped with the VM
+    unsafe {
+        vm.dispose();
+    }
+}
+
+/// Install `self`, `onmessage`, `postMessage` and `close` in the global object of a worker.
+fn init_worker_scope(
+    mut ctx: GcPointer<Context>,
+    receiver: Receiver<Serialized>,
+    sender: IoSender<WorkerEvent>,
+) -> Result<(), JsValue> {
+    let mut global_object = ctx.global_object();
+    global_object.put(ctx, "self".intern(), JsValue::new(global_object), false)?;
+    global_object.put(
+        ctx,
+        "onmessage".intern(),
+        JsValue::encode_null_value(),
+        false,
+    )?;
+    def_native_method!(
+        ctx,
+        global_object,
+        postMessage,
+        worker_scope_post_message,
+        2
+    )?;
+    def_native_method!(ctx, global_object, close, worker_scope_close, 0)?;
+    let source = ScopeSource {
+        port: Port::new(ctx, JsValue::new(global_object)),
+        receiver,
+        sender,
+        closed: false,
+    };
+    let id = ctx.vm.event_loop.add_source(Box::new(source));
+    global_object.put(ctx, port_key(), JsValue::new(id), false)?;
+    Ok(())
+}
+
+fn serialize_message(ctx: GcPointer<Context>, args: &Arguments) -> Result<Serialized, JsValue> {
+    let mut transfer = args.at(1);
+    if transfer.is_jsobject() && !transfer.get_jsobject().is_class(JsArray::class()) {
+        transfer = transfer.get_jsobject().get(ctx, "transfer".intern())?;
+    }
+    let mut buffers = vec![];
+    if transfer.is_jsobject() {
+        let mut list = transfer.get_jsobject();
+        let length = list.get(ctx, "length".intern())?.to_number(ctx)? as u32;
+        for i in 0..length {
+            buffers.push(list.get(ctx, Symbol::Index(i))?);
+        }
+    } else if !transfer.is_undefined() {
+        return Err(JsValue::new(
+            ctx.new_type_error("postMessage: transfer must be an array"),
+        ));
+    }
+    Serialized::new(ctx, args.at(0), &buffers)
+}
+
+fn port_key() -> Symbol {
+    "@@workerPort".intern().private()
+}
+
+fn worker_id(ctx: GcPointer<Context>, args: &Arguments) -> Result<u32, JsValue> {
+    if args.this.is_jsobject() && args.this.get_jsobject().is_class(JsWorker::class()) {
+        return Ok(args.this.get_jsobject().data::<JsWorker>().id);
+    }
+    Err(JsValue::new(ctx.new_type_error("this is not a Worker")))
+}
+
+fn port_id(ctx: GcPointer<Context>) -> Result<u32, JsValue> {
+    let id = ctx.global_object().get(ctx, port_key())?;
+    if id.is_number() {
+        Ok(id.get_number() as u32)
+    } else {
+        Err(JsValue::new(ctx.new_type_error("not running in a worker")))
+    }
+}
+
+/// Receiving end of a channel: the object whose handlers get the messages.
+struct Port {
+    ctx: GcPointer<Context>,
+    target: Persistent,
+    dispatch: Persistent,
+}
+
+impl Port {
+    fn new(ctx: GcPointer<Context>, target: JsValue) -> Self {
+        let dispatch = JsNativeFunction::new(ctx, "dispatch".intern(), worker_dispatch, 3);
+        Self {
+            ctx,
+            target: ctx.vm().persistent(target),
+            dispatch: ctx.vm().persistent(JsValue::new(dispatch)),
+        }
+    }
+
+    fn message(&self, message: Serialized, events: &mut Vec<IoEvent>) {
+        match message.deserialize(self.ctx) {
+            Ok(value) => self.event("message", value, events),
+            Err(error) => self.event("messageerror", error, events),
+        }
+    }
+
+    fn event(&self, kind: &str, value: JsValue, events: &mut Vec<IoEvent>) {
+        let kind = JsString::new(self.ctx, kind);
+        events.push(IoEvent {
+            ctx: self.ctx,
+            callback: self.dispatch.clone(),
+            args: vec![
+                self.target.clone(),
+                self.ctx.vm().persistent(JsValue::new(kind)),
+                self.ctx.vm().persistent(value),
+            ],
+        });
+    }
+}
+
+/// Parent side of a worker.
+struct WorkerSource {
+    port: Port,
+    receiver: Receiver<WorkerEvent>,
+    sender: IoSender<Serialized>,
+    handle: InterruptHandle,
+}
+
+impl IoSource for WorkerSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(WorkerEvent::Message(message)) => self.port.message(message, events),
+                Ok(WorkerEvent::Error(description)) => {
+                    let description = JsString::new(self.port.ctx, description);
+                    let error = JsError::new(self.port.ctx, description, None);
+                    self.port.event("error", JsValue::new(error), events);
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Worker side of the channel to the parent.
+struct ScopeSource {
+    port: Port,
+    receiver: Receiver<Serialized>,
+    sender: IoSender<WorkerEvent>,
+    closed: bool,
+}
+
+impl IoSource for ScopeSource {
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool {
+        while !self.closed {
+            match self.receiver.try_recv() {
+                Ok(message) => self.port.message(message, events),
+                Err(TryRecvError::Empty) => return true,
+                // the parent terminated the worker or went away
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+        false
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval, run_event_loop};
+    use crate::Platform;
+
+    #[test]
+    fn test_worker() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var results = [];
+             var worker = new Worker(
+                 'onmessage = event => {' +
+                 '    var message = event.data;' +
+                 '    new DataView(message.shared).setUint8(0, 42);' +
+                 '    postMessage({' +
+                 '        length: message.buffer.byteLength,' +
+                 '        first: new DataView(message.buffer).getUint8(0),' +
+                 '        cyclic: message.nested.self === message.nested,' +
+                 '        mapped: message.map.get(\\'nested\\') === message.nested,' +
+                 '        set: message.set.has(1) && message.set.size === 1' +
+                 '    });' +
+                 '    close();' +
+                 '};',
+                 { eval: true }
+             );
+             var buffer = new ArrayBuffer(8);
+             new DataView(buffer).setUint8(0, 7);
+             var shared = new SharedArrayBuffer(1);
+             var nested = {};
+             nested.self = nested;
+             var map = new Map([['nested', nested]]);
+             var set = new Set([1]);
+             var message = { buffer: buffer, shared: shared, nested: nested, map: map, set: set };
+             worker.postMessage(message, [buffer]);
+             var transferred = buffer.byteLength === 0;
+             var reply = null;
+             worker.onmessage = event => {
+                 reply = event.data;
+             };
+
+             var failure = null;
+             var failing = new Worker(\"throw new Error('boom')\", { eval: true });
+             failing.onerror = error => {
+                 failure = error;
+             };",
+        );
+        run_event_loop(ctx);
+        assert_js(ctx, "transferred");
+        assert_js(ctx, "reply.length === 8 && reply.first === 7");
+        assert_js(ctx, "reply.cyclic");
+        assert_js(ctx, "reply.mapped && reply.set");
+        assert_js(
+            ctx,
+            "worker instanceof Worker && Worker.prototype.onerror === null",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { Worker('x'); } catch (e) { return e instanceof TypeError; } })()",
+        );
+        assert_js(ctx, "new DataView(shared).getUint8(0) === 42");
+        assert_js(ctx, "failure.message.includes('boom')");
+    }
+}
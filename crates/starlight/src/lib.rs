@@ -33,6 +33,9 @@
     clippy::needless_range_loop
 )]
 
+// lets code generated by `starlight-derive` refer to `starlight::` inside this crate too
+extern crate self as starlight;
+
 use gc::cell::GcPointer;
 use options::Options;
 use std::sync::atomic::AtomicBool;
@@ -195,6 +198,7 @@ pub mod utils;
 pub mod gc;
 pub mod bytecode;
 pub mod bytecompiler;
+pub mod capi;
 pub mod codegen;
 pub mod comet;
 mod constant;
@@ -203,6 +207,7 @@ pub mod interpreter;
 pub mod jsrt;
 pub mod options;
 pub mod vm;
+pub use starlight_derive::{js_class, js_methods};
 pub struct Platform;
 use std::sync::atomic::Ordering;
 static INIT: AtomicBool = AtomicBool::new(false);
@@ -224,9 +229,10 @@ impl Platform {
     }
 }
 
+/// Deprecated alias of [capi::sl_platform_initialize].
 #[no_mangle]
 pub extern "C" fn platform_initialize() {
-    Platform::initialize();
+    capi::sl_platform_initialize();
 }
 
 pub mod prelude {
@@ -258,5 +264,8 @@ pub mod prelude {
 }
 
 pub trait JsTryFrom<T>: Sized {
+    /// Whether typed native functions may be called without an argument for a parameter of this
+    /// type. Only `Option<T>` sets it.
+    const OPTIONAL: bool = false;
     fn try_from(ctx: GcPointer<Context>, value: T) -> Result<Self, JsValue>;
 }
@@ -1,8 +1,8 @@
-use std::{num::ParseIntError, path::PathBuf};
+use std::{num::ParseIntError, path::PathBuf, time::Duration};
 
 use structopt::StructOpt;
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 pub struct Options {
     #[structopt(
         long = "sizeClassProgression",
@@ -18,6 +18,12 @@ pub struct Options {
         default_value="2GB",
         parse(try_from_str=parse_size_from_str))]
     pub heap_size: usize,
+    #[structopt(
+        long = "heapSoftLimit",
+        help = "Fail allocations once more than this many bytes are live instead of aborting",
+        parse(try_from_str = parse_size_from_str)
+    )]
+    pub heap_soft_limit: Option<usize>,
     #[structopt(
         long = "gc-threads",
         default_value = "4",
@@ -26,8 +32,31 @@ pub struct Options {
     pub gc_threads: u32,
     #[structopt(long = "parallelMarking", help = "Enable parallel marking GC")]
     pub parallel_marking: bool,
-    #[structopt(parse(from_os_str), help = "Input JS file")]
-    pub file: PathBuf,
+    #[structopt(
+        parse(from_os_str),
+        help = "Input JS file, `-` reads it from stdin and a REPL starts when omitted"
+    )]
+    pub file: Option<PathBuf>,
+    #[structopt(
+        short = "e",
+        long = "eval",
+        conflicts_with = "file",
+        help = "Run the given source instead of a file"
+    )]
+    pub eval: Option<String>,
+    #[structopt(long = "script", help = "Run the input as a classic script")]
+    pub script: bool,
+    #[structopt(
+        long = "module",
+        conflicts_with = "script",
+        help = "Run the input as a module (default)"
+    )]
+    pub module: bool,
+    #[structopt(
+        last = true,
+        help = "Arguments passed to the script after `--`, available as `std.args` and `process.argv`"
+    )]
+    pub script_args: Vec<String>,
     #[structopt(short = "d", long = "dumpBytecode", help = "Dump bytecode")]
     pub dump_bytecode: bool,
     #[structopt(long = "disableIC", help = "Disable inline caching")]
@@ -44,6 +73,16 @@ pub struct Options {
     pub codegen_plugins: bool,
     #[structopt(long = "verboseGC", help = "Verbose GC cycle")]
     pub verbose_gc: bool,
+    #[structopt(
+        long = "fuel",
+        help = "Terminate execution after this many loop iterations and calls"
+    )]
+    pub fuel: Option<u64>,
+    #[structopt(
+        long = "timeout",
+        help = "Terminate execution after this many milliseconds"
+    )]
+    pub timeout: Option<u64>,
 }
 
 impl Default for Options {
@@ -57,10 +96,17 @@ impl Default for Options {
             enable_ffi: false,
             size_class_progression: 1.4,
             heap_size: 2 * 1024 * 1024 * 1024,
-            file: PathBuf::new(),
+            heap_soft_limit: None,
+            file: None,
+            eval: None,
+            script: false,
+            module: false,
+            script_args: vec![],
             gc_threads: 4,
             verbose_gc: false,
             codegen_plugins: false,
+            fuel: None,
+            timeout: None,
         }
     }
 }
@@ -92,6 +138,13 @@ impl Options {
         self
     }
 
+    /// Fail allocations once more than `limit` bytes are live in the VM, see
+    /// [Heap::set_soft_limit](crate::gc::Heap::set_soft_limit).
+    pub fn with_heap_soft_limit(mut self, limit: usize) -> Self {
+        self.heap_soft_limit = Some(limit);
+        self
+    }
+
     pub fn with_gc_threads(mut self, threads: u32) -> Self {
         self.gc_threads = threads;
         self
@@ -116,6 +169,24 @@ impl Options {
         self.dump_stats = enable;
         self
     }
+
+    /// Arguments returned by `std.args` and at the end of `process.argv`.
+    pub fn with_script_args(mut self, args: Vec<String>) -> Self {
+        self.script_args = args;
+        self
+    }
+
+    /// Terminate execution after `fuel` loop iterations and calls.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Terminate execution once `timeout` has elapsed since the VM was created.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout.as_millis() as u64);
+        self
+    }
 }
 
 fn parse_size_from_str(s: &str) -> Result<usize, ParseIntError> {
@@ -2,13 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 use self::{
-    attributes::*, context::Context, object::JsObject, structure::Structure, symbol_table::Symbol,
+    attributes::*,
+    context::Context,
+    event_loop::EventLoop,
+    handles::{HandleStorage, Persistent, Rootable},
+    interrupt::{ExecutionLimits, InterruptHandle, TerminationReason},
+    module_loader::{FileSystemModuleLoader, ModuleLoader},
+    object::JsObject,
+    structure::Structure,
+    symbol_table::Symbol,
 };
 use crate::{
     bytecompiler::{ByteCompiler, CompileError},
     gc::Heap,
-    gc::{cell::GcCell, cell::GcPointer, cell::Trace, SimpleMarkingConstraint},
+    gc::{cell::GcCell, cell::GcPointer, cell::Trace, cell::WeakRef, SimpleMarkingConstraint},
     interpreter::callframe::CallFrame,
+    jsrt::console::{ConsoleHook, ConsoleLevel},
     options::Options,
 };
 use comet::{internal::finalize_trait::FinalizeTrait, visitor::Visitor};
@@ -16,6 +25,7 @@ use std::{
     collections::HashMap,
     ops::{Deref, DerefMut},
     ptr::null_mut,
+    time::Instant,
     u32, u8, usize,
 };
 use std::{fmt::Display, io::Write, sync::RwLock};
@@ -41,22 +51,30 @@ pub mod attributes;
 pub mod bigint;
 pub mod builder;
 pub mod builtins;
+pub mod capabilities;
 pub mod code_block;
 pub mod context;
 pub mod data_view;
 pub mod environment;
 pub mod error;
+pub mod event_loop;
 pub mod function;
 pub mod global;
+pub mod handles;
 pub mod indexed_elements;
+pub mod int32_array;
 pub mod interpreter;
+pub mod interrupt;
 pub mod map;
+pub mod module_loader;
 pub mod native_iterator;
 pub mod number;
 pub mod object;
 pub mod operations;
 pub mod perf;
 pub mod property_descriptor;
+#[cfg(feature = "serde")]
+pub mod serde;
 pub mod slot;
 pub mod stack_alignment;
 pub mod string;
@@ -64,6 +82,8 @@ pub mod structure;
 pub mod structure_builder;
 pub mod structure_chain;
 pub mod symbol_table;
+#[cfg(test)]
+pub(crate) mod testing;
 pub mod thread;
 pub mod typedarray;
 pub mod value;
@@ -143,10 +163,19 @@ pub struct VirtualMachine {
     #[allow(dead_code)]
     /// String that contains all the source code passed to [VirtualMachine::eval] and [VirtualMachine::evalm]
     pub(crate) eval_history: String,
-    pub(crate) persistent_roots: Rc<RefCell<HashMap<usize, JsValue>>>,
+    pub(crate) handles: Rc<RefCell<HandleStorage>>,
     pub(crate) sched_async_func: Option<Box<dyn Fn(Box<dyn FnOnce(GcPointer<Context>)>)>>,
+    /// Built-in event loop, used for microtasks when there is no `sched_async_func`.
+    pub(crate) event_loop: EventLoop,
+    pub(crate) console_hook: Option<ConsoleHook>,
+    /// Module loader used by contexts that do not have their own.
+    pub(crate) module_loader: Rc<dyn ModuleLoader>,
+    pub(crate) limits: ExecutionLimits,
 
     pub(crate) contexts: Vec<GcPointer<Context>>,
+    /// Realms created by [Context::new_realm]. Unlike `contexts` they are not roots, a realm is
+    /// collected once none of its objects is reachable.
+    pub(crate) realms: Vec<WeakRef<Context>>,
 
     pub(crate) context_snapshot: Rc<Box<[u8]>>,
 }
@@ -155,6 +184,8 @@ impl VirtualMachine {
     /// initialize a VirtualMachine with an async scheduler
     /// the async scheduler is used to asynchronously run jobs with the VirtualMachine
     /// this can be used for things like Promises, setImmediate, async functions
+    ///
+    /// Without a scheduler jobs go to the built-in event loop, see [Context::run_event_loop].
     /// # Example
     /// ```rust
     /// use starlight::Platform;
@@ -180,20 +211,56 @@ impl VirtualMachine {
         self.sched_async_func = Some(scheduler);
         self
     }
+    /// Send the output of `console` to `hook` instead of stdout and stderr. The hook also
+    /// receives the output of contexts that were not granted
+    /// [Capability::Stdio](capabilities::Capability::Stdio).
+    pub fn set_console_hook(&mut self, hook: impl Fn(ConsoleLevel, &str) + 'static) {
+        self.console_hook = Some(Box::new(hook));
+    }
+
     pub fn add_persistent_root(&mut self, obj: JsValue) -> PersistentRooted {
-        // for PoC only, todo use something like AutoIdMap for persistent_roots
+        self.persistent(obj)
+    }
 
-        let pr = &mut *self.persistent_roots.borrow_mut();
+    /// Keep `value` alive until the returned handle is dropped.
+    pub fn persistent<T: Rootable>(&mut self, value: T) -> Persistent<T> {
+        Persistent::new(&self.handles, value)
+    }
 
-        let mut id = 0;
-        while pr.contains_key(&id) {
-            id += 1;
-        }
-        pr.insert(id, obj);
-        PersistentRooted {
-            id,
-            map: self.persistent_roots.clone(),
-        }
+    /// Use `loader` to resolve and load modules in every context of this VM that does not have
+    /// its own loader. Defaults to [FileSystemModuleLoader].
+    pub fn set_module_loader(&mut self, loader: impl ModuleLoader + 'static) {
+        self.module_loader = Rc::new(loader);
+    }
+
+    /// Handle that terminates scripts running in this VM, usable from any thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.limits.handle()
+    }
+
+    /// Remaining fuel, `None` when execution is not limited by fuel.
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.limits.fuel()
+    }
+
+    /// Set fuel budget. One unit is consumed per loop iteration and call.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.limits.set_fuel(fuel);
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.limits.deadline()
+    }
+
+    /// Terminate execution once `deadline` passes.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.limits.set_deadline(deadline);
+    }
+
+    /// If the last evaluation was terminated return why. Subsequent calls return `None` until
+    /// another evaluation is terminated.
+    pub fn take_termination_reason(&mut self) -> Option<TerminationReason> {
+        self.limits.last_termination.take()
     }
 
     pub fn options(&self) -> &Options {
@@ -201,6 +268,7 @@ impl VirtualMachine {
     }
 
     pub fn new_raw(gc: Heap, options: Options, external_references: Option<Vec<usize>>) -> VM {
+        let limits = ExecutionLimits::new(&options);
         VirtualMachineRef(Box::into_raw(Box::new(Self {
             gc,
             options,
@@ -210,10 +278,15 @@ impl VirtualMachine {
             perf: perf::Perf::new(),
             eval_history: String::new(),
             top_call_frame: null_mut(),
-            persistent_roots: Default::default(),
+            handles: Default::default(),
             sched_async_func: None,
+            event_loop: Default::default(),
+            console_hook: None,
+            module_loader: Rc::new(FileSystemModuleLoader::new()),
+            limits,
             codegen_plugins: HashMap::new(),
             contexts: vec![],
+            realms: vec![],
             context_snapshot: Rc::new(Box::new([])),
         })))
     }
@@ -261,10 +334,7 @@ impl VirtualMachine {
 
                     ctx.trace(visitor)
                 });
-                let pr = &mut *vm.persistent_roots.borrow_mut();
-                pr.iter_mut().for_each(|entry| {
-                    entry.1.trace(visitor);
-                });
+                vm.handles.borrow().trace(visitor);
             },
         ));
     }
@@ -316,6 +386,16 @@ impl VirtualMachine {
         self.contexts.remove(index);
     }
 
+    /// Whether no realm of this VM is running a script.
+    pub(crate) fn is_idle(&self) -> bool {
+        self.contexts.iter().all(|ctx| ctx.stack.current.is_null())
+            && self
+                .realms
+                .iter()
+                .filter_map(|realm| realm.upgrade())
+                .all(|realm| realm.stack.current.is_null())
+    }
+
     pub fn context(&mut self, index: usize) -> GcPointer<Context> {
         let ctx = self.contexts.get(index);
         *ctx.unwrap()
@@ -334,23 +414,8 @@ impl VirtualMachine {
     }
 }
 
-pub struct PersistentRooted {
-    id: usize,
-    map: Rc<RefCell<HashMap<usize, JsValue>>>,
-}
-
-impl PersistentRooted {
-    pub fn get_value(&self) -> JsValue {
-        *self.map.borrow().get(&self.id).unwrap()
-    }
-}
-
-impl Drop for PersistentRooted {
-    fn drop(&mut self) {
-        let map = &mut *self.map.borrow_mut();
-        map.remove(&self.id);
-    }
-}
+/// Persistent root of a JS value, see [Persistent].
+pub type PersistentRooted = Persistent<JsValue>;
 
 use starlight_derive::GcTrace;
 use wtf_rs::unwrap_unchecked;
@@ -396,12 +461,17 @@ pub struct GlobalData {
     pub(crate) set_structure: Option<GcPointer<Structure>>,
     pub(crate) map_prototype: Option<GcPointer<JsObject>>,
     pub(crate) set_prototype: Option<GcPointer<JsObject>>,
+    pub(crate) map_iterator_structure: Option<GcPointer<Structure>>,
     pub(crate) regexp_structure: Option<GcPointer<Structure>>,
     pub(crate) regexp_prototype: Option<GcPointer<JsObject>>,
     pub(crate) array_buffer_prototype: Option<GcPointer<JsObject>>,
     pub(crate) array_buffer_structure: Option<GcPointer<Structure>>,
+    pub(crate) shared_array_buffer_prototype: Option<GcPointer<JsObject>>,
+    pub(crate) shared_array_buffer_structure: Option<GcPointer<Structure>>,
     pub(crate) data_view_structure: Option<GcPointer<Structure>>,
     pub(crate) data_view_prototype: Option<GcPointer<JsObject>>,
+    pub(crate) int32_array_structure: Option<GcPointer<Structure>>,
+    pub(crate) int32_array_prototype: Option<GcPointer<JsObject>>,
     pub(crate) spread_builtin: Option<GcPointer<JsObject>>,
     pub(crate) weak_ref_structure: Option<GcPointer<Structure>>,
     pub(crate) weak_ref_prototype: Option<GcPointer<JsObject>>,
@@ -448,12 +518,17 @@ impl Trace for GlobalData {
         self.set_structure.trace(vis);
         self.map_prototype.trace(vis);
         self.set_prototype.trace(vis);
+        self.map_iterator_structure.trace(vis);
         self.regexp_structure.trace(vis);
         self.regexp_prototype.trace(vis);
         self.array_buffer_prototype.trace(vis);
         self.array_buffer_structure.trace(vis);
+        self.shared_array_buffer_prototype.trace(vis);
+        self.shared_array_buffer_structure.trace(vis);
         self.data_view_prototype.trace(vis);
         self.data_view_structure.trace(vis);
+        self.int32_array_prototype.trace(vis);
+        self.int32_array_structure.trace(vis);
         self.spread_builtin.trace(vis);
         self.symbol_structure.trace(vis);
         self.weak_ref_prototype.trace(vis);
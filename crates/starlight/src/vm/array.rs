@@ -50,6 +50,18 @@ impl JsArray {
         arr.indexed.set_length(n);
         arr
     }
+
+    /// Like [JsArray::new] but throws `RangeError` when the heap limit is reached.
+    pub fn try_new(ctx: GcPointer<Context>, n: u32) -> Result<GcPointer<JsObject>, JsValue> {
+        let mut arr = JsObject::try_new(
+            ctx,
+            &ctx.global_data().array_structure.unwrap(),
+            Self::class(),
+            ObjectTag::Array,
+        )?;
+        arr.indexed.set_length(n);
+        Ok(arr)
+    }
     pub fn GetOwnPropertyNamesMethod(
         obj: &mut GcPointer<JsObject>,
         ctx: GcPointer<Context>,
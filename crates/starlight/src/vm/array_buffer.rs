@@ -4,12 +4,62 @@ use std::{
     intrinsics::unlikely,
     mem::{size_of, ManuallyDrop},
     ptr::null_mut,
+    sync::Arc,
 };
 
 use super::{class::JsClass, object::TypedJsObject, Context};
+
+/// Data block of a `SharedArrayBuffer`. It may be referenced by buffers in several
+/// [VirtualMachine](crate::vm::VirtualMachine)s at once and is freed when the last one goes away.
+pub struct SharedDataBlock {
+    data: *mut u8,
+    size: usize,
+}
+
+unsafe impl Send for SharedDataBlock {}
+unsafe impl Sync for SharedDataBlock {}
+
+impl SharedDataBlock {
+    pub fn new(size: usize) -> Option<Arc<Self>> {
+        let data = if size == 0 {
+            null_mut()
+        } else {
+            unsafe { libc::calloc(1, size).cast::<u8>() }
+        };
+        if size != 0 && data.is_null() {
+            return None;
+        }
+        Some(Arc::new(Self { data, size }))
+    }
+
+    pub fn data(&self) -> *mut u8 {
+        self.data
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for SharedDataBlock {
+    fn drop(&mut self) {
+        if !self.data.is_null() {
+            unsafe {
+                libc::free(self.data.cast());
+            }
+        }
+    }
+}
+
 pub struct JsArrayBuffer {
     pub(crate) data: *mut u8,
     pub(crate) attached: bool,
+    /// Capacity reserved for a resizable buffer. `None` for fixed-length buffers.
+    pub(crate) max_byte_length: Option<usize>,
+    /// Set when this object is a `SharedArrayBuffer`.
+    pub(crate) shared: Option<Arc<SharedDataBlock>>,
+    /// The data block as reported to the heap, it counts against the soft heap limit.
+    pub(crate) external: Option<ExternalMemory>,
 }
 
 extern "C" fn drop_array_buffer(x: GcPointer<JsObject>) {
@@ -80,15 +130,49 @@ impl JsArrayBuffer {
         *this.data::<Self>() = ManuallyDrop::new(Self {
             data: null_mut(),
             attached: false,
+            max_byte_length: None,
+            shared: None,
+            external: None,
         });
 
         *this.direct_mut(Self::BYTE_LENGTH_OFFSET) = JsValue::new(0u32);
         this
     }
 
+    /// Create `SharedArrayBuffer` object that uses `block` as its data block.
+    pub fn new_shared(
+        mut ctx: GcPointer<Context>,
+        block: Arc<SharedDataBlock>,
+    ) -> GcPointer<JsObject> {
+        let structure = ctx.global_data().shared_array_buffer_structure.unwrap();
+        let mut this = JsObject::new(ctx, &structure, Self::class(), ObjectTag::ArrayBuffer);
+        *this.direct_mut(Self::BYTE_LENGTH_OFFSET) = JsValue::new(block.size() as u32);
+        let external = ctx.heap().reserve_external(block.size());
+        *this.data::<Self>() = ManuallyDrop::new(Self {
+            data: block.data(),
+            attached: true,
+            max_byte_length: None,
+            shared: Some(block),
+            external: Some(external),
+        });
+        this
+    }
+
     pub fn attached(&self) -> bool {
         self.attached
     }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self.max_byte_length.is_some()
+    }
+
+    pub fn shared_data_block(&self) -> Option<Arc<SharedDataBlock>> {
+        self.shared.clone()
+    }
     pub fn copy_data_block_bytes(
         dst: TypedJsObject<Self>,
         dst_index: usize,
@@ -129,7 +213,19 @@ impl TypedJsObject<JsArrayBuffer> {
         unsafe { std::slice::from_raw_parts_mut(self.data, self.size()) }
     }
 
+    pub fn max_byte_length(&self) -> usize {
+        self.max_byte_length.unwrap_or_else(|| self.byte_length())
+    }
+
     pub fn detach(&mut self) {
+        self.external = None;
+        if self.shared.take().is_some() {
+            // the block is owned by `SharedDataBlock`
+            self.data = null_mut();
+            unsafe {
+                self.set_size(0);
+            }
+        }
         if !self.data.is_null() {
             unsafe {
                 libc::free(self.data.cast());
@@ -139,6 +235,68 @@ impl TypedJsObject<JsArrayBuffer> {
         }
         self.attached = false;
     }
+
+    /// Detach this buffer without freeing its data block and return the block, its byte length
+    /// and capacity. Used by `transfer` so that moving the contents never copies them.
+    pub fn take_data_block(&mut self) -> (*mut u8, usize, usize) {
+        self.external = None;
+        let data = std::mem::replace(&mut self.data, null_mut());
+        let size = self.byte_length();
+        let capacity = self.max_byte_length();
+        unsafe {
+            self.set_size(0);
+        }
+        self.attached = false;
+        (data, size, capacity)
+    }
+
+    /// Attach `data` with `size` usable bytes out of `capacity` allocated ones. When `resizable`
+    /// is true the buffer may later be resized up to `capacity` without reallocation.
+    pub unsafe fn adopt_data_block(
+        &mut self,
+        mut ctx: GcPointer<Context>,
+        data: *mut u8,
+        size: usize,
+        capacity: usize,
+        resizable: bool,
+    ) {
+        self.detach();
+        self.external = Some(ctx.heap().reserve_external(capacity));
+        self.data = data;
+        self.attached = true;
+        self.max_byte_length = if resizable { Some(capacity) } else { None };
+        self.set_size(size);
+    }
+
+    /// Resize a resizable buffer in place. Newly exposed bytes are zeroed.
+    pub fn resize(&mut self, ctx: GcPointer<Context>, new_size: usize) -> Result<(), JsValue> {
+        if !self.attached {
+            return Err(JsValue::new(ctx.new_type_error(
+                "ArrayBuffer.prototype.resize called on a detached ArrayBuffer",
+            )));
+        }
+        let max = match self.max_byte_length {
+            Some(max) => max,
+            None => {
+                return Err(JsValue::new(
+                    ctx.new_type_error("ArrayBuffer.prototype.resize: buffer is not resizable"),
+                ))
+            }
+        };
+        if new_size > max {
+            return Err(JsValue::new(ctx.new_range_error(
+                "ArrayBuffer.prototype.resize: new length exceeds maxByteLength",
+            )));
+        }
+        let size = self.byte_length();
+        unsafe {
+            if new_size > size {
+                std::ptr::write_bytes(self.data.add(size), 0, new_size - size);
+            }
+            self.set_size(new_size);
+        }
+        Ok(())
+    }
     pub unsafe fn set_size(&mut self, size: usize) {
         *self.object().direct_mut(JsArrayBuffer::BYTE_LENGTH_OFFSET) = JsValue::new(size as u32);
     }
@@ -148,22 +306,40 @@ impl TypedJsObject<JsArrayBuffer> {
         ctx: GcPointer<Context>,
         size: usize,
         zero: bool,
+    ) -> Result<(), JsValue> {
+        self.create_resizable_data_block(ctx, size, None, zero)
+    }
+
+    /// Allocate data block of `size` bytes. If `max_size` is set the whole capacity is
+    /// reserved up front so that `resize` never moves the data.
+    pub fn create_resizable_data_block(
+        &mut self,
+        mut ctx: GcPointer<Context>,
+        size: usize,
+        max_size: Option<usize>,
+        zero: bool,
     ) -> Result<(), JsValue> {
         self.detach();
-        if size == 0 {
+        self.max_byte_length = max_size;
+        let capacity = max_size.unwrap_or(size);
+        if capacity == 0 {
             self.attached = true;
             return Ok(());
         }
 
-        if unlikely(size > u32::MAX as usize) {
+        if unlikely(capacity > u32::MAX as usize) {
             let msg = JsString::new(ctx, "Cannot allocate a data block for the ArrayBuffer");
             return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
         }
+        let external = match ctx.heap().try_reserve_external(capacity) {
+            Some(external) => external,
+            None => return Err(JsValue::new(ctx.new_range_error("Out of memory"))),
+        };
         unsafe {
-            self.data = if zero {
-                libc::calloc(1, size).cast()
+            self.data = if zero || max_size.is_some() {
+                libc::calloc(1, capacity).cast()
             } else {
-                libc::malloc(size).cast()
+                libc::malloc(capacity).cast()
             };
 
             if unlikely(self.data.is_null()) {
@@ -171,6 +347,7 @@ impl TypedJsObject<JsArrayBuffer> {
                 return Err(JsValue::new(JsRangeError::new(ctx, msg, None)));
             }
             self.attached = true;
+            self.external = Some(external);
             self.set_size(size);
         }
         Ok(())
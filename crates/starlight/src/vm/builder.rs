@@ -3,6 +3,7 @@ use std::{mem::ManuallyDrop, usize};
 use crate::gc::cell::GcPointer;
 
 use super::{
+    capabilities::BuiltinGroup,
     class::JsClass,
     context::Context,
     function::JsAPI,
@@ -24,6 +25,10 @@ pub struct ClassBuilder {
 }
 
 pub trait Builtin {
+    /// Group that [ContextBuilder](super::capabilities::ContextBuilder::without_builtin) can
+    /// leave out. `None` for builtins that are always installed.
+    const GROUP: Option<BuiltinGroup> = None;
+
     fn init(mut _ctx: GcPointer<Context>) -> Result<(), JsValue> {
         todo!();
     }
@@ -44,14 +49,31 @@ pub trait ClassConstructor {
 }
 
 default impl<T: JsClass> ClassConstructor for T {
-    fn raw_constructor(mut ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
-        let name = Self::class().name.into();
-        let structure = ctx.get_structure(name).unwrap();
-        let object = JsObject::new(ctx, &structure, Self::class(), ObjectTag::Ordinary);
-        let data = Self::constructor(ctx, args)?;
-        *object.data::<Self>() = ManuallyDrop::new(data);
-        Ok(object.into())
+    fn raw_constructor(ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+        construct_class_instance::<Self>(ctx, args)
+    }
+}
+
+/// Allocate an instance of class registered with [Context::register_class] and initialize it
+/// with `T::constructor`.
+pub fn construct_class_instance<T: ClassConstructor + JsClass>(
+    mut ctx: GcPointer<Context>,
+    args: &Arguments,
+) -> Result<JsValue, JsValue> {
+    if !args.ctor_call {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "Class constructor {} cannot be invoked without 'new'",
+            T::class().name
+        ))));
     }
+    // the object is allocated only after the constructor succeeded so that its finalizer never
+    // sees uninitialized data
+    let data = T::constructor(ctx, args)?;
+    let name = T::class().name.into();
+    let structure = ctx.get_structure(name).unwrap();
+    let object = JsObject::new(ctx, &structure, T::class(), ObjectTag::Ordinary);
+    *object.data::<T>() = ManuallyDrop::new(data);
+    Ok(object.into())
 }
 
 pub struct ObjectBuilder {
@@ -287,3 +309,76 @@ impl ClassBuilder {
         Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::gc::cell::{GcPointer, Trace};
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, describe, eval};
+    use crate::vm::value::JsValue;
+    use crate::{js_class, js_methods, Platform};
+
+    #[js_class]
+    struct Counter {
+        count: i32,
+        step: i32,
+    }
+
+    impl Trace for Counter {}
+
+    #[js_methods]
+    impl Counter {
+        #[constructor]
+        fn new(start: i32, step: Option<i32>) -> Self {
+            Self {
+                count: start,
+                step: step.unwrap_or(1),
+            }
+        }
+
+        fn increment(&mut self) -> i32 {
+            self.count += self.step;
+            self.count
+        }
+
+        #[getter]
+        fn step(&self) -> i32 {
+            self.step
+        }
+
+        #[setter]
+        fn set_step(&mut self, step: i32) {
+            self.step = step;
+        }
+
+        fn with_prefix(&self, ctx: GcPointer<Context>, prefix: String) -> Result<String, JsValue> {
+            if prefix.is_empty() {
+                return Err(JsValue::new(ctx.new_range_error("empty prefix")));
+            }
+            Ok(format!("{}{}", prefix, self.count))
+        }
+    }
+
+    #[test]
+    fn test_derived_class() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        ctx.register_class::<Counter>()
+            .unwrap_or_else(|error| panic!("registering Counter threw {}", describe(ctx, error)));
+
+        eval(
+            ctx,
+            "var counter = new Counter(1);
+             counter.increment();
+             counter.step = 5;
+             counter.increment();",
+        );
+        assert_js(ctx, "counter.withPrefix('#') === '#7'");
+        assert_js(ctx, "counter.step === 5");
+        assert_js(
+            ctx,
+            "try { counter.increment('x'); Counter.prototype.increment(); false } catch (e) { e instanceof TypeError }",
+        );
+    }
+}
@@ -0,0 +1,363 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Capability based sandboxing of contexts.
+//!
+//! A [ContextBuilder] decides which builtins and internal modules a [Context] gets and what the
+//! scripts running in it may touch. Capabilities are checked twice: denied builtins are not
+//! installed at all, and natives that reach outside of the VM check the capabilities of the
+//! calling context, so a `File` constructor leaked from a trusted context still refuses to work in
+//! a sandboxed one.
+//!
+//! ```ignore
+//! let ctx = ContextBuilder::new(&mut vm)
+//!     .capabilities(Capabilities::none())
+//!     .without_internal_module("std")
+//!     .freeze_intrinsics(true)
+//!     .build()?;
+//! ```
+use std::{collections::HashSet, fmt, rc::Rc};
+
+use super::{
+    context::Context,
+    global::JsGlobal,
+    module_loader::{InMemoryModuleLoader, ModuleLoader},
+    object::{EnumerationMode, JsObject},
+    value::JsValue,
+    VirtualMachine,
+};
+use crate::gc::cell::GcPointer;
+
+/// Access to the outside world that a context may be granted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// `std.File`, `std:fs` and loading modules from the file system.
+    FileSystem,
+    /// `std.args`, `std:process`, `std:os` and the process of the host.
+    Process,
+    /// `print`, `readLine` and `std:io`.
+    Stdio,
+    /// TCP and UDP sockets of `std:net`.
+    Network,
+    /// `Worker` threads. Workers get the capabilities of the context that started them.
+    Workers,
+    /// The `FFI` object installed by [VirtualMachine::add_ffi](super::VirtualMachine).
+    Ffi,
+    /// Test262 hooks: the `$` object and `gc()`.
+    TestHooks,
+}
+
+impl Capability {
+    const ALL: [Capability; 7] = [
+        Capability::FileSystem,
+        Capability::Process,
+        Capability::Stdio,
+        Capability::Network,
+        Capability::Workers,
+        Capability::Ffi,
+        Capability::TestHooks,
+    ];
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::FileSystem => "file system access",
+            Self::Process => "process access",
+            Self::Stdio => "standard IO",
+            Self::Network => "network access",
+            Self::Workers => "worker threads",
+            Self::Ffi => "FFI",
+            Self::TestHooks => "test hooks",
+        })
+    }
+}
+
+/// Set of [Capability] granted to a context. The default grants everything.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    granted: u8,
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self {
+            granted: Capability::ALL.iter().fold(0, |bits, cap| bits | cap.bit()),
+        }
+    }
+
+    /// Nothing but the pure ECMAScript builtins, use this for untrusted code.
+    pub fn none() -> Self {
+        Self { granted: 0 }
+    }
+
+    pub fn grant(mut self, capability: Capability) -> Self {
+        self.granted |= capability.bit();
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.granted &= !capability.bit();
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.granted & capability.bit() != 0
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Optional groups of ECMAScript builtins. Builtins that the rest of the runtime depends on
+/// (`Object`, `Function`, `Array`, `String`, `RegExp`, errors...) are always installed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BuiltinGroup {
+    Math,
+    Promise,
+    /// `ArrayBuffer`, `SharedArrayBuffer`, `DataView` and `Int32Array`, and `TextEncoder` and
+    /// `TextDecoder` which work on them.
+    ArrayBuffer,
+    /// The `Atomics` object.
+    Atomics,
+    WeakRef,
+    Date,
+}
+
+/// Builder for a [Context] with restricted builtins and capabilities.
+pub struct ContextBuilder<'a> {
+    vm: &'a mut VirtualMachine,
+    capabilities: Capabilities,
+    excluded_builtins: Vec<BuiltinGroup>,
+    excluded_modules: Vec<String>,
+    loader: Option<Rc<dyn ModuleLoader>>,
+    freeze_intrinsics: bool,
+}
+
+impl<'a> ContextBuilder<'a> {
+    /// Builder of a context equal to the one returned by [Context::new].
+    pub fn new(vm: &'a mut VirtualMachine) -> Self {
+        Self {
+            vm,
+            capabilities: Capabilities::all(),
+            excluded_builtins: vec![],
+            excluded_modules: vec![],
+            loader: None,
+            freeze_intrinsics: false,
+        }
+    }
+
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn grant(mut self, capability: Capability) -> Self {
+        self.capabilities = self.capabilities.grant(capability);
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.capabilities = self.capabilities.deny(capability);
+        self
+    }
+
+    /// Do not install builtins of `group`.
+    pub fn without_builtin(mut self, group: BuiltinGroup) -> Self {
+        if !self.excluded_builtins.contains(&group) {
+            self.excluded_builtins.push(group);
+        }
+        self
+    }
+
+    /// Do not register internal module `name` (e.g. `"std"`).
+    pub fn without_internal_module(mut self, name: impl Into<String>) -> Self {
+        self.excluded_modules.push(name.into());
+        self
+    }
+
+    /// Module loader of the context. Without [Capability::FileSystem] the context gets an empty
+    /// [InMemoryModuleLoader] unless a loader is provided here.
+    pub fn module_loader(mut self, loader: impl ModuleLoader + 'static) -> Self {
+        self.loader = Some(Rc::new(loader));
+        self
+    }
+
+    /// Make every builtin object, its prototype and everything reachable from them frozen so
+    /// scripts cannot monkey-patch intrinsics shared with the embedder. The global object itself
+    /// stays extensible.
+    pub fn freeze_intrinsics(mut self, freeze: bool) -> Self {
+        self.freeze_intrinsics = freeze;
+        self
+    }
+
+    pub fn build(self) -> Result<GcPointer<Context>, JsValue> {
+        let ctx = self.build_unrooted()?;
+        ctx.vm().contexts.push(ctx);
+        Ok(ctx)
+    }
+
+    /// Build the context without registering it as a root of the VM. It stays alive only while
+    /// it is reachable, e.g. from functions created in it.
+    pub(crate) fn build_unrooted(self) -> Result<GcPointer<Context>, JsValue> {
+        let vm = self.vm;
+        let defer = vm.gc.defer();
+        let mut ctx = Context::new_empty(vm);
+        ctx.capabilities = self.capabilities;
+        ctx.loader = match self.loader {
+            Some(loader) => Some(loader),
+            None if !self.capabilities.allows(Capability::FileSystem) => {
+                Some(Rc::new(InMemoryModuleLoader::new()))
+            }
+            None => None,
+        };
+        ctx.global_object = Some(JsGlobal::new(ctx));
+        ctx.init_builtins(&self.excluded_builtins)?;
+        ctx.init_module_loader();
+        ctx.init_internal_modules();
+        for name in self.excluded_modules.iter() {
+            ctx.modules.remove(name);
+        }
+        if self.capabilities.allows(Capability::TestHooks) {
+            ctx.init_dollar();
+        }
+        if self.freeze_intrinsics {
+            ctx.freeze_intrinsics()?;
+        }
+        drop(defer);
+        Ok(ctx)
+    }
+}
+
+impl GcPointer<Context> {
+    /// Throw a `TypeError` unless this context was granted `capability`.
+    pub fn require_capability(mut self, capability: Capability) -> Result<(), JsValue> {
+        if self.capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(JsValue::new(self.new_type_error(format!(
+                "{} is not allowed in this context",
+                capability
+            ))))
+        }
+    }
+
+    /// Freeze all objects reachable from the bindings of the global object.
+    pub fn freeze_intrinsics(self) -> Result<(), JsValue> {
+        let mut global_object = self.global_object();
+        let mut names = vec![];
+        global_object.get_own_property_names(
+            self,
+            &mut |name, _| names.push(name),
+            EnumerationMode::IncludeNotEnumerable,
+        );
+
+        let mut worklist = vec![];
+        let mut visited = HashSet::new();
+        visited.insert(&*global_object as *const JsObject);
+        for name in names {
+            if let Some(desc) = global_object.get_own_property(self, name) {
+                if !desc.is_accessor() && desc.value().is_jsobject() {
+                    worklist.push(desc.value().get_jsobject());
+                }
+            }
+        }
+        worklist.extend(self.global_data.generator_prototype);
+
+        while let Some(mut object) = worklist.pop() {
+            if !visited.insert(&*object as *const JsObject) {
+                continue;
+            }
+            let mut names = vec![];
+            object.get_own_property_names(
+                self,
+                &mut |name, _| names.push(name),
+                EnumerationMode::IncludeNotEnumerable,
+            );
+            for name in names {
+                if let Some(desc) = object.get_own_property(self, name) {
+                    let values = if desc.is_accessor() {
+                        [desc.getter(), desc.setter()]
+                    } else {
+                        [desc.value(), JsValue::UNDEFINED]
+                    };
+                    for value in values.iter() {
+                        if value.is_jsobject() {
+                            worklist.push(value.get_jsobject());
+                        }
+                    }
+                }
+            }
+            if let Some(proto) = object.prototype() {
+                worklist.push(*proto);
+            }
+            object.freeze(self)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::capabilities::{BuiltinGroup, Capabilities, ContextBuilder};
+    use crate::vm::testing::{assert_js, eval_module};
+    use crate::Platform;
+
+    #[test]
+    fn test_sandboxed_context() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = ContextBuilder::new(&mut vm)
+            .capabilities(Capabilities::none())
+            .without_builtin(BuiltinGroup::Date)
+            .without_internal_module("std")
+            .freeze_intrinsics(true)
+            .build()
+            .unwrap_or_else(|_| panic!("Failed to build the context"));
+
+        assert_js(ctx, "typeof $ === 'undefined' && typeof gc === 'undefined'");
+        assert_js(
+            ctx,
+            "typeof readLine === 'undefined' && typeof print === 'undefined'",
+        );
+        assert_js(
+            ctx,
+            "typeof Date === 'undefined' && typeof Math === 'object'",
+        );
+        assert_js(
+            ctx,
+            "Object.isFrozen(Array.prototype) && Object.isFrozen(Object)",
+        );
+        assert_js(
+            ctx,
+            "(Object.prototype.polluted = 1, ({}).polluted === undefined)",
+        );
+        assert_js(ctx, "[3, 1, 2].sort().join() === '1,2,3'");
+        assert!(!ctx.modules_ref().contains_key("std"));
+        for module in ["std:process", "std:os", "std:fs", "std:net", "std:io"] {
+            let source = format!("import module from '{}';", module);
+            assert!(
+                ctx.evalm(None, false, &source).is_err(),
+                "{} was imported",
+                module
+            );
+        }
+
+        // path needs no capability unless it resolves against the working directory
+        eval_module(
+            ctx,
+            "import path from 'std:path';
+             globalThis.absolute = path.resolve('/a', 'b', '..', 'c');
+             try { path.resolve('a'); } catch (e) { globalThis.relative = e instanceof TypeError; }",
+        );
+        assert_js(ctx, "absolute === '/a/c'");
+        assert_js(ctx, "relative === true");
+    }
+}
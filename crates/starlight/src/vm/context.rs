@@ -1,6 +1,6 @@
 use crate::{define_op_builtins, gc::cell::GcCell, vm::Lrc};
-use comet::internal::finalize_trait::FinalizeTrait;
-use std::{collections::HashMap, ptr::null};
+use comet::internal::{finalize_trait::FinalizeTrait, gc_info::GCInfoTrait};
+use std::{collections::HashMap, ptr::null, rc::Rc};
 use swc_common::{errors::Handler, input::StringInput, FileName, SourceMap};
 use swc_ecmascript::parser::{Parser, Syntax};
 
@@ -24,14 +24,18 @@ use super::{
     array::JsArray,
     array_buffer::JsArrayBuffer,
     builder::{Builtin, ClassBuilder, ClassConstructor},
+    capabilities::{BuiltinGroup, Capabilities, Capability, ContextBuilder},
     class::JsClass,
     data_view::JsDataView,
     error::JsError,
-    error::{JsRangeError, JsReferenceError, JsTypeError},
+    error::{JsRangeError, JsReferenceError, JsTypeError, JsURIError},
     function::JsNativeFunction,
     function::{JsFunction, JsGeneratorFunction},
     global::JsGlobal,
+    int32_array::JsInt32Array,
     interpreter::{frame::CallFrame, stack::Stack},
+    map::JsMap,
+    module_loader::{module_directory, ModuleLoader},
     number::JsNumber,
     object::{JsObject, ObjectTag},
     promise::JsPromise,
@@ -44,11 +48,16 @@ use super::{
     GlobalData, ModuleKind, MyEmiter, VirtualMachine, VirtualMachineRef,
 };
 
+use crate::jsrt::atomics::JsAtomics;
 use crate::jsrt::boolean::JsBoolean;
+use crate::jsrt::console::{ConsoleState, JsConsole};
 use crate::jsrt::date::JsDate;
+use crate::jsrt::encoding::JsEncoding;
+use crate::jsrt::jsstd;
 use crate::jsrt::math::JsMath;
 use crate::jsrt::regexp::JsRegExp;
 use crate::jsrt::weak_ref::JsWeakRef;
+use crate::jsrt::worker::JsWorker;
 use crate::jsrt::SelfHost;
 
 // evalute context
@@ -60,6 +69,10 @@ pub struct Context {
     pub(crate) stacktrace: String,
     pub(crate) module_loader: Option<GcPointer<JsObject>>,
     pub(crate) modules: HashMap<String, ModuleKind>,
+    /// Overrides the module loader of the VM for this context.
+    pub(crate) loader: Option<Rc<dyn ModuleLoader>>,
+    pub(crate) capabilities: Capabilities,
+    pub(crate) console: ConsoleState,
     pub(crate) stack_len_max: u32,
     pub(crate) symbol_table: HashMap<Symbol, GcPointer<JsSymbol>>,
 }
@@ -100,6 +113,25 @@ impl Context {
         self.module_loader
     }
 
+    /// Use `loader` to resolve and load modules imported in this context instead of the loader
+    /// of the VM.
+    pub fn set_module_loader(&mut self, loader: impl ModuleLoader + 'static) {
+        self.loader = Some(Rc::new(loader));
+    }
+
+    /// Module loader used by this context.
+    pub fn loader(&self) -> Rc<dyn ModuleLoader> {
+        match self.loader {
+            Some(ref loader) => loader.clone(),
+            None => self.vm.module_loader.clone(),
+        }
+    }
+
+    /// Capabilities granted to scripts running in this context.
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
     pub fn new_raw() -> Context {
         Self {
             stack_len_max: Self::DEFAULT_STACK_LEN_MAX,
@@ -110,6 +142,9 @@ impl Context {
             stacktrace: String::new(),
             module_loader: None,
             modules: HashMap::new(),
+            loader: None,
+            capabilities: Capabilities::all(),
+            console: ConsoleState::default(),
             symbol_table: HashMap::new(),
         }
     }
@@ -124,6 +159,9 @@ impl Context {
             stacktrace: String::new(),
             module_loader: None,
             modules: HashMap::new(),
+            loader: None,
+            capabilities: Capabilities::all(),
+            console: ConsoleState::default(),
             symbol_table: HashMap::new(),
         };
         let ctx = vm.heap().allocate(context);
@@ -131,14 +169,14 @@ impl Context {
     }
 
     pub fn new(vm: &mut VirtualMachine) -> GcPointer<Context> {
-        let defer_ = vm.gc.defer();
-        let mut ctx = Context::new_empty(vm);
-        ctx.global_object = Some(JsGlobal::new(ctx));
-        ctx.init().expect("Context init failed");
-        vm.contexts.push(ctx);
-        drop(defer_);
+        ContextBuilder::new(vm)
+            .build()
+            .expect("Context init failed")
+    }
 
-        ctx
+    /// Build a context with restricted builtins and capabilities, see [ContextBuilder].
+    pub fn builder(vm: &mut VirtualMachine) -> ContextBuilder<'_> {
+        ContextBuilder::new(vm)
     }
 }
 impl GcPointer<Context> {
@@ -153,7 +191,8 @@ impl GcPointer<Context> {
         let name = T::class().name;
         let obj_proto = self.global_data().get_object_prototype();
         let structure = Structure::new_unique_indexed(self, Some(obj_proto), false);
-        let mut proto = JsObject::new(self, &structure, T::class(), ObjectTag::Ordinary);
+        // the prototype is an ordinary object, it has no instance data of `T`
+        let mut proto = JsObject::new(self, &structure, JsObject::class(), ObjectTag::Ordinary);
 
         let structure = Structure::new_indexed(self, Some(proto), false);
         let mut constructor = JsNativeFunction::new(self, name.intern(), T::raw_constructor, 1);
@@ -199,15 +238,25 @@ impl GcPointer<Context> {
     }
 
     pub fn init(&mut self) -> Result<(), JsValue> {
+        self.init_builtins(&[])?;
+        self.init_module_loader();
+        self.init_internal_modules();
+        if self.capabilities.allows(Capability::TestHooks) {
+            self.init_dollar();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn init_builtins(&mut self, excluded: &[BuiltinGroup]) -> Result<(), JsValue> {
         macro_rules! define_register_builtin {
             ($class: ident) => {
-                self.register_builtin::<$class>()?;
+                match <$class as Builtin>::GROUP {
+                    Some(group) if excluded.contains(&group) => {}
+                    _ => self.register_builtin::<$class>()?,
+                }
             };
         }
         define_op_builtins!(define_register_builtin);
-        self.init_module_loader();
-        self.init_internal_modules();
-        self.init_dollar();
         Ok(())
     }
 }
@@ -233,6 +282,24 @@ impl GcPointer<Context> {
         let msg = JsString::new(self, msg);
         JsRangeError::new(self, msg, None)
     }
+
+    /// Allocate `value` on the heap, throwing `RangeError` instead of aborting when the heap is
+    /// full.
+    pub fn try_allocate<T: GcCell + GCInfoTrait<T> + Trace + FinalizeTrait<T>>(
+        mut self,
+        value: T,
+    ) -> Result<GcPointer<T>, JsValue> {
+        match self.heap().try_allocate(value) {
+            Some(pointer) => Ok(pointer),
+            None => Err(JsValue::new(self.new_range_error("Out of memory"))),
+        }
+    }
+
+    /// Construct new URI error from provided string.
+    pub fn new_uri_error(mut self, msg: impl AsRef<str>) -> GcPointer<JsObject> {
+        let msg = JsString::new(self, msg);
+        JsURIError::new(self, msg, None)
+    }
 }
 
 impl GcPointer<Context> {
@@ -299,9 +366,15 @@ impl GcPointer<Context> {
         let fun = JsVMFunction::new(self, code, env);
         Ok(JsValue::encode_object_value(fun))
     }
-    pub fn compile_module(
+    pub fn compile_module(self, path: &str, name: &str, script: &str) -> Result<JsValue, JsValue> {
+        self.compile_module_with_base(path, &module_directory(path), name, script)
+    }
+
+    /// Compile module at `path` whose relative imports are resolved against `base`.
+    pub(crate) fn compile_module_with_base(
         mut self,
         path: &str,
+        base: &str,
         name: &str,
         script: &str,
     ) -> Result<JsValue, JsValue> {
@@ -328,19 +401,8 @@ impl GcPointer<Context> {
             }
         };
 
-        let mut code = ByteCompiler::compile_module(
-            self,
-            path,
-            &std::path::Path::new(&path)
-                .canonicalize()
-                .unwrap()
-                .parent()
-                .map(|x| x.to_str().unwrap().to_string())
-                .unwrap_or_else(|| "".to_string()),
-            name,
-            &module,
-        )
-        .map_err(|e| self.new_syntax_error(format!("Compile Error {:?}", e)))?;
+        let mut code = ByteCompiler::compile_module(self, path, base, name, &module)
+            .map_err(|e| self.new_syntax_error(format!("Compile Error {:?}", e)))?;
         code.name = name.intern();
 
         let env = Environment::new(self, 0);
@@ -453,16 +515,7 @@ impl GcPointer<Context> {
             let mut code = ByteCompiler::compile_module(
                 self,
                 &path.map(|x| x.to_owned()).unwrap_or_else(String::new),
-                &path
-                    .map(|path| {
-                        std::path::Path::new(&path)
-                            .canonicalize()
-                            .unwrap()
-                            .parent()
-                            .map(|x| x.to_str().unwrap().to_string())
-                            .unwrap_or_else(|| "".to_string())
-                    })
-                    .unwrap_or_else(|| "".to_string()),
+                &path.map(module_directory).unwrap_or_else(String::new),
                 &path.map(|x| x.to_owned()).unwrap_or_else(String::new),
                 &script,
             )
@@ -522,6 +575,47 @@ impl GcPointer<Context> {
         )
         .unwrap();
         assert!(self.modules.contains_key("std"));
+        // modules that need a capability the context lacks are not registered at all
+        let modules = [
+            (
+                "std:fs",
+                Some(Capability::FileSystem),
+                ModuleKind::NativeUninit(jsstd::fs::init_fs),
+            ),
+            (
+                "std:path",
+                None,
+                ModuleKind::NativeUninit(jsstd::path::init_path),
+            ),
+            (
+                "std:process",
+                Some(Capability::Process),
+                ModuleKind::NativeUninit(jsstd::process::init_process),
+            ),
+            (
+                "std:os",
+                Some(Capability::Process),
+                ModuleKind::NativeUninit(jsstd::process::init_os),
+            ),
+            (
+                "std:net",
+                Some(Capability::Network),
+                ModuleKind::NativeUninit(jsstd::net::init_net),
+            ),
+            (
+                "std:io",
+                Some(Capability::Stdio),
+                ModuleKind::NativeUninit(jsstd::io::init_io),
+            ),
+        ];
+        for (name, capability, module) in modules {
+            match capability {
+                Some(capability) if !self.capabilities.allows(capability) => (),
+                _ => {
+                    self.add_module(name, module).unwrap();
+                }
+            }
+        }
     }
 
     pub fn add_module(
@@ -555,6 +649,35 @@ impl GcPointer<Context> {
         None
     }
 
+    /// Create a new realm on the VM of this context. The realm has its own global object and
+    /// intrinsics, functions keep running in the realm that created them and values can be shared
+    /// between realms freely. The realm gets the capabilities of this context.
+    ///
+    /// Unlike contexts created by the embedder the realm is not a root of the VM, it is collected
+    /// once none of its objects is reachable.
+    pub fn new_realm(self) -> Result<GcPointer<Context>, JsValue> {
+        let mut vm = self.vm;
+        let realm = Context::builder(&mut vm)
+            .capabilities(self.capabilities)
+            .build_unrooted()?;
+        let weak = vm.heap().make_weak(realm);
+        vm.realms.retain(|realm| realm.upgrade().is_some());
+        vm.realms.push(weak);
+        Ok(realm)
+    }
+
+    /// Pop frames of a terminated script up to the native caller, skipping its `catch` blocks.
+    pub(crate) unsafe fn unwind_for_termination(&mut self) {
+        while let Some(frame) = self.stack.pop_frame() {
+            if frame.exit_on_return {
+                break;
+            }
+        }
+        if self.vm.is_idle() {
+            self.vm.limits.finish_termination();
+        }
+    }
+
     /// Return [Symbol](crate::vm::symbol_table::Symbol) description.
     pub fn description(&self, sym: Symbol) -> String {
         match sym {
@@ -571,10 +694,10 @@ impl GcPointer<Context> {
     {
         if let Some(scheduler) = &self.vm.sched_async_func {
             scheduler(Box::new(job));
-            Ok(())
         } else {
-            Err(JsValue::encode_object_value(JsString::new(self, "In order to use async you have to init the VirtualMachineOptions with with_async_scheduler()")))
+            self.vm.event_loop.enqueue_microtask(self, Box::new(job));
         }
+        Ok(())
     }
 
     /// Get stacktrace. If there was no error then returned string is empty.
@@ -597,3 +720,106 @@ impl Trace for Context {
 }
 
 impl FinalizeTrait<Context> for Context {}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn test_realms() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var other = $.createRealm();
+             var OtherArray = other.global.Array;
+             var arr = other.evalScript('[1, 2, 3]');
+             var getArray = other.evalScript('(function () { return Array; })');
+             var thrower = other.evalScript('(function () { throw new TypeError(\"x\"); })');
+             var error;
+             try { thrower(); } catch (e) { error = e; }",
+        );
+        assert_js(ctx, "OtherArray !== Array");
+        assert_js(ctx, "!(arr instanceof Array) && arr instanceof OtherArray");
+        assert_js(ctx, "Array.isArray(arr)");
+        assert_js(ctx, "getArray() === OtherArray");
+        assert_js(
+            ctx,
+            "error instanceof other.global.TypeError && !(error instanceof TypeError)",
+        );
+
+        // realms that are no longer reachable are collected
+        eval(ctx, "for (var i = 0; i < 16; i++) { $.createRealm(); }");
+        vm.heap().gc();
+        let live = vm
+            .realms
+            .iter()
+            .filter(|realm| realm.upgrade().is_some())
+            .count();
+        assert!(live < 16, "{} realms survived", live);
+    }
+
+    #[test]
+    fn test_heap_soft_limit() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        let limit = vm.heap().measure_live_bytes() + 1024 * 1024;
+        vm.heap().set_soft_limit(Some(limit));
+
+        // garbage does not count against the limit
+        let result = eval(
+            ctx,
+            "var n = 0; for (let i = 0; i < 100000; i++) { n += { x: 1 }.x; } n",
+        );
+        assert_eq!(result.get_number(), 100000.0);
+
+        assert_js(
+            ctx,
+            "var list = null;
+             try { while (true) { list = { next: list }; } } catch (e) { list = null; e instanceof RangeError }",
+        );
+        assert_js(
+            ctx,
+            "var buffers = [];
+             try {
+                 while (true) { buffers.push(new ArrayBuffer(256 * 1024)); }
+             } catch (e) { buffers = null; e instanceof RangeError }",
+        );
+
+        vm.heap()
+            .set_near_heap_limit_callback(|live, _| live + 1024 * 1024);
+        let result = eval(
+            ctx,
+            "var b = []; for (let i = 0; i < 100000; i++) { b.push({ x: i }); } b.length",
+        );
+        assert_eq!(result.get_number(), 100000.0);
+    }
+
+    #[test]
+    fn test_near_heap_limit_callback() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        let limit = vm.heap().measure_live_bytes() + 1024 * 1024;
+        vm.heap().set_soft_limit(Some(limit));
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        vm.heap().set_near_heap_limit_callback(move |_, limit| {
+            counter.set(counter.get() + 1);
+            limit + 1024 * 1024
+        });
+
+        // everything stays reachable, allocation goes on because the limit keeps being raised
+        let result = eval(
+            ctx,
+            "var kept = []; for (let i = 0; i < 200000; i++) { kept.push({ x: i }); } kept.length",
+        );
+        assert_eq!(result.get_number(), 200000.0);
+        assert!(calls.get() > 0);
+        assert!(vm.heap().soft_limit().unwrap() > limit);
+    }
+}
@@ -21,8 +21,9 @@ pub struct JsDataView {
     buffer: TypedJsObject<JsArrayBuffer>,
     /// offset is the position within the buffer that the DataView begins at.
     offset: usize,
-    /// length is the amount of bytes the DataView views inside the storage.
-    length: usize,
+    /// length is the amount of bytes the DataView views inside the storage. `None` when the
+    /// view tracks the length of a resizable buffer.
+    length: Option<usize>,
 }
 
 impl JsClass for JsDataView {
@@ -45,7 +46,7 @@ impl JsDataView {
         &mut self,
         buffer: TypedJsObject<JsArrayBuffer>,
         offset: usize,
-        length: usize,
+        length: Option<usize>,
     ) {
         self.buffer = buffer;
         self.offset = offset;
@@ -54,10 +55,26 @@ impl JsDataView {
     pub fn attached(&self) -> bool {
         self.buffer.attached()
     }
+
+    pub fn is_length_tracking(&self) -> bool {
+        self.length.is_none()
+    }
+
+    /// IsViewOutOfBounds: true when the buffer was detached or shrunk past the end of the view.
+    pub fn is_out_of_bounds(&self) -> bool {
+        if !self.attached() {
+            return true;
+        }
+        let buffer_length = self.buffer.byte_length();
+        match self.length {
+            Some(length) => self.offset + length > buffer_length,
+            None => self.offset > buffer_length,
+        }
+    }
     pub unsafe fn get<T: Copy + SwapByteOrder>(&self, offset: usize, little_endian: bool) -> T {
         assert!(self.attached(), "Cannot get on a detached buffer");
         assert!(
-            offset + size_of::<T>() <= self.length,
+            offset + size_of::<T>() <= self.byte_length(),
             "Trying to read past the end of the buffer"
         );
         let mut result = MaybeUninit::<T>::uninit();
@@ -84,7 +101,7 @@ impl JsDataView {
     ) {
         assert!(self.attached(), "Cannot set on a detached buffer");
         assert!(
-            offset + size_of::<T>() <= self.length,
+            offset + size_of::<T>() <= self.byte_length(),
             "Trying to write past the end of the buffer"
         );
         let value = if little_endian {
@@ -99,8 +116,15 @@ impl JsDataView {
         );
     }
 
+    /// Returns 0 for out of bounds views, callers that must throw check `is_out_of_bounds` first.
     pub fn byte_length(&self) -> usize {
-        self.length
+        if self.is_out_of_bounds() {
+            return 0;
+        }
+        match self.length {
+            Some(length) => length,
+            None => self.buffer.byte_length() - self.offset,
+        }
     }
 
     pub fn byte_offset(&self) -> usize {
@@ -111,7 +135,7 @@ impl JsDataView {
         ctx: GcPointer<Context>,
         buffer: TypedJsObject<JsArrayBuffer>,
         offset: usize,
-        length: usize,
+        length: Option<usize>,
     ) -> GcPointer<JsObject> {
         let map = ctx.global_data().data_view_structure.unwrap();
         let mut obj = JsObject::new(ctx, &map, Self::class(), ObjectTag::Ordinary);
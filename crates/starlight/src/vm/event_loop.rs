@@ -0,0 +1,579 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Built-in event loop.
+//!
+//! Promise jobs and `queueMicrotask` callbacks are microtasks, `setTimeout`, `setInterval` and
+//! `setImmediate` callbacks are macrotasks. The loop runs one macrotask at a time and drains the
+//! microtask queue after each of them, as HTML and Node.js do.
+//!
+//! The loop works in passes. A pass runs the timers that were due when it started, polls IO and
+//! then runs the immediates that were queued by then. Timers and immediates added by a pass wait
+//! for the next one, so neither a busy interval nor a chain of immediates starves the others. Embedders that installed their own
+//! scheduler with [VirtualMachine::with_async_scheduler](super::VirtualMachine::with_async_scheduler)
+//! receive microtasks there instead, timers still have to be driven with
+//! [Context::run_event_loop] or [Context::run_until_idle]. Exceptions thrown by `queueMicrotask`
+//! callbacks are returned by the next call that runs the loop, whichever scheduler ran them.
+//!
+//! IO that completes outside of the VM, like output of child processes or sockets, is registered
+//! as an [IoSource]. The loop polls its sources between macrotasks and turns their events into
+//! immediates, so callbacks of IO never interleave with other JS code. When nothing is ready the
+//! loop blocks in the poller of the operating system until a registered socket is ready or a
+//! thread sends through an [IoSender]. Embedders that drive the loop themselves poll the sources
+//! with every call to [Context::run_until_idle].
+//!
+//! ```ignore
+//! ctx.eval("setTimeout(() => print('later'), 10); Promise.resolve().then(() => print('now'))")?;
+//! ctx.run_event_loop()?;
+//! ```
+use mio::{event::Source, Events, Interest, Poll, Token, Waker};
+use std::{
+    any::Any,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    io,
+    sync::{
+        mpsc::{self, Receiver, SendError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{arguments::Arguments, context::Context, handles::Persistent, value::JsValue};
+use crate::gc::cell::GcPointer;
+
+type Job = Box<dyn FnOnce(GcPointer<Context>)>;
+
+/// Longest time the loop blocks before polling the execution limits and its sources again.
+/// Sources that cannot wake the loop, like a child that exits after closing its output, are
+/// noticed that late.
+const SLEEP_SLICE: Duration = Duration::from_millis(10);
+/// Shortest delay of timers. It also keeps `setInterval(f, 0)` from being due again at once.
+pub(crate) const MIN_TIMER_DELAY: Duration = Duration::from_millis(1);
+/// Readiness events received by one poll of the operating system.
+const EVENTS_CAPACITY: usize = 256;
+/// Token of the waker of [IoSender]s. Sockets are registered with [SOCKET_TOKEN], every source
+/// is polled when the loop wakes up so their tokens need not be told apart.
+const WAKE_TOKEN: Token = Token(0);
+const SOCKET_TOKEN: Token = Token(1);
+
+/// Something outside of the VM that produces events for JS, e.g. a child process.
+///
+/// Sources fed by other threads receive from an [EventLoop::channel], sockets are registered
+/// with [EventLoop::register], so the loop wakes up when they have something to poll.
+pub(crate) trait IoSource: Any {
+    /// Push the events that are ready without blocking to `events`. Returns `false` once the
+    /// source will not produce any more events and can be dropped.
+    ///
+    /// Polling must not run JS code, callbacks are invoked by the loop afterwards. Sources may
+    /// register new sources while they are polled, but cannot reach other sources.
+    fn poll(&mut self, events: &mut Vec<IoEvent>) -> bool;
+
+    /// Whether the source keeps [Context::run_event_loop] running. Sources that only wait for
+    /// JS to use them return `false`, they stay registered while other work remains.
+    fn is_active(&self) -> bool {
+        true
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Sending half of an [EventLoop::channel], for threads that produce the events of a source.
+/// Sending wakes the loop, and so does dropping the sender, so the source sees the channel
+/// disconnect.
+pub(crate) struct IoSender<T> {
+    sender: Sender<T>,
+    /// `None` if the poller could not be created, the loop polls every [SLEEP_SLICE] then.
+    waker: Option<Arc<Waker>>,
+}
+
+impl<T> IoSender<T> {
+    pub(crate) fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.sender.send(value)?;
+        self.wake();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = &self.waker {
+            let _ = waker.wake();
+        }
+    }
+}
+
+impl<T> Clone for IoSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+}
+
+impl<T> Drop for IoSender<T> {
+    fn drop(&mut self) {
+        self.wake();
+    }
+}
+
+/// Poller of the operating system the loop blocks in, created with the first channel or socket.
+struct Reactor {
+    poll: Poll,
+    events: Events,
+    waker: Arc<Waker>,
+}
+
+impl Reactor {
+    fn new() -> io::Result<Self> {
+        let poll = Poll::new()?;
+        let waker = Waker::new(poll.registry(), WAKE_TOKEN)?;
+        Ok(Self {
+            poll,
+            events: Events::with_capacity(EVENTS_CAPACITY),
+            waker: Arc::new(waker),
+        })
+    }
+}
+
+/// Event reported by an [IoSource]: `callback` is called with `args` as a macrotask.
+pub(crate) struct IoEvent {
+    pub ctx: GcPointer<Context>,
+    pub callback: Persistent,
+    pub args: Vec<Persistent>,
+}
+
+/// Timer or immediate callback waiting to run.
+struct Task {
+    ctx: GcPointer<Context>,
+    callback: Persistent,
+    args: Vec<Persistent>,
+    interval: Option<Duration>,
+}
+
+/// Queues of the built-in event loop of a VM.
+#[derive(Default)]
+pub(crate) struct EventLoop {
+    microtasks: VecDeque<(GcPointer<Context>, Job)>,
+    tasks: HashMap<u32, Task>,
+    /// Deadlines of timers ordered by expiration, ties are broken by creation order. Cleared
+    /// timers stay here until they would expire and are skipped then.
+    timers: BinaryHeap<Reverse<(Instant, u64, u32)>>,
+    immediates: VecDeque<u32>,
+    sources: HashMap<u32, Box<dyn IoSource>>,
+    reactor: Option<Reactor>,
+    /// First exception thrown by a `queueMicrotask` callback that was not reported yet.
+    uncaught: Option<Persistent>,
+    next_id: u32,
+    sequence: u64,
+}
+
+impl EventLoop {
+    pub(crate) fn enqueue_microtask(&mut self, ctx: GcPointer<Context>, job: Job) {
+        self.microtasks.push_back((ctx, job));
+    }
+
+    /// Record an exception that escaped a microtask. Only the first one is kept until it is
+    /// returned by the next call that runs the loop.
+    pub(crate) fn report_uncaught(&mut self, error: Persistent) {
+        if self.uncaught.is_none() {
+            self.uncaught = Some(error);
+        }
+    }
+
+    /// Register a timer that runs `callback` after `delay`, and then every `delay` if `repeat`
+    /// is set. Delays shorter than [MIN_TIMER_DELAY] are extended. Returns the id of the timer.
+    pub(crate) fn add_timer(
+        &mut self,
+        ctx: GcPointer<Context>,
+        callback: Persistent,
+        args: Vec<Persistent>,
+        delay: Duration,
+        repeat: bool,
+    ) -> u32 {
+        let delay = delay.max(MIN_TIMER_DELAY);
+        let id = self.add_task(Task {
+            ctx,
+            callback,
+            args,
+            interval: if repeat { Some(delay) } else { None },
+        });
+        self.schedule_timer(id, delay);
+        id
+    }
+
+    /// Register `callback` to run after the current macrotask. Returns the id of the immediate.
+    pub(crate) fn add_immediate(
+        &mut self,
+        ctx: GcPointer<Context>,
+        callback: Persistent,
+        args: Vec<Persistent>,
+    ) -> u32 {
+        let id = self.add_task(Task {
+            ctx,
+            callback,
+            args,
+            interval: None,
+        });
+        self.immediates.push_back(id);
+        id
+    }
+
+    /// Cancel a timer or an immediate. Unknown ids are ignored.
+    pub(crate) fn cancel(&mut self, id: u32) {
+        self.tasks.remove(&id);
+    }
+
+    /// Register a source that is polled until it reports that it is finished. Returns the id of
+    /// the source.
+    pub(crate) fn add_source(&mut self, source: Box<dyn IoSource>) -> u32 {
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.sources.insert(self.next_id, source);
+        self.next_id
+    }
+
+    /// Source `id` if it is still registered and of type `T`.
+    pub(crate) fn source_mut<T: IoSource>(&mut self, id: u32) -> Option<&mut T> {
+        self.sources
+            .get_mut(&id)
+            .and_then(|source| source.as_any_mut().downcast_mut::<T>())
+    }
+
+    pub(crate) fn remove_source(&mut self, id: u32) {
+        self.sources.remove(&id);
+    }
+
+    /// Channel whose sender wakes the loop, to pass the output of another thread to a source.
+    pub(crate) fn channel<T>(&mut self) -> (IoSender<T>, Receiver<T>) {
+        let (sender, receiver) = mpsc::channel();
+        let waker = self.reactor().ok().map(|reactor| reactor.waker.clone());
+        (IoSender { sender, waker }, receiver)
+    }
+
+    /// Register a non-blocking socket of a source, so the loop wakes up when it is ready for
+    /// `interest`. Readiness is edge-triggered: sources read and write until they would block.
+    /// Sockets are deregistered when they are dropped.
+    pub(crate) fn register(
+        &mut self,
+        socket: &mut impl Source,
+        interest: Interest,
+    ) -> io::Result<()> {
+        let reactor = self.reactor()?;
+        reactor
+            .poll
+            .registry()
+            .register(socket, SOCKET_TOKEN, interest)
+    }
+
+    fn reactor(&mut self) -> io::Result<&mut Reactor> {
+        let reactor = match self.reactor.take() {
+            Some(reactor) => reactor,
+            None => Reactor::new()?,
+        };
+        Ok(self.reactor.insert(reactor))
+    }
+
+    /// Block for up to `timeout`, or until a registered socket is ready or an [IoSender] woke
+    /// the loop.
+    fn wait(&mut self, timeout: Duration) {
+        match &mut self.reactor {
+            // errors are interrupted polls, the loop polls its sources and comes back
+            Some(reactor) => {
+                let _ = reactor.poll.poll(&mut reactor.events, Some(timeout));
+            }
+            None => std::thread::sleep(timeout),
+        }
+    }
+
+    fn add_task(&mut self, task: Task) -> u32 {
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        self.tasks.insert(self.next_id, task);
+        self.next_id
+    }
+
+    /// Let timer `id` expire after `delay`. A deadline too far away to be represented is never
+    /// reached, the timer stays registered until it is cleared but does not keep the loop running.
+    fn schedule_timer(&mut self, id: u32, delay: Duration) {
+        if let Some(deadline) = Instant::now().checked_add(delay) {
+            self.sequence += 1;
+            self.timers.push(Reverse((deadline, self.sequence, id)));
+        }
+    }
+
+    /// Remove the next timer of the pass that started at `now` when the last timer scheduled
+    /// had the number `sequence`.
+    fn next_timer(&mut self, now: Instant, sequence: u64) -> Option<u32> {
+        while let Some(&Reverse((deadline, scheduled, id))) = self.timers.peek() {
+            if !self.tasks.contains_key(&id) {
+                self.timers.pop();
+                continue;
+            }
+            if deadline > now || scheduled > sequence {
+                break;
+            }
+            self.timers.pop();
+            return Some(id);
+        }
+        None
+    }
+
+    /// Deadline of the next live timer.
+    fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some(&Reverse((deadline, _, id))) = self.timers.peek() {
+            if self.tasks.contains_key(&id) {
+                return Some(deadline);
+            }
+            self.timers.pop();
+        }
+        None
+    }
+}
+
+impl GcPointer<Context> {
+    /// Run queued microtasks, immediates and expired timers of the VM until none is left.
+    /// Timers that have not expired yet are left for later calls.
+    ///
+    /// Returns the first exception that was not caught by a timer, immediate or microtask
+    /// callback.
+    pub fn run_until_idle(self) -> Result<(), JsValue> {
+        self.run_loop(false)
+    }
+
+    /// Run the event loop of the VM until no work remains, sleeping until the next timer
+    /// expires when there is nothing else to do.
+    ///
+    /// Returns the first exception that was not caught by a timer, immediate or microtask
+    /// callback.
+    pub fn run_event_loop(self) -> Result<(), JsValue> {
+        self.run_loop(true)
+    }
+
+    /// Run all queued microtasks, including the ones queued while running them.
+    ///
+    /// Returns the first exception that was thrown by a `queueMicrotask` callback, the remaining
+    /// microtasks stay queued then. Rejections of promises are not exceptions.
+    pub fn run_microtasks(mut self) -> Result<(), JsValue> {
+        loop {
+            if let Some(error) = self.vm.event_loop.uncaught.take() {
+                return Err(error.get());
+            }
+            let (ctx, job) = match self.vm.event_loop.microtasks.pop_front() {
+                Some(microtask) => microtask,
+                None => return Ok(()),
+            };
+            self.check_termination()?;
+            job(ctx);
+        }
+    }
+
+    fn run_loop(mut self, wait: bool) -> Result<(), JsValue> {
+        loop {
+            self.run_microtasks()?;
+            if self.run_pass()? {
+                continue;
+            }
+            let deadline = self.vm.event_loop.next_deadline();
+            let active = self
+                .vm
+                .event_loop
+                .sources
+                .values()
+                .any(|source| source.is_active());
+            if !wait || (deadline.is_none() && !active) {
+                return Ok(());
+            }
+            self.check_termination()?;
+            let timeout = deadline.map_or(SLEEP_SLICE, |deadline| {
+                deadline
+                    .saturating_duration_since(Instant::now())
+                    .min(SLEEP_SLICE)
+            });
+            self.vm.event_loop.wait(timeout);
+        }
+    }
+
+    /// Run the timers that are due, poll IO and run the immediates queued until then. Timers
+    /// that expire while the pass runs, including intervals it reschedules, and immediates
+    /// queued by its callbacks are left for the next pass. Returns whether a callback ran.
+    fn run_pass(mut self) -> Result<bool, JsValue> {
+        let now = Instant::now();
+        let sequence = self.vm.event_loop.sequence;
+        let mut ran = false;
+        while let Some(id) = self.vm.event_loop.next_timer(now, sequence) {
+            self.run_task(id)?;
+            self.run_microtasks()?;
+            ran = true;
+        }
+        self.dispatch_io_events();
+        for _ in 0..self.vm.event_loop.immediates.len() {
+            let id = match self.vm.event_loop.immediates.pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            if !self.vm.event_loop.tasks.contains_key(&id) {
+                continue;
+            }
+            self.run_task(id)?;
+            self.run_microtasks()?;
+            ran = true;
+        }
+        Ok(ran)
+    }
+
+    /// Poll every IO source, dropping the finished ones, and queue their events as immediates.
+    fn dispatch_io_events(mut self) {
+        // the sources are taken out of the loop while they are polled, so they can register new
+        // ones, e.g. a listener registers the connections it accepts
+        let mut sources = std::mem::take(&mut self.vm.event_loop.sources);
+        let mut events = vec![];
+        sources.retain(|_, source| source.poll(&mut events));
+        let event_loop = &mut self.vm.event_loop;
+        event_loop.sources.extend(sources);
+        for event in events {
+            event_loop.add_immediate(event.ctx, event.callback, event.args);
+        }
+    }
+
+    fn run_task(mut self, id: u32) -> Result<(), JsValue> {
+        let (ctx, callback, mut args) = match self.vm.event_loop.tasks.get(&id) {
+            Some(task) => (
+                task.ctx,
+                task.callback.get(),
+                task.args.iter().map(|arg| arg.get()).collect::<Vec<_>>(),
+            ),
+            None => return Ok(()),
+        };
+        self.check_termination()?;
+        // the task stays registered while it runs so its callback and arguments stay rooted
+        let mut function = callback.get_jsobject();
+        let mut arguments = Arguments::new(JsValue::UNDEFINED, &mut args);
+        let result = function
+            .as_function_mut()
+            .call(ctx, &mut arguments, callback);
+
+        let event_loop = &mut self.vm.event_loop;
+        let interval = event_loop.tasks.get(&id).and_then(|task| task.interval);
+        match interval {
+            Some(interval) => event_loop.schedule_timer(id, interval),
+            None => event_loop.cancel(id),
+        }
+        result.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gc::cell::GcPointer;
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::symbol_table::Internable;
+    use crate::vm::testing::{assert_js, describe, eval, run_event_loop};
+    use crate::Platform;
+
+    /// `message` of the error reported by the event loop.
+    fn loop_error_message(ctx: GcPointer<Context>) -> String {
+        let error = ctx
+            .run_event_loop()
+            .err()
+            .expect("the error is not reported");
+        error
+            .get_jsobject()
+            .get(ctx, "message".intern())
+            .and_then(|message| message.to_string(ctx))
+            .unwrap_or_else(|error| panic!("reading the message threw {}", describe(ctx, error)))
+    }
+
+    #[test]
+    fn test_event_loop() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var log = [];
+             var ticks = 0;
+             var interval = setInterval(() => {
+                 if (++ticks === 3) clearInterval(interval);
+             }, 1);
+             setTimeout((a, b) => log.push('timeout ' + a + b), 50, 1, 2);
+             clearTimeout(setTimeout(() => log.push('cleared'), 0));
+             setImmediate(() => {
+                 log.push('immediate');
+                 Promise.resolve().then(() => log.push('microtask of immediate'));
+             });
+             Promise.resolve().then(() => log.push('promise'));
+             queueMicrotask(() => log.push('microtask'));
+             log.push('sync');",
+        );
+        run_event_loop(ctx);
+        assert_js(
+            ctx,
+            "log.join() === 'sync,promise,microtask,immediate,microtask of immediate,timeout 12'",
+        );
+        assert_js(ctx, "ticks === 3");
+
+        eval(ctx, "setTimeout(() => { throw new Error('uncaught') }, 0)");
+        assert_eq!(loop_error_message(ctx), "uncaught");
+
+        eval(
+            ctx,
+            "var after = false;
+             queueMicrotask(() => { throw new Error('microtask') });
+             queueMicrotask(() => { after = true });",
+        );
+        assert_eq!(loop_error_message(ctx), "microtask");
+        // the remaining microtasks run with the next call
+        run_event_loop(ctx);
+        assert_js(ctx, "after");
+    }
+
+    #[test]
+    fn test_timer_delays() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var fired = [];
+             setTimeout(() => fired.push('huge'), 1e25);
+             setTimeout(() => fired.push('negative'), -5);
+             setTimeout(() => fired.push('nan'), NaN);
+             setTimeout(() => fired.push('infinite'), Infinity);
+             setTimeout(() => fired.push('max'), 2 ** 31);",
+        );
+        run_event_loop(ctx);
+        assert_js(ctx, "fired.join() === 'huge,negative,nan,infinite,max'");
+    }
+
+    #[test]
+    fn test_interval_and_immediates() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        eval(
+            ctx,
+            "var ticks = 0;
+             var immediates = 0;
+             var interval = setInterval(() => {
+                 if (++ticks === 20) clearInterval(interval);
+             }, 0);
+             function again() {
+                 immediates++;
+                 if (ticks < 20) setImmediate(again);
+             }
+             setImmediate(again);",
+        );
+        run_event_loop(ctx);
+        // every pass that ran a tick of the interval also ran an immediate
+        assert_js(ctx, "ticks === 20 && immediates >= 20");
+
+        // a zero interval is not due again within the same call
+        eval(
+            ctx,
+            "var busy = 0;
+             var busyInterval = setInterval(() => busy++, 0);",
+        );
+        ctx.run_until_idle()
+            .unwrap_or_else(|error| panic!("run_until_idle threw {}", describe(ctx, error)));
+        eval(ctx, "clearInterval(busyInterval)");
+    }
+}
@@ -53,6 +53,11 @@ impl JsFunction {
     pub fn is_generator(&self) -> bool {
         matches!(self.ty, FuncType::Generator(_))
     }
+    /// Realm where this function was created. The function always runs in this realm, no matter
+    /// which realm calls it.
+    pub fn realm(&self) -> GcPointer<Context> {
+        self.ctx
+    }
     pub fn has_instance(
         &self,
         this: &mut GcPointer<JsObject>,
@@ -569,8 +574,154 @@ impl JsClosureFunction {
 
         func
     }
+
+    /// Create a function from a Rust function or closure with typed parameters. Arguments are
+    /// converted with [JsTryFrom](crate::JsTryFrom) and the result with [JsFrom], a missing
+    /// argument or a failed conversion throws a `TypeError`.
+    ///
+    /// ```
+    /// use starlight::vm::symbol_table::Internable;
+    /// use starlight::vm::value::JsValue;
+    /// use starlight::vm::context::Context;
+    /// use starlight::vm::function::JsClosureFunction;
+    /// use starlight::Platform;
+    /// use starlight::options::Options;
+    ///
+    /// let mut starlight_runtime = Platform::new_runtime(Options::default(), None);
+    /// let mut ctx = Context::new(&mut starlight_runtime);
+    /// let mut global = ctx.global_object();
+    ///
+    /// let repeat = JsClosureFunction::new_typed(
+    ///     ctx,
+    ///     "repeat".intern(),
+    ///     |count: u32, string: String, separator: Option<String>| -> Result<String, JsValue> {
+    ///         Ok(vec![string; count as usize].join(&separator.unwrap_or_default()))
+    ///     },
+    /// );
+    /// global.put(ctx, "repeat".intern(), JsValue::new(repeat), true);
+    ///
+    /// let outcome = ctx.eval("return repeat(3, 'a', '-');").ok().expect("function failed");
+    /// assert_eq!(outcome.to_string(ctx).ok().unwrap(), "a-a-a");
+    /// ```
+    pub fn new_typed<F, Params>(ctx: GcPointer<Context>, name: Symbol, f: F) -> GcPointer<JsObject>
+    where
+        F: IntoNativeFunction<Params>,
+    {
+        Self::new(ctx, name, move |ctx, args| f.invoke(ctx, args), F::arity())
+    }
+}
+
+/// Marker for typed native functions that take the [Context] as their first parameter.
+pub struct WithContext;
+
+/// Rust functions that can be called from JS with their arguments converted automatically.
+/// Implemented for functions and closures of up to eight parameters that implement
+/// `JsTryFrom<JsValue>`, optionally preceded by `GcPointer<Context>`.
+pub trait IntoNativeFunction<Params>: 'static {
+    /// Number of required parameters, used as the `length` of the function.
+    fn arity() -> u32;
+    fn invoke(&self, ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue>;
+}
+
+/// Return values of typed native functions. `Err` values are thrown.
+pub trait IntoNativeResult {
+    fn into_native_result(self, ctx: GcPointer<Context>) -> Result<JsValue, JsValue>;
+}
+
+impl<T, E> IntoNativeResult for Result<T, E>
+where
+    JsValue: JsFrom<T> + JsFrom<E>,
+{
+    fn into_native_result(self, ctx: GcPointer<Context>) -> Result<JsValue, JsValue> {
+        match self {
+            Ok(value) => Ok(JsValue::js_from(ctx, value)),
+            Err(error) => Err(JsValue::js_from(ctx, error)),
+        }
+    }
+}
+
+impl<T> IntoNativeResult for T
+where
+    JsValue: JsFrom<T>,
+{
+    fn into_native_result(self, ctx: GcPointer<Context>) -> Result<JsValue, JsValue> {
+        Ok(JsValue::js_from(ctx, self))
+    }
+}
+
+/// Throws a `TypeError` when fewer than `required` arguments were passed.
+pub fn check_argument_count(
+    ctx: GcPointer<Context>,
+    args: &Arguments,
+    required: u32,
+) -> Result<(), JsValue> {
+    if unlikely(args.size() < required as usize) {
+        return Err(JsValue::new(ctx.new_type_error(format!(
+            "Expected at least {} argument{} but got {}",
+            required,
+            if required == 1 { "" } else { "s" },
+            args.size()
+        ))));
+    }
+    Ok(())
 }
 
+macro_rules! impl_into_native_function {
+    ($($param: ident),*) => {
+        impl<F, R, $($param,)*> IntoNativeFunction<($($param,)*)> for F
+        where
+            F: Fn($($param),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: crate::JsTryFrom<JsValue>,)*
+        {
+            fn arity() -> u32 {
+                0 $(+ !<$param as crate::JsTryFrom<JsValue>>::OPTIONAL as u32)*
+            }
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn invoke(&self, ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+                check_argument_count(ctx, args, Self::arity())?;
+                let mut index = 0;
+                $(
+                    let $param = <$param as crate::JsTryFrom<JsValue>>::try_from(ctx, args.at(index))?;
+                    index += 1;
+                )*
+                (self)($($param),*).into_native_result(ctx)
+            }
+        }
+
+        impl<F, R, $($param,)*> IntoNativeFunction<(WithContext, $($param,)*)> for F
+        where
+            F: Fn(GcPointer<Context>, $($param),*) -> R + 'static,
+            R: IntoNativeResult,
+            $($param: crate::JsTryFrom<JsValue>,)*
+        {
+            fn arity() -> u32 {
+                0 $(+ !<$param as crate::JsTryFrom<JsValue>>::OPTIONAL as u32)*
+            }
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn invoke(&self, ctx: GcPointer<Context>, args: &Arguments) -> Result<JsValue, JsValue> {
+                check_argument_count(ctx, args, Self::arity())?;
+                let mut index = 0;
+                $(
+                    let $param = <$param as crate::JsTryFrom<JsValue>>::try_from(ctx, args.at(index))?;
+                    index += 1;
+                )*
+                (self)(ctx, $($param),*).into_native_result(ctx)
+            }
+        }
+    };
+}
+
+impl_into_native_function!();
+impl_into_native_function!(A);
+impl_into_native_function!(A, B);
+impl_into_native_function!(A, B, C);
+impl_into_native_function!(A, B, C, D);
+impl_into_native_function!(A, B, C, D, E);
+impl_into_native_function!(A, B, C, D, E, G);
+impl_into_native_function!(A, B, C, D, E, G, H);
+impl_into_native_function!(A, B, C, D, E, G, H, I);
+
 impl Trace for JsFunction {
     fn trace(&self, tracer: &mut Visitor) {
         self.construct_struct.trace(tracer);
@@ -651,6 +802,7 @@ impl GcPointer<JsObject> {
         let func = self.as_function_mut();
 
         let ctx = ctx;
+        let realm = func.ctx;
         if let Some(s) = func.construct_struct {
             return Ok(s);
         }
@@ -664,7 +816,8 @@ impl GcPointer<JsObject> {
                 if res.is_object() && res.get_object().is::<JsObject>() {
                     Some(res.get_object().downcast_unchecked())
                 } else {
-                    Some(ctx.global_data().get_object_prototype())
+                    // `Object.prototype` of the realm of the constructor
+                    Some(realm.global_data().get_object_prototype())
                 },
                 false,
             )
@@ -1043,3 +1196,114 @@ impl Trace for AsyncFunctionState {
             .for_each(|(env, _, _)| env.trace(visitor));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::JsClosureFunction;
+    use crate::options::Options;
+    use crate::prelude::*;
+    use crate::vm::context::Context;
+    use crate::vm::testing::{assert_js, eval};
+    use crate::Platform;
+    use std::{cell::Cell, rc::Rc};
+
+    fn define<F, Params>(ctx: GcPointer<Context>, name: &str, f: F)
+    where
+        F: IntoNativeFunction<Params>,
+    {
+        let name = name.intern();
+        let function = JsClosureFunction::new_typed(ctx, name, f);
+        ctx.global_object()
+            .put(ctx, name, JsValue::new(function), false)
+            .ok()
+            .expect("failed to define a typed function");
+    }
+
+    #[test]
+    fn test_typed_arity() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        define(ctx, "add", |a: i32, b: i32, c: Option<i32>| {
+            a + b + c.unwrap_or(0)
+        });
+
+        assert_js(
+            ctx,
+            "add.length === 2 && add(1, 2) === 3 && add(1, 2, 3) === 6",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { add(1); } catch (e) { return e instanceof TypeError && e.message === 'Expected at least 2 arguments but got 1'; } })()",
+        );
+    }
+
+    #[test]
+    fn test_typed_optional_object() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        define(
+            ctx,
+            "getX",
+            |ctx: GcPointer<Context>, object: Option<GcPointer<JsObject>>| match object {
+                Some(mut object) => object.get(ctx, "x".intern()),
+                None => Ok(JsValue::new(-1)),
+            },
+        );
+
+        assert_js(ctx, "getX.length === 0 && getX({ x: 4 }) === 4");
+        assert_js(
+            ctx,
+            "getX() === -1 && getX(undefined) === -1 && getX(null) === -1",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { getX(1); } catch (e) { return e instanceof TypeError; } })()",
+        );
+    }
+
+    #[test]
+    fn test_typed_string_conversion() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        define(ctx, "shout", |string: String| string.to_uppercase());
+
+        assert_js(ctx, "shout('abc') === 'ABC'");
+        assert_js(
+            ctx,
+            "(() => { try { shout(42); } catch (e) { return e instanceof TypeError && e.message === 'Expected string but found number'; } })()",
+        );
+        assert_js(
+            ctx,
+            "(() => { try { shout({ toString() { return 'x'; } }); } catch (e) { return e instanceof TypeError; } })()",
+        );
+    }
+
+    #[test]
+    fn test_typed_closure() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        define(ctx, "count", move |step: u32| -> Result<u32, JsValue> {
+            counter.set(counter.get() + step);
+            Ok(counter.get())
+        });
+        define(
+            ctx,
+            "fail",
+            |ctx: GcPointer<Context>, message: String| -> Result<(), JsValue> {
+                Err(JsValue::new(ctx.new_range_error(message)))
+            },
+        );
+
+        assert_js(ctx, "count(2) === 2 && count(3) === 5");
+        assert_eq!(calls.get(), 5);
+        assert_js(ctx, "typeof count === 'function' && count.name === 'count'");
+        assert_js(
+            ctx,
+            "(() => { try { fail('no'); } catch (e) { return e instanceof RangeError && e.message === 'no'; } })()",
+        );
+        eval(ctx, "count(1)");
+        assert_eq!(calls.get(), 6);
+    }
+}
@@ -0,0 +1,337 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Precise roots for embedders.
+//!
+//! A [Persistent] keeps its value alive until it is dropped. Persistent handles live in a slab with
+//! a free list, creating and dropping one is O(1) no matter how many exist.
+//!
+//! A [HandleScope] roots local values precisely: every [Local] created in a scope stays alive until
+//! the scope is dropped. An [EscapableHandleScope] lets one local outlive the scope, to return a
+//! value created in a nested scope. Only the innermost scope may create locals, and scopes must be
+//! dropped in the reverse order of their creation.
+//!
+//! Handles add roots, they do not replace the conservative scan of the native stack, and there is
+//! no way to run native code on a thread whose stack the collector does not scan. Comet only scans
+//! the thread that owns the heap and cannot register other threads or limit root scanning to
+//! handles, so a VM must only be used from the thread that created it.
+//!
+//! ```ignore
+//! let scope = HandleScope::new(&vm);
+//! let object = scope.local(JsObject::new_empty(ctx));
+//! {
+//!     let inner = EscapableHandleScope::new(&scope);
+//!     let string = inner.local(JsValue::new(JsString::new(ctx, "escaped")));
+//!     let string = inner.escape(string);
+//! }
+//! ```
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    ops::Deref,
+    rc::Rc,
+};
+
+use super::{value::JsValue, VirtualMachine};
+use crate::gc::cell::{GcCell, GcPointer, Trace, Visitor};
+
+/// Value that can be stored in a handle.
+pub trait Rootable: Copy {
+    fn to_value(self) -> JsValue;
+    /// # Safety
+    ///
+    /// `value` must have been created by [Rootable::to_value] of the same type.
+    unsafe fn from_value(value: JsValue) -> Self;
+}
+
+impl Rootable for JsValue {
+    fn to_value(self) -> JsValue {
+        self
+    }
+
+    unsafe fn from_value(value: JsValue) -> Self {
+        value
+    }
+}
+
+impl<T: GcCell> Rootable for GcPointer<T> {
+    fn to_value(self) -> JsValue {
+        JsValue::new(self)
+    }
+
+    unsafe fn from_value(value: JsValue) -> Self {
+        value.get_object().downcast_unchecked()
+    }
+}
+
+/// Slots of persistent handles and the stack of local handles of a VM.
+#[derive(Default)]
+pub(crate) struct HandleStorage {
+    slots: Vec<JsValue>,
+    free: Vec<usize>,
+    locals: Vec<JsValue>,
+    /// Number of live handle scopes.
+    depth: usize,
+}
+
+impl HandleStorage {
+    fn allocate(&mut self, value: JsValue) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.slots[index] = value;
+                index
+            }
+            None => {
+                self.slots.push(value);
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        self.slots[index] = JsValue::UNDEFINED;
+        self.free.push(index);
+    }
+
+    /// Number of live persistent handles.
+    #[cfg(test)]
+    pub(crate) fn persistent_count(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+}
+
+impl Trace for HandleStorage {
+    fn trace(&self, visitor: &mut Visitor) {
+        self.slots.iter().for_each(|value| value.trace(visitor));
+        self.locals.iter().for_each(|value| value.trace(visitor));
+    }
+}
+
+/// Handle that keeps a value alive until it is dropped, it can be stored anywhere.
+pub struct Persistent<T: Rootable = JsValue> {
+    index: usize,
+    storage: Rc<RefCell<HandleStorage>>,
+    marker: PhantomData<T>,
+}
+
+impl<T: Rootable> Persistent<T> {
+    pub(crate) fn new(storage: &Rc<RefCell<HandleStorage>>, value: T) -> Self {
+        let index = storage.borrow_mut().allocate(value.to_value());
+        Self {
+            index,
+            storage: storage.clone(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get(&self) -> T {
+        unsafe { T::from_value(self.storage.borrow().slots[self.index]) }
+    }
+
+    pub fn set(&self, value: T) {
+        self.storage.borrow_mut().slots[self.index] = value.to_value();
+    }
+}
+
+impl Persistent<JsValue> {
+    pub fn get_value(&self) -> JsValue {
+        self.get()
+    }
+
+    /// Root the value once more, the new handle is independent of this one.
+    pub fn clone_root(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl<T: Rootable> Clone for Persistent<T> {
+    fn clone(&self) -> Self {
+        Self::new(&self.storage, self.get())
+    }
+}
+
+impl<T: Rootable> Drop for Persistent<T> {
+    fn drop(&mut self) {
+        self.storage.borrow_mut().release(self.index);
+    }
+}
+
+/// Scope for [Local] handles. Locals created in the scope are roots until the scope is dropped.
+pub struct HandleScope {
+    storage: Rc<RefCell<HandleStorage>>,
+    start: usize,
+    level: usize,
+}
+
+impl HandleScope {
+    pub fn new(vm: &VirtualMachine) -> Self {
+        Self::enter(&vm.handles)
+    }
+
+    fn enter(storage: &Rc<RefCell<HandleStorage>>) -> Self {
+        let mut handles = storage.borrow_mut();
+        handles.depth += 1;
+        Self {
+            storage: storage.clone(),
+            start: handles.locals.len(),
+            level: handles.depth,
+        }
+    }
+
+    /// Root `value` until this scope is dropped.
+    pub fn local<T: Rootable>(&self, value: T) -> Local<'_, T> {
+        let mut handles = self.storage.borrow_mut();
+        assert_eq!(
+            handles.depth, self.level,
+            "locals can only be created in the innermost handle scope"
+        );
+        handles.locals.push(value.to_value());
+        Local {
+            storage: &self.storage,
+            index: handles.locals.len() - 1,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl Drop for HandleScope {
+    fn drop(&mut self) {
+        let mut handles = self.storage.borrow_mut();
+        if handles.depth != self.level {
+            // a nested scope is still alive, truncating would free the slots of its locals. The
+            // locals of this scope are leaked instead.
+            drop(handles);
+            if !std::thread::panicking() {
+                panic!("handle scopes must be dropped in the reverse order of their creation");
+            }
+            return;
+        }
+        handles.locals.truncate(self.start);
+        handles.depth -= 1;
+    }
+}
+
+/// Handle rooted by the [HandleScope] it was created in.
+pub struct Local<'s, T: Rootable = JsValue> {
+    storage: &'s RefCell<HandleStorage>,
+    index: usize,
+    marker: PhantomData<T>,
+}
+
+impl<'s, T: Rootable> Local<'s, T> {
+    pub fn get(&self) -> T {
+        unsafe { T::from_value(self.storage.borrow().locals[self.index]) }
+    }
+
+    pub fn set(&self, value: T) {
+        self.storage.borrow_mut().locals[self.index] = value.to_value();
+    }
+}
+
+impl<'s, T: Rootable> Clone for Local<'s, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'s, T: Rootable> Copy for Local<'s, T> {}
+
+/// [HandleScope] that can pass one local to the enclosing scope.
+pub struct EscapableHandleScope<'p> {
+    parent: &'p HandleScope,
+    slot: usize,
+    escaped: Cell<bool>,
+    scope: HandleScope,
+}
+
+impl<'p> EscapableHandleScope<'p> {
+    pub fn new(parent: &'p HandleScope) -> Self {
+        // the slot for the escaped value belongs to the parent scope
+        let slot = parent.local(JsValue::UNDEFINED).index;
+        Self {
+            parent,
+            slot,
+            escaped: Cell::new(false),
+            scope: HandleScope::enter(&parent.storage),
+        }
+    }
+
+    /// Move `local` to the parent scope. Can be called only once.
+    pub fn escape<T: Rootable>(&self, local: Local<'_, T>) -> Local<'p, T> {
+        assert!(
+            !self.escaped.replace(true),
+            "a scope can escape only one value"
+        );
+        self.parent.storage.borrow_mut().locals[self.slot] = local.get().to_value();
+        Local {
+            storage: &self.parent.storage,
+            index: self.slot,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'p> Deref for EscapableHandleScope<'p> {
+    type Target = HandleScope;
+    fn deref(&self) -> &HandleScope {
+        &self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::handles::{EscapableHandleScope, HandleScope};
+    use crate::vm::object::JsObject;
+    use crate::vm::string::JsString;
+    use crate::vm::symbol_table::Internable;
+    use crate::vm::testing::describe;
+    use crate::vm::value::JsValue;
+    use crate::Platform;
+
+    #[test]
+    fn test_handles() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let roots = (0..10_000)
+            .map(|i| vm.persistent(JsValue::new(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(vm.handles.borrow().persistent_count(), 10_000);
+        drop(roots);
+        assert_eq!(vm.handles.borrow().persistent_count(), 0);
+
+        let scope = HandleScope::new(&vm);
+        let object = scope.local(JsObject::new_empty(ctx));
+        let escaped = {
+            let inner = EscapableHandleScope::new(&scope);
+            let string = inner.local(JsString::new(ctx, "escaped"));
+            inner.escape(string)
+        };
+        let persistent = vm.persistent(JsString::new(ctx, "persistent"));
+        object
+            .get()
+            .put(ctx, "x".intern(), JsValue::new(escaped.get()), false)
+            .unwrap_or_else(|error| panic!("put threw {}", describe(ctx, error)));
+        vm.heap().gc();
+
+        assert_eq!(escaped.get().as_str(), "escaped");
+        assert_eq!(persistent.get().as_str(), "persistent");
+        let x = object
+            .get()
+            .get(ctx, "x".intern())
+            .unwrap_or_else(|error| panic!("get threw {}", describe(ctx, error)));
+        assert!(x.is_jsstring());
+    }
+
+    #[test]
+    #[should_panic(expected = "reverse order")]
+    fn test_handle_scope_drop_order() {
+        let vm = Platform::new_runtime(Options::default(), None);
+        let outer = HandleScope::new(&vm);
+        let inner = HandleScope::new(&vm);
+        let _local = inner.local(JsValue::new(1));
+        drop(outer);
+    }
+}
@@ -0,0 +1,233 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! `Int32Array`, the only integer indexed exotic object implemented so far. It exists so that
+//! `Atomics` can take the standard typed array arguments.
+use std::{
+    mem::{size_of, ManuallyDrop},
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use super::{
+    array_buffer::JsArrayBuffer,
+    attributes::*,
+    class::JsClass,
+    context::Context,
+    object::{EnumerationMode, JsObject, ObjectTag, TypedJsObject},
+    property_descriptor::PropertyDescriptor,
+    slot::Slot,
+    symbol_table::Symbol,
+};
+use crate::gc::cell::{GcPointer, Trace, Visitor};
+use crate::prelude::*;
+
+pub struct JsInt32Array {
+    buffer: TypedJsObject<JsArrayBuffer>,
+    /// Byte offset of the first element, always a multiple of 4.
+    offset: usize,
+    /// Number of elements. `None` when the array tracks the length of a resizable buffer.
+    length: Option<usize>,
+}
+
+impl JsClass for JsInt32Array {
+    fn class() -> &'static Class {
+        define_jsclass!(
+            JsInt32Array,
+            Int32Array,
+            None,
+            Some(trace_int32_array),
+            Some(int32_array_size)
+        )
+    }
+
+    fn GetOwnIndexedPropertySlotMethod(
+        obj: &mut GcPointer<JsObject>,
+        _ctx: GcPointer<Context>,
+        index: u32,
+        slot: &mut Slot,
+    ) -> bool {
+        match obj.data::<Self>().get(index as usize) {
+            Some(value) => {
+                slot.set(JsValue::new(value), object_data());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn PutIndexedSlotMethod(
+        obj: &mut GcPointer<JsObject>,
+        ctx: GcPointer<Context>,
+        index: u32,
+        val: JsValue,
+        _slot: &mut Slot,
+        _throwable: bool,
+    ) -> Result<(), JsValue> {
+        let value = val.to_int32(ctx)?;
+        // writes past the end are ignored, the conversion above may have shrunk the buffer
+        obj.data::<Self>().set(index as usize, value);
+        Ok(())
+    }
+
+    fn DefineOwnIndexedPropertySlotMethod(
+        obj: &mut GcPointer<JsObject>,
+        ctx: GcPointer<Context>,
+        index: u32,
+        desc: &PropertyDescriptor,
+        _slot: &mut Slot,
+        throwable: bool,
+    ) -> Result<bool, JsValue> {
+        let accepted = (index as usize) < obj.data::<Self>().length()
+            && !desc.is_accessor()
+            && (desc.is_configurable_absent() || desc.is_configurable())
+            && (desc.is_enumerable_absent() || desc.is_enumerable())
+            && (desc.is_writable_absent() || desc.is_writable());
+        if !accepted {
+            if throwable {
+                return Err(JsValue::new(ctx.new_type_error(format!(
+                    "Cannot redefine element {} of an Int32Array",
+                    index
+                ))));
+            }
+            return Ok(false);
+        }
+        if !desc.is_value_absent() {
+            let value = desc.value().to_int32(ctx)?;
+            obj.data::<Self>().set(index as usize, value);
+        }
+        Ok(true)
+    }
+
+    fn DeleteIndexedMethod(
+        obj: &mut GcPointer<JsObject>,
+        ctx: GcPointer<Context>,
+        index: u32,
+        throwable: bool,
+    ) -> Result<bool, JsValue> {
+        if (index as usize) < obj.data::<Self>().length() {
+            if throwable {
+                return Err(JsValue::new(ctx.new_type_error(format!(
+                    "Cannot delete element {} of an Int32Array",
+                    index
+                ))));
+            }
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn GetOwnPropertyNamesMethod(
+        obj: &mut GcPointer<JsObject>,
+        ctx: GcPointer<Context>,
+        collector: &mut dyn FnMut(Symbol, u32),
+        mode: EnumerationMode,
+    ) {
+        let length = obj.data::<Self>().length();
+        for index in 0..length as u32 {
+            collector(Symbol::Index(index), index);
+        }
+        JsObject::GetOwnPropertyNamesMethod(obj, ctx, collector, mode)
+    }
+}
+
+impl JsInt32Array {
+    pub const BYTES_PER_ELEMENT: usize = size_of::<i32>();
+
+    pub fn new(
+        ctx: GcPointer<Context>,
+        buffer: TypedJsObject<JsArrayBuffer>,
+        offset: usize,
+        length: Option<usize>,
+    ) -> GcPointer<JsObject> {
+        let structure = ctx.global_data().int32_array_structure.unwrap();
+        let mut obj = JsObject::new(ctx, &structure, Self::class(), ObjectTag::Int32Array);
+        *obj.data::<Self>() = ManuallyDrop::new(Self {
+            buffer,
+            offset,
+            length,
+        });
+        obj
+    }
+
+    pub fn get_buffer(&self) -> TypedJsObject<JsArrayBuffer> {
+        self.buffer
+    }
+
+    pub fn byte_offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn is_length_tracking(&self) -> bool {
+        self.length.is_none()
+    }
+
+    /// IsTypedArrayOutOfBounds: true when the buffer was detached or shrunk past the end of the
+    /// array.
+    pub fn is_out_of_bounds(&self) -> bool {
+        if !self.buffer.attached() {
+            return true;
+        }
+        let buffer_length = self.buffer.byte_length();
+        match self.length {
+            Some(length) => self.offset + length * Self::BYTES_PER_ELEMENT > buffer_length,
+            None => self.offset > buffer_length,
+        }
+    }
+
+    /// Number of elements, 0 for out of bounds arrays.
+    pub fn length(&self) -> usize {
+        if self.is_out_of_bounds() {
+            return 0;
+        }
+        match self.length {
+            Some(length) => length,
+            None => (self.buffer.byte_length() - self.offset) / Self::BYTES_PER_ELEMENT,
+        }
+    }
+
+    pub fn byte_length(&self) -> usize {
+        self.length() * Self::BYTES_PER_ELEMENT
+    }
+
+    /// Element `index`, `None` when it is out of bounds. Data blocks come from `malloc` and the
+    /// offset is a multiple of 4, so every element is aligned and can be accessed atomically.
+    /// That keeps plain reads and writes of `SharedArrayBuffer`s free of data races.
+    pub fn cell(&self, index: usize) -> Option<&AtomicI32> {
+        if index >= self.length() {
+            return None;
+        }
+        unsafe {
+            Some(
+                &*self
+                    .buffer
+                    .get_data_block()
+                    .add(self.offset + index * Self::BYTES_PER_ELEMENT)
+                    .cast::<AtomicI32>(),
+            )
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<i32> {
+        self.cell(index).map(|cell| cell.load(Ordering::Relaxed))
+    }
+
+    /// Store `value` at `index`. Returns false when the index is out of bounds.
+    pub fn set(&self, index: usize, value: i32) -> bool {
+        match self.cell(index) {
+            Some(cell) => {
+                cell.store(value, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[allow(improper_ctypes_definitions)]
+extern "C" fn trace_int32_array(tracer: &mut Visitor, obj: &JsObject) {
+    obj.data::<JsInt32Array>().buffer.trace(tracer);
+}
+
+extern "C" fn int32_array_size() -> usize {
+    size_of::<JsInt32Array>()
+}
@@ -23,6 +23,29 @@ pub mod frame;
 pub mod stack;
 
 impl GcPointer<Context> {
+    /// Poll execution limits of the VM. Returns uncatchable termination error if the script
+    /// has to stop.
+    #[inline]
+    pub(crate) fn check_termination(mut self) -> Result<(), JsValue> {
+        if unlikely(self.vm.gc.take_limit_exceeded()) && !self.vm.gc.check_soft_limit() {
+            // an allocation that could not throw exceeded the limit and collecting garbage did
+            // not bring the heap back under it
+            return Err(JsValue::new(self.new_range_error("Out of memory")));
+        }
+        match self.vm.limits.poll() {
+            None => Ok(()),
+            Some(reason) => {
+                let msg = JsString::new(self, format!("Execution terminated: {}", reason));
+                let error = JsValue::new(JsError::new(self, msg, None));
+                if self.vm.is_idle() {
+                    // nothing to unwind, we are called directly by the embedder
+                    self.vm.limits.finish_termination();
+                }
+                Err(error)
+            }
+        }
+    }
+
     pub(crate) fn perform_vm_call(
         mut self,
         func: &JsVMFunction,
@@ -190,6 +213,7 @@ unsafe fn eval_internal(
     scope: GcPointer<Environment>,
     callee: JsValue,
 ) -> Result<JsValue, JsValue> {
+    ctx.check_termination()?;
     let frame = ctx.stack.new_frame(0, callee, scope);
     if frame.is_none() {
         let msg = JsString::new(ctx, "stack overflow");
@@ -211,6 +235,10 @@ unsafe fn eval_internal(
             Ok(value) => return Ok(value),
             Err(e) => {
                 ctx.stacktrace = ctx.stacktrace();
+                if unlikely(ctx.vm.limits.terminating.is_some()) {
+                    ctx.unwind_for_termination();
+                    return Err(e);
+                }
 
                 if let Some(unwind_frame) = ctx.unwind() {
                     let (env, ip, sp) = (*unwind_frame).try_stack.pop().unwrap();
@@ -334,6 +362,9 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 ctx.heap().collect_if_necessary();
                 let offset = ip.cast::<i32>().read();
                 ip = ip.add(4);
+                if offset < 0 {
+                    ctx.check_termination()?;
+                }
                 ip = ip.offset(offset as isize);
             }
             Opcode::OP_JMP_IF_FALSE => {
@@ -341,6 +372,9 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 ip = ip.add(4);
                 let value = frame.pop();
                 if !value.to_boolean() {
+                    if offset < 0 {
+                        ctx.check_termination()?;
+                    }
                     ip = ip.offset(offset as _);
                 }
             }
@@ -349,9 +383,15 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 ip = ip.add(4);
                 let value = frame.pop();
                 if value.to_boolean() {
+                    if offset < 0 {
+                        ctx.check_termination()?;
+                    }
                     ip = ip.offset(offset as _);
                 }
             }
+            Opcode::OP_LOOPHINT => {
+                ctx.check_termination()?;
+            }
 
             Opcode::OP_POP => {
                 frame.pop();
@@ -807,6 +847,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
 
             Opcode::OP_CALL | Opcode::OP_TAILCALL => {
                 ctx.heap().collect_if_necessary();
+                ctx.check_termination()?;
                 let argc = ip.cast::<u32>().read();
                 ip = ip.add(4);
 
@@ -830,7 +871,8 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 frame.ip = ip;
                 stack.cursor = frame.sp;
 
-                if func.is_vm() {
+                // functions of other realms run on the stack of their own realm
+                if func.is_vm() && GcPointer::ptr_eq(&func.ctx, &ctx) {
                     let vm_fn = func.as_vm_mut();
                     let scope = JsValue::new(vm_fn.scope);
                     let (this, scope) = ctx.setup_for_vm_call(vm_fn, scope, &args_)?;
@@ -868,6 +910,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
             }
             Opcode::OP_NEW | Opcode::OP_TAILNEW => {
                 ctx.heap().collect_if_necessary();
+                ctx.check_termination()?;
                 let argc = ip.cast::<u32>().read();
                 ip = ip.add(4);
 
@@ -888,7 +931,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 letroot!(funcc = gcstack, func.get_jsobject());
                 let map = func_object.func_construct_map(ctx)?;
                 let func = func_object.as_function_mut();
-                let object = JsObject::new(ctx, &map, JsObject::class(), ObjectTag::Ordinary);
+                let object = JsObject::try_new(ctx, &map, JsObject::class(), ObjectTag::Ordinary)?;
                 letroot!(
                     args_ = gcstack,
                     Arguments::new(JsValue::new(object), &mut args)
@@ -897,7 +940,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 args_.ctor_call = true;
                 frame.ip = ip;
 
-                if func.is_vm() {
+                if func.is_vm() && GcPointer::ptr_eq(&func.ctx, &ctx) {
                     let vm_fn = func.as_vm_mut();
                     let scope = JsValue::new(vm_fn.scope);
                     let (this, scope) = ctx.setup_for_vm_call(vm_fn, scope, &args_)?;
@@ -1145,7 +1188,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
             }
 
             Opcode::OP_NEWOBJECT => {
-                let obj = JsObject::new_empty(ctx);
+                let obj = JsObject::try_new_empty(ctx)?;
                 frame.push(JsValue::encode_object_value(obj));
             }
 
@@ -1270,7 +1313,7 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 let count = ip.cast::<u32>().read_unaligned();
 
                 ip = ip.add(4);
-                letroot!(arr = gcstack, JsArray::new(ctx, count));
+                letroot!(arr = gcstack, JsArray::try_new(ctx, count)?);
                 let mut index = 0;
                 let mut did_put = 0;
                 while did_put < count {
@@ -1368,7 +1411,6 @@ pub unsafe fn eval(mut ctx: GcPointer<Context>, frame: *mut CallFrame) -> Result
                 frame.push(JsValue::new(val.is_jsobject()));
             }
             Opcode::OP_NOP => todo!(),
-            Opcode::OP_LOOPHINT => todo!(),
             Opcode::OP_PUSH_ENV => todo!(),
             Opcode::OP_POP_ENV => todo!(),
             Opcode::OP_SET_ENV => todo!(),
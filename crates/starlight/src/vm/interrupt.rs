@@ -0,0 +1,215 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Stopping running scripts.
+//!
+//! The interpreter polls for termination at loop back-edges and calls. Execution is terminated
+//! when an [InterruptHandle] requested it, when the fuel budget runs out or when the deadline
+//! passes. Termination unwinds every JS frame up to the embedder without running `catch` or
+//! `finally` blocks and the embedder receives an `Error` whose message starts with
+//! `Execution terminated`. [VirtualMachine::take_termination_reason](super::VirtualMachine::take_termination_reason)
+//! tells it apart from ordinary exceptions.
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::options::Options;
+
+/// Thread-safe handle used to terminate the scripts running in a
+/// [VirtualMachine](super::VirtualMachine) from another thread.
+///
+/// ```ignore
+/// let handle = vm.interrupt_handle();
+/// std::thread::spawn(move || {
+///     std::thread::sleep(Duration::from_secs(1));
+///     handle.interrupt();
+/// });
+/// assert!(ctx.eval("while (true) {}").is_err());
+/// ```
+#[derive(Clone)]
+pub struct InterruptHandle {
+    requested: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Request termination of the running script. If nothing runs, the next evaluation is
+    /// terminated as soon as it starts. The request is consumed once the script has unwound.
+    pub fn interrupt(&self) {
+        self.requested.store(true, Ordering::Release);
+    }
+
+    pub fn is_interrupt_requested(&self) -> bool {
+        self.requested.load(Ordering::Acquire)
+    }
+
+    /// Withdraw a pending request.
+    pub fn clear(&self) {
+        self.requested.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// Requested through an [InterruptHandle].
+    Interrupted,
+    /// Fuel budget is exhausted.
+    OutOfFuel,
+    /// Deadline has passed.
+    DeadlineExceeded,
+}
+
+impl fmt::Display for TerminationReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Interrupted => "interrupted",
+            Self::OutOfFuel => "out of fuel",
+            Self::DeadlineExceeded => "deadline exceeded",
+        })
+    }
+}
+
+/// Reading the clock on every poll is too slow, deadline is checked once per this many polls.
+const DEADLINE_CHECK_INTERVAL: u32 = 1024;
+
+pub(crate) struct ExecutionLimits {
+    requested: Arc<AtomicBool>,
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    deadline_countdown: u32,
+    /// Set while a terminated script unwinds.
+    pub(crate) terminating: Option<TerminationReason>,
+    /// Reason of the last termination that reached the embedder.
+    pub(crate) last_termination: Option<TerminationReason>,
+}
+
+impl ExecutionLimits {
+    pub(crate) fn new(options: &Options) -> Self {
+        Self {
+            requested: Arc::new(AtomicBool::new(false)),
+            fuel: options.fuel,
+            deadline: options
+                .timeout
+                .map(|timeout| Instant::now() + Duration::from_millis(timeout)),
+            deadline_countdown: 0,
+            terminating: None,
+            last_termination: None,
+        }
+    }
+
+    pub(crate) fn handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            requested: self.requested.clone(),
+        }
+    }
+
+    pub(crate) fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    pub(crate) fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    pub(crate) fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+        self.deadline_countdown = 0;
+    }
+
+    /// Consume one unit of fuel and check whether execution has to stop.
+    #[inline]
+    pub(crate) fn poll(&mut self) -> Option<TerminationReason> {
+        if self.terminating.is_some() {
+            return self.terminating;
+        }
+        let reason = if self.requested.load(Ordering::Relaxed) {
+            Some(TerminationReason::Interrupted)
+        } else {
+            self.poll_budget()
+        };
+        self.terminating = reason;
+        reason
+    }
+
+    #[inline]
+    fn poll_budget(&mut self) -> Option<TerminationReason> {
+        if let Some(ref mut fuel) = self.fuel {
+            if *fuel == 0 {
+                return Some(TerminationReason::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
+        if let Some(deadline) = self.deadline {
+            if self.deadline_countdown == 0 {
+                self.deadline_countdown = DEADLINE_CHECK_INTERVAL;
+                if Instant::now() >= deadline {
+                    return Some(TerminationReason::DeadlineExceeded);
+                }
+            }
+            self.deadline_countdown -= 1;
+        }
+        None
+    }
+
+    /// Called once the terminated script unwound to the embedder.
+    pub(crate) fn finish_termination(&mut self) {
+        if let Some(reason) = self.terminating.take() {
+            if reason == TerminationReason::Interrupted {
+                self.requested.store(false, Ordering::Release);
+            }
+            self.last_termination = Some(reason);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::options::Options;
+    use crate::vm::context::Context;
+    use crate::vm::interrupt::TerminationReason;
+    use crate::vm::testing::assert_js;
+    use crate::Platform;
+
+    #[test]
+    fn test_termination() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let mut ctx = Context::new(&mut vm);
+        vm.set_fuel(Some(10_000));
+
+        let result = ctx.eval("try { while (true) {} } catch (e) {} 'caught'");
+        assert!(result.is_err());
+        assert_eq!(
+            vm.take_termination_reason(),
+            Some(TerminationReason::OutOfFuel)
+        );
+
+        vm.set_fuel(None);
+        let handle = vm.interrupt_handle();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            handle.interrupt();
+        });
+        let result = ctx.eval("function f() { for (;;) {} } try { f() } catch (e) {}");
+        thread.join().unwrap();
+        assert!(result.is_err());
+        assert_eq!(
+            vm.take_termination_reason(),
+            Some(TerminationReason::Interrupted)
+        );
+
+        assert_js(
+            ctx,
+            "let x = 0; for (let i = 0; i < 100; i++) { x += i; } x === 4950",
+        );
+    }
+}
@@ -6,7 +6,92 @@ use super::value::HashValueZero;
 use crate::prelude::*;
 use std::collections::HashMap;
 use std::intrinsics::*;
-pub type MapInternal = HashMap<HashValueZero, JsValue>;
+
+/// Entries of a `Map` or `Set`, stored under the private `[[MapData]]` or `[[SetData]]` property
+/// of the object. Entries are kept in insertion order. A deleted entry leaves a hole so that
+/// iterators that are in progress keep their position, as the spec requires.
+#[derive(Default)]
+pub struct MapInternal {
+    indices: HashMap<HashValueZero, usize>,
+    entries: Vec<Option<(JsValue, JsValue)>>,
+}
+
+impl MapInternal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn contains_key(&self, key: JsValue) -> bool {
+        self.indices.contains_key(&HashValueZero(key))
+    }
+
+    pub fn get(&self, key: JsValue) -> Option<JsValue> {
+        let index = *self.indices.get(&HashValueZero(key))?;
+        self.entries[index].map(|(_, value)| value)
+    }
+
+    /// Set the value of `key`, returning the previous value. New keys are appended, `-0` is
+    /// stored as `+0`.
+    pub fn insert(&mut self, key: JsValue, value: JsValue) -> Option<JsValue> {
+        if let Some(index) = self.indices.get(&HashValueZero(key)) {
+            let entry = self.entries[*index].as_mut().unwrap();
+            return Some(std::mem::replace(&mut entry.1, value));
+        }
+        let key = if key.is_number() && key.get_number() == 0.0 {
+            JsValue::new(0i32)
+        } else {
+            key
+        };
+        self.indices.insert(HashValueZero(key), self.entries.len());
+        self.entries.push(Some((key, value)));
+        None
+    }
+
+    pub fn remove(&mut self, key: JsValue) -> Option<JsValue> {
+        let index = self.indices.remove(&HashValueZero(key))?;
+        self.entries[index].take().map(|(_, value)| value)
+    }
+
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+    }
+
+    /// Number of slots, including holes left by deleted entries. Iterators walk the slots up to
+    /// this bound with [MapInternal::entry_at] so that they see entries added while iterating.
+    pub fn slots(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entry_at(&self, slot: usize) -> Option<(JsValue, JsValue)> {
+        self.entries.get(slot).copied().flatten()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (JsValue, JsValue)> + '_ {
+        self.entries.iter().filter_map(|entry| *entry)
+    }
+}
+
+impl GcCell for MapInternal {}
+
+impl Trace for MapInternal {
+    fn trace(&self, visitor: &mut Visitor) {
+        for (key, value) in self.iter() {
+            key.trace(visitor);
+            value.trace(visitor);
+        }
+    }
+}
 
 pub struct JsMap {
     storage: MapInternal,
@@ -22,20 +107,17 @@ impl JsMap {
     }
 
     pub fn has(&self, val: JsValue) -> bool {
-        let val = HashValueZero(val);
-        self.storage.contains_key(&val)
+        self.storage.contains_key(val)
     }
 
     pub fn get(&self, val: JsValue) -> JsValue {
-        let key = HashValueZero(val);
         self.storage
-            .get(&key)
-            .copied()
+            .get(val)
             .unwrap_or(JsValue::encode_undefined_value())
     }
 
     pub fn set(&mut self, key: JsValue, val: JsValue) -> Option<JsValue> {
-        self.storage.insert(HashValueZero(key), val)
+        self.storage.insert(key, val)
     }
 
     pub fn clear(&mut self) {
@@ -43,7 +125,7 @@ impl JsMap {
     }
 
     pub fn delete(&mut self, key: JsValue) -> Option<JsValue> {
-        self.storage.remove(&HashValueZero(key))
+        self.storage.remove(key)
     }
 
     pub fn initialize(
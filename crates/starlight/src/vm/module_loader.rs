@@ -0,0 +1,456 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Module resolution and loading.
+//!
+//! Every `import` goes through the [ModuleLoader] registered on the [Context] (or, if the
+//! context has none, on its [VirtualMachine](super::VirtualMachine)). The loader first turns the
+//! specifier into a key that uniquely identifies the module and then supplies the module for
+//! that key. Modules are cached per context by their resolved key so `load` is called at most
+//! once per module.
+//!
+//! Embedders that keep scripts outside of the filesystem can register an
+//! [InMemoryModuleLoader] or implement [ModuleLoader] themselves:
+//!
+//! ```ignore
+//! let mut loader = InMemoryModuleLoader::new();
+//! loader.insert("lodash", "export function chunk(array, size) { ... }");
+//! loader.insert("/app/main.js", "import { chunk } from 'lodash';");
+//! ctx.set_module_loader(loader);
+//! ```
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{context::Context, object::JsObject, value::JsValue};
+use crate::gc::cell::GcPointer;
+
+/// Module supplied by [ModuleLoader::load].
+pub enum ModuleSource {
+    /// ES module source text. It is compiled and evaluated the first time it is imported.
+    Source(String),
+    /// Native module, `init` receives the module object and has to populate its `@exports`.
+    Native(fn(GcPointer<Context>, GcPointer<JsObject>) -> Result<(), JsValue>),
+    /// Prebuilt module object that contains an `@exports` property.
+    Module(GcPointer<JsObject>),
+}
+
+/// Resolves import specifiers and supplies modules for them.
+pub trait ModuleLoader {
+    /// Resolve `specifier` imported from a module located in `base`. `base` is the directory of
+    /// the importing module and is empty for modules evaluated without a path.
+    ///
+    /// The returned key is used to cache the module and is passed to [ModuleLoader::load].
+    /// Relative imports inside the loaded module are resolved against its parent directory.
+    fn resolve(
+        &self,
+        ctx: GcPointer<Context>,
+        specifier: &str,
+        base: &str,
+    ) -> Result<String, JsValue>;
+
+    /// Supply the module identified by `key` returned from [ModuleLoader::resolve].
+    fn load(&self, ctx: GcPointer<Context>, key: &str) -> Result<ModuleSource, JsValue>;
+}
+
+fn not_found(ctx: GcPointer<Context>, specifier: &str, base: &str) -> JsValue {
+    let message = if base.is_empty() {
+        format!("Module '{}' not found", specifier)
+    } else {
+        format!(
+            "Module '{}' not found (imported from '{}')",
+            specifier, base
+        )
+    };
+    JsValue::new(ctx.new_reference_error(message))
+}
+
+fn is_relative(specifier: &str) -> bool {
+    specifier.starts_with("./")
+        || specifier.starts_with("../")
+        || specifier == "."
+        || specifier == ".."
+}
+
+/// Lexically normalize `path`, i.e. remove `.` components and resolve `..` without touching
+/// the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    result.push("..");
+                }
+            }
+            component => result.push(component.as_os_str()),
+        }
+    }
+    result
+}
+
+/// Directory that relative imports of the module at `path` are resolved against.
+pub fn module_directory(path: &str) -> String {
+    let path = Path::new(path);
+    let path = path.canonicalize().unwrap_or_else(|_| normalize_path(path));
+    path.parent()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn read_module(ctx: GcPointer<Context>, key: &str) -> Result<ModuleSource, JsValue> {
+    match std::fs::read_to_string(key) {
+        Ok(source) => Ok(ModuleSource::Source(source)),
+        Err(e) => Err(JsValue::new(
+            ctx.new_type_error(format!("Failed to read module '{}': {}", key, e)),
+        )),
+    }
+}
+
+/// Loads modules from the filesystem. Every specifier is a path relative to the importing
+/// module, modules evaluated without a path resolve imports against `root`.
+pub struct FileSystemModuleLoader {
+    root: PathBuf,
+}
+
+impl FileSystemModuleLoader {
+    /// Loader resolving top level imports against the current working directory.
+    pub fn new() -> Self {
+        Self::with_root(PathBuf::from("."))
+    }
+
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn base_dir(&self, base: &str) -> PathBuf {
+        if base.is_empty() {
+            self.root.clone()
+        } else {
+            PathBuf::from(base)
+        }
+    }
+}
+
+impl Default for FileSystemModuleLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleLoader for FileSystemModuleLoader {
+    fn resolve(
+        &self,
+        ctx: GcPointer<Context>,
+        specifier: &str,
+        base: &str,
+    ) -> Result<String, JsValue> {
+        let path = self.base_dir(base).join(specifier);
+        match path.canonicalize() {
+            Ok(path) if path.is_file() => Ok(path.to_string_lossy().into_owned()),
+            _ => Err(not_found(ctx, specifier, base)),
+        }
+    }
+
+    fn load(&self, ctx: GcPointer<Context>, key: &str) -> Result<ModuleSource, JsValue> {
+        read_module(ctx, key)
+    }
+}
+
+/// Serves module sources from a map, e.g. scripts stored in a database. Keys are normalized
+/// paths: relative specifiers are resolved against the directory of the importing module and
+/// any other specifier (including bare ones like `"lodash"`) is looked up as is.
+#[derive(Default)]
+pub struct InMemoryModuleLoader {
+    modules: HashMap<String, String>,
+}
+
+impl InMemoryModuleLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `source` under `name`. Returns the previous source of the module if any.
+    pub fn insert(&mut self, name: impl AsRef<str>, source: impl Into<String>) -> Option<String> {
+        let name = normalize_path(Path::new(name.as_ref()));
+        self.modules
+            .insert(name.to_string_lossy().into_owned(), source.into())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let name = normalize_path(Path::new(name));
+        self.modules.remove(&*name.to_string_lossy())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        let name = normalize_path(Path::new(name));
+        self.modules.contains_key(&*name.to_string_lossy())
+    }
+}
+
+impl ModuleLoader for InMemoryModuleLoader {
+    fn resolve(
+        &self,
+        ctx: GcPointer<Context>,
+        specifier: &str,
+        base: &str,
+    ) -> Result<String, JsValue> {
+        let path = if is_relative(specifier) {
+            normalize_path(&Path::new(base).join(specifier))
+        } else {
+            normalize_path(Path::new(specifier))
+        };
+        let key = path.to_string_lossy().into_owned();
+        if self.modules.contains_key(&key) {
+            Ok(key)
+        } else {
+            Err(not_found(ctx, specifier, base))
+        }
+    }
+
+    fn load(&self, ctx: GcPointer<Context>, key: &str) -> Result<ModuleSource, JsValue> {
+        match self.modules.get(key) {
+            Some(source) => Ok(ModuleSource::Source(source.clone())),
+            None => Err(not_found(ctx, key, "")),
+        }
+    }
+}
+
+/// Node.js style resolution on top of the filesystem. Relative and absolute specifiers are
+/// resolved like files (`./a` tries `./a`, `./a.js`, `./a.mjs` and `./a/index.js`), bare
+/// specifiers are searched for in `node_modules` directories of the importing module and all
+/// of its ancestors. Package directories are entered through the `module` or `main` field of
+/// their `package.json`.
+pub struct NodeModuleLoader {
+    root: PathBuf,
+}
+
+impl NodeModuleLoader {
+    const EXTENSIONS: &'static [&'static str] = &["js", "mjs"];
+
+    /// Loader resolving top level imports against the current working directory.
+    pub fn new() -> Self {
+        Self::with_root(PathBuf::from("."))
+    }
+
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn load_as_file(path: &Path) -> Option<PathBuf> {
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        let name = path.file_name()?.to_str()?;
+        Self::EXTENSIONS
+            .iter()
+            .map(|extension| path.with_file_name(format!("{}.{}", name, extension)))
+            .find(|path| path.is_file())
+    }
+
+    fn load_as_directory(path: &Path) -> Option<PathBuf> {
+        if let Ok(package) = std::fs::read_to_string(path.join("package.json")) {
+            let fields = package_json_fields(&package);
+            for field in &["module", "main"] {
+                if let Some(main) = fields.get(*field) {
+                    let main = path.join(main);
+                    if let Some(found) =
+                        Self::load_as_file(&main).or_else(|| Self::load_as_index(&main))
+                    {
+                        return Some(found);
+                    }
+                }
+            }
+        }
+        Self::load_as_index(path)
+    }
+
+    fn load_as_index(path: &Path) -> Option<PathBuf> {
+        Self::EXTENSIONS
+            .iter()
+            .map(|extension| path.join(format!("index.{}", extension)))
+            .find(|path| path.is_file())
+    }
+
+    fn load_path(path: &Path) -> Option<PathBuf> {
+        Self::load_as_file(path).or_else(|| Self::load_as_directory(path))
+    }
+}
+
+impl Default for NodeModuleLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleLoader for NodeModuleLoader {
+    fn resolve(
+        &self,
+        ctx: GcPointer<Context>,
+        specifier: &str,
+        base: &str,
+    ) -> Result<String, JsValue> {
+        let base_dir = if base.is_empty() {
+            self.root.clone()
+        } else {
+            PathBuf::from(base)
+        };
+        let found = if is_relative(specifier) || Path::new(specifier).is_absolute() {
+            Self::load_path(&base_dir.join(specifier))
+        } else {
+            let base_dir = base_dir.canonicalize().unwrap_or(base_dir);
+            base_dir
+                .ancestors()
+                .filter(|dir| !matches!(dir.file_name(), Some(name) if name == "node_modules"))
+                .find_map(|dir| Self::load_path(&dir.join("node_modules").join(specifier)))
+        };
+        found
+            .and_then(|path| path.canonicalize().ok())
+            .map(|path| path.to_string_lossy().into_owned())
+            .ok_or_else(|| not_found(ctx, specifier, base))
+    }
+
+    fn load(&self, ctx: GcPointer<Context>, key: &str) -> Result<ModuleSource, JsValue> {
+        read_module(ctx, key)
+    }
+}
+
+/// Top level string fields of a `package.json` file. Malformed input yields the fields read
+/// before the error.
+fn package_json_fields(source: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut chars = source.chars().peekable();
+
+    fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+        if chars.next()? != '"' {
+            return None;
+        }
+        let mut result = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(result),
+                '\\' => match chars.next()? {
+                    'n' => result.push('\n'),
+                    't' => result.push('\t'),
+                    'r' => result.push('\r'),
+                    'b' => result.push('\u{8}'),
+                    'f' => result.push('\u{c}'),
+                    'u' => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&code, 16).ok()?;
+                        result.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    c => result.push(c),
+                },
+                c => result.push(c),
+            }
+        }
+    }
+
+    /// Skip a value that is not a string.
+    fn skip_value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<()> {
+        let mut depth = 0usize;
+        loop {
+            match *chars.peek()? {
+                '"' => {
+                    parse_string(chars)?;
+                    continue;
+                }
+                '{' | '[' => depth += 1,
+                '}' | ']' if depth == 0 => return Some(()),
+                '}' | ']' => depth -= 1,
+                ',' if depth == 0 => return Some(()),
+                _ => {}
+            }
+            chars.next();
+        }
+    }
+
+    let mut parse = || -> Option<()> {
+        skip_whitespace(&mut chars);
+        if chars.next()? != '{' {
+            return None;
+        }
+        loop {
+            skip_whitespace(&mut chars);
+            if *chars.peek()? == '}' {
+                return Some(());
+            }
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            if chars.next()? != ':' {
+                return None;
+            }
+            skip_whitespace(&mut chars);
+            if *chars.peek()? == '"' {
+                let value = parse_string(&mut chars)?;
+                fields.insert(key, value);
+            } else {
+                skip_value(&mut chars)?;
+            }
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                '}' => return Some(()),
+                _ => return None,
+            }
+        }
+    };
+    parse();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use crate::vm::{
+        context::Context,
+        module_loader::*,
+        testing::{assert_js, eval_module},
+    };
+    use crate::Platform;
+
+    #[test]
+    fn test_in_memory_module_loader() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let mut ctx = Context::new(&mut vm);
+
+        let mut loader = InMemoryModuleLoader::new();
+        loader.insert("lodash", "export function double(x) { return x * 2; }");
+        loader.insert("lib/math.js", "export let answer = 21;");
+        loader.insert(
+            "lib/main.js",
+            "import { answer } from './math.js'; export let result = answer;",
+        );
+        ctx.set_module_loader(loader);
+
+        eval_module(
+            ctx,
+            "import { double } from 'lodash';
+             import { result } from 'lib/main.js';
+             globalThis.moduleResult = double(result);",
+        );
+        assert_js(ctx, "globalThis.moduleResult === 42");
+    }
+
+    #[test]
+    fn test_package_json_fields() {
+        let fields = package_json_fields(
+            r#"{ "name": "lodash", "files": ["a", {"b": "}"}], "main": "dist/lodash.js" }"#,
+        );
+        assert_eq!(
+            fields.get("main").map(String::as_str),
+            Some("dist/lodash.js")
+        );
+        assert_eq!(fields.get("name").map(String::as_str), Some("lodash"));
+        assert!(!fields.contains_key("files"));
+    }
+}
@@ -880,6 +880,14 @@ impl JsObject {
         //("EMPTY STRUCT {:p}", structure);
         Self::new(ctx, &structure, Self::class(), ObjectTag::Ordinary)
     }
+    /// Like [JsObject::new_empty] but throws `RangeError` when the heap limit is reached.
+    pub fn try_new_empty(ctx: GcPointer<Context>) -> Result<GcPointer<Self>, JsValue> {
+        letroot!(
+            structure = stack,
+            ctx.global_data().empty_object_struct.unwrap()
+        );
+        Self::try_new(ctx, &structure, Self::class(), ObjectTag::Ordinary)
+    }
     /// Create new JS object instance with provided class, structure and tag.
     pub fn new(
         mut ctx: GcPointer<Context>,
@@ -887,6 +895,27 @@ impl JsObject {
         class: &'static Class,
         tag: ObjectTag,
     ) -> GcPointer<Self> {
+        let this = Self::build(ctx, structure, class, tag);
+        ctx.heap().allocate(this)
+    }
+
+    /// Like [JsObject::new] but throws `RangeError` when the heap limit is reached.
+    pub fn try_new(
+        ctx: GcPointer<Context>,
+        structure: &GcPointer<Structure>,
+        class: &'static Class,
+        tag: ObjectTag,
+    ) -> Result<GcPointer<Self>, JsValue> {
+        let this = Self::build(ctx, structure, class, tag);
+        ctx.try_allocate(this)
+    }
+
+    fn build(
+        ctx: GcPointer<Context>,
+        structure: &GcPointer<Structure>,
+        class: &'static Class,
+        tag: ObjectTag,
+    ) -> Self {
         let init = IndexedElements::new(ctx);
         //root!(indexed = stack, ctx.heap().allocate(init));
         letroot!(
@@ -898,7 +927,7 @@ impl JsObject {
             )
         );
         //("CREATE WITH {:p}", *structure);
-        let this = Self {
+        Self {
             structure: *structure,
             class,
 
@@ -907,8 +936,7 @@ impl JsObject {
             indexed: init,
             flags: OBJ_FLAG_EXTENSIBLE,
             tag,
-        };
-        ctx.heap().allocate(this)
+        }
     }
 
     // only for internal use
@@ -0,0 +1,947 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Conversions between [JsValue] and Rust types implementing `serde` traits.
+//!
+//! Enabled by the `serde` cargo feature. [to_value] builds plain objects, arrays and strings
+//! in a [Context] and [from_value] reads them back:
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     name: String,
+//!     retries: u32,
+//! }
+//!
+//! let value = starlight::vm::serde::to_value(ctx, &config)?;
+//! let config: Config = starlight::vm::serde::from_value(ctx, value)?;
+//! ```
+//!
+//! The data model follows `serde_json`: unit and `None` become `null`, sequences and tuples
+//! become arrays, maps and structs become objects and enums are externally tagged, i.e. a unit
+//! variant is a string and any other variant is an object with a single key.
+//!
+//! Deserialization errors carry the path of the offending value, e.g.
+//! `invalid type: string "ten", expected u32 at $.servers[1].retries`.
+use std::fmt::{self, Display};
+
+use ::serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor},
+    ser::{self, Serialize},
+};
+
+use super::{
+    array::JsArray,
+    context::Context,
+    object::{EnumerationMode, JsObject},
+    string::JsString,
+    symbol_table::{Internable, Symbol},
+    value::JsValue,
+};
+use crate::gc::cell::GcPointer;
+
+/// Error produced by [to_value] and [from_value].
+#[derive(Debug, Clone)]
+pub struct Error {
+    message: String,
+    path: Option<String>,
+}
+
+impl Error {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            path: None,
+        }
+    }
+
+    /// Error for an exception thrown while reading or writing a property.
+    fn exception(ctx: GcPointer<Context>, exception: JsValue) -> Self {
+        let message = exception
+            .to_string(ctx)
+            .unwrap_or_else(|_| String::from("uncaught exception"));
+        Self::new(message)
+    }
+
+    /// Record `path` unless an inner value already did so.
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.to_owned());
+        }
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Path of the value that caused this error, `$` being the root value. `None` for
+    /// serialization errors.
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// Convert this error into a `TypeError` that can be thrown to JS code.
+    pub fn into_js(self, ctx: GcPointer<Context>) -> JsValue {
+        JsValue::new(ctx.new_type_error(self.to_string()))
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "{} at {}", self.message, path),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+/// Serialize `value` into a JS value allocated in `ctx`.
+pub fn to_value<T: Serialize + ?Sized>(
+    ctx: GcPointer<Context>,
+    value: &T,
+) -> Result<JsValue, Error> {
+    value.serialize(Serializer::new(ctx))
+}
+
+/// Deserialize an instance of `T` from `value`.
+pub fn from_value<T: DeserializeOwned>(
+    ctx: GcPointer<Context>,
+    value: JsValue,
+) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(ctx, value))
+}
+
+/// Property key for `name`, canonical array indices become [Symbol::Index].
+fn property_key(name: &str) -> Symbol {
+    match name.parse::<u32>() {
+        Ok(index) if index != u32::MAX && index.to_string() == name => Symbol::Index(index),
+        _ => name.intern(),
+    }
+}
+
+fn put(
+    ctx: GcPointer<Context>,
+    mut object: GcPointer<JsObject>,
+    key: Symbol,
+    value: JsValue,
+) -> Result<(), Error> {
+    object
+        .put(ctx, key, value, true)
+        .map_err(|exception| Error::exception(ctx, exception))
+}
+
+/// Serializer producing [JsValue]s.
+pub struct Serializer {
+    ctx: GcPointer<Context>,
+}
+
+impl Serializer {
+    pub fn new(ctx: GcPointer<Context>) -> Self {
+        Self { ctx }
+    }
+
+    fn number(&self, value: f64) -> Result<JsValue, Error> {
+        Ok(JsValue::new(value))
+    }
+
+    /// `{ variant: value }`, the representation of non-unit enum variants.
+    fn tagged(&self, variant: &str, value: JsValue) -> Result<JsValue, Error> {
+        let object = JsObject::new_empty(self.ctx);
+        put(self.ctx, object, property_key(variant), value)?;
+        Ok(JsValue::new(object))
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = JsValue;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeObject;
+
+    fn serialize_bool(self, v: bool) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v as i32))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v as i32))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<JsValue, Error> {
+        if v as i32 as i64 == v {
+            return Ok(JsValue::new(v as i32));
+        }
+        self.number(v as f64)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v as i32))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<JsValue, Error> {
+        Ok(JsValue::new(v as i32))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<JsValue, Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<JsValue, Error> {
+        if v <= i32::MAX as u64 {
+            return Ok(JsValue::new(v as i32));
+        }
+        self.number(v as f64)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<JsValue, Error> {
+        self.number(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<JsValue, Error> {
+        self.number(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<JsValue, Error> {
+        let mut buf = [0; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<JsValue, Error> {
+        Ok(JsValue::new(JsString::new(self.ctx, v)))
+    }
+
+    /// Bytes are serialized as an array of numbers, the same way `serde_json` does it.
+    fn serialize_bytes(self, v: &[u8]) -> Result<JsValue, Error> {
+        let array = JsArray::new(self.ctx, v.len() as u32);
+        for (index, byte) in v.iter().enumerate() {
+            put(
+                self.ctx,
+                array,
+                Symbol::Index(index as u32),
+                JsValue::new(*byte as i32),
+            )?;
+        }
+        Ok(JsValue::new(array))
+    }
+
+    fn serialize_none(self) -> Result<JsValue, Error> {
+        Ok(JsValue::encode_null_value())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<JsValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<JsValue, Error> {
+        Ok(JsValue::encode_null_value())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<JsValue, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<JsValue, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<JsValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<JsValue, Error> {
+        let value = value.serialize(Serializer::new(self.ctx))?;
+        self.tagged(variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            ctx: self.ctx,
+            array: JsArray::new(self.ctx, len.unwrap_or(0) as u32),
+            index: 0,
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        let mut array = self.serialize_seq(Some(len))?;
+        array.variant = Some(variant);
+        Ok(array)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeObject, Error> {
+        Ok(SerializeObject {
+            ctx: self.ctx,
+            object: JsObject::new_empty(self.ctx),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeObject, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeObject, Error> {
+        let mut object = self.serialize_map(Some(len))?;
+        object.variant = Some(variant);
+        Ok(object)
+    }
+}
+
+/// Builds a `JsArray` for sequences, tuples and tuple variants.
+pub struct SerializeArray {
+    ctx: GcPointer<Context>,
+    array: GcPointer<JsObject>,
+    index: u32,
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer::new(self.ctx))?;
+        put(self.ctx, self.array, Symbol::Index(self.index), value)?;
+        self.index += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<JsValue, Error> {
+        let array = JsValue::new(self.array);
+        match self.variant {
+            Some(variant) => Serializer::new(self.ctx).tagged(variant, array),
+            None => Ok(array),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+/// Builds a plain `JsObject` for maps, structs and struct variants.
+pub struct SerializeObject {
+    ctx: GcPointer<Context>,
+    object: GcPointer<JsObject>,
+    key: Option<Symbol>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeObject {
+    fn field<T: ?Sized + Serialize>(&mut self, key: Symbol, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer::new(self.ctx))?;
+        put(self.ctx, self.object, key, value)
+    }
+
+    fn finish(self) -> Result<JsValue, Error> {
+        let object = JsValue::new(self.object);
+        match self.variant {
+            Some(variant) => Serializer::new(self.ctx).tagged(variant, object),
+            None => Ok(object),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(Serializer::new(self.ctx))?;
+        let key = if key.is_jsstring() {
+            property_key(key.get_jsstring().as_str())
+        } else if key.is_number() || key.is_bool() {
+            property_key(
+                &key.to_string(self.ctx)
+                    .map_err(|e| Error::exception(self.ctx, e))?,
+            )
+        } else {
+            return Err(Error::new("map key must be a string, a number or a bool"));
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new("serialize_value called before serialize_key"))?;
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(property_key(key), value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeObject {
+    type Ok = JsValue;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(property_key(key), value)
+    }
+
+    fn end(self) -> Result<JsValue, Error> {
+        self.finish()
+    }
+}
+
+/// Deserializer reading a [JsValue]. `path` is the location of the value inside the root
+/// value and is attached to every error raised while reading it.
+pub struct Deserializer {
+    ctx: GcPointer<Context>,
+    value: JsValue,
+    path: String,
+}
+
+impl Deserializer {
+    pub fn new(ctx: GcPointer<Context>, value: JsValue) -> Self {
+        Self {
+            ctx,
+            value,
+            path: String::from("$"),
+        }
+    }
+
+    fn child(&self, value: JsValue, path: String) -> Self {
+        Self {
+            ctx: self.ctx,
+            value,
+            path,
+        }
+    }
+
+    fn get(&self, mut object: GcPointer<JsObject>, key: Symbol) -> Result<JsValue, Error> {
+        object
+            .get(self.ctx, key)
+            .map_err(|exception| Error::exception(self.ctx, exception).at(&self.path))
+    }
+
+    fn is_array(&self) -> bool {
+        self.value.is_jsobject() && self.value.get_jsobject().is_class(JsArray::class())
+    }
+
+    /// Keys of the own enumerable string keyed properties of an object in property order. Values
+    /// are read only when they are deserialized: getters and conversions may collect, and only
+    /// the object, not values copied out of it, is reachable by the collector meanwhile.
+    fn keys(&self, mut object: GcPointer<JsObject>) -> Vec<Symbol> {
+        let mut keys = vec![];
+        object.get_own_property_names(
+            self.ctx,
+            &mut |name, _| {
+                if let Symbol::Key(_) | Symbol::Index(_) = name {
+                    keys.push(name);
+                }
+            },
+            EnumerationMode::Default,
+        );
+        keys
+    }
+
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        let value = self.value;
+        if value.is_undefined() || value.is_null() {
+            de::Unexpected::Unit
+        } else if value.is_bool() {
+            de::Unexpected::Bool(value.get_bool())
+        } else if value.is_number() {
+            de::Unexpected::Float(value.get_number())
+        } else if value.is_jsstring() {
+            de::Unexpected::Other("string")
+        } else if value.is_callable() {
+            de::Unexpected::Other("function")
+        } else if self.is_array() {
+            de::Unexpected::Seq
+        } else if value.is_jsobject() {
+            de::Unexpected::Map
+        } else {
+            de::Unexpected::Other("symbol")
+        }
+    }
+
+    fn invalid_type(&self, expected: &dyn de::Expected) -> Error {
+        let error: Error = de::Error::invalid_type(self.unexpected(), expected);
+        error.at(&self.path)
+    }
+
+    fn deserialize_array<'de, V: Visitor<'de>>(&self, visitor: V) -> Result<V::Value, Error> {
+        let array = self.value.get_jsobject();
+        let length = self.get(array, "length".intern())?;
+        let length = length
+            .to_uint32(self.ctx)
+            .map_err(|exception| Error::exception(self.ctx, exception).at(&self.path))?;
+        let mut access = ArrayAccess {
+            de: self,
+            array,
+            index: 0,
+            length,
+        };
+        let value = visitor
+            .visit_seq(&mut access)
+            .map_err(|error| error.at(&self.path))?;
+        if access.index < access.length {
+            let error: Error = de::Error::invalid_length(length as usize, &"fewer elements");
+            return Err(error.at(&self.path));
+        }
+        Ok(value)
+    }
+
+    fn deserialize_object<'de, V: Visitor<'de>>(&self, visitor: V) -> Result<V::Value, Error> {
+        let object = self.value.get_jsobject();
+        visitor
+            .visit_map(ObjectAccess {
+                de: self,
+                object,
+                keys: self.keys(object).into_iter(),
+                key: None,
+            })
+            .map_err(|error| error.at(&self.path))
+    }
+}
+
+fn key_path(path: &str, key: &str) -> String {
+    let is_identifier = matches!(key.chars().next(), Some(c) if c.is_alphabetic() || c == '_' || c == '$')
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+    if is_identifier {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{:?}]", path, key)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value;
+        let result: Result<V::Value, Error> = if value.is_undefined() || value.is_null() {
+            visitor.visit_unit()
+        } else if value.is_bool() {
+            visitor.visit_bool(value.get_bool())
+        } else if value.is_int32() {
+            visitor.visit_i32(value.get_int32())
+        } else if value.is_number() {
+            let number = value.get_number();
+            // integral doubles are visited as integers so that they deserialize into integer types
+            if number.trunc() == number && number.abs() <= 9007199254740991.0 {
+                visitor.visit_i64(number as i64)
+            } else {
+                visitor.visit_f64(number)
+            }
+        } else if value.is_jsstring() {
+            visitor.visit_str(value.get_jsstring().as_str())
+        } else if value.is_callable() {
+            return Err(Error::new("cannot deserialize a function").at(&self.path));
+        } else if self.is_array() {
+            return self.deserialize_array(visitor);
+        } else if value.is_jsobject() {
+            return self.deserialize_object(visitor);
+        } else {
+            return Err(Error::new("cannot deserialize a symbol").at(&self.path));
+        };
+        result.map_err(|error| error.at(&self.path))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_undefined() || self.value.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.is_array() {
+            self.deserialize_array(visitor)
+        } else {
+            Err(self.invalid_type(&visitor))
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.value.is_jsobject() && !self.value.is_callable() && !self.is_array() {
+            self.deserialize_object(visitor)
+        } else {
+            Err(self.invalid_type(&visitor))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let value = self.value;
+        if value.is_jsstring() {
+            let variant = value.get_jsstring().as_str().to_owned();
+            return visitor
+                .visit_enum(variant.into_deserializer())
+                .map_err(|error: Error| error.at(&self.path));
+        }
+        if value.is_jsobject() && !value.is_callable() && !self.is_array() {
+            let object = value.get_jsobject();
+            let keys = self.keys(object);
+            if let [key] = keys[..] {
+                let variant = self.ctx.description(key);
+                let value = self.get(object, key)?;
+                let path = key_path(&self.path, &variant);
+                return visitor.visit_enum(EnumAccess {
+                    variant,
+                    value: self.child(value, path),
+                });
+            }
+            return Err(Error::new(
+                "expected an object with a single key identifying the enum variant",
+            )
+            .at(&self.path));
+        }
+        Err(self.invalid_type(&"string or object"))
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'a> {
+    de: &'a Deserializer,
+    array: GcPointer<JsObject>,
+    index: u32,
+    length: u32,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for ArrayAccess<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index >= self.length {
+            return Ok(None);
+        }
+        let path = format!("{}[{}]", self.de.path, self.index);
+        let value = self.de.get(self.array, Symbol::Index(self.index))?;
+        self.index += 1;
+        seed.deserialize(self.de.child(value, path.clone()))
+            .map(Some)
+            .map_err(|error| error.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.length - self.index) as usize)
+    }
+}
+
+struct ObjectAccess<'a> {
+    de: &'a Deserializer,
+    object: GcPointer<JsObject>,
+    keys: std::vec::IntoIter<Symbol>,
+    /// Key and path of the property whose value is read by the next `next_value_seed`.
+    key: Option<(Symbol, String)>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for ObjectAccess<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.keys.next() {
+            Some(key) => {
+                let name = self.de.ctx.description(key);
+                let path = key_path(&self.de.path, &name);
+                let result = seed
+                    .deserialize(name.as_str().into_deserializer())
+                    .map_err(|error: Error| error.at(&path))?;
+                self.key = Some((key, path));
+                Ok(Some(result))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, path) = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new("next_value_seed called before next_key_seed"))?;
+        let value = self.de.get(self.object, key)?;
+        seed.deserialize(self.de.child(value, path.clone()))
+            .map_err(|error| error.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+}
+
+struct EnumAccess {
+    variant: String,
+    value: Deserializer,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = Deserializer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Deserializer), Error> {
+        let path = self.value.path.clone();
+        let variant = seed
+            .deserialize(self.variant.into_deserializer())
+            .map_err(|error: Error| error.at(&path))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        if self.value.is_undefined() || self.value.is_null() {
+            Ok(())
+        } else {
+            Err(self.invalid_type(&"unit variant"))
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let path = self.path.clone();
+        seed.deserialize(self).map_err(|error| error.at(&path))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::options::Options;
+    use crate::vm::{context::Context, serde::*, testing::eval};
+    use crate::Platform;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let mut map = BTreeMap::new();
+        map.insert(String::from("answer"), vec![Some(42.5), None]);
+        let value = to_value(ctx, &map).unwrap_or_else(|error| panic!("{}", error));
+        let back: BTreeMap<String, Vec<Option<f64>>> =
+            from_value(ctx, value).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(back, map);
+
+        let value = eval(ctx, "({ servers: [{ port: 80 }, { port: 'eighty' }] })");
+        let error = from_value::<BTreeMap<String, Vec<BTreeMap<String, u16>>>>(ctx, value)
+            .err()
+            .expect("deserialization should fail");
+        assert_eq!(error.path(), Some("$.servers[1].port"));
+    }
+
+    #[test]
+    fn test_deserialize_collecting_getters() {
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        // every value is only reachable until it is deserialized, the collection run by the
+        // getter of `b` must not free the value returned by the getter of `a`
+        let value = eval(
+            ctx,
+            "({ get a() { return { name: 'a' + 1 }; }, get b() { gc(); return { name: 'b' }; } })",
+        );
+        let map: BTreeMap<String, BTreeMap<String, String>> =
+            from_value(ctx, value).unwrap_or_else(|error| panic!("{}", error));
+        assert_eq!(map["a"]["name"], "a1");
+        assert_eq!(map["b"]["name"], "b");
+    }
+}
@@ -8,7 +8,7 @@ use super::Context;
 use crate::gc::cell::{GcCell, GcPointer, Trace, Visitor};
 use crate::prelude::*;
 use crate::vm::object::JsObject;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 use std::mem::ManuallyDrop;
 use std::sync::atomic::Ordering;
 use std::{mem::MaybeUninit, sync::atomic::AtomicU32};
@@ -53,15 +53,25 @@ impl SymbolTable {
             return SymbolID(*key.value());
         }
 
-        let string = Box::leak(string.to_string().into_boxed_str());
-        let make_new_key = || self.key.fetch_add(1, Ordering::Relaxed);
-        let key = *self
-            .symbols
-            .entry(string)
-            .or_insert_with(make_new_key)
-            .value();
-        self.ids.insert(key, string);
-        SymbolID(key)
+        let leaked: &'static str = Box::leak(string.to_string().into_boxed_str());
+        // the entry keeps the shard of `string` locked until the id is published, so another
+        // thread interning the same string waits and never sees an id without a description
+        match self.symbols.entry(leaked) {
+            Entry::Occupied(entry) => {
+                let key = *entry.get();
+                drop(entry);
+                unsafe {
+                    let _ = Box::from_raw(leaked as *const str as *mut str);
+                }
+                SymbolID(key)
+            }
+            Entry::Vacant(entry) => {
+                let key = self.key.fetch_add(1, Ordering::Relaxed);
+                self.ids.insert(key, leaked);
+                entry.insert(key);
+                SymbolID(key)
+            }
+        }
     }
 }
 
@@ -290,3 +300,32 @@ impl JsSymbolObject {
         obj
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SymbolTable;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_intern_across_threads() {
+        let table = Arc::new(SymbolTable::new());
+        let threads = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                std::thread::spawn(move || {
+                    for i in 0..1000 {
+                        let name = format!("symbol{}", i);
+                        let id = table.intern(&name);
+                        assert_eq!(table.description(id), name);
+                        assert_eq!(table.intern(&name), id);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(table.symbols.len(), 1000);
+        assert_eq!(table.ids.len(), 1000);
+    }
+}
@@ -0,0 +1,47 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Helpers for unit tests that evaluate JavaScript.
+use super::{context::Context, value::JsValue};
+use crate::gc::cell::GcPointer;
+
+/// Evaluate `script` and return its completion value. Panics with the thrown error.
+#[track_caller]
+pub(crate) fn eval(mut ctx: GcPointer<Context>, script: &str) -> JsValue {
+    match ctx.eval(script) {
+        Ok(value) => value,
+        Err(error) => panic!("`{}` threw {}", script, describe(ctx, error)),
+    }
+}
+
+/// Evaluate `source` as a module. Panics with the thrown error.
+#[track_caller]
+pub(crate) fn eval_module(ctx: GcPointer<Context>, source: &str) {
+    if let Err(error) = ctx.evalm(None, false, source) {
+        panic!("module threw {}", describe(ctx, error));
+    }
+}
+
+/// Run the event loop of `ctx` until it is idle. Panics with the error of a failed job.
+#[track_caller]
+pub(crate) fn run_event_loop(ctx: GcPointer<Context>) {
+    if let Err(error) = ctx.run_event_loop() {
+        panic!("event loop threw {}", describe(ctx, error));
+    }
+}
+
+/// Assert that `script` evaluates to `true`.
+#[track_caller]
+pub(crate) fn assert_js(ctx: GcPointer<Context>, script: &str) {
+    let value = eval(ctx, script);
+    if !(value.is_bool() && value.get_bool()) {
+        panic!("`{}` evaluated to {}", script, describe(ctx, value));
+    }
+}
+
+/// Convert `value` to a string for a panic message.
+pub(crate) fn describe(ctx: GcPointer<Context>, value: JsValue) -> String {
+    value
+        .to_string(ctx)
+        .unwrap_or_else(|_| "a value that cannot be converted to a string".to_owned())
+}
@@ -1,7 +1,7 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
-use crate::{gc::cell::*, jsrt::boolean::JsBoolean, vm::interpreter::SpreadValue};
+use crate::{gc::cell::*, jsrt::boolean::JsBoolean, vm::interpreter::SpreadValue, JsTryFrom};
 
 use std::{
     convert::TryFrom,
@@ -679,7 +679,7 @@ impl JsValue {
 
     pub fn to_string(&self, ctx: GcPointer<Context>) -> Result<String, JsValue> {
         if self.is_number() {
-            Ok(self.get_number().to_string())
+            Ok(crate::jsrt::number::number_to_js_string(self.get_number()))
         } else if self.is_null() {
             Ok("null".to_string())
         } else if self.is_undefined() {
@@ -1026,6 +1026,31 @@ mod tests {
         assert!(val.is_number());
         assert!(val.get_number().is_nan());
     }
+
+    #[test]
+    fn test_try_from_integer_bounds() {
+        use crate::{options::Options, vm::context::Context, JsTryFrom, Platform};
+        let mut vm = Platform::new_runtime(Options::default(), None);
+        let ctx = Context::new(&mut vm);
+
+        let two_pow_63 = 2f64.powi(63);
+        let two_pow_64 = 2f64.powi(64);
+        assert!(<u64 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(two_pow_64)).is_err());
+        assert!(<i64 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(two_pow_63)).is_err());
+        assert_eq!(
+            <i64 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(-two_pow_63)).ok(),
+            Some(i64::MIN)
+        );
+        assert_eq!(
+            <u64 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(two_pow_63)).ok(),
+            Some(1 << 63)
+        );
+        assert!(<u32 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(4294967296.0)).is_err());
+        assert_eq!(
+            <u32 as JsTryFrom<JsValue>>::try_from(ctx, JsValue::new(4294967295.0)).ok(),
+            Some(u32::MAX)
+        );
+    }
 }
 
 pub struct HashValueZero(pub JsValue);
@@ -1503,3 +1528,132 @@ impl JsFrom<String> for JsValue {
         JsValue::new(JsString::new(ctx, val))
     }
 }
+impl JsFrom<()> for JsValue {
+    fn js_from(_ctx: GcPointer<Context>, _val: ()) -> JsValue {
+        JsValue::encode_undefined_value()
+    }
+}
+impl<T> JsFrom<Option<T>> for JsValue
+where
+    JsValue: JsFrom<T>,
+{
+    fn js_from(ctx: GcPointer<Context>, val: Option<T>) -> JsValue {
+        match val {
+            Some(val) => JsValue::js_from(ctx, val),
+            None => JsValue::encode_undefined_value(),
+        }
+    }
+}
+
+fn unexpected_type(ctx: GcPointer<Context>, expected: &str, found: JsValue) -> JsValue {
+    JsValue::new(ctx.new_type_error(format!(
+        "Expected {} but found {}",
+        expected,
+        found.type_of()
+    )))
+}
+
+impl JsTryFrom<JsValue> for JsValue {
+    fn try_from(_ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        Ok(value)
+    }
+}
+
+impl JsTryFrom<JsValue> for f64 {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_number() {
+            Ok(value.get_number())
+        } else {
+            Err(unexpected_type(ctx, "number", value))
+        }
+    }
+}
+
+impl JsTryFrom<JsValue> for f32 {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        <f64 as JsTryFrom<JsValue>>::try_from(ctx, value).map(|x| x as f32)
+    }
+}
+
+macro_rules! try_from_integer {
+    ($($t: ty),*) => {$(
+        impl JsTryFrom<JsValue> for $t {
+            fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+                if value.is_int32() {
+                    if let Ok(x) = <$t as TryFrom<i32>>::try_from(value.get_int32()) {
+                        return Ok(x);
+                    }
+                } else if value.is_number() {
+                    let x = value.get_number();
+                    // `MAX as f64` rounds up to 2^64 and 2^63 for u64 and i64, so the upper bound
+                    // is the exclusive `MAX + 1`, which is a power of two and exact for every type.
+                    if x.trunc() == x && x >= <$t>::MIN as f64 && x < <$t>::MAX as f64 + 1.0 {
+                        return Ok(x as $t);
+                    }
+                } else {
+                    return Err(unexpected_type(ctx, "number", value));
+                }
+                Err(JsValue::new(ctx.new_type_error(format!(
+                    "Expected an integer in range of {} but found {}",
+                    stringify!($t),
+                    value.get_number()
+                ))))
+            }
+        })*
+    };
+}
+
+try_from_integer!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl JsTryFrom<JsValue> for bool {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_bool() {
+            Ok(value.get_bool())
+        } else {
+            Err(unexpected_type(ctx, "boolean", value))
+        }
+    }
+}
+
+impl JsTryFrom<JsValue> for String {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_jsstring() {
+            Ok(value.get_jsstring().as_str().to_owned())
+        } else {
+            Err(unexpected_type(ctx, "string", value))
+        }
+    }
+}
+
+impl JsTryFrom<JsValue> for GcPointer<JsString> {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_jsstring() {
+            Ok(value.get_jsstring())
+        } else {
+            Err(unexpected_type(ctx, "string", value))
+        }
+    }
+}
+
+impl JsTryFrom<JsValue> for GcPointer<JsObject> {
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_jsobject() {
+            Ok(value.get_jsobject())
+        } else {
+            Err(unexpected_type(ctx, "object", value))
+        }
+    }
+}
+
+/// `undefined` and `null` convert to `None`, parameters of this type may be omitted by callers
+/// of typed native functions.
+impl<T: JsTryFrom<JsValue>> JsTryFrom<JsValue> for Option<T> {
+    const OPTIONAL: bool = true;
+    fn try_from(ctx: GcPointer<Context>, value: JsValue) -> Result<Self, JsValue> {
+        if value.is_undefined() || value.is_null() {
+            Ok(None)
+        } else {
+            T::try_from(ctx, value).map(Some)
+        }
+    }
+}
@@ -0,0 +1,211 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+//! Tests of the `sl` binary.
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run `sl` with `args`, writing `stdin` to its standard input.
+fn sl(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sl"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start sl");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .expect("failed to write to sl");
+    child.wait_with_output().expect("failed to wait for sl")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_eval() {
+    let output = sl(&["-e", "print(1 + 2)"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn test_stdin() {
+    let output = sl(&["-"], "print('from ' + 'stdin')");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "from stdin\n");
+}
+
+#[test]
+fn test_script_args() {
+    let output = sl(
+        &[
+            "-e",
+            "import process from 'std:process';
+             print(process.argv.slice(1).join());
+             print(process.args().join());",
+            "--",
+            "first",
+            "--second",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "[eval],first,--second\nfirst,--second\n");
+}
+
+#[test]
+fn test_exit_codes() {
+    let output = sl(&["-e", "throw new Error('boom')"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("boom"));
+
+    let output = sl(&["-e", "syntax error ("], "");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = sl(
+        &[
+            "-e",
+            "import process from 'std:process'; print('before'); process.exit(3); print('after')",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(stdout(&output), "before\n");
+
+    let output = sl(&["missing-file.js"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_stdin_listen() {
+    let output = sl(
+        &[
+            "-e",
+            "import io from 'std:io';
+             io.stdin.listen(line => print(JSON.stringify(line)), { lines: true });",
+        ],
+        "one\r\ntwo\n\nthree",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "\"one\"\n\"two\"\n\"\"\n\"three\"\nnull\n");
+
+    let output = sl(
+        &[
+            "-e",
+            "import io from 'std:io';
+             let chunks = [];
+             io.stdin.listen(chunk => chunks.push(chunk), { encoding: 'buffer' });
+             setTimeout(function wait() {
+                 if (chunks[chunks.length - 1] !== null) return setTimeout(wait, 1);
+                 print(chunks.slice(0, -1).reduce((size, chunk) => size + chunk.byteLength, 0));
+             }, 1);",
+        ],
+        "\u{20ac}\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "4\n");
+}
+
+#[test]
+fn test_stdin_read_line() {
+    let output = sl(
+        &[
+            "-e",
+            "import io from 'std:io';
+             print(JSON.stringify(io.stdin.readLine()));
+             print(JSON.stringify(io.stdin.readLine('> ')));
+             print(JSON.stringify(io.stdin.readLine()));
+             print(io.stdin.readLine());",
+        ],
+        "first\r\nsecond\n\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "\"first\"\n> \"second\"\n\"\"\nnull\n");
+}
+
+#[test]
+fn test_stdin_read_size() {
+    // the buffer of a read is capped, asking for 4 GiB does not allocate them
+    let output = sl(
+        &[
+            "-e",
+            "import io from 'std:io';
+             print(io.stdin.read(2 ** 32 - 1));
+             print(io.stdin.read(2 ** 32 - 1));",
+        ],
+        "abc",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "abc\nnull\n");
+}
+
+#[test]
+fn test_write_ansi() {
+    // stdout is a pipe, so escape sequences are only kept with `ansi: true`
+    let output = sl(
+        &[
+            "-e",
+            "import io from 'std:io';
+             io.stdout.write('\\x1b[31mremoved\\x1b[0m\\n', { ansi: false });
+             io.stdout.write('\\x1b[32mauto\\x1b[0m\\n');
+             io.stdout.write('\\x1b[1mkept\\x1b[0m\\n', { ansi: true });
+             io.stderr.write('\\x1b[31merror\\x1b[0m\\n', { ansi: false });",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "removed\nauto\n\x1b[1mkept\x1b[0m\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "error\n");
+}
+
+/// Local modes of the terminal `fd`.
+#[cfg(unix)]
+fn local_modes(fd: i32) -> libc::tcflag_t {
+    let mut mode = std::mem::MaybeUninit::<libc::termios>::uninit();
+    unsafe {
+        assert_eq!(libc::tcgetattr(fd, mode.as_mut_ptr()), 0);
+        mode.assume_init().c_lflag
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exit_restores_raw_mode() {
+    use std::os::unix::io::FromRawFd;
+
+    let (mut master, mut terminal) = (0, 0);
+    let null = std::ptr::null_mut();
+    assert_eq!(
+        unsafe { libc::openpty(&mut master, &mut terminal, null, null, null) },
+        0
+    );
+    let cooked = libc::ICANON | libc::ECHO;
+    assert_eq!(local_modes(terminal) & cooked, cooked);
+
+    // the child gets its own descriptor of the terminal, ours is kept to check its mode
+    let stdin = unsafe { Stdio::from_raw_fd(libc::dup(terminal)) };
+    let output = Command::new(env!("CARGO_BIN_EXE_sl"))
+        .args(&[
+            "-e",
+            "import std from 'std';
+             std.io.stdin.setRawMode(true);
+             print(std.io.stdin.isRaw());
+             std.exit(0);",
+        ])
+        .stdin(stdin)
+        .output()
+        .expect("failed to run sl");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "true\n");
+    assert_eq!(local_modes(terminal) & cooked, cooked);
+    unsafe {
+        libc::close(terminal);
+        libc::close(master);
+    }
+}